@@ -19,6 +19,7 @@ pub struct EntityBuilder {
 	pub parent: EntityRef,
 	pub parent_follow: bool,
 	pub persist: bool,
+	pub frozen: bool,
 	pub rigid_body: RigidBody,
 	pub position: Isometry3,
 	pub velocity: Vec3,
@@ -35,6 +36,7 @@ impl EntityBuilder {
 			parent: EntityRef::null(),
 			parent_follow: false,
 			persist: false,
+			frozen: false,
 			rigid_body: RigidBodyBuilder::fixed().build(),
 			position: Isometry3::identity(),
 			velocity: Vec3::zeros(),
@@ -56,6 +58,11 @@ impl EntityBuilder {
 		self
 	}
 	
+	pub fn frozen(mut self, frozen: bool) -> Self {
+		self.frozen = frozen;
+		self
+	}
+	
 	pub fn rigid_body(mut self, rigid_body: RigidBody) -> Self {
 		self.rigid_body = rigid_body;
 		self
@@ -154,10 +161,12 @@ impl EntityBuilder {
 				angular_velocity: MutMark::new(self.angular_velocity),
 				hidden: self.hidden,
 			}),
+			previous_position: Cell::new(self.position),
+			interpolated: Cell::new(None),
 			initialized: Cell::new(false),
 			removed: Cell::new(false),
 			persist: Cell::new(self.persist),
-			frozen: Cell::new(false),
+			frozen: Cell::new(self.frozen),
 			components: BTreeMap::new(),
 			new_components: RefCell::new(self.components),
 			rigid_body: RigidBodyHandle::invalid(),
@@ -16,6 +16,7 @@ use crate::math::{Color, Isometry3, Point3, Vec3};
 use crate::renderer::{RenderContext, Renderer, RenderType};
 use crate::utils::{IntoBoxed, get_user_data, MutMark, InspectObject, GetSet, ExUi, SimpleInspect, ref_cell_iter};
 use super::{Application, Component, Physics, Hand};
+use super::physics::PhysicsEvent;
 pub use builder::EntityBuilder;
 pub use entity_ref::EntityRef;
 
@@ -36,6 +37,8 @@ pub struct Entity {
 	parent_offset: Cell<Option<Isometry3>>,
 	children: RefCell<Vec<EntityRef>>,
 	state: RefCell<EntityState>,
+	previous_position: Cell<Isometry3>,
+	interpolated: Cell<Option<Isometry3>>,
 	initialized: Cell<bool>,
 	removed: Cell<bool>,
 	persist: Cell<bool>,
@@ -58,6 +61,11 @@ impl Entity {
 			
 			rb.user_data = get_user_data(self.id, 0);
 			rb.set_position(*state.position, true);
+			
+			if self.frozen.get() {
+				rb.set_body_type(RigidBodyType::Fixed, false);
+			}
+			
 			rb.set_linvel(*state.velocity, true);
 			rb.set_angvel(*state.angular_velocity, true);
 			
@@ -144,6 +152,8 @@ impl Entity {
 		let mut state = self.state_mut();
 		let rigid_body = self.rigid_body(physics);
 		
+		self.previous_position.set(*state.position);
+		
 		*state.position = *rigid_body.position();
 		state.position.reset();
 		
@@ -154,6 +164,30 @@ impl Entity {
 		state.angular_velocity.reset();
 	}
 	
+	/// Temporarily replaces position with one blended between the last two physics steps. Skipped if position was changed since the last step.
+	pub fn interpolate(&self, alpha: f32) {
+		let mut state = self.state_mut();
+		
+		if state.position.mutated || self.interpolated.get().is_some() {
+			return;
+		}
+		
+		let actual = *state.position;
+		self.interpolated.set(Some(actual));
+		
+		*state.position = self.previous_position.get().lerp_slerp(&actual, alpha);
+		state.position.reset();
+	}
+	
+	pub fn restore_interpolation(&self) {
+		if let Some(actual) = self.interpolated.take() {
+			let mut state = self.state_mut();
+			
+			*state.position = actual;
+			state.position.reset();
+		}
+	}
+	
 	pub fn tick(&self, delta_time: Duration, application: &Application) -> Result<()> {
 		if let Some(parent) = self.parent.get(application) {
 			if let Some(parent_offset) = self.parent_offset.get() {
@@ -170,6 +204,22 @@ impl Entity {
 		Ok(())
 	}
 	
+	pub fn physics_event(&self, event: &PhysicsEvent, application: &Application) -> Result<()> {
+		for component in self.components.values() {
+			if component.inner().is_being_removed() {
+				continue;
+			}
+			
+			match event {
+				PhysicsEvent::CollisionStart(collision) => component.on_collision_start(&self, collision, application)?,
+				PhysicsEvent::CollisionEnd(collision) => component.on_collision_end(&self, collision, application)?,
+				PhysicsEvent::ContactForce(contact) => component.on_contact_force(&self, contact, application)?,
+			}
+		}
+		
+		Ok(())
+	}
+	
 	pub fn before_render(&mut self, context: &mut RenderContext, renderer: &mut Renderer) -> Result<bool> {
 		let mut is_transparent = false;
 		
@@ -233,6 +283,7 @@ impl Entity {
 	}
 	
 	pub fn on_gui(&self, ui: &mut Ui, application: &Application) {
+		use crate::component::registry;
 		use crate::utils::ExUi;
 		use egui::*;
 		
@@ -278,6 +329,38 @@ impl Entity {
 				.show(ui, &component.as_cref_dyn(), application)
 		}
 		
+		CollapsingHeader::new("Add Component")
+			.show(ui, |ui| {
+				let id = ui.id().with("Add Component");
+				let (mut name, mut params) = ui.ctx().data_mut(|d| d.get_temp::<(String, String)>(id))
+				                                     .unwrap_or_default();
+				
+				ComboBox::from_label("Type")
+					.selected_text(&name)
+					.show_ui(ui, |ui| {
+						for registered in registry::names() {
+							ui.selectable_value(&mut name, registered.to_string(), registered);
+						}
+					});
+				
+				ui.label("Parameters");
+				ui.code_editor(&mut params);
+				
+				if ui.button("Add").clicked() {
+					let result: Result<_> = try {
+						registry::load(&name, toml::from_str::<toml::Table>(&params)?.into(), application)?
+					};
+					
+					match result {
+						Ok(Some(component)) => { self.add_component(component); },
+						Ok(None) => eprintln!("Component {} is not available in this mode.", name),
+						Err(err) => eprintln!("Failed to add component {}: {}", name, err),
+					}
+				}
+				
+				ui.ctx().data_mut(|d| d.insert_temp(id, (name, params)));
+			});
+		
 		CollapsingHeader::new("Tags")
 			.default_open(true)
 			.show(ui, |ui| {
@@ -309,6 +392,12 @@ impl Entity {
 		    .and_then(|c| c.as_any().downcast_ref::<C>())
 	}
 	
+	pub fn components(&self) -> impl Iterator<Item = &dyn Component> {
+		self.components
+		    .values()
+		    .map(|c| &**c)
+	}
+	
 	pub fn pending_component(&self, id: u64) -> bool {
 		self.new_components
 			.borrow_mut()
@@ -360,6 +449,10 @@ impl Entity {
 		self.parent.clone()
 	}
 	
+	pub fn parent_offset(&self) -> Option<Isometry3> {
+		self.parent_offset.get()
+	}
+	
 	pub fn root<'a, 'b: 'a>(&'b self, application: &'a Application) -> &'a Entity {
 		let mut parent = self;
 		
@@ -478,6 +571,10 @@ impl Entity {
 		} else { false }
 	}
 	
+	pub fn is_frozen(&self) -> bool {
+		self.frozen.get()
+	}
+	
 	pub fn unfreeze(&self, physics: &mut Physics) -> bool {
 		if self.frozen.replace(false) {
 			self.rigid_body_mut(physics)
@@ -18,7 +18,7 @@ use crate::config::NovrConfig;
 use crate::math::{AMat4, Isometry3, Mat4, Perspective3, PI, PMat4, projective_clip, SubsetOfLossy, Vec4, VRSlice};
 use crate::renderer::{IMAGE_FORMAT, RenderContext, Renderer, RenderTarget, RenderTargetContext};
 use crate::utils::{default_tracked_pose, FramebufferBundle, OpenVRPtr};
-use super::VR;
+use super::{VrBackend, VR};
 use background::Background;
 use camera::Camera;
 
@@ -50,25 +50,20 @@ impl Eyes {
 		Self::new(min_framebuffer_size, (view, view), (projection, projection), (raw, raw), None, camera, renderer)
 	}
 	
-	pub fn new_vr(vr: Arc<VR>, camera: Option<Box<dyn Camera>>, renderer: &mut Renderer) -> Result<Eyes> {
-		let vr_lock = vr.lock().unwrap();
-		let min_framebuffer_size = vr_lock.system.recommended_render_target_size();
+	/// Eye parameters are taken from `vr`, frames are submitted to `openvr` compositor if present.
+	pub fn new_vr(vr: &dyn VrBackend, openvr: Option<Arc<VR>>, camera: Option<Box<dyn Camera>>, renderer: &mut Renderer) -> Result<Eyes> {
+		let min_framebuffer_size = vr.recommended_render_target_size();
 		
-		let view_left  = AMat4::from_superset_lossy(&Mat4::from_slice34(&vr_lock.system.eye_to_head_transform(openvr::Eye::Left ))).inverse();
-		let view_right = AMat4::from_superset_lossy(&Mat4::from_slice34(&vr_lock.system.eye_to_head_transform(openvr::Eye::Right))).inverse();
+		let view_left  = AMat4::from_superset_lossy(&Mat4::from_slice34(&vr.eye_to_head_transform(openvr::Eye::Left ))).inverse();
+		let view_right = AMat4::from_superset_lossy(&Mat4::from_slice34(&vr.eye_to_head_transform(openvr::Eye::Right))).inverse();
 		
-		let proj_left  = projective_clip() * PMat4::from_superset_lossy(&Mat4::from_slice44(&vr_lock.system.projection_matrix(openvr::Eye::Left, 0.1, 100.0)));
-		let proj_right = projective_clip() * PMat4::from_superset_lossy(&Mat4::from_slice44(&vr_lock.system.projection_matrix(openvr::Eye::Right, 0.1, 100.0)));
+		let proj_left  = projective_clip() * PMat4::from_superset_lossy(&Mat4::from_slice44(&vr.projection_matrix(openvr::Eye::Left, 0.1, 100.0)));
+		let proj_right = projective_clip() * PMat4::from_superset_lossy(&Mat4::from_slice44(&vr.projection_matrix(openvr::Eye::Right, 0.1, 100.0)));
 		
-		let raw_left  = vr_lock.system.projection_raw(openvr::Eye::Left);
-		let raw_left = vector!(-raw_left.left, raw_left.right, -raw_left.top, raw_left.bottom);
+		let raw_left  = vr.raw_projection(openvr::Eye::Left);
+		let raw_right = vr.raw_projection(openvr::Eye::Right);
 		
-		let raw_right = vr_lock.system.projection_raw(openvr::Eye::Right);
-		let raw_right = vector!(-raw_right.left, raw_right.right, -raw_right.top, raw_right.bottom);
-		
-		drop(vr_lock);
-		
-		Self::new(min_framebuffer_size, (view_left, view_right), (proj_left, proj_right), (raw_left, raw_right), Some(vr), camera, renderer)
+		Self::new(min_framebuffer_size, (view_left, view_right), (proj_left, proj_right), (raw_left, raw_right), openvr, camera, renderer)
 	}
 	
 	pub fn new(min_framebuffer_size: (u32, u32), view: (AMat4, AMat4), projection: (PMat4, PMat4), raw_projection: (Vec4, Vec4), vr: Option<Arc<VR>>, camera: Option<Box<dyn Camera>>, renderer: &mut Renderer) -> Result<Eyes> {
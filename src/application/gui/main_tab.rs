@@ -2,7 +2,7 @@ use std::hash::Hash;
 use std::ops::RangeInclusive;
 use egui::*;
 
-use crate::debug;
+use crate::{config, debug};
 use crate::utils::{end_row_interact, ExUi, id_fmt};
 use super::super::{Application, Entity, EntityRef};
 
@@ -26,6 +26,28 @@ pub fn main_ui(ui: &mut Ui, application: &Application) {
 	ui.separator();
 	ui.label(RichText::new("Scene").strong());
 	
+	ui.horizontal(|ui| {
+		let id = Id::new("ScenePath");
+		let mut path = ui.ctx().data_mut(|d| d.get_temp::<String>(id))
+		                 .unwrap_or_else(|| config::get().scene.clone());
+		
+		ui.text_edit_singleline(&mut path);
+		
+		if ui.button("Save").clicked() {
+			if let Err(err) = application.save_scene(&path) {
+				eprintln!("Failed to save scene {}: {}", path, err);
+			}
+		}
+		
+		if ui.button("Load").clicked() {
+			if let Err(err) = application.load_scene(&path) {
+				eprintln!("Failed to load scene {}: {}", path, err);
+			}
+		}
+		
+		ui.ctx().data_mut(|d| d.insert_temp(id, path));
+	});
+	
 	let sel_ent = application.gui_selection.borrow().entity_or_component();
 	
 	ScrollArea::vertical()
@@ -39,6 +39,36 @@ pub fn physics_ui(physics: &mut Physics, ui: &mut Ui, application: &Application)
 				});
 		});
 	
+	CollapsingHeader::new("Time Control")
+		.default_open(true)
+		.show(ui, |ui| {
+			let time_control = &mut physics.time_control;
+			
+			ui.horizontal(|ui| {
+				if ui.button(if time_control.paused { "Resume" } else { "Pause" }).clicked() {
+					time_control.toggle_pause();
+				}
+				
+				if ui.button("Step").clicked() {
+					let count = time_control.step_count;
+					time_control.step(count);
+				}
+				
+				ui.add(DragValue::new(&mut time_control.step_count).clamp_range(1..=1000).suffix(" steps"));
+			});
+			
+			let len = time_control.len();
+			if len > 0 {
+				let mut position = time_control.position();
+				
+				if ui.add(Slider::new(&mut position, 0..=len - 1).text("History")).changed() {
+					time_control.seek(position);
+				}
+			}
+			
+			ui.label(format!("{}/{} snapshots, P - pause, . - step, , - rewind, Shift - x10", len, time_control.capacity()));
+		});
+	
 	CollapsingHeader::new(format!("Rigid Bodies ({})", physics.rigid_body_set.len()))
 		.id_source("Rigid Bodies")
 		.open((sel_rb != RigidBodyHandle::invalid()).then_some(true))
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter, Debug};
 use openvr::{TrackedDeviceIndex, ControllerState};
+use serde_derive::{Deserialize, Serialize};
 
 mod device;
 mod state;
@@ -12,7 +13,7 @@ pub use state::InputState;
 pub type Key = winit::event::VirtualKeyCode;
 pub type MouseButton = winit::event::MouseButton;
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Deserialize, Serialize)]
 pub enum Hand {
 	Left,
 	Right,
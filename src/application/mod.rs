@@ -1,8 +1,9 @@
 #![allow(unused_imports)]
 
 use std::iter;
-use std::cell::RefCell;
+use std::cell::{RefCell, RefMut};
 use std::collections::{BTreeMap, VecDeque};
+use std::path::Path;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use anyhow::Result;
@@ -10,6 +11,8 @@ use thiserror::Error;
 use openvr::{MAX_TRACKED_DEVICE_COUNT, TrackedControllerRole, TrackedDeviceIndex};
 use openvr::compositor::WaitPoses;
 use openvr::tracked_device_index::HMD;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
 use rapier3d::dynamics::{GenericJoint, JointAxesMask, JointAxis, RigidBodyType};
 use rapier3d::prelude::ColliderBuilder;
 use smallvec::SmallVec;
@@ -21,6 +24,11 @@ pub mod vr;
 mod bench;
 mod eyes;
 mod gui;
+mod offscreen;
+mod prefab;
+mod recording;
+mod scene;
+mod time_control;
 mod window;
 
 use crate::{config, debug};
@@ -31,6 +39,8 @@ use crate::component::katamari::Katamari;
 use crate::component::miku::Miku;
 use crate::component::model::MMDModel;
 use crate::component::model::mmd::asset::PmxAsset;
+use crate::component::model::SimpleModel;
+use crate::component::model::simple::SimpleAsset;
 use crate::component::model::simple::asset::ObjAsset;
 use crate::component::pc_controlled::PCControlled;
 use crate::component::physics::joint::JointComponent;
@@ -45,25 +55,35 @@ use crate::renderer::{Renderer, RenderTarget};
 use crate::utils::default_wait_poses;
 pub use entity::{Entity, EntityRef};
 pub use input::{Hand, Input, Key, MouseButton};
-pub use physics::Physics;
-pub use vr::VR;
+pub use physics::{Collision, ContactForce, Physics, PhysicsEvent};
+pub use prefab::{Prefab, PrefabCollection};
+pub use recording::{FrameRecord, InputRecorder, InputReplay};
+pub use scene::Scene;
+pub use time_control::{PhysicsSnapshot, TimeControl};
+pub use vr::{SimulatedVr, VrBackend, VR};
 use bench::Benchmark;
 use eyes::{camera, Eyes};
 use gui::{ApplicationGui, GuiSelection};
+use offscreen::OffscreenTarget;
 use window::Window;
 
 
 pub struct Application {
-	pub vr: Option<Arc<VR>>,
-	pub renderer: RefCell<Renderer>,
+	pub vr: Option<Arc<dyn VrBackend>>,
 	pub physics: RefCell<Physics>,
 	pub vr_poses: WaitPoses,
 	pub pov: EntityRef,
 	pub miku: ComponentRef<Miku>,
 	pub detached_pov: EntityRef,
 	pub input: Input,
+	pub prefabs: PrefabCollection,
+	renderer: Option<RefCell<Renderer>>,
 	eyes: Option<Eyes>,
 	window: Option<Window>,
+	offscreen: Option<OffscreenTarget>,
+	recorder: Option<InputRecorder>,
+	replay: Option<InputReplay>,
+	rng: RefCell<StdRng>,
 	entities: BTreeMap<u64, Entity>,
 	new_entities: RefCell<VecDeque<Entity>>,
 	bench: RefCell<Benchmark>,
@@ -74,177 +94,256 @@ pub struct Application {
 impl Application {
 	pub fn new() -> Result<Application> {
 		let config = config::get();
-		let vr = (!config.novr.enabled).then(|| VR::new())
-		                               .transpose()?
-		                               .map(Arc::new);
+		let render_frame = config.render_frame.enabled;
+		let openvr = (!config.novr.enabled && !config.simvr.enabled && !render_frame).then(|| VR::new())
+		                                                                             .transpose()?
+		                                                                             .map(Arc::new);
 		
-		if vr.is_none() && config.camera.driver == CameraAPI::OpenVR {
+		if openvr.is_none() && !render_frame && config.camera.driver == CameraAPI::OpenVR {
 			return Err(OpenVRCameraInNoVRError.into());
 		}
 		
-		let mut renderer = Renderer::new(vr.clone())?;
-		
-		let camera: Box<dyn camera::Camera> = match config.camera.driver {
-			#[cfg(feature = "opencv-camera")]
-			CameraAPI::OpenCV => Box::new(camera::OpenCV::new()?),
-			CameraAPI::OpenVR => Box::new(camera::OpenVR::new(vr.clone().unwrap())?),
-			#[cfg(windows)]
-			CameraAPI::Escapi => Box::new(camera::Escapi::new()?),
-			CameraAPI::Dummy => Box::new(camera::Dummy::new()),
+		let vr: Option<Arc<dyn VrBackend>> = if render_frame {
+			None
+		} else if config.simvr.enabled {
+			Some(Arc::new(SimulatedVr::new(&config.simvr, &config.novr)?))
+		} else {
+			openvr.clone().map(|vr| vr as Arc<dyn VrBackend>)
 		};
 		
-		let eyes = if let Some(ref vr) = vr {
-			Eyes::new_vr(vr.clone(), Some(camera), &mut renderer)?
+		let mut renderer = Renderer::new(openvr.clone())?;
+		
+		let (eyes, window, offscreen) = if render_frame {
+			(None, None, Some(OffscreenTarget::new(&config.render_frame, &mut renderer)?))
 		} else {
-			Eyes::new_novr(&config::get().novr, Some(camera), &mut renderer)?
+			let camera: Box<dyn camera::Camera> = match config.camera.driver {
+				#[cfg(feature = "opencv-camera")]
+				CameraAPI::OpenCV => Box::new(camera::OpenCV::new()?),
+				CameraAPI::OpenVR => Box::new(camera::OpenVR::new(openvr.clone().unwrap())?),
+				#[cfg(windows)]
+				CameraAPI::Escapi => Box::new(camera::Escapi::new()?),
+				CameraAPI::Dummy => Box::new(camera::Dummy::new()),
+			};
+			
+			let eyes = if let Some(ref vr) = vr {
+				Eyes::new_vr(&**vr, openvr.clone(), Some(camera), &mut renderer)?
+			} else {
+				Eyes::new_novr(&config::get().novr, Some(camera), &mut renderer)?
+			};
+			
+			let window = Window::new(Some(eyes.framebuffer_size()), &renderer)?;
+			
+			(Some(eyes), Some(window), None)
 		};
 		
-		let window = Window::new(Some(eyes.framebuffer_size()), &renderer)?;
+		Application::from_parts(vr, Some(renderer), eyes, window, offscreen)
+	}
+	
+	/// Runs the simulation without any window, VR or renderer. Models are skipped.
+	pub fn new_headless() -> Result<Application> {
+		Application::from_parts(None, None, None, None, None)
+	}
+	
+	/// Shared by both constructors. Loads the configured scene, or the default one if there is none.
+	fn from_parts(vr: Option<Arc<dyn VrBackend>>,
+	              renderer: Option<Renderer>,
+	              eyes: Option<Eyes>,
+	              window: Option<Window>,
+	              offscreen: Option<OffscreenTarget>)
+	              -> Result<Application> {
+		let config = config::get();
+		let replay = (!config.replay.is_empty()).then(|| InputReplay::open(&config.replay)).transpose()?;
+		let seed = replay.as_ref().map_or_else(rand::random, |replay| replay.header.seed);
+		// History can only be scrubbed from the window's GUI, there is no point in capturing it otherwise
+		let history = if window.is_some() { config.physics.history as usize } else { 0 };
 		
 		let application = Application {
 			vr,
-			renderer: RefCell::new(renderer),
-			physics: RefCell::new(Physics::new()),
+			renderer: renderer.map(RefCell::new),
+			physics: RefCell::new(Physics::new(history)),
 			vr_poses: default_wait_poses(),
 			pov: EntityRef::null(),
 			miku: ComponentRef::null(),
 			detached_pov: EntityRef::null(),
 			input: Input::new(),
+			prefabs: PrefabCollection::load("props.toml")?,
 			bench: RefCell::new(Benchmark::new()),
-			eyes: Some(eyes),
-			window: Some(window),
+			eyes,
+			window,
+			offscreen,
+			recorder: (!config.record.is_empty()).then(|| InputRecorder::create(&config.record, seed)).transpose()?,
+			replay,
+			rng: RefCell::new(StdRng::seed_from_u64(seed)),
 			entities: BTreeMap::new(),
 			new_entities: RefCell::new(VecDeque::new()),
 			gui: RefCell::new(ApplicationGui::new()),
 			gui_selection: RefCell::new(GuiSelection::default()),
 		};
 		
-		{
-			let renderer = &mut application.renderer.borrow_mut();
-			
-			application.add_entity(
-				Entity::builder("ඞ")
-					.translation(point!(0.0, 20.0, 2.0))
-					// .component(SrgbTest::new(renderer)?)
+		if !config.scene.is_empty() {
+			application.load_scene(&config.scene)?;
+		} else {
+			application.load_default_scene()?;
+		}
+		
+		Ok(application)
+	}
+	
+	fn load_default_scene(&self) -> Result<()> {
+		let config = config::get();
+		
+		self.add_entity(
+			Entity::builder("ඞ")
+				.translation(point!(0.0, 20.0, 2.0))
+				// .component(SrgbTest::new(renderer)?)
+				.build()
+		);
+		
+		let mut floor = Entity::builder("Floor")
+			.translation(point!(0.0, 0.0, 0.0))
+			.collider(ColliderBuilder::halfspace(Vec3::y_axis()).build())
+			.tag("World", true)
+			.hidden(config.camera.driver != CameraAPI::Dummy && !config.render_frame.enabled);
+		
+		if let Some(model) = self.load_model(ObjAsset::at("shapes/floor.obj").texture("shapes/floor.png"))? {
+			floor = floor.component(model);
+		}
+		
+		self.add_entity(floor.build());
+		
+		if self.vr.is_some() {
+			self.add_entity(
+				Entity::builder("VR Root")
+					.component(VrRoot::new())
+					.tag("NoGrab", true)
 					.build()
 			);
-			
-			application.add_entity(
-				Entity::builder("Floor")
-					.translation(point!(0.0, 0.0, 0.0))
-					.component(renderer.load(ObjAsset::at("shapes/floor.obj", "shapes/floor.png"))?)
-					.collider(ColliderBuilder::halfspace(Vec3::y_axis()).build())
-					.tag("World", true)
-					.hidden(config.camera.driver != CameraAPI::Dummy)
+		} else {
+			let pov = self.add_entity(
+				Entity::builder("(You)")
+					.translation(point!(0.0, 1.5, 1.5))
+					.component(PoV::new(!self.headless()))
+					.component(PCControlled::new())
+					.tag("Head", true)
 					.build()
 			);
 			
-			if application.vr.is_some() {
-				application.add_entity(
-					Entity::builder("VR Root")
-						.component(VrRoot::new())
-						.tag("NoGrab", true)
-						.build()
-				);
-			} else {
-				let pov = application.add_entity(
-					Entity::builder("(You)")
-						.translation(point!(0.0, 1.5, 1.5))
-						.component(PoV::new(true))
-						.component(PCControlled::new())
-						.tag("Head", true)
-						.build()
-				);
+			for (hand, offset, model_path, texture_path) in [(Hand::Left, -0.2, "hand/hand_l.obj", "hand/hand_l.png"),
+			                                                 (Hand::Right, 0.2, "hand/hand_r.obj", "hand/hand_r.png")] {
+				let builder = Entity::builder("Hand")
+					.position(Isometry3::new(vector!(offset, 1.3, 1.1).into(), vector!(PI * 0.25, 0.0, 0.0)))
+					.parent(pov.clone(), true);
 				
-				application.add_entity(
-					Entity::builder("Hand")
-						.position(Isometry3::new(vector!(-0.2, 1.3, 1.1).into(), vector!(PI * 0.25, 0.0, 0.0)))
-						.parent(pov.clone(), true)
-						.component(renderer.load(ObjAsset::at("hand/hand_l.obj", "hand/hand_l.png"))?)
-						.component(HandComponent::new(Hand::Left))
-						.collider_from_aabb(1000.0)
-						.tag("Hand", Hand::Left)
-						.build()
-				);
+				let builder = match self.load_model(ObjAsset::at(model_path).texture(texture_path))? {
+					Some(model) => builder.component(model).collider_from_aabb(1000.0),
+					None => builder.collider(ColliderBuilder::cuboid(0.04, 0.02, 0.08).density(1000.0).build()),
+				};
 				
-				application.add_entity(
-					Entity::builder("Hand")
-						.position(Isometry3::new(vector!(0.2, 1.3, 1.1).into(), vector!(PI * 0.25, 0.0, 0.0)))
-						.parent(pov.clone(), true)
-						.component(renderer.load(ObjAsset::at("hand/hand_r.obj", "hand/hand_r.png"))?)
-						.component(HandComponent::new(Hand::Right))
-						.collider_from_aabb(1000.0)
-						.tag("Hand", Hand::Right)
-						.build()
+				self.add_entity(
+					builder.component(HandComponent::new(hand))
+					       .tag("Hand", hand)
+					       .build()
 				);
 			}
+		}
+		
+		// self.add_entity(
+		// 	Entity::builder("ToolGun")
+		// 		.translation(point!(0.0, 1.0, 1.0))
+		// 		.component(self.renderer()?.load(ObjAsset::at("toolgun/toolgun.obj").texture("toolgun/toolgun.png"))?)
+		// 		.component(ToolGun::new(Isometry3::from_parts(vector!(0.0, -0.03, 0.03).into(),
+		// 		                                              Rot3::from_euler_angles(PI * 0.25, PI, 0.0)),
+		// 		                        self)?)
+		// 		.collider_from_aabb(100.0)
+		// 		.build()
+		// );
+		
+		// self.add_entity(
+		// 	Entity::builder("初音ミク")
+		// 		.translation(point!(3.0, 0.0, 0.0))
+		// 		.rotation(Rot3::from_euler_angles(0.0, PI * 0.0, 0.0))
+		// 		.component(Miku::new(PmxAsset::at("YYB式初音ミクCrude Hair/YYB式初音ミクCrude Hair.pmx"))
+		// 			.touch_ignore(vec!["ForeLock1".to_string()]))
+		// 		.build()
+		// );
+		
+		if !self.headless() {
+			let renderer = &mut *self.renderer()?;
 			
-			// application.add_entity(
-			// 	Entity::builder("ToolGun")
-			// 		.translation(point!(0.0, 1.0, 1.0))
-			// 		.component(renderer.load(ObjAsset::at("toolgun/toolgun.obj", "toolgun/toolgun.png"))?)
-			// 		.component(ToolGun::new(Isometry3::from_parts(vector!(0.0, -0.03, 0.03).into(),
-			// 		                                              Rot3::from_euler_angles(PI * 0.25, PI, 0.0)),
-			// 		                        renderer).unwrap())
-			// 		.collider_from_aabb(100.0)
-			// 		.build()
-			// );
-			
-			// application.add_entity(
-			// 	Entity::builder("初音ミク")
-			// 		.translation(point!(3.0, 0.0, 0.0))
-			// 		.rotation(Rot3::from_euler_angles(0.0, PI * 0.0, 0.0))
-			// 		.component(Miku::new(PmxAsset::at("YYB式初音ミクCrude Hair/YYB式初音ミクCrude Hair.pmx")))
-			// 		.build()
-			// );
-			
-			application.add_entity(
+			self.add_entity(
 				Entity::builder("test 2")
 					.translation(point!(-3.0, 4.0, -2.0))
 					.rotation(Rot3::from_euler_angles(0.0, PI * 0.0, 0.0))
 					.component(MMDModel::new(renderer.load(PmxAsset::at("test2/test2.pmx"))?, renderer)?)
 					.build()
 			);
-			
-			// application.add_entity(
-			// 	Entity::builder("Katamari")
-			// 		.position(Isometry3::new(vector!(3.0, 1.3, -2.0), vector!(0.0, 0.0, 0.0)))
-			// 		.component(renderer.load(ObjAsset::at("katamari/katamari_baked.obj", "katamari/katamari_baked.png"))?)
-			// 		.component(Katamari::new())
-			// 		.gravity_scale(10.0)
-			// 		.damping(1.0, 0.0)
-			// 		.rigid_body_type(RigidBodyType::Dynamic)
-			// 		.build()
-			// );
 		}
 		
-		Ok(application)
+		// self.add_entity(
+		// 	Entity::builder("Katamari")
+		// 		.position(Isometry3::new(vector!(3.0, 1.3, -2.0), vector!(0.0, 0.0, 0.0)))
+		// 		.component(self.renderer()?.load(ObjAsset::at("katamari/katamari_baked.obj").texture("katamari/katamari_baked.png"))?)
+		// 		.component(Katamari::new())
+		// 		.gravity_scale(10.0)
+		// 		.damping(1.0, 0.0)
+		// 		.rigid_body_type(RigidBodyType::Dynamic)
+		// 		.build()
+		// );
+		
+		Ok(())
+	}
+	
+	/// Returns `None` in headless mode.
+	fn load_model(&self, asset: impl Into<SimpleAsset>) -> Result<Option<SimpleModel>> {
+		if self.headless() {
+			return Ok(None);
+		}
+		
+		Ok(Some(self.renderer()?.load(asset.into())?))
 	}
 	
 	pub fn run(mut self) -> Result<()> {
 		let mut instant = Instant::now();
+		let mut frame = 0;
+		let headless = config::get().headless.clone();
+		let physics_config = config::get().physics.clone();
+		let render_frame = config::get().render_frame.clone();
 		
 		while !self.input.quitting {
 			let mut delta_time = instant.elapsed();
 			instant = Instant::now();
 			
-			if delta_time.as_millis() > 250 {
+			if self.headless() && headless.frame_time > 0 {
+				delta_time = Duration::from_millis(headless.frame_time);
+			} else if self.offscreen.is_some() {
+				delta_time = self.physics.get_mut().step_duration;
+			} else if delta_time.as_millis() > 250 {
 				println!("Can't keep up! Delta time: {:.2}s", delta_time.as_secs_f32());
 				delta_time = Duration::from_millis(250);
 			}
 			
 			self.input.reset();
 			
-			if let Some(window) = &mut self.window {
-				window.pull_events(&mut self.input);
+			if self.replay.is_some() {
+				self.handle_replay_input(&mut delta_time)?;
+				
+				self.bench.get_mut().tick("Inputs");
+			} else {
+				if let Some(window) = &mut self.window {
+					window.pull_events(&mut self.input);
+				}
+				
+				self.bench.get_mut().tick("Inputs");
+				
+				if self.vr.is_some() {
+					self.handle_vr_input(delta_time)?;
+					
+					self.bench.get_mut().tick("VR Sync");
+				}
 			}
 			
-			self.bench.get_mut().tick("Inputs");
-			
-			if self.vr.is_some() {
-				self.handle_vr_input()?;
-				
-				self.bench.get_mut().tick("VR Sync");
+			if let Some(recorder) = &mut self.recorder {
+				recorder.record(&FrameRecord::capture(delta_time, &self.input, &self.vr_poses))?;
 			}
 			
 			self.bench.get_mut().new_frame();
@@ -258,20 +357,37 @@ impl Application {
 			
 			self.bench.get_mut().tick("Setup");
 			
-			{
+			self.handle_time_control_input();
+			
+			if self.physics.get_mut().restore_snapshot() {
 				let mut physics = self.physics.borrow_mut();
 				
-				for entity in self.dfs_entities() {
-					entity.before_physics(&self, &mut physics);
-				}
-				
-				physics.step(Duration::from_millis(1000 / 140)); // TODO: use deltaTime?
-				
 				for entity in self.dfs_entities() {
 					entity.after_physics(&self, &mut physics);
 				}
 			}
 			
+			let steps = self.physics.get_mut().accumulate(delta_time);
+			
+			for _ in 0..steps {
+				{
+					let mut physics = self.physics.borrow_mut();
+					let step_duration = physics.step_duration;
+					
+					for entity in self.dfs_entities() {
+						entity.before_physics(&self, &mut physics);
+					}
+					
+					physics.step(step_duration);
+					
+					for entity in self.dfs_entities() {
+						entity.after_physics(&self, &mut physics);
+					}
+				}
+				
+				self.dispatch_physics_events()?;
+			}
+			
 			self.physics.borrow().debug_draw(&self);
 			
 			self.bench.get_mut().tick("Physics");
@@ -293,7 +409,13 @@ impl Application {
 			
 			self.bench.get_mut().tick("Tick");
 			
-			self.renderer.get_mut().begin_frame()?;
+			if physics_config.interpolate {
+				let alpha = self.physics.get_mut().interpolation_alpha();
+				
+				for entity in self.entities.values() {
+					entity.interpolate(alpha);
+				}
+			}
 			
 			let pov = self.pov
 			              .get(&self).map(|e| *e.state().position)
@@ -301,49 +423,137 @@ impl Application {
 			let detached_pov = self.detached_pov.get(&self).map(|e| *e.state().position);
 			let hmd_pose = self.vr_poses.render[HMD as usize].device_to_absolute_tracking().clone();
 			
-			if let Some(eyes) = &mut self.eyes {
-				eyes.set_hmd_pose(hmd_pose);
-				eyes.load_background(pov, self.renderer.get_mut())?;
-			}
-			
-			if let Some(window) = &mut self.window {
-				match window.regen_swapchain(self.renderer.get_mut()) {
-					Err(err) if err.is::<WindowSwapchainNeedRetry>() => {},
-					result => result?,
+			if let Some(renderer) = &mut self.renderer {
+				let renderer = renderer.get_mut();
+				
+				renderer.begin_frame()?;
+				
+				if let Some(eyes) = &mut self.eyes {
+					eyes.set_hmd_pose(hmd_pose);
+					eyes.load_background(pov, renderer)?;
 				}
-			}
-			
-			self.bench.get_mut().tick("Render Setup");
-			
-			if let Some(eyes) = &mut self.eyes {
-				self.renderer.get_mut().render(pov, &mut self.entities, eyes)?;
 				
-				self.bench.get_mut().tick("Render Eyes");
-			
 				if let Some(window) = &mut self.window {
-					if let Some(detached_pov) = detached_pov {
-						self.renderer.get_mut().render(detached_pov, &mut self.entities, window)?;
-					} else {
-						window.mirror_from(eyes.last_frame(), self.renderer.get_mut())?;
+					match window.regen_swapchain(renderer) {
+						Err(err) if err.is::<WindowSwapchainNeedRetry>() => {},
+						result => result?,
+					}
+				}
+				
+				self.bench.get_mut().tick("Render Setup");
+				
+				if let Some(eyes) = &mut self.eyes {
+					renderer.render(pov, &mut self.entities, eyes)?;
+					
+					self.bench.get_mut().tick("Render Eyes");
+					
+					if let Some(window) = &mut self.window {
+						if let Some(detached_pov) = detached_pov {
+							renderer.render(detached_pov, &mut self.entities, window)?;
+						} else {
+							window.mirror_from(eyes.last_frame(), renderer)?;
+						}
+						
+						self.bench.get_mut().tick("Render Window");
 					}
+				} else if let Some(window) = &mut self.window {
+					renderer.render(pov, &mut self.entities, window)?;
 					
 					self.bench.get_mut().tick("Render Window");
+				} else if let Some(offscreen) = &mut self.offscreen {
+					if !config::get().debug {
+						debug::clear();
+					}
+					
+					let camera_pos = if render_frame.position != render_frame.target {
+						Isometry3::look_at_rh(&render_frame.position.into(), &render_frame.target.into(), &Vec3::y()).inverse()
+					} else {
+						pov
+					};
+					
+					renderer.render(camera_pos, &mut self.entities, offscreen)?;
+					
+					self.bench.get_mut().tick("Render Offscreen");
 				}
-			} else if let Some(window) = &mut self.window {
-				self.renderer.get_mut().render(pov, &mut self.entities, window)?;
 				
-				self.bench.get_mut().tick("Render Window");
+				renderer.end_frame()?;
+				
+				self.bench.get_mut().tick("Render End");
+			} else {
+				debug::clear();
 			}
 			
-			self.renderer.get_mut().end_frame()?;
-			
-			self.bench.get_mut().tick("Render End");
+			if physics_config.interpolate {
+				for entity in self.entities.values() {
+					entity.restore_interpolation();
+				}
+			}
 			
 			self.cleanup_loop()?;
 			
 			self.bench.get_mut().tick("Cleanup");
+			
+			frame += 1;
+			if self.headless() && headless.frames > 0 && frame >= headless.frames {
+				self.input.quitting = true;
+			}
+			
+			if let Some(offscreen) = &self.offscreen {
+				let last = frame >= render_frame.frames;
+				
+				if last || (render_frame.interval > 0 && frame % render_frame.interval == 0) {
+					let path = if render_frame.interval > 0 {
+						offscreen::numbered_path(&render_frame.output, frame)
+					} else {
+						render_frame.output.clone().into()
+					};
+					
+					offscreen.save(&path, self.renderer.as_mut().unwrap().get_mut())?;
+					println!("Saved frame {} to {}", frame, path.display());
+				}
+				
+				if last {
+					self.input.quitting = true;
+				}
+			}
+		}
+		
+		Ok(())
+	}
+	
+	pub fn renderer(&self) -> Result<RefMut<Renderer>, HeadlessError> {
+		self.renderer
+		    .as_ref()
+		    .map(RefCell::borrow_mut)
+		    .ok_or(HeadlessError)
+	}
+	
+	/// Seeded from the replayed recording, so components drawing from it behave the same as when it was recorded.
+	pub fn rng(&self) -> RefMut<StdRng> {
+		self.rng.borrow_mut()
+	}
+	
+	pub fn headless(&self) -> bool {
+		self.renderer.is_none()
+	}
+	
+	pub fn save_scene(&self, path: impl AsRef<Path>) -> Result<()> {
+		Scene::capture(self)?.save(path)
+	}
+	
+	/// Removes all current entities and replaces them with the ones from the scene file.
+	pub fn load_scene(&self, path: impl AsRef<Path>) -> Result<()> {
+		let scene = Scene::load(path)?;
+		
+		for entity in self.entities.values() {
+			entity.remove();
 		}
 		
+		self.new_entities.borrow_mut().clear();
+		self.physics.borrow_mut().time_control.clear();
+		
+		scene.spawn(self)?;
+		
 		Ok(())
 	}
 	
@@ -356,6 +566,11 @@ impl Application {
 		entity_ref
 	}
 	
+	/// Spawns prefab defined in `props.toml`, together with its children.
+	pub fn add_prefab(&self, name: &str, position: Isometry3) -> Result<EntityRef> {
+		self.prefabs.spawn(name, position, self)
+	}
+	
 	#[allow(dead_code)]
 	pub fn entity(&self, id: u64) -> Option<&Entity> {
 		self.entities.get(&id)
@@ -421,6 +636,26 @@ impl Application {
 		self.gui_selection.borrow().clone()
 	}
 	
+	fn dispatch_physics_events(&self) -> Result<()> {
+		let events = {
+			let physics = &mut *self.physics.borrow_mut();
+			physics.event_handler.drain(&physics.collider_set)
+		};
+		
+		for event in events {
+			let collider = match &event {
+				PhysicsEvent::CollisionStart(collision) | PhysicsEvent::CollisionEnd(collision) => &collision.collider,
+				PhysicsEvent::ContactForce(contact) => &contact.collider,
+			};
+			
+			if let Some(entity) = collider.entity().get(self) {
+				entity.physics_event(&event, self)?;
+			}
+		}
+		
+		Ok(())
+	}
+	
 	fn setup_loop(&mut self) -> Result<()> {
 		let mut clean = false;
 		while !clean {
@@ -488,21 +723,66 @@ impl Application {
 		Ok(())
 	}
 	
-	fn handle_vr_input(&mut self) -> Result<()> {
-		let vr = self.vr.as_ref().expect("VR has not been initialized.").lock().unwrap();
+	fn handle_time_control_input(&mut self) {
+		let keyboard = &self.input.keyboard;
+		let time_control = &mut self.physics.get_mut().time_control;
+		let count = if keyboard.pressed(Key::LShift) { 10 } else { 1 };
 		
-		self.vr_poses = vr.compositor.wait_get_poses()?;
+		if keyboard.down(Key::P) {
+			time_control.toggle_pause();
+		}
+		
+		if keyboard.down(Key::Period) {
+			time_control.step(count);
+		}
 		
-		if let Some(id) = vr.system.tracked_device_index_for_controller_role(TrackedControllerRole::LeftHand) {
+		if keyboard.down(Key::Comma) {
+			time_control.rewind(count as usize);
+		}
+	}
+	
+	/// Window events are still pulled to keep it responsive, but only quit requests are taken from them.
+	fn handle_replay_input(&mut self, delta_time: &mut Duration) -> Result<()> {
+		if let Some(window) = &mut self.window {
+			let mut live = Input::new();
+			window.pull_events(&mut live);
+			self.input.quitting |= live.quitting;
+		}
+		
+		let replay = self.replay.as_mut().expect("Replay has not been started.");
+		
+		if let Some(frame) = replay.next_frame()? {
+			*delta_time = frame.delta_time;
+			frame.apply(&mut self.input, &mut self.vr_poses);
+		} else {
+			println!("Replay finished after {} frames.", replay.frame());
+			self.replay = None;
+			
+			if self.headless() {
+				self.input.quitting = true;
+			}
+		}
+		
+		Ok(())
+	}
+	
+	fn handle_vr_input(&mut self, delta_time: Duration) -> Result<()> {
+		let vr = self.vr.clone().expect("VR has not been initialized.");
+		
+		vr.update(&self.input, delta_time)?;
+		
+		self.vr_poses = vr.wait_get_poses()?;
+		
+		if let Some(id) = vr.tracked_device_index_for_controller_role(TrackedControllerRole::LeftHand) {
 			self.input.set_controller_id(Hand::Left, id);
 		}
 		
-		if let Some(id) = vr.system.tracked_device_index_for_controller_role(TrackedControllerRole::RightHand) {
+		if let Some(id) = vr.tracked_device_index_for_controller_role(TrackedControllerRole::RightHand) {
 			self.input.set_controller_id(Hand::Right, id);
 		}
 		
 		for id in 0..(MAX_TRACKED_DEVICE_COUNT as TrackedDeviceIndex) {
-			if let Some(state) = vr.system.controller_state(id) {
+			if let Some(state) = vr.controller_state(id) {
 				self.input.update_controller(id, state);
 			}
 		}
@@ -514,3 +794,7 @@ impl Application {
 #[derive(Debug, Error)]
 #[error("OpenvR unavailable. You can't use openvr background with --novr flag.")]
 pub struct OpenVRCameraInNoVRError;
+
+#[derive(Debug, Error)]
+#[error("Renderer is not available in headless mode.")]
+pub struct HeadlessError;
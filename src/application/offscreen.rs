@@ -0,0 +1,134 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::Result;
+use image::{imageops, RgbaImage};
+use image::imageops::FilterType;
+use simba::scalar::SubsetOf;
+use vulkano::buffer::{Buffer, BufferUsage, Subbuffer};
+use vulkano::command_buffer::CopyImageToBufferInfo;
+use vulkano::format::ClearValue;
+use vulkano::image::AttachmentImage;
+use vulkano::memory::allocator::MemoryUsage;
+
+use crate::config::RenderFrameConfig;
+use crate::math::{AMat4, Isometry3, Perspective3, PMat4, Vec2, projective_clip, PI};
+use crate::renderer::{LAYERS, RenderContext, Renderer, RenderTarget, RenderTargetContext};
+use crate::utils::{FramebufferBundle, IntoInfo};
+
+
+/// Render target that reads rendered frames back to the CPU, used to save them as images.
+pub struct OffscreenTarget {
+	fb: FramebufferBundle,
+	view: (AMat4, AMat4),
+	projection: PMat4,
+	fov: Vec2,
+	size: (u32, u32),
+	stereo: bool,
+	clear_values: Vec<Option<ClearValue>>,
+	buffer: Subbuffer<[u8]>,
+}
+
+impl OffscreenTarget {
+	pub fn new(config: &RenderFrameConfig, renderer: &mut Renderer) -> Result<Self> {
+		let size = (config.width, config.height);
+		let fb = renderer.create_framebuffer(size)?;
+		let fb_size = fb.size();
+		
+		let aspect = size.0 as f32 / size.1 as f32;
+		let fovx = config.fov / 180.0 * PI;
+		let fovy = ((fovx / 2.0).tan() / aspect).atan() * 2.0;
+		let projection = projective_clip() * Perspective3::new(aspect, fovy, 0.1, 100.0).as_projective();
+		
+		let view = if config.stereo {
+			(Isometry3::translation(config.ipd / 2.0, 0.0, 0.0).to_superset(), Isometry3::translation(-config.ipd / 2.0, 0.0, 0.0).to_superset())
+		} else {
+			(AMat4::identity(), AMat4::identity())
+		};
+		
+		let mut clear_values = fb.clear_values.clone();
+		if !config.transparent {
+			clear_values[0] = Some(ClearValue::Float([0.0, 0.0, 0.0, 1.0]));
+		}
+		
+		let buffer = Buffer::new_slice(&renderer.memory_allocator,
+		                               BufferUsage::TRANSFER_DST.into_info(),
+		                               MemoryUsage::Download.into_info(),
+		                               fb_size.0 as u64 * fb_size.1 as u64 * 4 * LAYERS as u64)?;
+		
+		Ok(OffscreenTarget {
+			fb,
+			view,
+			projection,
+			fov: vector!(fovx, fovy),
+			size,
+			stereo: config.stereo,
+			clear_values,
+			buffer,
+		})
+	}
+	
+	/// Waits for rendering to finish and reads the last frame. Stereo frames are placed side by side.
+	pub fn capture(&self, renderer: &mut Renderer) -> Result<RgbaImage> {
+		renderer.wait_idle()?;
+		
+		let (width, height) = self.fb.size();
+		let data = self.buffer.read()?;
+		let layer_size = (width * height * 4) as usize;
+		let eyes = if self.stereo { 2 } else { 1 };
+		
+		let mut image = RgbaImage::new(self.size.0 * eyes, self.size.1);
+		
+		for eye in 0..eyes {
+			let layer = RgbaImage::from_raw(width, height, data[layer_size * eye as usize..layer_size * (eye as usize + 1)].to_vec()).unwrap();
+			let layer = if (width, height) != self.size {
+				imageops::resize(&layer, self.size.0, self.size.1, FilterType::Triangle)
+			} else {
+				layer
+			};
+			
+			imageops::replace(&mut image, &layer, (self.size.0 * eye) as i64, 0);
+		}
+		
+		Ok(image)
+	}
+	
+	pub fn save(&self, path: impl AsRef<Path>, renderer: &mut Renderer) -> Result<()> {
+		self.capture(renderer)?.save(path)?;
+		
+		Ok(())
+	}
+}
+
+impl RenderTarget for OffscreenTarget {
+	fn create_context(&mut self, camera_pos: Isometry3) -> Result<Option<RenderTargetContext>> {
+		let center_pos = camera_pos.inverse();
+		
+		Ok(Some(RenderTargetContext::new(self.fb.clone(),
+		                                 (self.view.0 * center_pos, self.view.1 * center_pos),
+		                                 (self.projection, self.projection),
+		                                 (self.fov, self.fov))))
+	}
+	
+	fn clear_values(&self) -> &[Option<ClearValue>] {
+		&self.clear_values
+	}
+	
+	fn last_frame(&self) -> &Arc<AttachmentImage> {
+		&self.fb.main_image
+	}
+	
+	fn after_render(&mut self, context: &mut RenderContext, _renderer: &mut Renderer) -> Result<()> {
+		context.builder.copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(self.fb.main_image.clone(), self.buffer.clone()))?;
+		
+		Ok(())
+	}
+}
+
+/// Appends frame number to the file name, eg. `frame.png` becomes `frame_0042.png`.
+pub fn numbered_path(path: impl AsRef<Path>, frame: u64) -> PathBuf {
+	let path = path.as_ref();
+	let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+	let extension = path.extension().unwrap_or("png".as_ref()).to_string_lossy();
+	
+	path.with_file_name(format!("{}_{:04}.{}", stem, frame, extension))
+}
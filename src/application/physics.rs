@@ -1,11 +1,16 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
 use std::time::Duration;
 use nalgebra::{Quaternion, Unit};
 use rapier3d::prelude::*;
 
-use crate::debug;
+use crate::{config, debug};
+use crate::component::ComponentRef;
+use crate::component::physics::collider::ColliderComponent;
 use crate::math::{Color, Isometry3, Point3, Rot3, Vec3, PI};
-use crate::utils::{RigidBodyEx, ColliderEx};
+use crate::utils::{RigidBodyEx, ColliderEx, from_user_data};
 use super::{Application, EntityRef};
+use super::time_control::{PhysicsSnapshot, TimeControl};
 
 
 pub struct Physics {
@@ -13,6 +18,10 @@ pub struct Physics {
 	pub collider_set: ColliderSet,
 	pub gravity: Vec3,
 	pub time_scale: f32,
+	pub step_duration: Duration,
+	pub max_substeps: u32,
+	pub accumulator: Duration,
+	pub time_control: TimeControl,
 	pub integration_parameters: IntegrationParameters,
 	pub physics_pipeline: PhysicsPipeline,
 	pub query_pipeline: QueryPipeline,
@@ -23,7 +32,150 @@ pub struct Physics {
 	pub multibody_joint_set: MultibodyJointSet,
 	pub ccd_solver: CCDSolver,
 	pub physics_hooks: (),
-	pub event_handler: (),
+	pub event_handler: PhysicsEvents,
+}
+
+/// Collision between two colliders, as seen by the entity receiving it.
+#[derive(Debug, Clone)]
+pub struct Collision {
+	pub collider: ComponentRef<ColliderComponent>,
+	pub other: EntityRef,
+	pub other_collider: ComponentRef<ColliderComponent>,
+	pub sensor: bool,
+	/// One of the colliders has been removed.
+	pub removed: bool,
+	/// World space contact points. Empty for sensors and ended collisions.
+	pub points: Vec<Point3>,
+	/// Contact normal pointing towards the other collider.
+	pub normal: Vec3,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContactForce {
+	pub collider: ComponentRef<ColliderComponent>,
+	pub other: EntityRef,
+	pub other_collider: ComponentRef<ColliderComponent>,
+	pub total_force: Vec3,
+	pub total_force_magnitude: f32,
+	pub max_force_direction: Vec3,
+	pub max_force_magnitude: f32,
+}
+
+#[derive(Debug, Clone)]
+pub enum PhysicsEvent {
+	CollisionStart(Collision),
+	CollisionEnd(Collision),
+	ContactForce(ContactForce),
+}
+
+/// Collects rapier events during the step. Owners of colliders are remembered, so events of removed colliders can still be delivered.
+#[derive(Default)]
+pub struct PhysicsEvents {
+	events: Mutex<Vec<PhysicsEvent>>,
+	owners: Mutex<HashMap<ColliderHandle, u128>>,
+}
+
+impl PhysicsEvents {
+	/// `history` is the number of snapshots kept for rewinding, `0` skips capturing them at all.
+	pub fn new(history: usize) -> Self {
+		Self::default()
+	}
+	
+	pub fn drain(&mut self, colliders: &ColliderSet) -> Vec<PhysicsEvent> {
+		self.owners.get_mut().unwrap().retain(|handle, _| colliders.contains(*handle));
+		
+		self.events.get_mut().unwrap().drain(..).collect()
+	}
+	
+	fn owner(&self, handle: ColliderHandle, colliders: &ColliderSet) -> (EntityRef, ComponentRef<ColliderComponent>) {
+		let mut owners = self.owners.lock().unwrap();
+		let user_data = match colliders.get(handle) {
+			Some(collider) => *owners.entry(handle).or_insert(collider.user_data),
+			None => owners.get(&handle).copied().unwrap_or_default(),
+		};
+		
+		if user_data == 0 {
+			return (EntityRef::null(), ComponentRef::null());
+		}
+		
+		let (eid, cid) = from_user_data(user_data);
+		
+		(EntityRef::new(eid), ComponentRef::new(eid, cid))
+	}
+}
+
+impl EventHandler for PhysicsEvents {
+	fn handle_collision_event(&self, _bodies: &RigidBodySet, colliders: &ColliderSet, event: CollisionEvent, contact_pair: Option<&ContactPair>) {
+		let (entity1, collider1) = self.owner(event.collider1(), colliders);
+		let (entity2, collider2) = self.owner(event.collider2(), colliders);
+		
+		let mut points = vec![];
+		let mut normal = Vec3::zeros();
+		
+		if let (Some(contact_pair), Some(position1)) = (contact_pair, colliders.get(event.collider1()).map(Collider::position)) {
+			for manifold in &contact_pair.manifolds {
+				normal = manifold.data.normal;
+				points.extend(manifold.points.iter().map(|point| position1 * point.local_p1));
+			}
+		}
+		
+		let collision1 = Collision {
+			collider: collider1.clone(),
+			other: entity2,
+			other_collider: collider2.clone(),
+			sensor: event.sensor(),
+			removed: event.removed(),
+			points,
+			normal,
+		};
+		
+		let collision2 = Collision {
+			collider: collider2,
+			other: entity1,
+			other_collider: collider1,
+			normal: -normal,
+			..collision1.clone()
+		};
+		
+		let mut events = self.events.lock().unwrap();
+		
+		if event.started() {
+			events.push(PhysicsEvent::CollisionStart(collision1));
+			events.push(PhysicsEvent::CollisionStart(collision2));
+		} else {
+			events.push(PhysicsEvent::CollisionEnd(collision1));
+			events.push(PhysicsEvent::CollisionEnd(collision2));
+		}
+	}
+	
+	fn handle_contact_force_event(&self, dt: f32, _bodies: &RigidBodySet, colliders: &ColliderSet, contact_pair: &ContactPair, total_force_magnitude: f32) {
+		let event = ContactForceEvent::from_contact_pair(dt, contact_pair, total_force_magnitude);
+		let (entity1, collider1) = self.owner(event.collider1, colliders);
+		let (entity2, collider2) = self.owner(event.collider2, colliders);
+		
+		let force1 = ContactForce {
+			collider: collider1.clone(),
+			other: entity2,
+			other_collider: collider2.clone(),
+			total_force: event.total_force,
+			total_force_magnitude: event.total_force_magnitude,
+			max_force_direction: event.max_force_direction,
+			max_force_magnitude: event.max_force_magnitude,
+		};
+		
+		let force2 = ContactForce {
+			collider: collider2,
+			other: entity1,
+			other_collider: collider1,
+			total_force: -event.total_force,
+			max_force_direction: -event.max_force_direction,
+			..force1.clone()
+		};
+		
+		let mut events = self.events.lock().unwrap();
+		events.push(PhysicsEvent::ContactForce(force1));
+		events.push(PhysicsEvent::ContactForce(force2));
+	}
 }
 
 
@@ -34,6 +186,10 @@ impl Physics {
 			collider_set: ColliderSet::new(),
 			gravity: vector!(0.0, -9.81, 0.0),
 			time_scale: 1.0,
+			step_duration: Duration::from_secs(1) / config::get().physics.step_rate.max(1),
+			max_substeps: config::get().physics.max_substeps.max(1),
+			accumulator: Duration::ZERO,
+			time_control: TimeControl::new(history),
 			integration_parameters: IntegrationParameters {
 				erp: 0.8,
 				joint_erp: 0.5,
@@ -48,8 +204,38 @@ impl Physics {
 			multibody_joint_set: MultibodyJointSet::new(),
 			ccd_solver: CCDSolver::new(),
 			physics_hooks: (),
-			event_handler: (),
+			event_handler: PhysicsEvents::new(),
+		}
+	}
+	
+	/// Adds frame time to the accumulator and returns how many fixed steps should be simulated this frame.
+	pub fn accumulate(&mut self, delta_time: Duration) -> u32 {
+		if self.time_control.paused {
+			self.accumulator = Duration::ZERO;
+			return self.time_control.take_pending_steps();
+		}
+		
+		self.accumulator += delta_time;
+		
+		let steps = (self.accumulator.as_nanos() / self.step_duration.as_nanos()) as u32;
+		
+		if steps > self.max_substeps {
+			self.accumulator = Duration::ZERO;
+			return self.max_substeps;
 		}
+		
+		self.accumulator -= self.step_duration * steps;
+		
+		steps
+	}
+	
+	/// Fraction of the next step that has already elapsed, used to blend between the last two physics states.
+	pub fn interpolation_alpha(&self) -> f32 {
+		if self.time_control.paused {
+			return 1.0;
+		}
+		
+		(self.accumulator.as_secs_f32() / self.step_duration.as_secs_f32()).clamp(0.0, 1.0)
 	}
 	
 	pub fn step(&mut self, delta_time: Duration) {
@@ -71,6 +257,25 @@ impl Physics {
 		
 		self.query_pipeline.update(&self.rigid_body_set,
 		                           &self.collider_set);
+		
+		if self.time_control.capacity() > 0 {
+			let snapshot = PhysicsSnapshot::capture(self);
+			self.time_control.record(snapshot);
+		}
+	}
+	
+	/// Applies snapshot selected in time control, if any. Returns true if physics state has changed.
+	pub fn restore_snapshot(&mut self) -> bool {
+		if let Some(snapshot) = self.time_control.take_restore() {
+			snapshot.restore(self);
+			
+			self.query_pipeline.update(&self.rigid_body_set,
+			                           &self.collider_set);
+			
+			true
+		} else {
+			false
+		}
 	}
 	
 	pub fn debug_draw(&self, application: &Application) {
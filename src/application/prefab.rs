@@ -0,0 +1,341 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use linked_hash_map::LinkedHashMap;
+use thiserror::Error;
+use serde::Deserialize;
+use rapier3d::dynamics::{GenericJoint, GenericJointBuilder, JointAxesMask, JointAxis, RigidBodyBuilder, RigidBodyType};
+use rapier3d::geometry::{ActiveEvents, Collider, ColliderBuilder, ColliderShape, Group, InteractionGroups};
+
+use crate::component::registry;
+use crate::component::model::SimpleModel;
+use crate::component::model::simple::SimpleAsset;
+use crate::component::physics::joint::JointComponent;
+use crate::component::seat::Seat;
+use crate::math::{AABB, Isometry3, PI, Vec3};
+use crate::renderer::assets_manager::AssetsManager;
+use super::{Application, Entity, EntityRef};
+use super::entity::EntityBuilder;
+use super::scene::{ColliderSave, ComponentSave, RigidBodyTypeDef, TagSave};
+
+
+const MAX_DEPTH: usize = 16;
+
+/// Collider generated from the model's AABB (or `phys_aabb`) when prefab has no explicit colliders.
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum PropCollider {
+	Box,
+	Sphere,
+	CylinderX,
+	CylinderY,
+	CylinderZ,
+	Capsule,
+	ConePX,
+	ConePY,
+	ConePZ,
+	ConeNX,
+	ConeNY,
+	ConeNZ,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Prefab {
+	pub name: Option<String>,
+	pub model: Option<PathBuf>,
+	pub texture: Option<PathBuf>,
+	#[serde(default)] pub collider: PropCollider,
+	pub phys_aabb: Option<[f32; 6]>,
+	pub tip: Option<String>,
+	pub seat: Option<[f32; 6]>,
+	#[serde(default = "default_body_type", with = "RigidBodyTypeDef")] pub body_type: RigidBodyType,
+	#[serde(default = "default_density")] pub density: f32,
+	#[serde(default)] pub mass: f32,
+	#[serde(default = "default_gravity_scale")] pub gravity_scale: f32,
+	#[serde(default)] pub linear_damping: f32,
+	#[serde(default)] pub angular_damping: f32,
+	pub groups: Option<[u32; 2]>,
+	/// Reports collisions of the generated collider to components, explicit `colliders` have their own flag.
+	#[serde(default)] pub collision_events: bool,
+	#[serde(default)] pub tags: BTreeMap<String, TagSave>,
+	#[serde(default)] pub colliders: Vec<ColliderSave>,
+	#[serde(default)] pub components: Vec<ComponentSave>,
+	#[serde(default)] pub children: Vec<PrefabChild>,
+	#[serde(default)] pub joints: Vec<PrefabJoint>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PrefabChild {
+	pub prefab: String,
+	/// Used to reference this child in joints.
+	pub name: Option<String>,
+	#[serde(default = "Isometry3::identity")] pub position: Isometry3,
+	#[serde(default)] pub follow: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PrefabJoint {
+	/// Child name, or the prefab itself if omitted.
+	pub a: Option<String>,
+	pub b: Option<String>,
+	#[serde(default)] pub kind: JointKind,
+	#[serde(default = "Isometry3::identity")] pub frame_a: Isometry3,
+	#[serde(default = "Isometry3::identity")] pub frame_b: Isometry3,
+	/// Limits of the free axis (X) of revolute and prismatic joints.
+	pub limits: Option<[f32; 2]>,
+}
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JointKind {
+	Fixed,
+	Spherical,
+	Revolute,
+	Prismatic,
+}
+
+pub struct PrefabCollection {
+	prefabs: LinkedHashMap<String, Prefab>,
+}
+
+impl PrefabCollection {
+	pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+		let file = fs::read_to_string(AssetsManager::find_asset_path(path)?)?;
+		
+		Ok(PrefabCollection {
+			prefabs: toml::from_str(&file)?,
+		})
+	}
+	
+	pub fn get(&self, name: &str) -> Option<&Prefab> {
+		self.prefabs.get(name)
+	}
+	
+	pub fn iter(&self) -> impl Iterator<Item = (&String, &Prefab)> {
+		self.prefabs.iter()
+	}
+	
+	pub fn spawn(&self, name: &str, position: Isometry3, application: &Application) -> Result<EntityRef> {
+		let entities = self.instantiate(name, position, None, application, 0)?;
+		let root = entities[0].as_ref();
+		
+		for entity in entities {
+			application.add_entity(entity);
+		}
+		
+		Ok(root)
+	}
+	
+	/// Builds the prefab and all its descendants. The prefab's root entity is always first.
+	fn instantiate(&self, name: &str, position: Isometry3, parent: Option<(EntityRef, bool)>, application: &Application, depth: usize) -> Result<Vec<Entity>> {
+		if depth > MAX_DEPTH {
+			return Err(PrefabRecursionError(name.to_string()).into());
+		}
+		
+		let prefab = self.get(name).ok_or_else(|| UnknownPrefabError(name.to_string()))?;
+		
+		let mut builder = prefab.builder(name, application)?
+		                        .position(position);
+		
+		if let Some((parent, follow)) = parent {
+			builder = builder.parent(parent, follow);
+		}
+		
+		let mut entities = vec![builder.build()];
+		let mut named = HashMap::new();
+		
+		for child in &prefab.children {
+			let spawned = self.instantiate(&child.prefab,
+			                               position * child.position,
+			                               Some((entities[0].as_ref(), child.follow)),
+			                               application,
+			                               depth + 1)?;
+			
+			if let Some(child_name) = &child.name {
+				named.insert(child_name.as_str(), entities.len());
+			}
+			
+			entities.extend(spawned);
+		}
+		
+		for joint in &prefab.joints {
+			let find = |child: &Option<String>| match child {
+				Some(child) => named.get(child.as_str()).copied().ok_or_else(|| UnknownPrefabChildError(child.clone(), name.to_string())),
+				None => Ok(0),
+			};
+			
+			let a = find(&joint.a)?;
+			let b = find(&joint.b)?;
+			
+			entities[a].add_component(JointComponent::new(joint.build(), entities[b].as_ref()));
+		}
+		
+		Ok(entities)
+	}
+}
+
+impl Prefab {
+	fn builder(&self, key: &str, application: &Application) -> Result<EntityBuilder> {
+		let mut builder = Entity::builder(self.name.as_deref().unwrap_or(key))
+			.rigid_body(RigidBodyBuilder::new(self.body_type)
+			                             .additional_mass(self.mass)
+			                             .gravity_scale(self.gravity_scale)
+			                             .linear_damping(self.linear_damping)
+			                             .angular_damping(self.angular_damping)
+			                             .build());
+		
+		let model = self.load_model(application)?;
+		let aabb = match (self.phys_aabb, &model) {
+			(Some([x1, y1, z1, x2, y2, z2]), _) => Some(AABB::new(point!(x1, y1, z1), point!(x2, y2, z2))),
+			(None, Some(model)) => Some(model.aabb()),
+			(None, None) => None,
+		};
+		
+		if let Some(model) = model {
+			builder = builder.component(model);
+		}
+		
+		if self.colliders.is_empty() {
+			if let Some(aabb) = aabb {
+				let mut collider = self.collider.build(aabb, self.density);
+				
+				if let Some([memberships, filter]) = self.groups {
+					collider.set_collision_groups(InteractionGroups::new(Group::from_bits_truncate(memberships),
+					                                                     Group::from_bits_truncate(filter)));
+				}
+				
+				if self.collision_events {
+					collider.set_active_events(ActiveEvents::COLLISION_EVENTS);
+				}
+				
+				builder = builder.collider(collider);
+			}
+		}
+		
+		for collider in &self.colliders {
+			builder = builder.collider(collider.build());
+		}
+		
+		if let Some([x1, y1, z1, x2, y2, z2]) = self.seat {
+			builder = builder.component(Seat::new(AABB::new(point!(x1, y1, z1), point!(x2, y2, z2))));
+		}
+		
+		for (name, tag) in &self.tags {
+			builder = match *tag {
+				TagSave::Bool(value) => builder.tag(name, value),
+				TagSave::Hand(value) => builder.tag(name, value),
+			};
+		}
+		
+		for component in &self.components {
+			if let Some(component) = registry::load(&component.name, component.params.clone(), application)? {
+				builder.components.push(component);
+			}
+		}
+		
+		Ok(builder)
+	}
+	
+	/// Model is skipped in headless mode.
+	pub fn load_model(&self, application: &Application) -> Result<Option<SimpleModel>> {
+		match self.asset() {
+			Some(asset) if !application.headless() => Ok(Some(application.renderer()?.load(asset)?)),
+			_ => Ok(None),
+		}
+	}
+	
+	pub fn asset(&self) -> Option<SimpleAsset> {
+		Some(SimpleAsset::at(self.model.as_ref()?, self.texture.as_deref()))
+	}
+}
+
+impl PropCollider {
+	pub fn build(self, aabb: AABB, density: f32) -> Collider {
+		let extents = aabb.extents();
+		let center = aabb.center();
+		
+		let collider = match self {
+			PropCollider::Box       => ColliderBuilder::new(ColliderShape::cuboid(extents.x / 2.0, extents.y / 2.0, extents.z / 2.0)).translation(center.coords),
+			PropCollider::Sphere    => ColliderBuilder::new(ColliderShape::ball(extents.max() / 2.0)).translation(center.coords),
+			PropCollider::CylinderX => ColliderBuilder::new(ColliderShape::cylinder(extents.x / 2.0, extents.yz().max() / 2.0)).translation(center.coords).rotation(vector!(0.0, 0.0, PI / 2.0)),
+			PropCollider::CylinderY => ColliderBuilder::new(ColliderShape::cylinder(extents.y / 2.0, extents.xz().max() / 2.0)).translation(center.coords),
+			PropCollider::CylinderZ => ColliderBuilder::new(ColliderShape::cylinder(extents.z / 2.0, extents.xy().max() / 2.0)).translation(center.coords).rotation(vector!(PI / 2.0, 0.0, 0.0)),
+			PropCollider::ConePX    => ColliderBuilder::new(ColliderShape::cone(extents.x / 2.0, extents.yz().max() / 2.0)).translation(center.coords).rotation(vector!(0.0, 0.0, PI / 2.0)),
+			PropCollider::ConePY    => ColliderBuilder::new(ColliderShape::cone(extents.y / 2.0, extents.xz().max() / 2.0)).translation(center.coords),
+			PropCollider::ConePZ    => ColliderBuilder::new(ColliderShape::cone(extents.z / 2.0, extents.xy().max() / 2.0)).translation(center.coords).rotation(vector!(PI / 2.0, 0.0, 0.0)),
+			PropCollider::ConeNX    => ColliderBuilder::new(ColliderShape::cone(extents.x / 2.0, extents.yz().max() / 2.0)).translation(center.coords).rotation(vector!(0.0, 0.0, -PI / 2.0)),
+			PropCollider::ConeNY    => ColliderBuilder::new(ColliderShape::cone(extents.y / 2.0, extents.xz().max() / 2.0)).translation(center.coords).rotation(vector!(0.0, 0.0, PI)),
+			PropCollider::ConeNZ    => ColliderBuilder::new(ColliderShape::cone(extents.z / 2.0, extents.xy().max() / 2.0)).translation(center.coords).rotation(vector!(-PI / 2.0, 0.0, 0.0)),
+			PropCollider::Capsule   => {
+				let max = extents.max();
+				let radius;
+				let offset;
+				
+				if extents.x == max {
+					radius = extents.yz().max() / 2.0;
+					offset = *Vec3::x_axis() * (extents.x - radius) / 2.0;
+				} else if extents.y == max {
+					radius = extents.xz().max() / 2.0;
+					offset = *Vec3::y_axis() * (extents.y - radius) / 2.0;
+				} else {
+					radius = extents.xy().max() / 2.0;
+					offset = *Vec3::z_axis() * (extents.z - radius) / 2.0;
+				}
+				
+				ColliderBuilder::new(ColliderShape::capsule(center - offset, center + offset, radius))
+			},
+		};
+		
+		collider.density(density)
+		        .build()
+	}
+}
+
+impl Default for PropCollider {
+	fn default() -> Self {
+		PropCollider::Box
+	}
+}
+
+impl PrefabJoint {
+	fn build(&self) -> GenericJoint {
+		let (locked_axes, free_axis) = match self.kind {
+			JointKind::Fixed => (JointAxesMask::LOCKED_FIXED_AXES, None),
+			JointKind::Spherical => (JointAxesMask::LOCKED_SPHERICAL_AXES, None),
+			JointKind::Revolute => (JointAxesMask::LOCKED_REVOLUTE_AXES, Some(JointAxis::AngX)),
+			JointKind::Prismatic => (JointAxesMask::LOCKED_PRISMATIC_AXES, Some(JointAxis::X)),
+		};
+		
+		let mut joint = GenericJointBuilder::new(locked_axes)
+			.local_frame1(self.frame_a)
+			.local_frame2(self.frame_b);
+		
+		if let (Some(axis), Some(limits)) = (free_axis, self.limits) {
+			joint = joint.limits(axis, limits);
+		}
+		
+		joint.build()
+	}
+}
+
+impl Default for JointKind {
+	fn default() -> Self {
+		JointKind::Fixed
+	}
+}
+
+fn default_body_type() -> RigidBodyType { RigidBodyType::Dynamic }
+fn default_density() -> f32 { 100.0 }
+fn default_gravity_scale() -> f32 { 1.0 }
+
+#[derive(Debug, Error)]
+#[error("Unknown prefab: {0}")]
+pub struct UnknownPrefabError(String);
+
+#[derive(Debug, Error)]
+#[error("Prefab {0} exceeds maximum nesting depth. Does it contain itself?")]
+pub struct PrefabRecursionError(String);
+
+#[derive(Debug, Error)]
+#[error("Unknown child {0} in prefab {1}")]
+pub struct UnknownPrefabChildError(String, String);
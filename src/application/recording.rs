@@ -0,0 +1,289 @@
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::Path;
+use std::time::Duration;
+use anyhow::Result;
+use thiserror::Error;
+use openvr::{ControllerAxis, ControllerState, TrackedDeviceIndex, TrackedDevicePose};
+use openvr::compositor::WaitPoses;
+use openvr_sys::{TrackedDevicePose_t, HmdMatrix34_t, HmdVector3_t, ETrackingResult_TrackingResult_Running_OK, ETrackingResult_TrackingResult_Uninitialized};
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+
+use crate::config;
+use super::{Input, Key, MouseButton};
+use super::input::InputDevice;
+
+
+const RECORDING_VERSION: u32 = 2;
+
+/// First line of a recording file. Replays are only deterministic if started with the same scene and physics step rate.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RecordingHeader {
+	pub version: u32,
+	pub step_rate: u32,
+	pub scene: String,
+	/// Seed of `Application::rng`.
+	pub seed: u64,
+}
+
+/// Inputs and tracked device poses of a single frame.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct FrameRecord {
+	pub delta_time: Duration,
+	pub keyboard: DeviceRecord<Key>,
+	pub mouse: DeviceRecord<MouseButton>,
+	pub controller_left: Option<TrackedDeviceIndex>,
+	pub controller_right: Option<TrackedDeviceIndex>,
+	pub controllers: Vec<ControllerRecord>,
+	pub render_poses: Vec<PoseRecord>,
+	pub game_poses: Vec<PoseRecord>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DeviceRecord<K> {
+	pub pressed: Vec<K>,
+	/// Buttons pressed and released within the same frame.
+	pub tapped: Vec<K>,
+	pub axis: Vec<(usize, f32)>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ControllerRecord {
+	pub index: TrackedDeviceIndex,
+	pub packet_num: u32,
+	pub button_pressed: u64,
+	pub button_touched: u64,
+	pub axis: [[f32; 2]; 5],
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PoseRecord {
+	pub index: TrackedDeviceIndex,
+	pub device_to_absolute_tracking: [[f32; 4]; 3],
+	pub velocity: [f32; 3],
+	pub angular_velocity: [f32; 3],
+	pub valid: bool,
+}
+
+impl FrameRecord {
+	pub fn capture(delta_time: Duration, input: &Input, poses: &WaitPoses) -> Self {
+		let mut controllers: Vec<ControllerRecord> = input.controller_state
+		                                                  .iter()
+		                                                  .map(|(&index, state)| ControllerRecord::capture(index, state))
+		                                                  .collect();
+		controllers.sort_by_key(|controller| controller.index);
+		
+		FrameRecord {
+			delta_time,
+			keyboard: DeviceRecord::capture(&input.keyboard),
+			mouse: DeviceRecord::capture(&input.mouse),
+			controller_left: input.controller_left,
+			controller_right: input.controller_right,
+			controllers,
+			render_poses: PoseRecord::capture_all(&poses.render),
+			game_poses: PoseRecord::capture_all(&poses.game),
+		}
+	}
+	
+	/// Feeds recorded state into `input` the same way live devices would. `input` should be reset beforehand.
+	pub fn apply(&self, input: &mut Input, poses: &mut WaitPoses) {
+		self.keyboard.apply(&mut input.keyboard);
+		self.mouse.apply(&mut input.mouse);
+		
+		if let Some(id) = self.controller_left {
+			input.set_controller_id(super::Hand::Left, id);
+		}
+		
+		if let Some(id) = self.controller_right {
+			input.set_controller_id(super::Hand::Right, id);
+		}
+		
+		for controller in &self.controllers {
+			input.update_controller(controller.index, controller.state());
+		}
+		
+		PoseRecord::apply_all(&self.render_poses, &mut poses.render);
+		PoseRecord::apply_all(&self.game_poses, &mut poses.game);
+	}
+}
+
+impl<K: Copy + Hash + Eq + Ord> DeviceRecord<K> {
+	fn capture(device: &InputDevice<K>) -> Self {
+		let mut pressed: Vec<K> = device.buttons.iter().filter(|(_, state)| state.pressed).map(|(&key, _)| key).collect();
+		let mut tapped: Vec<K> = device.buttons.iter().filter(|(_, state)| state.down && !state.pressed).map(|(&key, _)| key).collect();
+		let mut axis: Vec<(usize, f32)> = device.axis.iter().filter(|(_, value)| **value != 0.0).map(|(&id, &value)| (id, value)).collect();
+		
+		pressed.sort();
+		tapped.sort();
+		axis.sort_by_key(|(id, _)| *id);
+		
+		DeviceRecord { pressed, tapped, axis }
+	}
+	
+	fn apply(&self, device: &mut InputDevice<K>) {
+		let released: Vec<K> = device.buttons.iter()
+		                                     .filter(|(key, state)| state.pressed && !self.pressed.contains(*key))
+		                                     .map(|(&key, _)| key)
+		                                     .collect();
+		
+		for key in released {
+			device.update_button(key, false);
+		}
+		
+		for &key in &self.pressed {
+			device.update_button(key, true);
+		}
+		
+		for &key in &self.tapped {
+			device.update_button(key, true);
+			device.update_button(key, false);
+		}
+		
+		for &(id, value) in &self.axis {
+			device.update_axis(id, value);
+		}
+	}
+}
+
+impl ControllerRecord {
+	fn capture(index: TrackedDeviceIndex, state: &ControllerState) -> Self {
+		ControllerRecord {
+			index,
+			packet_num: state.packet_num,
+			button_pressed: state.button_pressed,
+			button_touched: state.button_touched,
+			axis: state.axis.map(|axis| [axis.x, axis.y]),
+		}
+	}
+	
+	fn state(&self) -> ControllerState {
+		ControllerState {
+			packet_num: self.packet_num,
+			button_pressed: self.button_pressed,
+			button_touched: self.button_touched,
+			axis: self.axis.map(|[x, y]| ControllerAxis { x, y }),
+		}
+	}
+}
+
+impl PoseRecord {
+	fn capture_all(poses: &[TrackedDevicePose]) -> Vec<Self> {
+		poses.iter()
+		     .enumerate()
+		     .filter(|(_, pose)| pose.device_is_connected())
+		     .map(|(index, pose)| PoseRecord {
+			     index: index as TrackedDeviceIndex,
+			     device_to_absolute_tracking: *pose.device_to_absolute_tracking(),
+			     velocity: *pose.velocity(),
+			     angular_velocity: *pose.angular_velocity(),
+			     valid: pose.pose_is_valid(),
+		     })
+		     .collect()
+	}
+	
+	fn apply_all(records: &[Self], poses: &mut [TrackedDevicePose]) {
+		for record in records {
+			if let Some(pose) = poses.get_mut(record.index as usize) {
+				*pose = TrackedDevicePose::from(TrackedDevicePose_t {
+					mDeviceToAbsoluteTracking: HmdMatrix34_t { m: record.device_to_absolute_tracking },
+					vVelocity: HmdVector3_t { v: record.velocity },
+					vAngularVelocity: HmdVector3_t { v: record.angular_velocity },
+					eTrackingResult: if record.valid { ETrackingResult_TrackingResult_Running_OK } else { ETrackingResult_TrackingResult_Uninitialized },
+					bPoseIsValid: record.valid,
+					bDeviceIsConnected: true,
+				});
+			}
+		}
+	}
+}
+
+/// Writes recording as JSON lines, so that sessions which crashed are still readable up to the last frame.
+pub struct InputRecorder {
+	writer: BufWriter<File>,
+}
+
+impl InputRecorder {
+	pub fn create(path: impl AsRef<Path>, seed: u64) -> Result<Self> {
+		let mut recorder = InputRecorder {
+			writer: BufWriter::new(File::create(path)?),
+		};
+		
+		let config = config::get();
+		recorder.write(&RecordingHeader {
+			version: RECORDING_VERSION,
+			step_rate: config.physics.step_rate,
+			scene: config.scene.clone(),
+			seed,
+		})?;
+		
+		Ok(recorder)
+	}
+	
+	pub fn record(&mut self, frame: &FrameRecord) -> Result<()> {
+		self.write(frame)
+	}
+	
+	fn write(&mut self, value: &impl Serialize) -> Result<()> {
+		serde_json::to_writer(&mut self.writer, value)?;
+		self.writer.write_all(b"\n")?;
+		self.writer.flush()?;
+		
+		Ok(())
+	}
+}
+
+pub struct InputReplay {
+	pub header: RecordingHeader,
+	lines: Lines<BufReader<File>>,
+	frame: usize,
+}
+
+impl InputReplay {
+	pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+		let mut lines = BufReader::new(File::open(path)?).lines();
+		let header: RecordingHeader = Self::parse(&mut lines)?.ok_or(RecordingVersionError(None))?;
+		
+		if header.version != RECORDING_VERSION {
+			return Err(RecordingVersionError(Some(header.version)).into());
+		}
+		
+		let config = config::get();
+		if header.step_rate != config.physics.step_rate || header.scene != config.scene {
+			eprintln!("Warning: Recording was made with step rate {} and scene {:?}, replay may diverge.", header.step_rate, header.scene);
+		}
+		
+		Ok(InputReplay {
+			header,
+			lines,
+			frame: 0,
+		})
+	}
+	
+	/// Returns `None` once the recording has ended.
+	pub fn next_frame(&mut self) -> Result<Option<FrameRecord>> {
+		let frame = Self::parse(&mut self.lines)?;
+		
+		if frame.is_some() {
+			self.frame += 1;
+		}
+		
+		Ok(frame)
+	}
+	
+	pub fn frame(&self) -> usize {
+		self.frame
+	}
+	
+	fn parse<T: DeserializeOwned>(lines: &mut Lines<BufReader<File>>) -> Result<Option<T>> {
+		match lines.next() {
+			Some(line) => Ok(Some(serde_json::from_str(&line?)?)),
+			None => Ok(None),
+		}
+	}
+}
+
+#[derive(Debug, Error)]
+#[error("Unsupported recording version: {0:?}")]
+pub struct RecordingVersionError(Option<u32>);
@@ -0,0 +1,321 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use rapier3d::dynamics::{RigidBody, RigidBodyBuilder, RigidBodyType};
+use rapier3d::geometry::{ActiveEvents, Collider, ColliderBuilder, Group, InteractionGroups, SharedShape, TypedShape};
+
+use crate::component::{registry, ComponentBase};
+use crate::component::physics::collider::ColliderComponent;
+use crate::math::{Isometry3, Point3, Vec3};
+use super::{Application, Entity, EntityRef, Hand};
+
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Scene {
+	#[serde(default)] pub entities: Vec<EntitySave>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct EntitySave {
+	pub id: u64,
+	pub name: String,
+	#[serde(default)] pub parent: Option<u64>,
+	#[serde(default)] pub parent_offset: Option<Isometry3>,
+	pub position: Isometry3,
+	#[serde(default)] pub velocity: Vec3,
+	#[serde(default)] pub angular_velocity: Vec3,
+	#[serde(default)] pub hidden: bool,
+	#[serde(default)] pub frozen: bool,
+	#[serde(default)] pub persist: bool,
+	#[serde(default)] pub tags: BTreeMap<String, TagSave>,
+	#[serde(default)] pub rigid_body: RigidBodySave,
+	#[serde(default)] pub colliders: Vec<ColliderSave>,
+	#[serde(default)] pub components: Vec<ComponentSave>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum TagSave {
+	Bool(bool),
+	Hand(Hand),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RigidBodySave {
+	#[serde(with = "RigidBodyTypeDef")] pub body_type: RigidBodyType,
+	pub gravity_scale: f32,
+	pub linear_damping: f32,
+	pub angular_damping: f32,
+	pub ccd: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(remote = "RigidBodyType", rename_all = "snake_case")]
+pub(super) enum RigidBodyTypeDef {
+	Dynamic,
+	Fixed,
+	KinematicPositionBased,
+	KinematicVelocityBased,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ColliderSave {
+	pub shape: ShapeSave,
+	#[serde(default = "Isometry3::identity")] pub position: Isometry3,
+	#[serde(default = "default_density")] pub density: f32,
+	#[serde(default = "default_friction")] pub friction: f32,
+	#[serde(default)] pub restitution: f32,
+	#[serde(default)] pub sensor: bool,
+	#[serde(default = "default_groups")] pub memberships: u32,
+	#[serde(default = "default_groups")] pub filter: u32,
+	#[serde(default)] pub collision_events: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ShapeSave {
+	Ball { radius: f32 },
+	Cuboid { half_extents: Vec3 },
+	Capsule { a: Point3, b: Point3, radius: f32 },
+	Cylinder { half_height: f32, radius: f32 },
+	Cone { half_height: f32, radius: f32 },
+	Halfspace { normal: Vec3 },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ComponentSave {
+	pub name: String,
+	#[serde(default = "empty_params")] pub params: toml::Value,
+}
+
+fn empty_params() -> toml::Value {
+	toml::Table::new().into()
+}
+
+fn default_density() -> f32 { 1.0 }
+fn default_friction() -> f32 { 0.5 }
+fn default_groups() -> u32 { u32::MAX }
+
+impl Scene {
+	pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+		let file = fs::read_to_string(path)?;
+		
+		Ok(toml::from_str(&file)?)
+	}
+	
+	pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+		fs::write(path, toml::to_string_pretty(self)?)?;
+		
+		Ok(())
+	}
+	
+	pub fn capture(application: &Application) -> Result<Self> {
+		let mut entities = vec![];
+		
+		for entity in application.dfs_entities() {
+			if entity.tag("NoSave").unwrap_or_default() || entity.is_being_removed() {
+				continue;
+			}
+			
+			if let Some(parent) = entity.parent().get(application) {
+				if !entities.iter().any(|saved: &EntitySave| saved.id == parent.id) {
+					continue;
+				}
+			}
+			
+			entities.push(EntitySave::capture(entity, application)?);
+		}
+		
+		Ok(Scene { entities })
+	}
+	
+	pub fn spawn(&self, application: &Application) -> Result<Vec<EntityRef>> {
+		let mut refs = HashMap::new();
+		let mut spawned = vec![];
+		
+		for save in &self.entities {
+			let parent = save.parent
+			                 .and_then(|id| refs.get(&id).cloned())
+			                 .unwrap_or_else(EntityRef::null);
+			
+			let entity = save.build(parent, application)?;
+			
+			refs.insert(save.id, entity.as_ref());
+			spawned.push(application.add_entity(entity));
+		}
+		
+		Ok(spawned)
+	}
+}
+
+impl EntitySave {
+	fn capture(entity: &Entity, application: &Application) -> Result<Self> {
+		let physics = &*application.physics.borrow();
+		let state = entity.state();
+		
+		let tags = entity.tags
+		                 .borrow()
+		                 .iter()
+		                 .filter_map(|(name, value)| {
+			                 if let Some(value) = value.downcast_ref::<bool>() { Some((name.clone(), TagSave::Bool(*value))) }
+			                 else if let Some(value) = value.downcast_ref::<Hand>() { Some((name.clone(), TagSave::Hand(*value))) }
+			                 else { None }
+		                 })
+		                 .collect();
+		
+		let mut rigid_body = RigidBodySave::from(entity.rigid_body(physics));
+		if entity.is_frozen() {
+			rigid_body.body_type = RigidBodyType::Dynamic;
+		}
+		
+		let colliders = entity.iter_component_by_type::<ColliderComponent>()
+		                      .filter(|collider| !collider.is_transient())
+		                      .filter_map(|collider| ColliderSave::from_collider(collider.inner(physics), &entity.name))
+		                      .collect();
+		
+		let mut components = vec![];
+		for component in entity.components() {
+			if let Some(params) = component.save(entity, application)? {
+				components.push(ComponentSave {
+					name: component.name().to_string(),
+					params,
+				});
+			}
+		}
+		
+		Ok(EntitySave {
+			id: entity.id,
+			name: entity.name.clone(),
+			parent: entity.parent().get(application).map(|parent| parent.id),
+			parent_offset: entity.parent_offset(),
+			position: *state.position,
+			velocity: *state.velocity,
+			angular_velocity: *state.angular_velocity,
+			hidden: state.hidden,
+			frozen: entity.is_frozen(),
+			persist: entity.persists(),
+			tags,
+			rigid_body,
+			colliders,
+			components,
+		})
+	}
+	
+	fn build(&self, parent: EntityRef, application: &Application) -> Result<Entity> {
+		let mut builder = Entity::builder(&self.name)
+			.position(self.position)
+			.velocity(self.velocity)
+			.angular_velocity(self.angular_velocity)
+			.hidden(self.hidden)
+			.frozen(self.frozen)
+			.parent(parent, self.parent_offset.is_some())
+			.rigid_body(self.rigid_body.build());
+		
+		if self.persist {
+			builder = builder.persist();
+		}
+		
+		for (name, tag) in &self.tags {
+			builder = match *tag {
+				TagSave::Bool(value) => builder.tag(name, value),
+				TagSave::Hand(value) => builder.tag(name, value),
+			};
+		}
+		
+		for collider in &self.colliders {
+			builder = builder.collider(collider.build());
+		}
+		
+		for component in &self.components {
+			if let Some(component) = registry::load(&component.name, component.params.clone(), application)? {
+				builder.components.push(component);
+			}
+		}
+		
+		Ok(builder.build())
+	}
+}
+
+impl From<&RigidBody> for RigidBodySave {
+	fn from(rigid_body: &RigidBody) -> Self {
+		RigidBodySave {
+			body_type: rigid_body.body_type(),
+			gravity_scale: rigid_body.gravity_scale(),
+			linear_damping: rigid_body.linear_damping(),
+			angular_damping: rigid_body.angular_damping(),
+			ccd: rigid_body.is_ccd_enabled(),
+		}
+	}
+}
+
+impl RigidBodySave {
+	fn build(&self) -> RigidBody {
+		RigidBodyBuilder::new(self.body_type)
+		                 .gravity_scale(self.gravity_scale)
+		                 .linear_damping(self.linear_damping)
+		                 .angular_damping(self.angular_damping)
+		                 .ccd_enabled(self.ccd)
+		                 .build()
+	}
+}
+
+impl Default for RigidBodySave {
+	fn default() -> Self {
+		(&RigidBodyBuilder::fixed().build()).into()
+	}
+}
+
+impl ColliderSave {
+	fn from_collider(collider: &Collider, entity_name: &str) -> Option<Self> {
+		let shape = match collider.shape().as_typed_shape() {
+			TypedShape::Ball(ball) => ShapeSave::Ball { radius: ball.radius },
+			TypedShape::Cuboid(cuboid) => ShapeSave::Cuboid { half_extents: cuboid.half_extents },
+			TypedShape::Capsule(capsule) => ShapeSave::Capsule { a: capsule.segment.a, b: capsule.segment.b, radius: capsule.radius },
+			TypedShape::Cylinder(cylinder) => ShapeSave::Cylinder { half_height: cylinder.half_height, radius: cylinder.radius },
+			TypedShape::Cone(cone) => ShapeSave::Cone { half_height: cone.half_height, radius: cone.radius },
+			TypedShape::HalfSpace(halfspace) => ShapeSave::Halfspace { normal: *halfspace.normal },
+			_ => {
+				eprintln!("Unable to save collider of type {:?} in {}", collider.shape().shape_type(), entity_name);
+				return None;
+			},
+		};
+		
+		let groups = collider.collision_groups();
+		
+		Some(ColliderSave {
+			shape,
+			position: collider.position_wrt_parent().copied().unwrap_or_else(|| *collider.position()),
+			density: collider.density(),
+			friction: collider.friction(),
+			restitution: collider.restitution(),
+			sensor: collider.is_sensor(),
+			memberships: groups.memberships.bits(),
+			filter: groups.filter.bits(),
+			collision_events: collider.active_events().contains(ActiveEvents::COLLISION_EVENTS),
+		})
+	}
+	
+	pub(super) fn build(&self) -> Collider {
+		let shape = match self.shape {
+			ShapeSave::Ball { radius } => SharedShape::ball(radius),
+			ShapeSave::Cuboid { half_extents } => SharedShape::cuboid(half_extents.x, half_extents.y, half_extents.z),
+			ShapeSave::Capsule { a, b, radius } => SharedShape::capsule(a, b, radius),
+			ShapeSave::Cylinder { half_height, radius } => SharedShape::cylinder(half_height, radius),
+			ShapeSave::Cone { half_height, radius } => SharedShape::cone(half_height, radius),
+			ShapeSave::Halfspace { normal } => SharedShape::halfspace(nalgebra::Unit::new_normalize(normal)),
+		};
+		
+		ColliderBuilder::new(shape)
+		                .position(self.position)
+		                .density(self.density)
+		                .friction(self.friction)
+		                .restitution(self.restitution)
+		                .sensor(self.sensor)
+		                .collision_groups(InteractionGroups::new(Group::from_bits_truncate(self.memberships),
+		                                                         Group::from_bits_truncate(self.filter)))
+		                .active_events(if self.collision_events { ActiveEvents::COLLISION_EVENTS } else { ActiveEvents::empty() })
+		                .build()
+	}
+}
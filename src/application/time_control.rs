@@ -0,0 +1,187 @@
+use std::collections::VecDeque;
+use rapier3d::dynamics::{ImpulseJoint, ImpulseJointHandle, RigidBodyHandle};
+
+use crate::math::{Isometry3, Vec3};
+use super::Physics;
+
+
+/// State of rigid bodies and joints after a single physics step.
+/// Contact manifolds and solver caches are not captured, so resumed simulation may differ slightly from the original.
+#[derive(Debug, Clone)]
+pub struct PhysicsSnapshot {
+	bodies: Vec<(RigidBodyHandle, BodySnapshot)>,
+	joints: Vec<(ImpulseJointHandle, ImpulseJoint)>,
+}
+
+#[derive(Debug, Clone)]
+struct BodySnapshot {
+	position: Isometry3,
+	linvel: Vec3,
+	angvel: Vec3,
+	sleeping: bool,
+}
+
+impl PhysicsSnapshot {
+	pub fn capture(physics: &Physics) -> Self {
+		let bodies = physics.rigid_body_set
+		                    .iter()
+		                    .map(|(handle, rigid_body)| (handle, BodySnapshot {
+			                    position: *rigid_body.position(),
+			                    linvel: *rigid_body.linvel(),
+			                    angvel: *rigid_body.angvel(),
+			                    sleeping: rigid_body.is_sleeping(),
+		                    }))
+		                    .collect();
+		
+		let joints = physics.impulse_joint_set
+		                    .iter()
+		                    .map(|(handle, joint)| (handle, joint.clone()))
+		                    .collect();
+		
+		PhysicsSnapshot { bodies, joints }
+	}
+	
+	/// Bodies and joints created after the snapshot are left untouched, removed ones are skipped.
+	pub fn restore(&self, physics: &mut Physics) {
+		for (handle, body) in &self.bodies {
+			if let Some(rigid_body) = physics.rigid_body_set.get_mut(*handle) {
+				rigid_body.set_position(body.position, false);
+				rigid_body.set_linvel(body.linvel, false);
+				rigid_body.set_angvel(body.angvel, false);
+				
+				if body.sleeping {
+					rigid_body.sleep();
+				} else {
+					rigid_body.wake_up(true);
+				}
+			}
+		}
+		
+		for (handle, joint) in &self.joints {
+			if let Some(target) = physics.impulse_joint_set.get_mut(*handle) {
+				*target = joint.clone();
+			}
+		}
+	}
+}
+
+pub struct TimeControl {
+	pub paused: bool,
+	pub step_count: u32,
+	history: VecDeque<PhysicsSnapshot>,
+	capacity: usize,
+	cursor: Option<usize>,
+	pending_steps: u32,
+	pending_restore: bool,
+}
+
+impl TimeControl {
+	pub fn new(capacity: usize) -> Self {
+		TimeControl {
+			paused: false,
+			step_count: 1,
+			history: VecDeque::with_capacity(capacity),
+			capacity,
+			cursor: None,
+			pending_steps: 0,
+			pending_restore: false,
+		}
+	}
+	
+	pub fn pause(&mut self) {
+		self.paused = true;
+	}
+	
+	/// Resumes simulation. If history was scrubbed, everything after the current snapshot is discarded.
+	pub fn resume(&mut self) {
+		self.truncate_future();
+		self.paused = false;
+	}
+	
+	pub fn toggle_pause(&mut self) {
+		if self.paused {
+			self.resume();
+		} else {
+			self.pause();
+		}
+	}
+	
+	/// Pauses and schedules `count` steps for the next frame.
+	pub fn step(&mut self, count: u32) {
+		self.truncate_future();
+		self.paused = true;
+		self.pending_steps += count;
+	}
+	
+	/// Pauses and moves `count` snapshots back in history.
+	pub fn rewind(&mut self, count: usize) {
+		let position = self.position().saturating_sub(count);
+		
+		self.seek(position);
+	}
+	
+	/// Pauses and moves to snapshot at `position`, `0` being the oldest one.
+	pub fn seek(&mut self, position: usize) {
+		if self.history.is_empty() {
+			return;
+		}
+		
+		let position = position.min(self.history.len() - 1);
+		
+		self.paused = true;
+		self.pending_steps = 0;
+		self.pending_restore |= self.position() != position;
+		self.cursor = Some(position);
+	}
+	
+	pub fn position(&self) -> usize {
+		self.cursor.unwrap_or(self.history.len().saturating_sub(1))
+	}
+	
+	pub fn len(&self) -> usize {
+		self.history.len()
+	}
+	
+	pub fn capacity(&self) -> usize {
+		self.capacity
+	}
+	
+	pub fn clear(&mut self) {
+		self.history.clear();
+		self.cursor = None;
+		self.pending_restore = false;
+	}
+	
+	pub fn record(&mut self, snapshot: PhysicsSnapshot) {
+		if self.capacity == 0 {
+			return;
+		}
+		
+		self.truncate_future();
+		
+		while self.history.len() >= self.capacity {
+			self.history.pop_front();
+		}
+		
+		self.history.push_back(snapshot);
+	}
+	
+	pub(super) fn take_pending_steps(&mut self) -> u32 {
+		std::mem::take(&mut self.pending_steps)
+	}
+	
+	/// Returns the snapshot physics should be reset to, if history was scrubbed since the last call.
+	pub(super) fn take_restore(&mut self) -> Option<PhysicsSnapshot> {
+		if !std::mem::take(&mut self.pending_restore) {
+			return None;
+		}
+		
+		self.history.get(self.position()).cloned()
+	}
+	
+	fn truncate_future(&mut self) {
+		if let Some(cursor) = self.cursor.take() {
+			self.history.truncate(cursor + 1);
+		}
+	}
+}
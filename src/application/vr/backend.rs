@@ -0,0 +1,123 @@
+use std::time::Duration;
+use anyhow::Result;
+use image::{DynamicImage, ImageBuffer};
+use openvr::{ControllerState, Eye, TrackedControllerRole, TrackedDeviceClass, TrackedDeviceIndex, render_models};
+use openvr::compositor::WaitPoses;
+use openvr_sys::ETrackedDeviceProperty_Prop_RenderModelName_String;
+
+use crate::component::model::simple::Vertex;
+use crate::math::Vec4;
+use super::super::Input;
+use super::VR;
+
+
+/// Source of tracked devices, controller inputs and HMD optics.
+/// Implemented by OpenVR runtime and by `SimulatedVr`, which fakes a headset and two controllers.
+pub trait VrBackend {
+	/// Returns underlying OpenVR runtime, if any. Compositor, tracked cameras and Vulkan interop require it.
+	fn openvr(&self) -> Option<&VR> { None }
+	
+	/// Called once per frame, before poses are fetched.
+	fn update(&self, _input: &Input, _delta_time: Duration) -> Result<()> { Ok(()) }
+	
+	fn wait_get_poses(&self) -> Result<WaitPoses>;
+	fn is_tracked_device_connected(&self, index: TrackedDeviceIndex) -> bool;
+	fn tracked_device_class(&self, index: TrackedDeviceIndex) -> TrackedDeviceClass;
+	fn controller_role(&self, index: TrackedDeviceIndex) -> Option<TrackedControllerRole>;
+	fn tracked_device_index_for_controller_role(&self, role: TrackedControllerRole) -> Option<TrackedDeviceIndex>;
+	fn controller_state(&self, index: TrackedDeviceIndex) -> Option<ControllerState>;
+	fn render_model(&self, index: TrackedDeviceIndex) -> Result<Option<VrRenderModel>>;
+	
+	fn recommended_render_target_size(&self) -> (u32, u32);
+	fn eye_to_head_transform(&self, eye: Eye) -> [[f32; 4]; 3];
+	fn projection_matrix(&self, eye: Eye, near: f32, far: f32) -> [[f32; 4]; 4];
+	/// Tangents of half angles of eye frustum: left, right, top, bottom.
+	fn raw_projection(&self, eye: Eye) -> Vec4;
+}
+
+pub struct VrRenderModel {
+	pub vertices: Vec<Vertex>,
+	pub indices: Vec<u16>,
+	pub texture: DynamicImage,
+}
+
+impl VrBackend for VR {
+	fn openvr(&self) -> Option<&VR> {
+		Some(self)
+	}
+	
+	fn wait_get_poses(&self) -> Result<WaitPoses> {
+		Ok(self.lock().unwrap().compositor.wait_get_poses()?)
+	}
+	
+	fn is_tracked_device_connected(&self, index: TrackedDeviceIndex) -> bool {
+		self.lock().unwrap().system.is_tracked_device_connected(index)
+	}
+	
+	fn tracked_device_class(&self, index: TrackedDeviceIndex) -> TrackedDeviceClass {
+		self.lock().unwrap().system.tracked_device_class(index)
+	}
+	
+	fn controller_role(&self, index: TrackedDeviceIndex) -> Option<TrackedControllerRole> {
+		self.lock().unwrap().system.get_controller_role_for_tracked_device_index(index)
+	}
+	
+	fn tracked_device_index_for_controller_role(&self, role: TrackedControllerRole) -> Option<TrackedDeviceIndex> {
+		self.lock().unwrap().system.tracked_device_index_for_controller_role(role)
+	}
+	
+	fn controller_state(&self, index: TrackedDeviceIndex) -> Option<ControllerState> {
+		self.lock().unwrap().system.controller_state(index)
+	}
+	
+	fn render_model(&self, index: TrackedDeviceIndex) -> Result<Option<VrRenderModel>> {
+		let vr = self.lock().unwrap();
+		let model_name = vr.system.string_tracked_device_property(index, ETrackedDeviceProperty_Prop_RenderModelName_String)?;
+		
+		let model = match vr.render_models.load_render_model(&model_name) {
+			Ok(Some(model)) => model,
+			Ok(None) => return Ok(None),
+			Err(err) => {
+				dprintln!("Failed to load model \"{}\": {}", model_name.to_string_lossy(), err);
+				return Ok(None);
+			}
+		};
+		
+		let texture = match vr.render_models.load_texture(model.diffuse_texture_id().unwrap())? {
+			Some(texture) => texture,
+			None => return Ok(None),
+		};
+		
+		let size = texture.dimensions();
+		
+		Ok(Some(VrRenderModel {
+			vertices: model.vertices().iter().map(Into::into).collect(),
+			indices: model.indices().iter().copied().map(Into::into).collect(),
+			texture: DynamicImage::ImageRgba8(ImageBuffer::from_raw(size.0 as u32, size.1 as u32, texture.data().into()).unwrap()),
+		}))
+	}
+	
+	fn recommended_render_target_size(&self) -> (u32, u32) {
+		self.lock().unwrap().system.recommended_render_target_size()
+	}
+	
+	fn eye_to_head_transform(&self, eye: Eye) -> [[f32; 4]; 3] {
+		self.lock().unwrap().system.eye_to_head_transform(eye)
+	}
+	
+	fn projection_matrix(&self, eye: Eye, near: f32, far: f32) -> [[f32; 4]; 4] {
+		self.lock().unwrap().system.projection_matrix(eye, near, far)
+	}
+	
+	fn raw_projection(&self, eye: Eye) -> Vec4 {
+		let raw = self.lock().unwrap().system.projection_raw(eye);
+		
+		vector!(-raw.left, raw.right, -raw.top, raw.bottom)
+	}
+}
+
+impl From<&render_models::Vertex> for Vertex {
+	fn from(vertex: &render_models::Vertex) -> Self {
+		Vertex::new(vertex.position, vertex.normal, vertex.texture_coord)
+	}
+}
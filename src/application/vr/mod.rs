@@ -5,10 +5,14 @@ use anyhow::Result;
 use thiserror::Error;
 use openvr::{Context, System, Compositor, RenderModels};
 
+mod backend;
 mod camera_service;
+mod simulated;
 mod tracked_camera;
 
+pub use backend::{VrBackend, VrRenderModel};
 pub use camera_service::CameraService;
+pub use simulated::{SimulatedButton, SimulatedScript, SimulatedVr};
 pub use tracked_camera::{TrackedCamera, FrameType};
 
 
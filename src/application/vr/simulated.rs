@@ -0,0 +1,412 @@
+use std::fs;
+use std::sync::Mutex;
+use std::time::Duration;
+use anyhow::Result;
+use thiserror::Error;
+use openvr::{ControllerAxis, ControllerState, Eye, MAX_TRACKED_DEVICE_COUNT, TrackedControllerRole, TrackedDeviceClass, TrackedDeviceIndex, TrackedDevicePose};
+use openvr::compositor::WaitPoses;
+use openvr_sys::{TrackedDevicePose_t, HmdMatrix34_t, HmdVector3_t, ETrackingResult_TrackingResult_Running_OK};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{NovrConfig, SimvrConfig};
+use crate::math::{Isometry3, Perspective3, Rot3, Vec3, Vec4, VRSlice, PI, from_euler};
+use crate::utils::default_wait_poses;
+use super::super::{Input, Key, MouseButton};
+use super::{VrBackend, VrRenderModel};
+
+
+const HMD: TrackedDeviceIndex = 0;
+const CONTROLLER_LEFT: TrackedDeviceIndex = 1;
+const CONTROLLER_RIGHT: TrackedDeviceIndex = 2;
+
+/// Fake headset with two controllers, driven by keyboard and mouse or by a script file.
+///
+/// Keyboard: WASD, Space and LControl move the headset, mouse looks around and LShift speeds up.
+/// Holding Z or X moves left or right controller with mouse instead, W and S push it away or closer.
+/// Mouse buttons press trigger and grip of the right controller, or the left one while LAlt is held. R presses menu button.
+pub struct SimulatedVr {
+	state: Mutex<SimulatedState>,
+	script: Option<SimulatedScript>,
+	frame_buffer_size: (u32, u32),
+	aspect: f32,
+	fovy: f32,
+	ipd: f32,
+}
+
+struct SimulatedState {
+	time: Duration,
+	position: Vec3,
+	pitch: f32,
+	yaw: f32,
+	hmd: SimulatedDevice,
+	controllers: [SimulatedController; 2],
+}
+
+#[derive(Clone)]
+struct SimulatedDevice {
+	pose: Isometry3,
+	velocity: Vec3,
+	angular_velocity: Vec3,
+}
+
+struct SimulatedController {
+	device: SimulatedDevice,
+	/// Offset from the headset, used in keyboard mode.
+	offset: Isometry3,
+	buttons: u64,
+	packet_num: u32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SimulatedButton {
+	Menu,
+	Grip,
+	Touchpad,
+	Trigger,
+}
+
+/// Keyframes of device poses in tracking space. Poses are interpolated between keyframes, buttons are held until the next one.
+/// Missing poses are carried over from the previous keyframe.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SimulatedScript {
+	#[serde(default, rename = "loop")] pub looping: bool,
+	pub keyframes: Vec<SimulatedKeyframe>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SimulatedKeyframe {
+	pub time: f32,
+	#[serde(default)] pub hmd: Option<Isometry3>,
+	#[serde(default)] pub left: Option<Isometry3>,
+	#[serde(default)] pub right: Option<Isometry3>,
+	#[serde(default)] pub left_buttons: Vec<SimulatedButton>,
+	#[serde(default)] pub right_buttons: Vec<SimulatedButton>,
+}
+
+impl SimulatedVr {
+	pub fn new(config: &SimvrConfig, novr: &NovrConfig) -> Result<Self> {
+		let script = (!config.script.is_empty()).then(|| SimulatedScript::load(&config.script))
+		                                        .transpose()?;
+		
+		let aspect = novr.frame_buffer_width as f32 / novr.frame_buffer_height as f32;
+		let fovx = novr.fov / 180.0 * PI;
+		let fovy = ((fovx / 2.0).tan() / aspect).atan() * 2.0;
+		
+		let position = vector!(0.0, 1.6, 0.0);
+		let hmd = SimulatedDevice::new(Isometry3::translation(position.x, position.y, position.z));
+		let controllers = [
+			SimulatedController::new(Isometry3::new(vector!(-0.2, -0.3, -0.4), vector!(PI * 0.25, 0.0, 0.0)), &hmd.pose),
+			SimulatedController::new(Isometry3::new(vector!(0.2, -0.3, -0.4), vector!(PI * 0.25, 0.0, 0.0)), &hmd.pose),
+		];
+		
+		Ok(SimulatedVr {
+			state: Mutex::new(SimulatedState {
+				time: Duration::ZERO,
+				position,
+				pitch: 0.0,
+				yaw: 0.0,
+				hmd,
+				controllers,
+			}),
+			script,
+			frame_buffer_size: (novr.frame_buffer_width, novr.frame_buffer_height),
+			aspect,
+			fovy,
+			ipd: config.ipd,
+		})
+	}
+	
+	fn update_keyboard(state: &mut SimulatedState, input: &Input, delta_time: Duration) {
+		let get_key = |key: Key| input.keyboard.pressed(key) as i32 as f32;
+		let mouse_x = input.mouse.axis(0);
+		let mouse_y = input.mouse.axis(1);
+		let dt = delta_time.as_secs_f32();
+		
+		let moved = if input.keyboard.pressed(Key::Z) { Some(0) }
+		            else if input.keyboard.pressed(Key::X) { Some(1) }
+		            else { None };
+		
+		if let Some(id) = moved {
+			let offset = &mut state.controllers[id].offset;
+			let depth = get_key(Key::S) - get_key(Key::W);
+			
+			offset.translation.vector += vector!(mouse_x * 0.001, -mouse_y * 0.001, depth * 0.5 * dt);
+		} else {
+			let x = get_key(Key::D) - get_key(Key::A);
+			let y = get_key(Key::Space) - get_key(Key::LControl);
+			let z = get_key(Key::S) - get_key(Key::W);
+			let dist = (0.5 + get_key(Key::LShift) * 1.0) * dt;
+			
+			state.yaw += -mouse_x * 0.01;
+			state.pitch = (state.pitch + -mouse_y * 0.01).clamp(-PI / 2.0, PI / 2.0);
+			
+			let yaw = Rot3::from_axis_angle(&Vec3::y_axis(), state.yaw);
+			state.position += yaw * vector!(x, 0.0, z) * dist + Vec3::y() * y * dist;
+		}
+		
+		let hmd = Isometry3::from_parts(state.position.into(), from_euler(state.pitch, state.yaw, 0.0));
+		state.hmd.set_pose(hmd, delta_time);
+		
+		let active = if input.keyboard.pressed(Key::LAlt) { 0 } else { 1 };
+		
+		for (id, controller) in state.controllers.iter_mut().enumerate() {
+			let mut buttons = vec![];
+			
+			if id == active {
+				if input.mouse.pressed(MouseButton::Left) { buttons.push(SimulatedButton::Trigger); }
+				if input.mouse.pressed(MouseButton::Right) { buttons.push(SimulatedButton::Grip); }
+				if input.keyboard.pressed(Key::R) { buttons.push(SimulatedButton::Menu); }
+			}
+			
+			controller.device.set_pose(hmd * controller.offset, delta_time);
+			controller.set_buttons(&buttons);
+		}
+	}
+	
+	fn update_script(state: &mut SimulatedState, script: &SimulatedScript, delta_time: Duration) {
+		state.time += delta_time;
+		
+		let frame = script.sample(state.time.as_secs_f32());
+		
+		state.hmd.set_pose(frame.hmd.unwrap_or(state.hmd.pose), delta_time);
+		
+		for (controller, (pose, buttons)) in state.controllers.iter_mut().zip([(frame.left, &frame.left_buttons), (frame.right, &frame.right_buttons)]) {
+			controller.device.set_pose(pose.unwrap_or(controller.device.pose), delta_time);
+			controller.set_buttons(buttons);
+		}
+	}
+	
+	fn device(state: &SimulatedState, index: TrackedDeviceIndex) -> Option<&SimulatedDevice> {
+		match index {
+			HMD => Some(&state.hmd),
+			CONTROLLER_LEFT => Some(&state.controllers[0].device),
+			CONTROLLER_RIGHT => Some(&state.controllers[1].device),
+			_ => None,
+		}
+	}
+}
+
+impl VrBackend for SimulatedVr {
+	fn update(&self, input: &Input, delta_time: Duration) -> Result<()> {
+		let state = &mut *self.state.lock().unwrap();
+		
+		if let Some(script) = &self.script {
+			Self::update_script(state, script, delta_time);
+		} else {
+			Self::update_keyboard(state, input, delta_time);
+		}
+		
+		Ok(())
+	}
+	
+	fn wait_get_poses(&self) -> Result<WaitPoses> {
+		let state = self.state.lock().unwrap();
+		let mut poses = default_wait_poses();
+		
+		for index in 0..MAX_TRACKED_DEVICE_COUNT as TrackedDeviceIndex {
+			if let Some(device) = Self::device(&state, index) {
+				poses.render[index as usize] = device.tracked_pose();
+				poses.game[index as usize] = device.tracked_pose();
+			}
+		}
+		
+		Ok(poses)
+	}
+	
+	fn is_tracked_device_connected(&self, index: TrackedDeviceIndex) -> bool {
+		matches!(index, HMD | CONTROLLER_LEFT | CONTROLLER_RIGHT)
+	}
+	
+	fn tracked_device_class(&self, index: TrackedDeviceIndex) -> TrackedDeviceClass {
+		match index {
+			HMD => TrackedDeviceClass::HMD,
+			CONTROLLER_LEFT | CONTROLLER_RIGHT => TrackedDeviceClass::Controller,
+			_ => TrackedDeviceClass::Invalid,
+		}
+	}
+	
+	fn controller_role(&self, index: TrackedDeviceIndex) -> Option<TrackedControllerRole> {
+		match index {
+			CONTROLLER_LEFT => Some(TrackedControllerRole::LeftHand),
+			CONTROLLER_RIGHT => Some(TrackedControllerRole::RightHand),
+			_ => None,
+		}
+	}
+	
+	fn tracked_device_index_for_controller_role(&self, role: TrackedControllerRole) -> Option<TrackedDeviceIndex> {
+		match role {
+			TrackedControllerRole::LeftHand => Some(CONTROLLER_LEFT),
+			TrackedControllerRole::RightHand => Some(CONTROLLER_RIGHT),
+		}
+	}
+	
+	fn controller_state(&self, index: TrackedDeviceIndex) -> Option<ControllerState> {
+		let state = self.state.lock().unwrap();
+		
+		match index {
+			CONTROLLER_LEFT => Some(state.controllers[0].controller_state()),
+			CONTROLLER_RIGHT => Some(state.controllers[1].controller_state()),
+			_ => None,
+		}
+	}
+	
+	fn render_model(&self, _index: TrackedDeviceIndex) -> Result<Option<VrRenderModel>> {
+		Ok(None)
+	}
+	
+	fn recommended_render_target_size(&self) -> (u32, u32) {
+		self.frame_buffer_size
+	}
+	
+	fn eye_to_head_transform(&self, eye: Eye) -> [[f32; 4]; 3] {
+		let offset = match eye {
+			Eye::Left => -self.ipd / 2.0,
+			Eye::Right => self.ipd / 2.0,
+		};
+		
+		Isometry3::translation(offset, 0.0, 0.0).to_homogeneous().to_slice34()
+	}
+	
+	fn projection_matrix(&self, _eye: Eye, near: f32, far: f32) -> [[f32; 4]; 4] {
+		Perspective3::new(self.aspect, self.fovy, near, far).to_homogeneous().to_slice44()
+	}
+	
+	fn raw_projection(&self, _eye: Eye) -> Vec4 {
+		let tan_x = (self.fovy / 2.0).tan() * self.aspect;
+		let tan_y = (self.fovy / 2.0).tan();
+		
+		vector!(tan_x, tan_x, tan_y, tan_y)
+	}
+}
+
+impl SimulatedDevice {
+	fn new(pose: Isometry3) -> Self {
+		SimulatedDevice {
+			pose,
+			velocity: Vec3::zeros(),
+			angular_velocity: Vec3::zeros(),
+		}
+	}
+	
+	fn set_pose(&mut self, pose: Isometry3, delta_time: Duration) {
+		let dt = delta_time.as_secs_f32();
+		
+		if dt > 0.0 {
+			self.velocity = (pose.translation.vector - self.pose.translation.vector) / dt;
+			self.angular_velocity = (pose.rotation * self.pose.rotation.inverse()).scaled_axis() / dt;
+		}
+		
+		self.pose = pose;
+	}
+	
+	fn tracked_pose(&self) -> TrackedDevicePose {
+		TrackedDevicePose::from(TrackedDevicePose_t {
+			mDeviceToAbsoluteTracking: HmdMatrix34_t { m: self.pose.to_homogeneous().to_slice34() },
+			vVelocity: HmdVector3_t { v: self.velocity.into() },
+			vAngularVelocity: HmdVector3_t { v: self.angular_velocity.into() },
+			eTrackingResult: ETrackingResult_TrackingResult_Running_OK,
+			bPoseIsValid: true,
+			bDeviceIsConnected: true,
+		})
+	}
+}
+
+impl SimulatedController {
+	fn new(offset: Isometry3, hmd: &Isometry3) -> Self {
+		SimulatedController {
+			device: SimulatedDevice::new(hmd * offset),
+			offset,
+			buttons: 0,
+			packet_num: 0,
+		}
+	}
+	
+	fn set_buttons(&mut self, buttons: &[SimulatedButton]) {
+		let buttons = buttons.iter().fold(0, |acc, button| acc | button.mask());
+		
+		if buttons != self.buttons {
+			self.buttons = buttons;
+			self.packet_num += 1;
+		}
+	}
+	
+	fn controller_state(&self) -> ControllerState {
+		let trigger = if self.buttons & SimulatedButton::Trigger.mask() != 0 { 1.0 } else { 0.0 };
+		let mut axis = [ControllerAxis { x: 0.0, y: 0.0 }; 5];
+		axis[1].x = trigger;
+		
+		ControllerState {
+			packet_num: self.packet_num,
+			button_pressed: self.buttons,
+			button_touched: self.buttons,
+			axis,
+		}
+	}
+}
+
+impl SimulatedButton {
+	pub fn mask(self) -> u64 {
+		1 << match self {
+			SimulatedButton::Menu => 1,
+			SimulatedButton::Grip => 2,
+			SimulatedButton::Touchpad => 32,
+			SimulatedButton::Trigger => 33,
+		}
+	}
+}
+
+impl SimulatedScript {
+	pub fn load(path: &str) -> Result<Self> {
+		let mut script: SimulatedScript = toml::from_str(&fs::read_to_string(path)?)?;
+		
+		if script.keyframes.is_empty() {
+			return Err(EmptyScriptError(path.to_string()).into());
+		}
+		
+		script.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+		
+		for id in 1..script.keyframes.len() {
+			let (previous, next) = script.keyframes.split_at_mut(id);
+			let (previous, next) = (&previous[id - 1], &mut next[0]);
+			
+			next.hmd = next.hmd.or(previous.hmd);
+			next.left = next.left.or(previous.left);
+			next.right = next.right.or(previous.right);
+		}
+		
+		Ok(script)
+	}
+	
+	fn sample(&self, time: f32) -> SimulatedKeyframe {
+		let duration = self.keyframes.last().unwrap().time;
+		let time = if self.looping && duration > 0.0 { time % duration } else { time };
+		
+		let next_id = self.keyframes.iter().position(|keyframe| keyframe.time > time);
+		
+		let (previous, next) = match next_id {
+			None => return self.keyframes.last().unwrap().clone(),
+			Some(0) => return self.keyframes[0].clone(),
+			Some(id) => (&self.keyframes[id - 1], &self.keyframes[id]),
+		};
+		
+		let alpha = (time - previous.time) / (next.time - previous.time);
+		let blend = |a: Option<Isometry3>, b: Option<Isometry3>| match (a, b) {
+			(Some(a), Some(b)) => Some(a.lerp_slerp(&b, alpha)),
+			(a, b) => a.or(b),
+		};
+		
+		SimulatedKeyframe {
+			time,
+			hmd: blend(previous.hmd, next.hmd),
+			left: blend(previous.left, next.left),
+			right: blend(previous.right, next.right),
+			left_buttons: previous.left_buttons.clone(),
+			right_buttons: previous.right_buttons.clone(),
+		}
+	}
+}
+
+#[derive(Debug, Error)]
+#[error("Simulated VR script {0} has no keyframes")]
+pub struct EmptyScriptError(String);
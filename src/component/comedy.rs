@@ -30,7 +30,7 @@ impl Comedy {
 	pub fn new(renderer: &mut Renderer) -> Result<Self> {
 		Ok(Comedy {
 			inner: ComponentInner::from_render_type(RenderType::Transparent),
-			model: renderer.load(ObjAsset::at("comedy/base.obj", "comedy/base.png"))?,
+			model: renderer.load(ObjAsset::at("comedy/base.obj").texture("comedy/base.png"))?,
 			iris: EntityRef::null(),
 			iris_pos: Cell::new(Isometry3::identity()),
 		})
@@ -2,6 +2,7 @@ use std::cell::Cell;
 use std::sync::Arc;
 use anyhow::Result;
 use egui::Ui;
+use serde_derive::{Deserialize, Serialize};
 use vulkano::pipeline::{Pipeline, GraphicsPipeline, PipelineBindPoint};
 
 use crate::application::{Application, Entity};
@@ -9,10 +10,16 @@ use crate::math::{Similarity3, Color};
 use crate::renderer::{RenderContext, Renderer, RenderType};
 use crate::renderer::pipelines::default::{DefaultGlowPipeline, GlowPc};
 use crate::utils::{AutoCommandBufferBuilderEx, ExUi};
-use super::{Component, ComponentBase, ComponentInner};
+use super::{Component, ComponentBase, ComponentInner, ComponentLoad};
 use super::model::SimpleModel;
 
 
+#[derive(Deserialize, Serialize)]
+struct GlowParams {
+	color: Color,
+	size: f32,
+}
+
 #[derive(ComponentBase)]
 pub struct Glow {
 	#[inner] inner: ComponentInner,
@@ -34,7 +41,23 @@ impl Glow {
 	}
 }
 
+impl ComponentLoad for Glow {
+	fn load(params: toml::Value, application: &Application) -> Result<Option<Self>> {
+		if application.headless() {
+			return Ok(None);
+		}
+		
+		let params: GlowParams = params.try_into()?;
+		
+		Ok(Some(Glow::new(params.color, params.size, &mut *application.renderer()?)?))
+	}
+}
+
 impl Component for Glow {
+	fn save(&self, _entity: &Entity, _application: &Application) -> Result<Option<toml::Value>> {
+		Ok(Some(toml::Value::try_from(GlowParams { color: self.color.get(), size: self.size.get() })?))
+	}
+	
 	// fn start(&self, entity: &Entity, _application: &Application) -> Result<()> {
 	// 	if let Some(model) = AnyModel::find(entity) {
 	// 		*self.model.borrow_mut() = Some(model);
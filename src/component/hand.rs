@@ -7,12 +7,13 @@ use rapier3d::dynamics::FixedJoint;
 use rapier3d::geometry::Ball;
 use rapier3d::pipeline::QueryFilter;
 use rapier3d::prelude::ColliderHandle;
+use serde_derive::{Deserialize, Serialize};
 
 use crate::debug;
 use crate::application::{Entity, Application, Hand, EntityRef};
 use crate::math::{Point3, Color, Translation3, Isometry3};
 use crate::utils::{ColliderEx, ExUi};
-use super::{Component, ComponentBase, ComponentInner, ComponentRef};
+use super::{Component, ComponentBase, ComponentInner, ComponentLoad, ComponentRef};
 use super::physics::joint::JointComponent;
 use super::vr::VrTracked;
 
@@ -34,6 +35,11 @@ enum Grab {
 	Kinematic(EntityRef),
 }
 
+#[derive(Deserialize, Serialize)]
+struct HandParams {
+	hand: Hand,
+}
+
 #[derive(ComponentBase, Debug)]
 pub struct HandComponent {
 	#[inner] inner: ComponentInner,
@@ -63,7 +69,19 @@ impl HandComponent {
 	}
 }
 
+impl ComponentLoad for HandComponent {
+	fn load(params: toml::Value, _application: &Application) -> Result<Option<Self>> {
+		let params: HandParams = params.try_into()?;
+		
+		Ok(Some(HandComponent::new(params.hand)))
+	}
+}
+
 impl Component for HandComponent {
+	fn save(&self, _entity: &Entity, _application: &Application) -> Result<Option<toml::Value>> {
+		Ok(Some(toml::Value::try_from(HandParams { hand: self.hand })?))
+	}
+	
 	fn tick(&self, entity: &Entity, application: &Application, delta_time: Duration) -> Result<()> {
 		if let Some(item) = self.grabbed_entity().get(application) {
 			if application.input.use3_btn(self.hand).down {
@@ -7,7 +7,7 @@ use smallvec::SmallVec;
 use crate::application::{Entity, Application};
 use crate::math::PI;
 use crate::utils::ColliderEx;
-use super::{Component, ComponentBase, ComponentInner, ComponentRef};
+use super::{Component, ComponentBase, ComponentInner, ComponentLoad, ComponentRef};
 use super::physics::collider::ColliderComponent;
 
 
@@ -26,14 +26,24 @@ impl Katamari {
 	}
 }
 
+impl ComponentLoad for Katamari {
+	fn load(_params: toml::Value, _application: &Application) -> Result<Option<Self>> {
+		Ok(Some(Katamari::new()))
+	}
+}
+
 impl Component for Katamari {
+	fn save(&self, _entity: &Entity, _application: &Application) -> Result<Option<toml::Value>> {
+		Ok(Some(toml::Table::new().into()))
+	}
+	
 	fn start(&self, entity: &Entity, _application: &Application) -> Result<()> {
 		self.collider.set(
 			entity.add_component(ColliderComponent::new(
 				ColliderBuilder::ball(0.6)
 				                .density(10000.0)
 				                .build()
-			))
+			).transient())
 		);
 		
 		Ok(())
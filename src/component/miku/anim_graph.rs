@@ -0,0 +1,593 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+use anyhow::Result;
+use serde_derive::Deserialize;
+use thiserror::Error;
+
+use crate::application::{Application, Entity};
+use crate::math::{Isometry3, Rot3, Similarity3, Translation3};
+use crate::renderer::Renderer;
+use super::super::model::MMDModel;
+use super::super::model::mmd::MMDModelState;
+use super::super::model::mmd::vmd::{VmdAsset, VmdBindings, VmdMotion, VMD_FPS};
+use super::anim_set::{AnimFile, AnimSet};
+use super::{Bones, Morphs, MikuMapping};
+
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LayerDesc {
+	pub name: String,
+	#[serde(default = "default_one")]
+	pub weight: f32,
+	/// Bone roles affected by the layer, including their children. Empty means all bones.
+	#[serde(default)]
+	pub mask_bones: Vec<String>,
+	/// Morph roles affected by the layer. Empty means all morphs.
+	#[serde(default)]
+	pub mask_morphs: Vec<String>,
+	pub states: Vec<StateDesc>,
+	#[serde(default)]
+	pub transitions: Vec<TransitionDesc>,
+}
+
+/// State plays one of: animation set (`proc`), VMD clip (`clip`) or procedural controller (`controller`).
+#[derive(Clone, Debug, Deserialize)]
+pub struct StateDesc {
+	pub name: String,
+	pub proc: Option<String>,
+	pub clip: Option<PathBuf>,
+	pub controller: Option<String>,
+	#[serde(default)]
+	pub looping: bool,
+	#[serde(default = "default_one")]
+	pub speed: f32,
+}
+
+/// Transition fires on `event`, when `param` goes `above` or `below` a value or when the current state `finished`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TransitionDesc {
+	/// Any state if missing.
+	pub from: Option<String>,
+	pub to: String,
+	pub event: Option<String>,
+	pub param: Option<String>,
+	pub above: Option<f32>,
+	pub below: Option<f32>,
+	#[serde(default)]
+	pub finished: bool,
+	/// Crossfade duration in seconds.
+	#[serde(default)]
+	pub fade: f32,
+}
+
+fn default_one() -> f32 { 1.0 }
+
+/// Bone and morph values indexed by model ids. `None` leaves the value of lower layers.
+#[derive(Clone, Debug, Default)]
+pub struct Pose {
+	pub bones: Vec<Option<Isometry3>>,
+	pub morphs: Vec<Option<f32>>,
+}
+
+impl Pose {
+	pub fn new(bones: usize, morphs: usize) -> Self {
+		Pose {
+			bones: vec![None; bones],
+			morphs: vec![None; morphs],
+		}
+	}
+	
+	pub fn clear(&mut self) {
+		self.bones.iter_mut().for_each(|bone| *bone = None);
+		self.morphs.iter_mut().for_each(|morph| *morph = None);
+	}
+	
+	/// Blends `other` over this pose. Values missing in `other` are kept.
+	pub fn blend(&mut self, other: &Pose, weight: f32, bones_mask: Option<&[bool]>, morphs_mask: Option<&[bool]>) {
+		for (id, (bone, &target)) in self.bones.iter_mut().zip(&other.bones).enumerate() {
+			if let Some(target) = target {
+				if bones_mask.map_or(true, |mask| mask[id]) {
+					*bone = Some(bone.unwrap_or_else(Isometry3::identity).lerp_slerp(&target, weight));
+				}
+			}
+		}
+		
+		for (id, (morph, &target)) in self.morphs.iter_mut().zip(&other.morphs).enumerate() {
+			if let Some(target) = target {
+				if morphs_mask.map_or(true, |mask| mask[id]) {
+					let value = morph.unwrap_or(0.0);
+					*morph = Some(value + (target - value) * weight);
+				}
+			}
+		}
+	}
+	
+	/// Interpolates towards `other`. Values missing on either side are treated as rest pose.
+	pub fn crossfade(&mut self, other: &Pose, t: f32) {
+		for (bone, target) in self.bones.iter_mut().zip(&other.bones) {
+			if bone.is_some() || target.is_some() {
+				let from = bone.unwrap_or_else(Isometry3::identity);
+				*bone = Some(from.lerp_slerp(&target.unwrap_or_else(Isometry3::identity), t));
+			}
+		}
+		
+		for (morph, target) in self.morphs.iter_mut().zip(&other.morphs) {
+			if morph.is_some() || target.is_some() {
+				let from = morph.unwrap_or(0.0);
+				*morph = Some(from + (target.unwrap_or(0.0) - from) * t);
+			}
+		}
+	}
+}
+
+/// Data available to animation sources while sampling.
+pub struct AnimContext<'a> {
+	pub entity: &'a Entity,
+	pub application: &'a Application,
+	pub model: &'a MMDModelState,
+	pub mapping: &'a MikuMapping,
+	pub sets: &'a mut [AnimSet],
+	pub delta_time: f32,
+}
+
+/// Procedural animation source, eg. look-at. Registered in `AnimGraph` by name and referenced by states.
+pub trait AnimController {
+	fn sample(&mut self, ctx: &mut AnimContext, pose: &mut Pose);
+}
+
+pub enum AnimSource {
+	None,
+	Proc(String),
+	Clip(ClipSource),
+	Controller(String),
+}
+
+pub struct ClipSource {
+	motion: Arc<VmdMotion>,
+	bindings: VmdBindings,
+	pub time: f32,
+	pub looping: bool,
+	pub speed: f32,
+}
+
+impl ClipSource {
+	fn sample(&mut self, delta_time: f32, pose: &mut Pose) {
+		let duration = self.motion.duration();
+		self.time += delta_time * self.speed;
+		
+		if self.looping && duration > 0.0 {
+			self.time = self.time.rem_euclid(duration);
+		} else {
+			self.time = self.time.clamp(0.0, duration);
+		}
+		
+		let frame = self.time * VMD_FPS;
+		
+		for &(track_id, bone_id) in &self.bindings.bones {
+			let (translation, rotation) = self.motion.bones[track_id].sample(frame);
+			pose.bones[bone_id] = Some(Isometry3::from_parts(translation.into(), rotation));
+		}
+		
+		for &(track_id, morph_id) in &self.bindings.morphs {
+			pose.morphs[morph_id] = Some(self.motion.morphs[track_id].sample(frame));
+		}
+	}
+	
+	fn finished(&self) -> bool {
+		!self.looping && self.time >= self.motion.duration()
+	}
+}
+
+impl AnimSource {
+	fn enter(&mut self, sets: &mut [AnimSet], controllers: &HashMap<String, Box<dyn AnimController>>) {
+		match self {
+			AnimSource::Proc(name) => if let Some(set) = sets.iter_mut().find(|set| set.name == *name) {
+				set.stop();
+				set.play();
+			},
+			AnimSource::Clip(clip) => clip.time = 0.0,
+			AnimSource::Controller(name) => if !controllers.contains_key(name) {
+				eprintln!("Animation controller '{}' is not registered, its state leaves the pose unchanged.", name);
+			},
+			AnimSource::None => {},
+		}
+	}
+	
+	fn finished(&self, sets: &[AnimSet]) -> bool {
+		match self {
+			AnimSource::Proc(name) => sets.iter().find(|set| set.name == *name).map_or(true, |set| !set.playing()),
+			AnimSource::Clip(clip) => clip.finished(),
+			AnimSource::None | AnimSource::Controller(_) => false,
+		}
+	}
+	
+	fn sample(&mut self, ctx: &mut AnimContext, controllers: &mut HashMap<String, Box<dyn AnimController>>, pose: &mut Pose) {
+		match self {
+			AnimSource::None => {},
+			AnimSource::Proc(name) => {
+				let mapping = ctx.mapping;
+				let set = match ctx.sets.iter_mut().find(|set| set.name == *name) {
+					Some(set) => set,
+					None => return,
+				};
+				
+				for (bone, anim) in set.bones.iter_mut() {
+					let value = Rot3::identity().slerp(&anim.get(), set.weight);
+					
+					if let Some(id) = mapping.bone(*bone) {
+						pose.bones[id] = Some(Isometry3::from_parts(Translation3::identity(), value));
+					}
+				}
+				
+				for (morph, anim) in set.morphs.iter_mut() {
+					let value = anim.get() * set.weight;
+					
+					if let Some(id) = mapping.morph(*morph) {
+						pose.morphs[id] = Some(value);
+					}
+				}
+			},
+			AnimSource::Clip(clip) => clip.sample(ctx.delta_time, pose),
+			AnimSource::Controller(name) => if let Some(controller) = controllers.get_mut(name) {
+				controller.sample(ctx, pose);
+			},
+		}
+	}
+}
+
+pub struct AnimState {
+	pub name: String,
+	pub source: AnimSource,
+}
+
+pub enum Condition {
+	Event(String),
+	Above(String, f32),
+	Below(String, f32),
+	Finished,
+}
+
+pub struct Transition {
+	pub from: Option<usize>,
+	pub to: usize,
+	pub condition: Condition,
+	pub fade: f32,
+}
+
+struct Fade {
+	from: usize,
+	elapsed: f32,
+	duration: f32,
+}
+
+/// State machine whose output is blended over lower layers.
+pub struct AnimLayer {
+	pub name: String,
+	pub weight: f32,
+	pub states: Vec<AnimState>,
+	pub transitions: Vec<Transition>,
+	bones_mask: Option<Vec<bool>>,
+	morphs_mask: Option<Vec<bool>>,
+	current: usize,
+	entered: bool,
+	fade: Option<Fade>,
+	pose: Pose,
+	fade_pose: Pose,
+}
+
+impl AnimLayer {
+	pub fn current(&self) -> &AnimState {
+		&self.states[self.current]
+	}
+	
+	pub fn set_state(&mut self, state: usize, fade: f32) {
+		if state == self.current { return; }
+		
+		self.fade = if fade > 0.0 {
+			Some(Fade { from: self.current, elapsed: 0.0, duration: fade })
+		} else {
+			None
+		};
+		self.current = state;
+		self.entered = false;
+	}
+	
+	fn update_transitions(&mut self, params: &BTreeMap<String, f32>, events: &[String], sets: &[AnimSet]) {
+		let current = self.current;
+		let finished = self.states[current].source.finished(sets);
+		
+		let transition = self.transitions
+		                     .iter()
+		                     .filter(|transition| transition.from.map_or(transition.to != current, |from| from == current))
+		                     .find(|transition| match &transition.condition {
+			                     Condition::Event(event) => events.contains(event),
+			                     Condition::Above(param, value) => params.get(param).map_or(false, |param| param > value),
+			                     Condition::Below(param, value) => params.get(param).map_or(false, |param| param < value),
+			                     Condition::Finished => finished,
+		                     })
+		                     .map(|transition| (transition.to, transition.fade));
+		
+		if let Some((to, fade)) = transition {
+			self.set_state(to, fade);
+		}
+	}
+	
+	fn update(&mut self, base: &Pose, ctx: &mut AnimContext, controllers: &mut HashMap<String, Box<dyn AnimController>>) -> Pose {
+		if !self.entered {
+			self.states[self.current].source.enter(ctx.sets, controllers);
+			self.entered = true;
+		}
+		
+		self.pose.clear();
+		self.states[self.current].source.sample(ctx, controllers, &mut self.pose);
+		
+		let mut result = base.clone();
+		result.blend(&self.pose, 1.0, None, None);
+		
+		if let Some(fade) = &mut self.fade {
+			fade.elapsed += ctx.delta_time;
+			
+			self.fade_pose.clear();
+			self.states[fade.from].source.sample(ctx, controllers, &mut self.fade_pose);
+			
+			let mut from = base.clone();
+			from.blend(&self.fade_pose, 1.0, None, None);
+			from.crossfade(&result, (fade.elapsed / fade.duration).min(1.0));
+			result = from;
+			
+			if fade.elapsed >= fade.duration {
+				self.fade = None;
+			}
+		}
+		
+		result
+	}
+}
+
+/// Layered state machines driving bones and morphs of the character.
+pub struct AnimGraph {
+	pub layers: Vec<AnimLayer>,
+	pub params: BTreeMap<String, f32>,
+	events: Vec<String>,
+	controllers: HashMap<String, Box<dyn AnimController>>,
+	pose: Pose,
+	/// Bones animated in the last frame, reset to rest pose when no layer drives them anymore.
+	animated: Vec<bool>,
+}
+
+impl AnimGraph {
+	/// Builds graph described in the file. Without any layers, every animation set gets its own layer.
+	pub fn build(file: &AnimFile, model: &MMDModel, mapping: &MikuMapping, renderer: &mut Renderer) -> Result<Self> {
+		let state = model.state();
+		let bones_count = state.bones.len();
+		let morphs_count = state.morphs.len();
+		
+		let layers = if file.layers.is_empty() {
+			file.sets
+			    .keys()
+			    .map(|name| AnimLayer {
+				    name: name.clone(),
+				    weight: 1.0,
+				    states: vec![AnimState { name: name.clone(), source: AnimSource::Proc(name.clone()) }],
+				    transitions: vec![],
+				    bones_mask: None,
+				    morphs_mask: None,
+				    current: 0,
+				    entered: true,
+				    fade: None,
+				    pose: Pose::new(bones_count, morphs_count),
+				    fade_pose: Pose::new(bones_count, morphs_count),
+			    })
+			    .collect()
+		} else {
+			file.layers
+			    .iter()
+			    .map(|desc| build_layer(desc, file, model, &state, mapping, renderer))
+			    .collect::<Result<_>>()?
+		};
+		
+		Ok(AnimGraph {
+			layers,
+			params: file.params.clone(),
+			events: vec![],
+			controllers: HashMap::new(),
+			pose: Pose::new(bones_count, morphs_count),
+			animated: vec![false; bones_count],
+		})
+	}
+	
+	pub fn add_controller(&mut self, name: impl Into<String>, controller: impl AnimController + 'static) {
+		self.controllers.insert(name.into(), Box::new(controller));
+	}
+	
+	/// Takes over parameters and registered controllers of the graph this one replaces, eg. after reloading the file.
+	pub fn inherit(&mut self, old: &mut AnimGraph) {
+		for (name, value) in &old.params {
+			self.params.insert(name.clone(), *value);
+		}
+		
+		self.controllers.extend(old.controllers.drain());
+	}
+	
+	pub fn set_param(&mut self, name: impl Into<String>, value: f32) {
+		self.params.insert(name.into(), value);
+	}
+	
+	/// Queues event, consumed by transitions on the next update.
+	pub fn trigger(&mut self, event: impl Into<String>) {
+		self.events.push(event.into());
+	}
+	
+	/// Evaluates all layers and returns resulting pose.
+	pub fn update(&mut self, ctx: &mut AnimContext) -> &Pose {
+		self.pose.clear();
+		
+		for layer in self.layers.iter_mut() {
+			layer.update_transitions(&self.params, &self.events, ctx.sets);
+			
+			let result = layer.update(&self.pose, ctx, &mut self.controllers);
+			self.pose.blend(&result, layer.weight, layer.bones_mask.as_deref(), layer.morphs_mask.as_deref());
+		}
+		
+		self.events.clear();
+		
+		&self.pose
+	}
+	
+	/// Writes the last evaluated pose to the model.
+	pub fn apply(&mut self, state: &mut MMDModelState) {
+		for (id, bone) in self.pose.bones.iter().enumerate() {
+			if let Some(bone) = bone {
+				state.bones[id].anim_transform = Similarity3::from_isometry(*bone, 1.0);
+			} else if self.animated[id] {
+				state.bones[id].anim_transform = Similarity3::identity();
+			}
+			
+			self.animated[id] = bone.is_some();
+		}
+		
+		for (id, morph) in self.pose.morphs.iter().enumerate() {
+			if let Some(morph) = morph {
+				state.morphs[id] = morph.clamp(0.0, 1.0);
+			}
+		}
+	}
+}
+
+fn build_layer(desc: &LayerDesc, file: &AnimFile, model: &MMDModel, state: &MMDModelState, mapping: &MikuMapping, renderer: &mut Renderer) -> Result<AnimLayer> {
+	if desc.states.is_empty() {
+		return Err(EmptyLayerError(desc.name.clone()).into());
+	}
+	
+	let states = desc.states
+	                 .iter()
+	                 .map(|state| {
+		                 let source = if let Some(set) = &state.proc {
+			                 if !file.sets.contains_key(set) {
+				                 return Err(UnknownAnimError::new("animation set", set, &desc.name).into());
+			                 }
+			                 AnimSource::Proc(set.clone())
+		                 } else if let Some(clip) = &state.clip {
+			                 let motion = renderer.load(VmdAsset::at(clip))?;
+			                 let bindings = motion.bind(model);
+			                 
+			                 AnimSource::Clip(ClipSource {
+				                 motion,
+				                 bindings,
+				                 time: 0.0,
+				                 looping: state.looping,
+				                 speed: state.speed,
+			                 })
+		                 } else if let Some(controller) = &state.controller {
+			                 AnimSource::Controller(controller.clone())
+		                 } else {
+			                 AnimSource::None
+		                 };
+		                 
+		                 Ok(AnimState { name: state.name.clone(), source })
+	                 })
+	                 .collect::<Result<Vec<_>>>()?;
+	
+	let find_state = |name: &str| states.iter()
+	                                    .position(|state| state.name == name)
+	                                    .ok_or_else(|| UnknownAnimError::new("state", name, &desc.name));
+	
+	let transitions = desc.transitions
+	                      .iter()
+	                      .map(|transition| {
+		                      let condition = match (&transition.event, &transition.param, transition.above, transition.below) {
+			                      (Some(event), _, _, _) => Condition::Event(event.clone()),
+			                      (_, Some(param), Some(above), _) => Condition::Above(param.clone(), above),
+			                      (_, Some(param), _, Some(below)) => Condition::Below(param.clone(), below),
+			                      _ if transition.finished => Condition::Finished,
+			                      _ => return Err(NoConditionError(transition.to.clone(), desc.name.clone()).into()),
+		                      };
+		                      
+		                      Ok(Transition {
+			                      from: transition.from.as_deref().map(find_state).transpose()?,
+			                      to: find_state(&transition.to)?,
+			                      condition,
+			                      fade: transition.fade,
+		                      })
+	                      })
+	                      .collect::<Result<Vec<_>>>()?;
+	
+	let bones_mask = if desc.mask_bones.is_empty() {
+		None
+	} else {
+		let mut roots = vec![false; state.bones.len()];
+		
+		for role in &desc.mask_bones {
+			let id = Bones::from_role(role).and_then(|bone| mapping.bone(bone))
+			                               .ok_or_else(|| UnknownAnimError::new("bone", role, &desc.name))?;
+			roots[id] = true;
+		}
+		
+		// Walk is bounded, in case parents of a broken model form a cycle
+		let mask = state.bones
+		                .iter()
+		                .map(|bone| {
+			                std::iter::successors(Some(bone.id), |&id| state.bones[id].parent)
+				                .take(state.bones.len())
+				                .any(|id| roots[id])
+		                })
+		                .collect::<Vec<_>>();
+		
+		Some(mask)
+	};
+	
+	let morphs_mask = if desc.mask_morphs.is_empty() {
+		None
+	} else {
+		let mut mask = vec![false; state.morphs.len()];
+		
+		for role in &desc.mask_morphs {
+			let id = Morphs::from_role(role).and_then(|morph| mapping.morph(morph))
+			                                .ok_or_else(|| UnknownAnimError::new("morph", role, &desc.name))?;
+			mask[id] = true;
+		}
+		
+		Some(mask)
+	};
+	
+	Ok(AnimLayer {
+		name: desc.name.clone(),
+		weight: desc.weight,
+		states,
+		transitions,
+		bones_mask,
+		morphs_mask,
+		current: 0,
+		entered: false,
+		fade: None,
+		pose: Pose::new(state.bones.len(), state.morphs.len()),
+		fade_pose: Pose::new(state.bones.len(), state.morphs.len()),
+	})
+}
+
+#[derive(Debug, Error)]
+#[error("Animation layer {0} has no states.")]
+pub struct EmptyLayerError(String);
+
+#[derive(Debug, Error)]
+#[error("Transition to {0} in animation layer {1} has no condition.")]
+pub struct NoConditionError(String, String);
+
+#[derive(Debug, Error)]
+#[error("Unknown {kind} '{name}' in animation layer {layer}.")]
+pub struct UnknownAnimError {
+	kind: &'static str,
+	name: String,
+	layer: String,
+}
+
+impl UnknownAnimError {
+	fn new(kind: &'static str, name: &str, layer: &str) -> Self {
+		UnknownAnimError {
+			kind,
+			name: name.to_string(),
+			layer: layer.to_string(),
+		}
+	}
+}
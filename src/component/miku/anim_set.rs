@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use serde_derive::Deserialize;
+
+use crate::math::{Rot3, Vec3};
+use super::anim_graph::LayerDesc;
+use super::proc_anim::{DurationRange, Easing, Interpolate, ProcAnim};
+use super::enums::{Bones, Morphs};
+
+
+/// Root of animations file, e.g. `anims/miku.toml`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct AnimFile {
+	#[serde(default)]
+	pub sets: BTreeMap<String, AnimSetDesc>,
+	/// Initial values of animation graph parameters.
+	#[serde(default)]
+	pub params: BTreeMap<String, f32>,
+	#[serde(default)]
+	pub layers: Vec<LayerDesc>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AnimSetDesc {
+	#[serde(default)]
+	pub autoplay: bool,
+	#[serde(default = "default_weight")]
+	pub weight: f32,
+	#[serde(default)]
+	pub bones: Vec<TrackDesc<Vec3>>,
+	#[serde(default)]
+	pub morphs: Vec<TrackDesc<f32>>,
+}
+
+/// Animation of single bone or morph. Bone rotations are euler angles in degrees.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TrackDesc<V> {
+	pub role: String,
+	pub initial: Option<V>,
+	#[serde(default)]
+	pub repeat: bool,
+	#[serde(default)]
+	pub frames: Vec<FrameDesc<V>>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum FrameDesc<V> {
+	Anim {
+		anim: V,
+		time: DurationRange,
+		#[serde(default = "default_easing")]
+		easing: Easing,
+	},
+	Wait {
+		wait: DurationRange,
+	},
+}
+
+fn default_weight() -> f32 { 1.0 }
+
+fn default_easing() -> Easing { Easing::Linear }
+
+impl<V: Clone> TrackDesc<V> {
+	fn build<T: Interpolate + Clone>(&self, autoplay: bool, initial: T, rng: &mut impl Rng, convert: impl Fn(&V) -> T) -> ProcAnim<T> {
+		let initial = self.initial.as_ref().map_or(initial, &convert);
+		let mut anim = ProcAnim::new(initial, StdRng::seed_from_u64(rng.gen()));
+		
+		for frame in &self.frames {
+			anim = match frame {
+				FrameDesc::Anim { anim: target, time, easing } => anim.anim(convert(target), time.clone(), easing.clone()),
+				FrameDesc::Wait { wait } => anim.wait(wait.clone()),
+			};
+		}
+		
+		if self.repeat { anim = anim.repeat(); }
+		if !autoplay { anim = anim.no_autoplay(); }
+		
+		anim
+	}
+}
+
+/// Named group of procedural animations which can be played, stopped and blended together.
+pub struct AnimSet {
+	pub name: String,
+	pub weight: f32,
+	pub bones: Vec<(Bones, ProcAnim<Rot3>)>,
+	pub morphs: Vec<(Morphs, ProcAnim<f32>)>,
+}
+
+impl AnimSet {
+	/// Each track gets its own generator seeded from `rng`.
+	pub fn from_desc(name: &str, desc: &AnimSetDesc, rng: &mut impl Rng) -> Self {
+		let bones = desc.bones
+		                .iter()
+		                .filter_map(|track| match Bones::from_role(&track.role) {
+			                Some(bone) => Some((bone, track.build(desc.autoplay, Rot3::identity(), rng, |angles| {
+				                let angles = angles.map(f32::to_radians);
+				                Rot3::from_euler_angles(angles.x, angles.y, angles.z)
+			                }))),
+			                None => {
+				                eprintln!("Unknown bone role {} in animation set {}", track.role, name);
+				                None
+			                },
+		                })
+		                .collect();
+		
+		let morphs = desc.morphs
+		                 .iter()
+		                 .filter_map(|track| match Morphs::from_role(&track.role) {
+			                 Some(morph) => Some((morph, track.build(desc.autoplay, 0.0, rng, |&value| value))),
+			                 None => {
+				                 eprintln!("Unknown morph role {} in animation set {}", track.role, name);
+				                 None
+			                 },
+		                 })
+		                 .collect();
+		
+		AnimSet {
+			name: name.to_string(),
+			weight: desc.weight,
+			bones,
+			morphs,
+		}
+	}
+	
+	pub fn playing(&self) -> bool {
+		self.bones.iter().any(|(_, anim)| !anim.stopped())
+			|| self.morphs.iter().any(|(_, anim)| !anim.stopped())
+	}
+	
+	pub fn play(&mut self) {
+		self.bones.iter_mut().for_each(|(_, anim)| anim.play());
+		self.morphs.iter_mut().for_each(|(_, anim)| anim.play());
+	}
+	
+	pub fn stop(&mut self) {
+		self.bones.iter_mut().for_each(|(_, anim)| anim.stop());
+		self.morphs.iter_mut().for_each(|(_, anim)| anim.stop());
+	}
+}
@@ -1,486 +1,99 @@
-
-#[repr(usize)]
-#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
-pub enum Bones {
-	ViewCnt = 0,
-	Root = 1,
-	Center = 2,
-	Groove = 3,
-	Waist = 4,
-	UpperBody = 5,
-	UpperBody2 = 6,
-	Neck = 7,
-	Head = 8,
-	LeftEye = 9,
-	RightEye = 10,
-	BothEyes = 11,
-	Center2 = 12,
-	Chin = 13,
-	LeftEyebrow = 14,
-	RightEyebrow = 15,
-	LeftEyebrow2 = 16,
-	RightEyebrow2 = 17,
-	Forelock1 = 18,
-	RightForelock = 19,
-	RightForelock1 = 20,
-	LeftForelock = 21,
-	LeftForelock1 = 22,
-	Necktie1 = 23,
-	Necktie2 = 24,
-	Necktie3 = 25,
-	Necktie4 = 26,
-	LeftR = 27,
-	LeftRFirst = 28,
-	RightR = 29,
-	RightRFirst = 30,
-	LowerHalfOfBody = 31,
-	ShoulderL = 32,
-	Leftshoulder = 33,
-	LeftshoulderC = 34,
-	ShoulderR = 35,
-	Rightshoulder = 36,
-	RightshoulderC = 37,
-	LeftArm = 38,
-	ArmTwistL = 39,
-	LeftArmWrench1 = 40,
-	LeftArmWrench2 = 41,
-	LeftArmWrench3 = 42,
-	RightArm = 43,
-	ArmTwistR = 44,
-	RightArmWrench1 = 45,
-	RightArmWrench2 = 46,
-	RightArmWrench3 = 47,
-	LeftElbow = 48,
-	WristTwistL = 49,
-	LeftHandscrew1 = 50,
-	LeftHandscrew2 = 51,
-	LeftHandTorsion3 = 52,
-	RightElbow = 53,
-	WristTwistR = 54,
-	RightHandWrench1 = 55,
-	RightHandWrench2 = 56,
-	RightHandWrench3 = 57,
-	LeftWrist = 58,
-	DummyL = 59,
-	RightWrist = 60,
-	DummyR = 61,
-	LeftThumb0 = 62,
-	LeftThumb1 = 63,
-	LeftThumb2 = 64,
-	LeftMiddleFinger1 = 65,
-	LeftMiddleFinger2 = 66,
-	LeftMiddleFinger3 = 67,
-	LeftLittleFinger1 = 68,
-	LeftLittleFinger2 = 69,
-	LeftLittleFinger3 = 70,
-	LeftIndexFinger1 = 71,
-	LeftIndexFinger2 = 72,
-	LeftIndexFinger3 = 73,
-	LeftRingFinger1 = 74,
-	LeftRingFinger2 = 75,
-	LeftRingFinger3 = 76,
-	RightThumb0 = 77,
-	RightThumb1 = 78,
-	RightThumb2 = 79,
-	RightMiddleFinger1 = 80,
-	RightMiddleFinger2 = 81,
-	RightMiddleFinger3 = 82,
-	RightLittleFinger1 = 83,
-	RightLittleFinger2 = 84,
-	RightLittleFinger3 = 85,
-	RightIndexFinger1 = 86,
-	RightIndexFinger2 = 87,
-	RightIndexFinger3 = 88,
-	RightRingFinger1 = 89,
-	RightRingFinger2 = 90,
-	RightRingFinger3 = 91,
-	Center3 = 92,
-	Center4 = 93,
-	LeftHipCancel = 94,
-	LeftThigh = 95,
-	RightHipCancel = 96,
-	RightThigh = 97,
-	LeftLeg2 = 98,
-	RightLeg2 = 99,
-	LeftFeet = 100,
-	RightFeet = 101,
-	LeftToe = 102,
-	RightToe = 103,
-	LegIkpL = 104,
-	LeftFootIk = 105,
-	LegIkpR = 106,
-	RightFootIk = 107,
-	LeftToeIk = 108,
-	RightToeIk = 109,
-	Tongue0 = 110,
-	Tongue1 = 111,
-	Tongue2 = 112,
-	UpperTooth = 113,
-	HeadTip = 114,
-	RightEyeTip = 115,
-	LeftEyeTip = 116,
-	BothEyeTips = 117,
-	LeftHandTip = 118,
-	RightHandTip = 119,
-	LeftThumb2Tips = 120,
-	LeftMiddleFinger3Tips = 121,
-	LeftPinky3Tips = 122,
-	LeftThumb3Tips = 123,
-	LeftRingFinger3Tips = 124,
-	RightThumb2Tips = 125,
-	RightMiddleFinger3Tips = 126,
-	RightPinky3Tips = 127,
-	RightThumb3Tips = 128,
-	RightRingFinger3Tips = 129,
-	LowerBodyTip = 130,
-	LeftToeIkTip = 131,
-	RightToeIkTip = 132,
-	LeftFootIkTip = 133,
-	RightFootIkTip = 134,
-	CenterTip = 135,
-	Skirt0x0 = 136,
-	Skirt0x1 = 137,
-	Skirt0x2 = 138,
-	Skirt0x3 = 139,
-	Skirt0x4 = 140,
-	Skirt0x5 = 141,
-	Skirt0x6 = 142,
-	Skirt0x7 = 143,
-	Skirt0x8 = 144,
-	Skirt0x9 = 145,
-	Skirt0x10 = 146,
-	Skirt0x11 = 147,
-	Skirt0x12 = 148,
-	Skirt0x13 = 149,
-	Skirt0x14 = 150,
-	Skirt0x15 = 151,
-	Skirt1x0 = 152,
-	Skirt1x1 = 153,
-	Skirt1x2 = 154,
-	Skirt1x3 = 155,
-	Skirt1x4 = 156,
-	Skirt1x5 = 157,
-	Skirt1x6 = 158,
-	Skirt1x7 = 159,
-	Skirt1x8 = 160,
-	Skirt1x9 = 161,
-	Skirt1x10 = 162,
-	Skirt1x11 = 163,
-	Skirt1x12 = 164,
-	Skirt1x13 = 165,
-	Skirt1x14 = 166,
-	Skirt1x15 = 167,
-	RightY = 168,
-	RightYAhead = 169,
-	LeftY = 170,
-	LeftYTip = 171,
-	RightLeg = 172,
-	RightKnee = 173,
-	RightAnkle = 174,
-	Toe2R = 175,
-	LeftLeg = 176,
-	LeftKnee = 177,
-	LeftAnkle = 178,
-	Toe2L = 179,
-	RightH1 = 180,
-	RightH2 = 181,
-	RightH3 = 182,
-	RightH4 = 183,
-	RightH5 = 184,
-	RightH6 = 185,
-	RightH7 = 186,
-	RightH8 = 187,
-	RightH9 = 188,
-	RightH10 = 189,
-	RightH11 = 190,
-	RightH12 = 191,
-	RightH13 = 192,
-	RightH14 = 193,
-	RightH15 = 194,
-	RightH16 = 195,
-	RightH17 = 196,
-	RightH18 = 197,
-	RightH19 = 198,
-	RightH20 = 199,
-	RightHFirst = 200,
-	RightH21 = 201,
-	RightH22 = 202,
-	RightH23 = 203,
-	RightH24 = 204,
-	RightH25 = 205,
-	RightH2First = 206,
-	RightH31 = 207,
-	RightH32 = 208,
-	RightH3First = 209,
-	RightH41 = 210,
-	RightH4First = 211,
-	RightH51 = 212,
-	RightH52 = 213,
-	RightH53 = 214,
-	RightH5First = 215,
-	LeftH1 = 216,
-	LeftH2 = 217,
-	LeftH3 = 218,
-	LeftH4 = 219,
-	LeftH5 = 220,
-	LeftH6 = 221,
-	LeftH7 = 222,
-	LeftH8 = 223,
-	LeftH9 = 224,
-	LeftH10 = 225,
-	LeftH11 = 226,
-	LeftH12 = 227,
-	LeftH13 = 228,
-	LeftH14 = 229,
-	LeftH15 = 230,
-	LeftH16 = 231,
-	LeftH17 = 232,
-	LeftH18 = 233,
-	LeftH19 = 234,
-	LeftH20 = 235,
-	LeftH21 = 236,
-	LeftH22 = 237,
-	LeftH23 = 238,
-	LeftH24 = 239,
-	LeftH25 = 240,
-	LeftH31 = 241,
-	LeftH32 = 242,
-	LeftH41 = 243,
-	LeftH51 = 244,
-	LeftH52 = 245,
-	LeftH53 = 246,
+macro_rules! roles {
+	(
+		$( #[$meta:meta] )*
+		pub enum $name:ident {
+			$( $variant:ident = $role:literal [ $( $pattern:literal ),* ], )*
+		}
+	) => {
+		$( #[$meta] )*
+		#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+		pub enum $name {
+			$( $variant, )*
+		}
+		
+		impl $name {
+			pub const ALL: &'static [$name] = &[ $( $name::$variant, )* ];
+			
+			pub fn from_role(role: &str) -> Option<Self> {
+				match role {
+					$( $role => Some($name::$variant), )*
+					_ => None,
+				}
+			}
+			
+			/// Key of the role in `model.toml`.
+			pub fn role(self) -> &'static str {
+				match self {
+					$( $name::$variant => $role, )*
+				}
+			}
+			
+			/// Standard MMD names, used unless `model.toml` maps the role.
+			pub fn default_patterns(self) -> &'static [&'static str] {
+				match self {
+					$( $name::$variant => &[ $( $pattern ),* ], )*
+				}
+			}
+		}
+	}
 }
 
-#[repr(usize)]
-#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
-pub enum Morphs {
-	Serious = 0,
-	BeEmbarrassed = 1,
-	Grin = 2,
-	Anger = 3,
-	Parallel = 4,
-	Near = 5,
-	NearLeft = 6,
-	NearRight = 7,
-	Away = 8,
-	Left = 9,
-	LeftRight = 10,
-	Short = 11,
-	Up = 12,
-	UpLeft = 13,
-	TopRight = 14,
-	Bottom = 15,
-	LowerLeft = 16,
-	LowerRight = 17,
-	Front = 18,
-	TopOfEyebrow = 19,
-	LeftEyebrow = 20,
-	RightEyebrow = 21,
-	CoarseEyebrows = 22,
-	Blink = 23,
-	Wink2 = 24,
-	Wink2Right = 25,
-	BBlink = 26,
-	BWink2 = 27,
-	BWink2Right = 28,
-	Laughter = 29,
-	Wink = 30,
-	WinkRight = 31,
-	BLaugh = 32,
-	BWink = 33,
-	BWinkRight = 34,
-	Clink = 35,
-	Squeak = 36,
-	ASmile = 37,
-	Huh = 38,
-	Surprise = 39,
-	Eyes = 40,
-	SmallPupil = 41,
-	EyesVertical = 42,
-	HorribleChild = 43,
-	No = 44,
-	DroopyEyes = 45,
-	CameraEye = 46,
-	HachiMe = 47,
-	LaughingEyes = 48,
-	StarryEyes = 49,
-	Huh2 = 50,
-	A = 51,
-	Ah2 = 52,
-	I2 = 53,
-	I3 = 54,
-	U = 55,
-	E = 56,
-	O = 57,
-	N = 58,
-	TriangleEyes = 59,
-	TriangleMouth = 60,
-	SquareMouth = 61,
-	WMouth = 62,
-	Ohm = 63,
-	CatMouth = 64,
-	Smiling = 65,
-	Grin2 = 66,
-	Peeking = 67,
-	MouthAngleUp = 68,
-	LeftCornerOfMouthRaised = 69,
-	RightCornerOfMouthUp = 70,
-	LowerLeftCornerOfMouth = 71,
-	LowerLeftCornerOfMouth2 = 72,
-	LowerRightCornerOfMouth = 73,
-	WideMouth = 74,
-	LeftsideOfMouthWidened = 75,
-	RightsideOfMouthWidened = 76,
-	ToothlessUpper = 77,
-	ToothlessBottom = 78,
-	NoTongue = 79,
-	LowerEyelashesErased = 80,
-	HlErased1 = 81,
-	Moxie = 82,
-	LeftMottling = 83,
-	Rightsquirming = 84,
-	Tears = 85,
-	LongTear = 86,
-	UpperTear = 87,
-	LowerTear = 88,
-	BeforeTear = 89,
-	NearTears = 90,
-	Embarrassment = 91,
-	Embarrassment2 = 92,
-	Embarrassment3 = 93,
-	NoseLineErased = 94,
-	LongNoseLine = 95,
-	LeftLight = 96,
-	RightLight = 97,
-	LeftExl = 98,
-	RightExl = 99,
-	LeftTriangle = 100,
-	RightExl2 = 101,
-	LeftAnger = 102,
-	RightAnger = 103,
-	Leftsweat = 104,
-	Rightsweat = 105,
-	Undersweat = 106,
-	Eyelashes = 107,
-	// TheContourLineDisappearsAnd = 108,
-	// ToonBecomesWhite = 109,
-	// ToonDeepens = 110,
-	// SpaDisappear = 111,
-	// Disappear = 112,
-	// AllBlackMode = 113,
-	// AllGrayMode = 114,
-	// AllWhiteMode = 115,
-	// SmallFaceDisappear = 116,
-	// RedFaceDisappears = 117,
-	// Glasses = 118,
-	// MicrophoneOff = 119,
-	// HpOff = 120,
-	// Tpose = 121,
-	// Tpose2 = 122,
-	// ShoulderPose = 123,
-	// DirectToFinger = 124,
-	// LeftHandHalfOpen = 125,
-	// RightHandHalfOpen = 126,
-	// LeftHandGoo = 127,
-	// RightHandGoo = 128,
-	// SpreadYourFingers = 129,
-	// KeepYourFingersTogether = 130,
-	// EyeDark = 131,
-	// HairshadowOff = 132,
-	// TDApose = 133
+roles! {
+	/// Bones driven by `Miku`, resolved by name when the model loads.
+	pub enum Bones {
+		Center = "center" ["センター", "center"],
+		Groove = "groove" ["グルーブ", "groove"],
+		Waist = "waist" ["腰", "waist"],
+		UpperBody = "upper_body" ["上半身", "upper body"],
+		UpperBody2 = "upper_body2" ["上半身2", "上半身２", "upper body2"],
+		Neck = "neck" ["首", "neck"],
+		Head = "head" ["頭", "head"],
+		LeftEye = "left_eye" ["左目", "eye_L"],
+		RightEye = "right_eye" ["右目", "eye_R"],
+		BothEyes = "both_eyes" ["両目", "eyes"],
+		LowerBody = "lower_body" ["下半身", "lower body"],
+		LeftShoulder = "left_shoulder" ["左肩", "shoulder_L"],
+		RightShoulder = "right_shoulder" ["右肩", "shoulder_R"],
+		LeftArm = "left_arm" ["左腕", "arm_L"],
+		RightArm = "right_arm" ["右腕", "arm_R"],
+		LeftElbow = "left_elbow" ["左ひじ", "elbow_L"],
+		RightElbow = "right_elbow" ["右ひじ", "elbow_R"],
+		LeftWrist = "left_wrist" ["左手首", "wrist_L"],
+		RightWrist = "right_wrist" ["右手首", "wrist_R"],
+		LeftLeg = "left_leg" ["左足", "leg_L"],
+		RightLeg = "right_leg" ["右足", "leg_R"],
+		LeftKnee = "left_knee" ["左ひざ", "knee_L"],
+		RightKnee = "right_knee" ["右ひざ", "knee_R"],
+		LeftAnkle = "left_ankle" ["左足首", "ankle_L"],
+		RightAnkle = "right_ankle" ["右足首", "ankle_R"],
+	}
 }
 
-#[repr(usize)]
-#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
-pub enum RigidBodies {
-	Root = 0,
-	Forelock1 = 1,
-	RightForelock = 2,
-	RightForelock1 = 3,
-	LeftForelock = 4,
-	LeftForelock1 = 5,
-	Necktie1 = 6,
-	Necktie2 = 7,
-	Necktie3 = 8,
-	Necktie4 = 9,
-	LeftR = 10,
-	RightR = 11,
-	Skirt0x0 = 12,
-	Skirt0x1 = 13,
-	Skirt0x2 = 14,
-	Skirt0x3 = 15,
-	Skirt0x4 = 16,
-	Skirt0x5 = 17,
-	Skirt0x6 = 18,
-	Skirt0x7 = 19,
-	Skirt0x8 = 20,
-	Skirt0x9 = 21,
-	Skirt0x10 = 22,
-	Skirt0x11 = 23,
-	Skirt0x12 = 24,
-	Skirt0x13 = 25,
-	Skirt0x14 = 26,
-	Skirt0x15 = 27,
-	RightY = 28,
-	LeftY = 29,
-	RightH2 = 30,
-	RightH3 = 31,
-	RightH4 = 32,
-	RightH5 = 33,
-	RightH6 = 34,
-	RightH7 = 35,
-	RightH8 = 36,
-	RightH9 = 37,
-	RightH10 = 38,
-	RightH11 = 39,
-	RightH12 = 40,
-	RightH13 = 41,
-	RightH14 = 42,
-	RightH15 = 43,
-	RightH16 = 44,
-	RightH17 = 45,
-	RightH18 = 46,
-	RightH19 = 47,
-	RightH20 = 48,
-	RightH2x1 = 49,
-	RightH2x2 = 50,
-	RightH2x3 = 51,
-	RightH2x4 = 52,
-	RightH2x5 = 53,
-	RightH3x1 = 54,
-	RightH3x2 = 55,
-	RightH4x1 = 56,
-	RightH5x1 = 57,
-	RightH5x2 = 58,
-	RightH5x3 = 59,
-	LeftH2 = 60,
-	LeftH3 = 61,
-	LeftH4 = 62,
-	LeftH5 = 63,
-	LeftH6 = 64,
-	LeftH7 = 65,
-	LeftH8 = 66,
-	LeftH9 = 67,
-	LeftH10 = 68,
-	LeftH11 = 69,
-	LeftH12 = 70,
-	LeftH13 = 71,
-	LeftH14 = 72,
-	LeftH15 = 73,
-	LeftH16 = 74,
-	LeftH17 = 75,
-	LeftH18 = 76,
-	LeftH19 = 77,
-	LeftH20 = 78,
-	LeftH2x1 = 79,
-	LeftH2x2 = 80,
-	LeftH2x3 = 81,
-	LeftH2x4 = 82,
-	LeftH2x5 = 83,
-	LeftH3x1 = 84,
-	LeftH3x2 = 85,
-	LeftH4x1 = 86,
-	LeftH5x1 = 87,
-	LeftH5x2 = 88,
-	LeftH5x3 = 89,
+roles! {
+	/// Expressions used by `Miku`, resolved by name when the model loads.
+	pub enum Morphs {
+		Serious = "serious" ["真面目"],
+		Troubled = "troubled" ["困る"],
+		Smile = "smile" ["にこり"],
+		Anger = "anger" ["怒り"],
+		Blink = "blink" ["まばたき"],
+		Laugh = "laugh" ["笑い"],
+		Wink = "wink" ["ウィンク"],
+		WinkRight = "wink_right" ["ウィンク右", "ｳｨﾝｸ右"],
+		Wink2 = "wink2" ["ウィンク２", "ウィンク2"],
+		Wink2Right = "wink2_right" ["ウィンク２右", "ｳｨﾝｸ２右", "ウィンク2右"],
+		Calm = "calm" ["なごみ"],
+		Surprised = "surprised" ["びっくり"],
+		A = "a" ["あ"],
+		I = "i" ["い"],
+		U = "u" ["う"],
+		E = "e" ["え"],
+		O = "o" ["お"],
+		Grin = "grin" ["にやり"],
+		Beam = "beam" ["にっこり"],
+		Embarrassment = "embarrassment" ["照れ"],
+		Embarrassment2 = "embarrassment2" ["照れ2", "照れ２"],
+		Embarrassment3 = "embarrassment3" ["照れ3", "照れ３"],
+		Tears = "tears" ["涙"],
+	}
 }
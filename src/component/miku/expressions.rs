@@ -0,0 +1,143 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use rand::Rng;
+
+use crate::math::Vec2;
+use super::lip_sync::{LipSyncTrack, VOWELS};
+use super::proc_anim::DurationRange;
+use super::{MikuMapping, Morphs};
+
+
+/// Seconds it takes to close and then open the eyes during a blink.
+const BLINK_CLOSE: f32 = 0.07;
+const BLINK_OPEN: f32 = 0.12;
+const DOUBLE_BLINK_CHANCE: f64 = 0.15;
+
+/// Drives eye and mouth morphs on top of animations: randomized blinking and lip sync.
+pub struct ExpressionDriver {
+	pub auto_blink: bool,
+	/// Seconds between blinks, as `[min, max]`.
+	pub blink_interval: Vec2,
+	/// Multiplier of mouth morphs.
+	pub lip_sync_gain: f32,
+	/// Audio file to lip sync to, loaded by `Miku` on next tick.
+	pub requested: Option<PathBuf>,
+	blink_timer: f32,
+	blink_time: Option<f32>,
+	eyes_closed: f32,
+	blink: f32,
+	lip_sync: Option<LipSync>,
+}
+
+pub struct LipSync {
+	pub name: String,
+	pub track: Arc<LipSyncTrack>,
+	pub time: f32,
+	pub looping: bool,
+}
+
+impl ExpressionDriver {
+	pub fn new() -> Self {
+		ExpressionDriver {
+			auto_blink: true,
+			blink_interval: vector!(0.5, 5.0),
+			lip_sync_gain: 1.0,
+			requested: None,
+			blink_timer: 1.0,
+			blink_time: None,
+			eyes_closed: 0.0,
+			blink: 0.0,
+			lip_sync: None,
+		}
+	}
+	
+	/// Keeps the eyes shut for a while, eg. when embarrassed.
+	pub fn close_eyes(&mut self, duration: f32) {
+		self.eyes_closed = self.eyes_closed.max(duration);
+		self.blink_time = None;
+	}
+	
+	pub fn speak(&mut self, name: impl Into<String>, track: Arc<LipSyncTrack>, looping: bool) {
+		self.lip_sync = Some(LipSync {
+			name: name.into(),
+			track,
+			time: 0.0,
+			looping,
+		});
+	}
+	
+	pub fn stop_speaking(&mut self) {
+		self.lip_sync = None;
+	}
+	
+	pub fn lip_sync(&self) -> Option<&LipSync> {
+		self.lip_sync.as_ref()
+	}
+	
+	pub fn update(&mut self, delta_time: f32, mapping: &MikuMapping, morphs: &mut [f32], rng: &mut impl Rng) {
+		self.update_blink(delta_time, rng);
+		
+		if let Some(id) = mapping.morph(Morphs::Blink) {
+			morphs[id] = morphs[id].max(self.blink);
+		}
+		
+		if let Some(lip_sync) = &mut self.lip_sync {
+			lip_sync.time += delta_time;
+			
+			let duration = lip_sync.track.duration();
+			if lip_sync.looping && duration > 0.0 {
+				lip_sync.time %= duration;
+			}
+			
+			let visemes = lip_sync.track.sample(lip_sync.time);
+			
+			for (&morph, weight) in VOWELS.iter().zip(visemes) {
+				if let Some(id) = mapping.morph(morph) {
+					morphs[id] = morphs[id].max((weight * self.lip_sync_gain).clamp(0.0, 1.0));
+				}
+			}
+			
+			if lip_sync.time >= duration {
+				self.lip_sync = None;
+			}
+		}
+	}
+	
+	fn update_blink(&mut self, delta_time: f32, rng: &mut impl Rng) {
+		if self.eyes_closed > 0.0 {
+			self.eyes_closed -= delta_time;
+			self.blink = (self.blink + delta_time / BLINK_CLOSE).min(1.0);
+			self.blink_timer = DurationRange::from(self.blink_interval).sample(rng);
+			return;
+		}
+		
+		if let Some(time) = &mut self.blink_time {
+			*time += delta_time;
+			
+			self.blink = if *time < BLINK_CLOSE {
+				*time / BLINK_CLOSE
+			} else {
+				1.0 - ((*time - BLINK_CLOSE) / BLINK_OPEN).min(1.0)
+			};
+			
+			if *time >= BLINK_CLOSE + BLINK_OPEN {
+				self.blink_time = None;
+			}
+			return;
+		}
+		
+		self.blink = (self.blink - delta_time / BLINK_OPEN).max(0.0);
+		
+		if !self.auto_blink { return; }
+		
+		self.blink_timer -= delta_time;
+		if self.blink_timer <= 0.0 {
+			self.blink_time = Some(0.0);
+			self.blink_timer = if rng.gen_bool(DOUBLE_BLINK_CHANCE) {
+				0.1
+			} else {
+				DurationRange::from(self.blink_interval).sample(rng)
+			};
+		}
+	}
+}
@@ -1,13 +1,18 @@
-use egui::{Grid, Id, RichText, ScrollArea, Ui};
+use egui::{ComboBox, DragValue, Grid, Id, RichText, ScrollArea, Slider, Ui};
 
 use crate::application::Application;
-use crate::math::Color;
+use crate::math::{Color, Isometry3};
 use crate::utils::{end_row_interact, ExUi, id_fmt};
 use super::super::ComponentBase;
+use super::super::model::MMDModel;
 use super::super::model::mmd::BodyPart;
+use super::super::model::mmd::vpd::VpdPose;
 use super::Miku;
 
 
+const DEFAULT_POSE: &str = "poses/pose.vpd";
+
+
 pub fn miku_gui(miku: &Miku, ui: &mut Ui, application: &Application) {
 	let model = match miku.model.get(application) {
 		Some(model) => model,
@@ -68,6 +73,28 @@ pub fn miku_gui(miku: &Miku, ui: &mut Ui, application: &Application) {
 		}
 	});
 	
+	pose(ui, id.with("pose"), &miku_ent.name, model);
+	
+	ui.separator();
+	
+	anim_sets(ui, id.with("anim sets"), miku);
+	
+	ui.separator();
+	
+	anim_graph(ui, id.with("anim graph"), miku);
+	
+	ui.separator();
+	
+	expressions(ui, id.with("expressions"), miku);
+	
+	ui.separator();
+	
+	if let Some(look_at) = miku.look_at.get(application) {
+		ui.inspect_collapsing()
+		  .title("Look At")
+		  .show(ui, look_at, application);
+	}
+	
 	ui.separator();
 	
 	if let Some(selected_bone) = selected_bone {
@@ -87,13 +114,16 @@ pub fn miku_gui(miku: &Miku, ui: &mut Ui, application: &Application) {
 			  .title("MMD Rigid Body")
 			  .default_open(true)
 			  .show(ui, rb, application);
-			
-			if let Some(bone) = model.state_mut().bones.get_mut(selected_bone) {
-				ui.inspect_collapsing()
-				  .title("Bone")
-				  .show(ui, bone, application);
-			}
-			
+		}
+		
+		// Bones without rigid bodies can still be posed
+		if let Some(bone) = model.state_mut().bones.get_mut(selected_bone) {
+			ui.inspect_collapsing()
+			  .title("Bone")
+			  .show(ui, bone, application);
+		}
+		
+		if let Some(rb) = rb {
 			ui.inspect_collapsing()
 			  .title("Entity")
 			  .show(ui, rb.entity(application), application);
@@ -105,6 +135,152 @@ pub fn miku_gui(miku: &Miku, ui: &mut Ui, application: &Application) {
 	}
 }
 
+fn pose(ui: &mut Ui, id: Id, name: &str, model: &MMDModel) {
+	ui.horizontal(|ui| {
+		let mut path = ui.ctx().data_mut(|d| d.get_temp::<String>(id))
+		                 .unwrap_or_else(|| DEFAULT_POSE.to_string());
+		
+		ui.text_edit_singleline(&mut path);
+		
+		if ui.button("Load").clicked() {
+			if let Err(err) = VpdPose::load(&path).map(|pose| pose.apply(model)) {
+				eprintln!("Failed to load pose {}: {}", path, err);
+			}
+		}
+		
+		if ui.button("Save").clicked() {
+			if let Err(err) = VpdPose::capture(model, name).save(&path) {
+				eprintln!("Failed to save pose {}: {}", path, err);
+			}
+		}
+		
+		if ui.button("Clear").clicked() {
+			for bone in &mut model.state_mut().bones {
+				bone.pose_transform = Isometry3::identity();
+			}
+		}
+		
+		ui.ctx().data_mut(|d| d.insert_temp(id, path));
+	});
+}
+
+fn anim_sets(ui: &mut Ui, id: impl Into<Id>, miku: &Miku) {
+	let mut anims = match miku.anims.try_borrow_mut() {
+		Ok(anims) => anims,
+		Err(_) => return,
+	};
+	
+	Grid::new(id)
+		.striped(true)
+		.num_columns(3)
+		.show(ui, |ui| {
+			for set in anims.iter_mut() {
+				ui.label(&set.name);
+				
+				let playing = set.playing();
+				if ui.button(if playing { "Stop" } else { "Play" }).clicked() {
+					if playing { set.stop() } else { set.play() }
+				}
+				
+				ui.add(Slider::new(&mut set.weight, 0.0..=1.0).text("Weight"));
+				ui.end_row();
+			}
+		});
+}
+
+fn expressions(ui: &mut Ui, id: Id, miku: &Miku) {
+	let mut expressions = match miku.expressions.try_borrow_mut() {
+		Ok(expressions) => expressions,
+		Err(_) => return,
+	};
+	
+	Grid::new(id)
+		.num_columns(2)
+		.show(ui, |ui| {
+			ui.inspect_row("Auto Blink", &mut expressions.auto_blink, ());
+			ui.inspect_row("Blink Interval", &mut expressions.blink_interval, ());
+			ui.inspect_row("Lip Sync Gain", &mut expressions.lip_sync_gain, (0.01, 0.0..=4.0));
+			
+			match expressions.lip_sync() {
+				Some(lip_sync) => ui.inspect_row("Speaking", format!("{} ({:.1} / {:.1}s)", lip_sync.name, lip_sync.time, lip_sync.track.duration()), ()),
+				None => ui.inspect_row("Speaking", RichText::new("NONE").monospace().italics(), ()),
+			}
+		});
+	
+	ui.horizontal(|ui| {
+		let path_id = id.with("path");
+		let mut path = ui.ctx().data_mut(|d| d.get_temp::<String>(path_id))
+		                 .unwrap_or_default();
+		
+		ui.text_edit_singleline(&mut path);
+		
+		if ui.button("Speak").clicked() {
+			expressions.requested = Some(path.clone().into());
+		}
+		
+		if ui.button("Stop").clicked() {
+			expressions.stop_speaking();
+		}
+		
+		ui.ctx().data_mut(|d| d.insert_temp(path_id, path));
+	});
+}
+
+fn anim_graph(ui: &mut Ui, id: impl Into<Id>, miku: &Miku) {
+	let mut graph = match miku.graph.try_borrow_mut() {
+		Ok(graph) => graph,
+		Err(_) => return,
+	};
+	
+	let graph = match &mut *graph {
+		Some(graph) => graph,
+		None => return,
+	};
+	
+	let id = id.into();
+	
+	Grid::new(id.with("layers"))
+		.striped(true)
+		.num_columns(3)
+		.show(ui, |ui| {
+			for (layer_id, layer) in graph.layers.iter_mut().enumerate() {
+				ui.label(&layer.name);
+				
+				let mut selected = None;
+				ComboBox::from_id_source(id.with(layer_id))
+					.selected_text(&layer.current().name)
+					.show_ui(ui, |ui| {
+						for (state_id, state) in layer.states.iter().enumerate() {
+							if ui.selectable_label(state.name == layer.current().name, &state.name).clicked() {
+								selected = Some(state_id);
+							}
+						}
+					});
+				
+				if let Some(state) = selected {
+					layer.set_state(state, 0.3);
+				}
+				
+				ui.add(Slider::new(&mut layer.weight, 0.0..=1.0).text("Weight"));
+				ui.end_row();
+			}
+		});
+	
+	if graph.params.is_empty() { return; }
+	
+	ui.separator();
+	
+	Grid::new(id.with("params"))
+		.num_columns(2)
+		.show(ui, |ui| {
+			for (name, value) in graph.params.iter_mut() {
+				ui.label(name);
+				ui.add(DragValue::new(value).speed(0.01));
+				ui.end_row();
+			}
+		});
+}
+
 fn list(ui: &mut Ui, id: impl Into<Id>, selected: Option<usize>, elements: impl Iterator<Item=(usize, RichText)>) -> Option<usize> {
 	let mut new_selection = None;
 	
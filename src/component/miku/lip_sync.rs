@@ -0,0 +1,273 @@
+use std::f32::consts::PI;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::Result;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::renderer::Renderer;
+use crate::renderer::assets_manager::{AssetKey, AssetsManager};
+use crate::utils::AudioClip;
+use super::Morphs;
+
+
+/// Mouth morphs driven by lip sync, in `Visemes` order.
+pub const VOWELS: [Morphs; 5] = [Morphs::A, Morphs::I, Morphs::U, Morphs::E, Morphs::O];
+
+/// Weights of a, i, u, e and o mouth shapes.
+pub type Visemes = [f32; 5];
+
+/// Analysis frames per second.
+const FRAME_RATE: f32 = 60.0;
+/// Approximate first and second formants of Japanese vowels, in Hz.
+const FORMANTS: [(f32, f32); 5] = [(800.0, 1200.0), (300.0, 2300.0), (350.0, 1300.0), (500.0, 1900.0), (500.0, 900.0)];
+/// Centers of band-pass filters used to estimate the formants.
+const BANDS: [f32; 12] = [250.0, 320.0, 400.0, 500.0, 630.0, 800.0, 1000.0, 1250.0, 1600.0, 2000.0, 2500.0, 3150.0];
+const F1_RANGE: (f32, f32) = (200.0, 1000.0);
+const F2_RANGE: (f32, f32) = (800.0, 3200.0);
+/// Loudness below the clip's peak, in dB, at which the mouth is closed.
+const SILENCE_DB: f32 = 36.0;
+/// Quieter clips are measured against this level, in dBFS, instead of their peak, so silence doesn't open the mouth.
+const MIN_PEAK_DB: f32 = -40.0;
+
+#[derive(Clone, Hash, Debug, Deserialize, Serialize)]
+pub struct LipSyncAsset {
+	path: PathBuf,
+}
+
+impl LipSyncAsset {
+	pub fn at(audio_path: impl AsRef<Path>) -> Self {
+		LipSyncAsset {
+			path: audio_path.as_ref().to_path_buf(),
+		}
+	}
+}
+
+impl AssetKey for LipSyncAsset {
+	type Asset = Arc<LipSyncTrack>;
+	
+	fn load(&self, _assets_manager: &mut AssetsManager, _renderer: &mut Renderer) -> Result<Self::Asset> {
+		let clip = AudioClip::read(&self.path, AssetsManager::find_asset(&self.path)?)?;
+		
+		Ok(Arc::new(LipSyncTrack::analyze(&clip)))
+	}
+}
+
+impl Display for LipSyncAsset {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "lip sync of {}", self.path.to_string_lossy())
+	}
+}
+
+/// Mouth shapes over time, computed offline from an audio clip.
+#[derive(Debug, Clone)]
+pub struct LipSyncTrack {
+	pub frames: Vec<Visemes>,
+}
+
+impl LipSyncTrack {
+	/// Mouth opening follows loudness, while shape is picked by comparing estimated formants with these of the vowels.
+	pub fn analyze(clip: &AudioClip) -> Self {
+		let hop = ((clip.sample_rate as f32 / FRAME_RATE) as usize).max(1);
+		let nyquist = clip.sample_rate as f32 / 2.0;
+		let mut filters: Vec<_> = BANDS.iter()
+		                               .filter(|&&freq| freq < nyquist * 0.9)
+		                               .map(|&freq| (freq, Biquad::band_pass(freq, 2.0, clip.sample_rate as f32)))
+		                               .collect();
+		
+		let mut loudness = vec![];
+		let mut spectra = vec![];
+		
+		for chunk in clip.samples.chunks(hop) {
+			let energy = chunk.iter().map(|s| s * s).sum::<f32>() / chunk.len() as f32;
+			let bands: Vec<_> = filters.iter_mut()
+			                           .map(|(freq, filter)| (*freq, chunk.iter().map(|&s| filter.process(s).powi(2)).sum::<f32>()))
+			                           .collect();
+			
+			loudness.push(10.0 * (energy + 1.0e-10).log10());
+			spectra.push(bands);
+		}
+		
+		let peak = loudness.iter().copied().fold(MIN_PEAK_DB, f32::max);
+		let mut frames = Vec::with_capacity(loudness.len());
+		let mut smoothed = [0.0; 5];
+		
+		for (db, bands) in loudness.iter().zip(&spectra) {
+			let opening = ((db - peak + SILENCE_DB) / SILENCE_DB).clamp(0.0, 1.0).powi(2);
+			let target = vowel_weights(bands).map(|weight| weight * opening);
+			
+			// Mouth opens faster than it closes
+			for (value, target) in smoothed.iter_mut().zip(target) {
+				let rate = if target > *value { 0.6 } else { 0.25 };
+				*value += (target - *value) * rate;
+			}
+			
+			frames.push(smoothed);
+		}
+		
+		LipSyncTrack { frames }
+	}
+	
+	/// Length in seconds.
+	pub fn duration(&self) -> f32 {
+		self.frames.len() as f32 / FRAME_RATE
+	}
+	
+	pub fn sample(&self, time: f32) -> Visemes {
+		let frame = (time * FRAME_RATE).max(0.0);
+		let id = frame as usize;
+		
+		match (self.frames.get(id), self.frames.get(id + 1)) {
+			(Some(prev), Some(next)) => {
+				let t = frame.fract();
+				let mut result = *prev;
+				for (value, next) in result.iter_mut().zip(next) {
+					*value += (next - *value) * t;
+				}
+				result
+			},
+			(Some(last), None) => *last,
+			_ => [0.0; 5],
+		}
+	}
+}
+
+/// Soft classification of the frame into vowels, summing to 1.
+fn vowel_weights(bands: &[(f32, f32)]) -> Visemes {
+	let centroid = |(min, max): (f32, f32)| {
+		let (sum, weight) = bands.iter()
+		                         .filter(|(freq, _)| (min..=max).contains(freq))
+		                         .fold((0.0, 0.0), |(sum, weight), (freq, energy)| (sum + freq.ln() * energy, weight + energy));
+		
+		if weight > 0.0 { Some(sum / weight) } else { None }
+	};
+	
+	let (f1, f2) = match (centroid(F1_RANGE), centroid(F2_RANGE)) {
+		(Some(f1), Some(f2)) => (f1, f2),
+		_ => return [1.0, 0.0, 0.0, 0.0, 0.0],
+	};
+	
+	let mut weights = FORMANTS.map(|(v1, v2)| {
+		let distance = (f1 - v1.ln()).powi(2) + (f2 - v2.ln()).powi(2);
+		(-distance / 0.05).exp()
+	});
+	
+	let total: f32 = weights.iter().sum();
+	if total <= f32::EPSILON {
+		return [1.0, 0.0, 0.0, 0.0, 0.0];
+	}
+	
+	weights.iter_mut().for_each(|weight| *weight /= total);
+	weights
+}
+
+/// Band-pass filter with 0 dB peak gain, from RBJ Audio EQ Cookbook.
+struct Biquad {
+	b0: f32,
+	b2: f32,
+	a1: f32,
+	a2: f32,
+	x1: f32,
+	x2: f32,
+	y1: f32,
+	y2: f32,
+}
+
+impl Biquad {
+	fn band_pass(freq: f32, q: f32, sample_rate: f32) -> Self {
+		let w0 = 2.0 * PI * freq / sample_rate;
+		let alpha = w0.sin() / (2.0 * q);
+		let a0 = 1.0 + alpha;
+		
+		Biquad {
+			b0: alpha / a0,
+			b2: -alpha / a0,
+			a1: -2.0 * w0.cos() / a0,
+			a2: (1.0 - alpha) / a0,
+			x1: 0.0,
+			x2: 0.0,
+			y1: 0.0,
+			y2: 0.0,
+		}
+	}
+	
+	fn process(&mut self, x: f32) -> f32 {
+		let y = self.b0 * x + self.b2 * self.x2 - self.a1 * self.y1 - self.a2 * self.y2;
+		
+		self.x2 = self.x1;
+		self.x1 = x;
+		self.y2 = self.y1;
+		self.y1 = y;
+		
+		y
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::utils::encode_wav;
+	use super::*;
+	
+	const SAMPLE_RATE: u32 = 16000;
+	
+	/// Half a second of two sines at the formants of the vowel.
+	fn vowel(id: usize) -> Vec<f32> {
+		let (f1, f2) = FORMANTS[id];
+		
+		(0..SAMPLE_RATE as usize / 2).map(|sample| sample as f32 / SAMPLE_RATE as f32 * 2.0 * PI)
+		                             .map(|phase| (phase * f1).sin() * 0.4 + (phase * f2).sin() * 0.4)
+		                             .collect()
+	}
+	
+	fn analyze(samples: &[f32]) -> LipSyncTrack {
+		LipSyncTrack::analyze(&AudioClip::read_wav(&encode_wav(SAMPLE_RATE, samples)[..]).unwrap())
+	}
+	
+	fn middle(track: &LipSyncTrack) -> Visemes {
+		track.frames[track.frames.len() / 2]
+	}
+	
+	#[test]
+	fn silence_closes_mouth() {
+		let track = analyze(&vec![0.0; SAMPLE_RATE as usize / 2]);
+		
+		assert!(track.frames.iter().flatten().all(|&weight| weight < 0.01));
+	}
+	
+	#[test]
+	fn loud_signal_opens_mouth() {
+		let mut samples = vec![0.0; SAMPLE_RATE as usize / 2];
+		samples.extend(vowel(0));
+		
+		let track = analyze(&samples);
+		let opening = |frame: &Visemes| frame.iter().sum::<f32>();
+		
+		assert!(opening(&track.frames[10]) < 0.01);
+		assert!(opening(track.frames.last().unwrap()) > 0.9);
+	}
+	
+	#[test]
+	fn vowels_match_formants() {
+		for id in 0..VOWELS.len() {
+			let frame = middle(&analyze(&vowel(id)));
+			let best = (0..frame.len()).max_by(|&a, &b| frame[a].total_cmp(&frame[b])).unwrap();
+			
+			assert_eq!(best, id, "vowel {:?} recognized as {:?}: {:?}", VOWELS[id], VOWELS[best], frame);
+		}
+	}
+	
+	#[test]
+	fn sample_interpolates() {
+		let track = LipSyncTrack {
+			frames: vec![[0.0; 5], [1.0, 0.0, 0.5, 0.0, 0.0]],
+		};
+		let close = |a: Visemes, b: Visemes| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 0.001);
+		
+		assert!(close(track.sample(-1.0), [0.0; 5]));
+		assert!(close(track.sample(0.25 / FRAME_RATE), [0.25, 0.0, 0.125, 0.0, 0.0]));
+		assert!(close(track.sample(0.5 / FRAME_RATE), [0.5, 0.0, 0.25, 0.0, 0.0]));
+		assert!(close(track.sample(1.5 / FRAME_RATE), [1.0, 0.0, 0.5, 0.0, 0.0]));
+		// Mouth closes after the clip ends
+		assert!(close(track.sample(10.0), [0.0; 5]));
+	}
+}
@@ -0,0 +1,251 @@
+use std::cell::RefCell;
+use std::time::Duration;
+use anyhow::Result;
+use egui::Ui;
+use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::application::{Application, Entity};
+use crate::math::{Point3, Vec2};
+use crate::utils::ExUi;
+use super::super::{Component, ComponentBase, ComponentInner, ComponentLoad, ComponentRef};
+use super::super::model::MMDModel;
+use super::super::model::mmd::BoneLookAt;
+use super::proc_anim::DurationRange;
+use super::Bones;
+
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LookAtConfig {
+	/// Tag of the entity to look at.
+	pub target: String,
+	pub neck: LookAtBone,
+	pub head: LookAtBone,
+	pub eyes: LookAtBone,
+	/// How quickly the gaze follows the target.
+	pub smoothing: f32,
+	/// Seconds between eye saccades, as `[min, max]`.
+	pub saccade_interval: Vec2,
+	/// Maximum angle of eye saccades, in degrees.
+	pub saccade_angle: f32,
+	/// Seconds spent looking at the target before losing interest, as `[min, max]`.
+	pub interest: Vec2,
+	/// Seconds spent looking away before looking back, as `[min, max]`.
+	pub boredom: Vec2,
+	/// Targets further away are ignored.
+	pub max_distance: f32,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LookAtBone {
+	/// Fraction of the remaining rotation taken by the bone. Neck goes first, then head, then eyes.
+	pub weight: f32,
+	/// Maximum yaw and pitch, in degrees.
+	pub limits: Vec2,
+}
+
+impl Default for LookAtConfig {
+	fn default() -> Self {
+		LookAtConfig {
+			target: "Head".to_string(),
+			neck: LookAtBone { weight: 0.3, limits: vector!(30.0, 20.0) },
+			head: LookAtBone { weight: 0.6, limits: vector!(50.0, 30.0) },
+			eyes: LookAtBone { weight: 1.0, limits: vector!(15.0, 10.0) },
+			smoothing: 4.0,
+			saccade_interval: vector!(0.3, 2.5),
+			saccade_angle: 2.0,
+			interest: vector!(4.0, 12.0),
+			boredom: vector!(1.0, 4.0),
+			max_distance: 5.0,
+		}
+	}
+}
+
+struct LookAtState {
+	/// Smoothed point of gaze, in model space.
+	gaze: Option<Point3>,
+	/// Fades in and out with interest.
+	weight: f32,
+	interested: bool,
+	interest_timer: f32,
+	saccade: Vec2,
+	saccade_timer: f32,
+}
+
+/// Aims eyes, head and neck of `MMDModel` of the same entity at the tagged entity, by default the player's head.
+#[derive(ComponentBase)]
+pub struct LookAt {
+	#[inner] inner: ComponentInner,
+	pub config: RefCell<LookAtConfig>,
+	/// Components created by other ones, eg. `Miku`, are saved by their owners.
+	persistent: bool,
+	model: ComponentRef<MMDModel>,
+	bones: RefCell<Vec<(Bones, usize)>>,
+	state: RefCell<LookAtState>,
+}
+
+impl LookAt {
+	pub fn new(config: LookAtConfig) -> Self {
+		LookAt {
+			inner: ComponentInner::new_norender(),
+			config: RefCell::new(config),
+			persistent: false,
+			model: ComponentRef::null(),
+			bones: RefCell::new(Vec::new()),
+			state: RefCell::new(LookAtState {
+				gaze: None,
+				weight: 0.0,
+				interested: false,
+				interest_timer: 0.0,
+				saccade: Vec2::zeros(),
+				saccade_timer: 0.0,
+			}),
+		}
+	}
+	
+	fn bind(&self, model: &MMDModel) {
+		let find = |bone: Bones| model.find_bone(bone.role(), bone.default_patterns()).map(|id| (bone, id));
+		
+		let mut bones: Vec<_> = [Bones::Neck, Bones::Head, Bones::LeftEye, Bones::RightEye].iter()
+		                                                                                     .filter_map(|&bone| find(bone))
+		                                                                                     .collect();
+		
+		// Some models move eyes only together
+		if !bones.iter().any(|(bone, _)| matches!(bone, Bones::LeftEye | Bones::RightEye)) {
+			bones.extend(find(Bones::BothEyes));
+		}
+		
+		self.model.set(model.as_cref());
+		*self.bones.borrow_mut() = bones;
+	}
+}
+
+impl ComponentLoad for LookAt {
+	fn load(params: toml::Value, _application: &Application) -> Result<Option<Self>> {
+		let mut look_at = LookAt::new(params.try_into()?);
+		look_at.persistent = true;
+		
+		Ok(Some(look_at))
+	}
+}
+
+impl Component for LookAt {
+	fn save(&self, _entity: &Entity, _application: &Application) -> Result<Option<toml::Value>> {
+		if !self.persistent {
+			return Ok(None);
+		}
+		
+		Ok(Some(toml::Value::try_from(&*self.config.borrow())?))
+	}
+	
+	fn tick(&self, entity: &Entity, application: &Application, delta_time: Duration) -> Result<()> {
+		// Model can be added after this component, eg. by Miku
+		if self.model.get(application).is_none() {
+			match entity.find_component_by_type::<MMDModel>() {
+				Some(model) => self.bind(model),
+				None => return Ok(()),
+			}
+		}
+		
+		let model = match self.model.get(application) {
+			Some(model) => model,
+			None => return Ok(()),
+		};
+		
+		let config = self.config.borrow();
+		let state = &mut *self.state.borrow_mut();
+		let delta_time = delta_time.as_secs_f32();
+		let mut rng = application.rng();
+		
+		state.interest_timer -= delta_time;
+		if state.interest_timer <= 0.0 {
+			state.interested = !state.interested;
+			state.interest_timer = DurationRange::from(if state.interested { config.interest } else { config.boredom }).sample(&mut *rng);
+		}
+		
+		state.saccade_timer -= delta_time;
+		if state.saccade_timer <= 0.0 {
+			// Config comes from the scene file, a negative angle would be an empty range
+			let angle = config.saccade_angle.to_radians().abs();
+			state.saccade = vector!(rng.gen_range(-angle..=angle), rng.gen_range(-angle..=angle) * 0.5);
+			state.saccade_timer = DurationRange::from(config.saccade_interval).sample(&mut *rng);
+		}
+		
+		let inv_ent_pos = entity.state().position.inverse();
+		let target = application.find_entity(|e| e.tag(&config.target).unwrap_or_default())
+		                        .map(|target| inv_ent_pos * (*target.state().position * Point3::origin()))
+		                        .filter(|target| target.coords.magnitude() < config.max_distance);
+		
+		let factor = 1.0 - (-config.smoothing * delta_time).exp();
+		let wanted_weight = if state.interested && target.is_some() { 1.0 } else { 0.0 };
+		state.weight += (wanted_weight - state.weight) * factor;
+		
+		if let Some(target) = target {
+			state.gaze = Some(match state.gaze {
+				Some(gaze) => gaze + (target - gaze) * factor,
+				None => target,
+			});
+		}
+		
+		let model_state = &mut *model.state_mut();
+		
+		for &(bone, id) in self.bones.borrow().iter() {
+			let (bone_config, offset) = match bone {
+				Bones::Neck => (&config.neck, Vec2::zeros()),
+				Bones::Head => (&config.head, Vec2::zeros()),
+				_ => (&config.eyes, state.saccade),
+			};
+			
+			model_state.bones[id].look_at = match state.gaze {
+				Some(target) if state.weight > 0.01 => Some(BoneLookAt {
+					target,
+					weight: bone_config.weight * state.weight,
+					limits: bone_config.limits.map(f32::to_radians),
+					offset: offset * state.weight,
+				}),
+				_ => None,
+			};
+		}
+		
+		Ok(())
+	}
+	
+	fn end(&self, _entity: &Entity, application: &Application) -> Result<()> {
+		if let Some(model) = self.model.get(application) {
+			let model_state = &mut *model.state_mut();
+			
+			for &(_, id) in self.bones.borrow().iter() {
+				model_state.bones[id].look_at = None;
+			}
+		}
+		
+		Ok(())
+	}
+	
+	fn on_inspect(&self, _entity: &Entity, ui: &mut Ui, application: &Application) {
+		let config = &mut *self.config.borrow_mut();
+		let state = self.state.borrow();
+		
+		ui.inspect_row("Model", &self.model, application);
+		ui.inspect_row("Bones", format!("{}", self.bones.borrow().len()), ());
+		ui.inspect_row("Interested", format!("{} ({:.1}s)", state.interested, state.interest_timer), ());
+		
+		ui.label("Target Tag");
+		ui.text_edit_singleline(&mut config.target);
+		ui.end_row();
+		
+		ui.inspect_row("Neck Weight", &mut config.neck.weight, (0.01, 0.0..=1.0));
+		ui.inspect_row("Neck Limits", &mut config.neck.limits, ());
+		ui.inspect_row("Head Weight", &mut config.head.weight, (0.01, 0.0..=1.0));
+		ui.inspect_row("Head Limits", &mut config.head.limits, ());
+		ui.inspect_row("Eyes Weight", &mut config.eyes.weight, (0.01, 0.0..=1.0));
+		ui.inspect_row("Eyes Limits", &mut config.eyes.limits, ());
+		ui.inspect_row("Smoothing", &mut config.smoothing, (0.1, 0.0..=30.0));
+		ui.inspect_row("Saccade Interval", &mut config.saccade_interval, ());
+		ui.inspect_row("Saccade Angle", &mut config.saccade_angle, (0.1, 0.0..=15.0));
+		ui.inspect_row("Interest", &mut config.interest, ());
+		ui.inspect_row("Boredom", &mut config.boredom, ());
+		ui.inspect_row("Max Distance", &mut config.max_distance, (0.1, 0.0..=100.0));
+	}
+}
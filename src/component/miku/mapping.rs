@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+
+use super::super::model::MMDModel;
+use super::{Bones, Morphs};
+
+
+/// Ids of bones and morphs of the loaded model, by role.
+#[derive(Default)]
+pub struct MikuMapping {
+	bones: HashMap<Bones, usize>,
+	morphs: HashMap<Morphs, usize>,
+}
+
+impl MikuMapping {
+	pub fn resolve(model: &MMDModel) -> Self {
+		let bones = Bones::ALL.iter()
+		                      .filter_map(|&bone| model.find_bone(bone.role(), bone.default_patterns()).map(|id| (bone, id)))
+		                      .collect::<HashMap<_, _>>();
+		
+		let morphs = Morphs::ALL.iter()
+		                        .filter_map(|&morph| model.find_morph(morph.role(), morph.default_patterns()).map(|id| (morph, id)))
+		                        .collect::<HashMap<_, _>>();
+		
+		if bones.len() < Bones::ALL.len() || morphs.len() < Morphs::ALL.len() {
+			dprintln!("Mapped {} of {} bones and {} of {} morphs",
+			          bones.len(), Bones::ALL.len(),
+			          morphs.len(), Morphs::ALL.len());
+		}
+		
+		MikuMapping { bones, morphs }
+	}
+	
+	pub fn bone(&self, bone: Bones) -> Option<usize> {
+		self.bones.get(&bone).copied()
+	}
+	
+	pub fn morph(&self, morph: Morphs) -> Option<usize> {
+		self.morphs.get(&morph).copied()
+	}
+}
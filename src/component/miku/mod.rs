@@ -1,103 +1,119 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::time::Duration;
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 use anyhow::Result;
 use egui::Ui;
-use simba::scalar::SubsetOf;
+use serde_derive::{Deserialize, Serialize};
 
+mod anim_graph;
+mod anim_set;
 mod enums;
+mod expressions;
 mod gui;
+mod lip_sync;
+mod look_at;
+mod mapping;
 mod proc_anim;
 
 use crate::application::{Entity, Application};
-use crate::math::Rot3;
+use crate::renderer::assets_manager::{AssetsManager, TomlAsset};
 use crate::utils::num_key;
-use super::{Component, ComponentBase, ComponentInner, ComponentRef};
+use super::{Component, ComponentBase, ComponentInner, ComponentLoad, ComponentRef};
 use super::model::MMDModel;
 use super::model::mmd::{BodyPart, MMDRigidBody};
-use super::model::mmd::asset::PmxAsset;
+use super::model::mmd::asset::MMDAsset;
+pub use anim_graph::{AnimContext, AnimController, AnimGraph, AnimLayer, Pose};
+pub use anim_set::{AnimFile, AnimSet};
 pub use enums::{Bones, Morphs};
-use proc_anim::{ProcAnim, Easing};
+pub use expressions::ExpressionDriver;
+pub use lip_sync::{LipSyncAsset, LipSyncTrack};
+pub use look_at::{LookAt, LookAtConfig};
+pub use mapping::MikuMapping;
 
 
+const DEFAULT_ANIMS: &str = "anims/miku.toml";
+const ANIMS_RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
 const MORPH_PRESETS: &[(usize, &[Morphs])] = &[
 	(1, &[Morphs::Embarrassment]),
 	(2, &[Morphs::Embarrassment3]),
-	(3, &[Morphs::BeEmbarrassed]),
+	(3, &[Morphs::Troubled]),
 	(4, &[Morphs::Embarrassment2]),
-	(5, &[Morphs::Smiling]),
-	(6, &[Morphs::ASmile]),
-	(7, &[Morphs::Grin]),
-	(8, &[Morphs::Grin2]),
-	(9, &[Morphs::BWinkRight]),
-	(0, &[Morphs::BWink2Right]),
+	(5, &[Morphs::Grin]),
+	(6, &[Morphs::Calm]),
+	(7, &[Morphs::Smile]),
+	(8, &[Morphs::Beam]),
+	(9, &[Morphs::WinkRight]),
+	(0, &[Morphs::Wink2Right]),
 ];
 
+#[derive(Deserialize, Serialize)]
+struct MikuParams {
+	asset: MMDAsset,
+	#[serde(default = "default_anims")]
+	anims: PathBuf,
+	#[serde(default)]
+	look_at: LookAtConfig,
+	#[serde(default)]
+	touch_ignore: Vec<String>,
+}
+
+fn default_anims() -> PathBuf { DEFAULT_ANIMS.into() }
+
 #[derive(ComponentBase)]
 pub struct Miku {
 	#[inner] inner: ComponentInner,
-	asset: PmxAsset,
+	asset: MMDAsset,
 	model: ComponentRef<MMDModel>,
-	pub anims: RefCell<(
-		HashMap<Bones, ProcAnim<Rot3>>,
-		HashMap<Morphs, ProcAnim<f32>>,
-	)>,
+	mapping: RefCell<MikuMapping>,
+	look_at_config: LookAtConfig,
+	look_at: ComponentRef<LookAt>,
+	touch_ignore: Vec<String>,
+	anims_path: PathBuf,
+	anims_modified: Cell<Option<SystemTime>>,
+	anims_checked: Cell<Option<Instant>>,
+	pub anims: RefCell<Vec<AnimSet>>,
+	pub graph: RefCell<Option<AnimGraph>>,
+	pub expressions: RefCell<ExpressionDriver>,
 }
 
 impl Miku {
-	pub fn new(asset: PmxAsset) -> Self {
+	pub fn new(asset: impl Into<MMDAsset>) -> Self {
 		Miku {
 			inner: ComponentInner::new_norender(),
-			asset,
+			asset: asset.into(),
 			model: ComponentRef::null(),
-			anims: RefCell::new((
-				collection!(
-					Bones::UpperBody2 => ProcAnim::new(Rot3::identity())
-					                              .anim(Rot3::from_euler_angles(0.05, 0.0, 0.0), 3.0, Easing::EaseInOut)
-					                              .anim(Rot3::from_euler_angles(0.0, 0.0, 0.0), 3.0, Easing::EaseInOut)
-					                              .wait(0.5)
-					                              .repeat(),
-					// Bones::ShoulderL => ProcAnim::new(Rot3::identity())
-					//                              .anim(Rot3::from_euler_angles(0.0, 0.0, -0.1), 3.0, Easing::EaseInOut)
-					//                              .anim(Rot3::from_euler_angles(0.0, 0.0, 0.0), 3.0, Easing::EaseInOut)
-					//                              .wait(0.5)
-					//                              .repeat(),
-					// Bones::ShoulderR => ProcAnim::new(Rot3::identity())
-					//                              .anim(Rot3::from_euler_angles(0.0, 0.0, 0.1), 3.0, Easing::EaseInOut)
-					//                              .anim(Rot3::from_euler_angles(0.0, 0.0, 0.0), 3.0, Easing::EaseInOut)
-					//                              .wait(0.5)
-					//                              .repeat(),
-					// Bones::LeftArm => ProcAnim::new(Rot3::identity())
-					//                            .anim(Rot3::from_euler_angles(0.0, 0.0, 0.605), 3.0, Easing::EaseOut)
-					//                            .anim(Rot3::from_euler_angles(0.0, 0.0, 0.6), 3.0, Easing::EaseOut)
-					//                            .wait(0.5)
-					//                            .repeat(),
-					// Bones::RightArm => ProcAnim::new(Rot3::identity())
-					//                            .anim(Rot3::from_euler_angles(0.0, 0.0, -0.605), 3.0, Easing::EaseOut)
-					//                            .anim(Rot3::from_euler_angles(0.0, 0.0, -0.6), 3.0, Easing::EaseOut)
-					//                            .wait(0.5)
-					//                            .repeat(),
-				), collection!(
-					Morphs::Blink => ProcAnim::new(0.0)
-					                          .wait(0.5..5.0)
-					                          .anim(1.0, 0.1, Easing::EaseIn)
-					                          .wait(0.0..0.2)
-					                          .anim(0.0, 0.1, Easing::EaseOut)
-					                          .repeat(),
-					Morphs::Embarrassment => ProcAnim::new(0.0)
-					                                  .anim(1.0, 15.0, Easing::Step)
-					                                  .anim(0.0, 5.0, Easing::Step)
-					                                  .no_autoplay(),
-					Morphs::Smiling => ProcAnim::new(0.0)
-					                            .anim(1.0, 1.0, Easing::EaseIn)
-					                            .wait(15.0)
-					                            .anim(0.0, 1.0, Easing::EaseOut)
-					                            .no_autoplay(),
-				)
-			)),
+			mapping: RefCell::new(MikuMapping::default()),
+			look_at_config: LookAtConfig::default(),
+			look_at: ComponentRef::null(),
+			touch_ignore: Vec::new(),
+			anims_path: DEFAULT_ANIMS.into(),
+			anims_modified: Cell::new(None),
+			anims_checked: Cell::new(None),
+			anims: RefCell::new(Vec::new()),
+			graph: RefCell::new(None),
+			expressions: RefCell::new(ExpressionDriver::new()),
 		}
 	}
 	
+	pub fn anims_file(mut self, path: impl AsRef<Path>) -> Self {
+		self.anims_path = path.as_ref().to_path_buf();
+		self
+	}
+	
+	pub fn look_at(mut self, config: LookAtConfig) -> Self {
+		self.look_at_config = config;
+		self
+	}
+	
+	/// Rigid bodies, by name, whose movement doesn't count as Miku being touched, eg. hair swinging on its own.
+	pub fn touch_ignore(mut self, names: Vec<String>) -> Self {
+		self.touch_ignore = names;
+		self
+	}
+	
 	pub fn body_part<'a>(&self, body_part: BodyPart, application: &'a Application) -> Option<&'a MMDRigidBody> {
 		self.model
 			.get(application)
@@ -107,65 +123,163 @@ impl Miku {
 			.filter_map(|rb| rb.get(application))
 			.find(|rb| rb.body_part == Some(body_part))
 	}
+	
+	pub fn mapping(&self) -> Ref<MikuMapping> {
+		self.mapping.borrow()
+	}
+	
+	/// Reloads animation sets and graph if the file changed since last load. Set weights, graph parameters and controllers are kept.
+	fn reload_anims(&self, model: &MMDModel, application: &Application) -> Result<()> {
+		let asset = TomlAsset::<AnimFile>::at(&self.anims_path);
+		let modified = fs::metadata(AssetsManager::find_asset_path(asset.path())?)?.modified()?;
+		
+		if self.anims_modified.get() == Some(modified) {
+			return Ok(());
+		}
+		
+		let first_load = self.anims_modified.get().is_none();
+		self.anims_modified.set(Some(modified));
+		
+		let renderer = &mut *application.renderer()?;
+		let file = renderer.reload(asset)?;
+		let mut graph = AnimGraph::build(&file, model, &self.mapping.borrow(), renderer)?;
+		
+		if let Some(old_graph) = &mut *self.graph.borrow_mut() {
+			graph.inherit(old_graph);
+		}
+		
+		let mut anims = self.anims.borrow_mut();
+		let mut rng = application.rng();
+		let weights: BTreeMap<String, f32> = anims.iter()
+		                                          .map(|set| (set.name.clone(), set.weight))
+		                                          .collect();
+		
+		*anims = file.sets
+		             .iter()
+		             .map(|(name, desc)| {
+			             let mut set = AnimSet::from_desc(name, desc, &mut *rng);
+			             if let Some(&weight) = weights.get(name) { set.weight = weight; }
+			             set
+		             })
+		             .collect();
+		
+		*self.graph.borrow_mut() = Some(graph);
+		
+		if !first_load {
+			println!("Reloaded {}", self.anims_path.to_string_lossy());
+		}
+		
+		Ok(())
+	}
+}
+
+impl ComponentLoad for Miku {
+	fn load(params: toml::Value, _application: &Application) -> Result<Option<Self>> {
+		let params: MikuParams = params.try_into()?;
+		
+		Ok(Some(Miku::new(params.asset)
+			.anims_file(params.anims)
+			.look_at(params.look_at)
+			.touch_ignore(params.touch_ignore)))
+	}
 }
 
 impl Component for Miku {
-	fn start(&self, entity: &Entity, application: &Application) -> Result<()> {
-		let model = application.renderer.borrow_mut().load(self.asset.clone())?;
-		self.model.set(entity.add_component(MMDModel::new(model, &mut *application.renderer.borrow_mut())?));
+	fn save(&self, _entity: &Entity, application: &Application) -> Result<Option<toml::Value>> {
+		let look_at = match self.look_at.get(application) {
+			Some(look_at) => look_at.config.borrow().clone(),
+			None => self.look_at_config.clone(),
+		};
 		
+		Ok(Some(toml::Value::try_from(MikuParams {
+			asset: self.asset.clone(),
+			anims: self.anims_path.clone(),
+			look_at,
+			touch_ignore: self.touch_ignore.clone(),
+		})?))
+	}
+	
+	fn start(&self, entity: &Entity, application: &Application) -> Result<()> {
 		application.miku.set(self.as_cref());
 		
+		let model = MMDModel::from_asset(self.asset.clone(), application)?;
+		*self.mapping.borrow_mut() = MikuMapping::resolve(&model);
+		self.model.set(entity.add_component(model));
+		self.look_at.set(entity.add_component(LookAt::new(self.look_at_config.clone())));
+		
 		Ok(())
 	}
 	
 	fn tick(&self, entity: &Entity, application: &Application, delta_time: Duration) -> Result<()> {
-		let mut model = self.model
-		                    .using(entity)
-		                    .unwrap()
-		                    .state
-		                    .borrow_mut();
-		
-		for morph in model.morphs.iter_mut() {
-			*morph = (*morph - 5.0 * delta_time.as_secs_f32()).clamp(0.0, 1.0);
-		}
+		let model = match self.model.using(entity) {
+			Some(model) => model,
+			None => return Ok(()),
+		};
 		
-		let active = MORPH_PRESETS.iter().filter(|p| application.input.keyboard.pressed(num_key(p.0))).flat_map(|p| p.1.iter());
-		
-		for &id in active {
-			let id = id as usize;
-			model.morphs[id] = (model.morphs[id] + 10.0 * delta_time.as_secs_f32()).clamp(0.0, 1.0);
+		// Motions are loaded through the renderer, headless Miku is only simulated
+		if !application.headless() && self.anims_checked.get().map_or(true, |checked| checked.elapsed() > ANIMS_RELOAD_INTERVAL) {
+			self.anims_checked.set(Some(Instant::now()));
+			
+			if let Err(err) = self.reload_anims(model, application) {
+				eprintln!("Failed to load animations {}: {}", self.anims_path.to_string_lossy(), err);
+			}
 		}
 		
+		let mapping = self.mapping.borrow();
 		let mut anims = self.anims.borrow_mut();
+		let mut graph = self.graph.borrow_mut();
+		let mut expressions = self.expressions.borrow_mut();
 		
-		for rb in model.rigid_bodies.iter() {
-			if let Some(rb) = rb.entity().get(application) {
-				if rb.name != "ForeLock1" && rb.state().velocity.magnitude() > 0.075 {
-					anims.1.get_mut(&Morphs::Embarrassment).unwrap().play();
-					anims.1.get_mut(&Morphs::Smiling).unwrap().play();
-					if !anims.1.get_mut(&Morphs::Blink).unwrap().stopped() {
-						anims.1.get_mut(&Morphs::Blink).unwrap().overdrive(
-							ProcAnim::new(0.0)
-								.anim(1.0, 0.3, Easing::EaseIn)
-								.wait(3.0)
-								.anim(0.0, 0.3, Easing::EaseOut)
-								.wait(3.0)
-						);
+		if let Some(path) = expressions.requested.take() {
+			match application.renderer().map_err(anyhow::Error::from).and_then(|mut renderer| renderer.load(LipSyncAsset::at(&path))) {
+				Ok(track) => expressions.speak(path.to_string_lossy(), track, false),
+				Err(err) => eprintln!("Failed to load lip sync {}: {}", path.to_string_lossy(), err),
+			}
+		}
+		
+		{
+			let state = &mut *model.state_mut();
+			
+			for morph in state.morphs.iter_mut() {
+				*morph = (*morph - 5.0 * delta_time.as_secs_f32()).clamp(0.0, 1.0);
+			}
+			
+			let active = MORPH_PRESETS.iter()
+			                          .filter(|p| application.input.keyboard.pressed(num_key(p.0)))
+			                          .flat_map(|p| p.1.iter())
+			                          .filter_map(|&morph| mapping.morph(morph));
+			
+			for id in active {
+				state.morphs[id] = (state.morphs[id] + 10.0 * delta_time.as_secs_f32()).clamp(0.0, 1.0);
+			}
+			
+			for rb in state.rigid_bodies.iter() {
+				if let Some(rb) = rb.entity().get(application) {
+					if !self.touch_ignore.contains(&rb.name) && rb.state().velocity.magnitude() > 0.075 {
+						if let Some(graph) = &mut *graph {
+							graph.trigger("embarrassed");
+						}
+						
+						expressions.close_eyes(3.0);
 					}
 				}
 			}
 		}
 		
-		for (&bone, anim) in anims.0.iter_mut() {
-			let value = anim.get();
-			model.bones[bone as usize].anim_transform = value.to_superset();
+		if let Some(graph) = &mut *graph {
+			graph.update(&mut AnimContext {
+				entity,
+				application,
+				model: &model.state(),
+				mapping: &mapping,
+				sets: &mut anims,
+				delta_time: delta_time.as_secs_f32(),
+			});
+			
+			graph.apply(&mut model.state_mut());
 		}
 		
-		for (&morph, anim) in anims.1.iter_mut() {
-			let value = anim.get();
-			model.morphs[morph as usize] = value;
-		}
+		expressions.update(delta_time.as_secs_f32(), &mapping, &mut model.state_mut().morphs, &mut *application.rng());
 		
 		Ok(())
 	}
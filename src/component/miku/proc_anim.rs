@@ -2,9 +2,11 @@ use std::fmt::{Debug, Formatter};
 use std::ops::Range;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use rand::{Rng, thread_rng};
+use rand::Rng;
+use rand::rngs::StdRng;
+use serde_derive::Deserialize;
 
-use crate::math::Rot3;
+use crate::math::{Rot3, Vec2};
 
 
 #[derive(Debug, Clone)]
@@ -17,10 +19,12 @@ pub struct ProcAnim<V> {
 	repeat: bool,
 	stopped: bool,
 	overdrive: Option<Box<ProcAnim<V>>>,
+	/// Draws durations of ranged frames.
+	rng: StdRng,
 }
 
 impl<V: Interpolate + Clone> ProcAnim<V> {
-	pub fn new(initial: V) -> Self {
+	pub fn new(initial: V, rng: StdRng) -> Self {
 		ProcAnim {
 			from: initial,
 			frames: Vec::new(),
@@ -30,6 +34,7 @@ impl<V: Interpolate + Clone> ProcAnim<V> {
 			repeat: false,
 			stopped: false,
 			overdrive: None,
+			rng,
 		}
 	}
 	
@@ -37,7 +42,7 @@ impl<V: Interpolate + Clone> ProcAnim<V> {
 		let end_state = self.end_state();
 		let duration = time.into();
 		
-		if self.frames.is_empty() { self.frame_dur = duration.sample(); }
+		if self.frames.is_empty() { self.frame_dur = duration.sample(&mut self.rng); }
 		
 		self.frames.push(Frame {
 			target: end_state,
@@ -51,7 +56,7 @@ impl<V: Interpolate + Clone> ProcAnim<V> {
 	pub fn anim(mut self, target: V, time: impl Into<DurationRange>, easing: Easing) -> Self {
 		let duration = time.into();
 		
-		if self.frames.is_empty() { self.frame_dur = duration.sample(); }
+		if self.frames.is_empty() { self.frame_dur = duration.sample(&mut self.rng); }
 		
 		self.frames.push(Frame {
 			target,
@@ -123,7 +128,7 @@ impl<V: Interpolate + Clone> ProcAnim<V> {
 				}
 			}
 			
-			self.frame_dur = self.frames[self.frame_cur].duration.sample();
+			self.frame_dur = self.frames[self.frame_cur].duration.sample(&mut self.rng);
 		}
 		
 		self.frames[self.frame_cur].at(elapsed / self.frame_dur, &self.from)
@@ -171,7 +176,8 @@ impl<V: Interpolate> Frame<V> {
 }
 
 #[allow(unused)]
-#[derive(Clone)]
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Easing {
 	Step,
 	Linear,
@@ -180,6 +186,7 @@ pub enum Easing {
 	EaseOut,
 	EaseInOut,
 	Bezier(f32, f32, f32, f32),
+	#[serde(skip)]
 	Custom(Arc<dyn Fn(f32) -> f32>),
 }
 
@@ -236,17 +243,35 @@ impl Interpolate for Rot3 {
 	}
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(from = "DurationRangeDesc")]
 pub enum DurationRange {
 	Fixed(f32),
 	Range(Range<f32>),
 }
 
+/// Either fixed number of seconds or `[min, max]` range.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DurationRangeDesc {
+	Fixed(f32),
+	Range([f32; 2]),
+}
+
+impl From<DurationRangeDesc> for DurationRange {
+	fn from(desc: DurationRangeDesc) -> Self {
+		match desc {
+			DurationRangeDesc::Fixed(fixed) => DurationRange::Fixed(fixed),
+			DurationRangeDesc::Range([min, max]) => (min..max).into(),
+		}
+	}
+}
+
 impl DurationRange {
-	fn sample(&self) -> f32 {
+	pub fn sample(&self, rng: &mut impl Rng) -> f32 {
 		match self {
 			DurationRange::Fixed(val) => *val,
-			DurationRange::Range(range) => thread_rng().gen_range(range.clone()),
+			DurationRange::Range(range) => rng.gen_range(range.clone()),
 		}
 	}
 }
@@ -257,8 +282,20 @@ impl From<f32> for DurationRange {
 	}
 }
 
+/// Empty or reversed ranges collapse to their start, as sampling them would panic.
 impl From<Range<f32>> for DurationRange {
 	fn from(range: Range<f32>) -> Self {
-		DurationRange::Range(range)
+		if range.start < range.end {
+			DurationRange::Range(range)
+		} else {
+			DurationRange::Fixed(range.start)
+		}
+	}
+}
+
+/// `[min, max]` range, as edited in GUI.
+impl From<Vec2> for DurationRange {
+	fn from(range: Vec2) -> Self {
+		(range.x..range.y).into()
 	}
 }
@@ -18,6 +18,7 @@ pub mod model;
 pub mod pc_controlled;
 pub mod physics;
 pub mod pov;
+pub mod registry;
 pub mod seat;
 pub mod srgb_test;
 pub mod test;
@@ -26,6 +27,7 @@ pub mod toolgun;
 pub mod vr;
 
 use crate::application::{Application, Entity, EntityRef};
+use crate::application::physics::{Collision, ContactForce};
 use crate::renderer::{RenderContext, Renderer, RenderType};
 use crate::utils::{next_uid, IntoBoxed, ExUi};
 
@@ -69,6 +71,12 @@ pub trait Component: ComponentBase {
 	fn end(&self, entity: &Entity, application: &Application) -> Result<()> { Ok(()) }
 	fn on_inspect(&self, entity: &Entity, ui: &mut Ui, application: &Application) {}
 	fn on_inspect_extra(&self, entity: &Entity, ui: &mut Ui, application: &Application) {}
+	fn save(&self, entity: &Entity, application: &Application) -> Result<Option<toml::Value>> { Ok(None) }
+	/// Only colliders with `ActiveEvents::COLLISION_EVENTS` produce these and `on_collision_end`, see `ColliderComponent::collision_events`.
+	fn on_collision_start(&self, entity: &Entity, collision: &Collision, application: &Application) -> Result<()> { Ok(()) }
+	fn on_collision_end(&self, entity: &Entity, collision: &Collision, application: &Application) -> Result<()> { Ok(()) }
+	/// Only colliders with `ActiveEvents::CONTACT_FORCE_EVENTS` produce these, see `ColliderBuilder::contact_force_event_threshold`.
+	fn on_contact_force(&self, entity: &Entity, contact: &ContactForce, application: &Application) -> Result<()> { Ok(()) }
 	fn on_gui(&self, entity: &Entity, ui: &mut Ui, application: &Application) {
 		Grid::new(self.inner().id)
 			.num_columns(2)
@@ -87,6 +95,11 @@ pub trait Component: ComponentBase {
 	{ Box::new(self) }
 }
 
+/// Components which can be constructed from serialized parameters, see `registry`.
+pub trait ComponentLoad: Component + Sized {
+	fn load(params: toml::Value, application: &Application) -> Result<Option<Self>>;
+}
+
 impl IntoBoxed<dyn Component> for Box<dyn Component> {
 	fn into(self) -> Box<dyn Component> {
 		self
@@ -3,6 +3,7 @@ use std::sync::Arc;
 use std::time::Duration;
 use anyhow::Result;
 use egui::Ui;
+use serde_derive::{Deserialize, Serialize};
 use vulkano::buffer::{Buffer, BufferUsage, Subbuffer};
 use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryCommandBufferAbstract};
 use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
@@ -18,13 +19,19 @@ use crate::renderer::{RenderContext, Renderer, RenderType};
 use crate::renderer::assets_manager::TextureAsset;
 use crate::renderer::pipelines::PipelineNoLayoutError;
 use crate::utils::{BufferEx, IntoInfo, ExUi, FenceCheck};
-use super::super::{Component, ComponentBase, ComponentInner};
+use super::super::{Component, ComponentBase, ComponentInner, ComponentLoad};
 use pipeline::{FoodPipeline, Vertex, Pc};
 
 
+#[derive(Deserialize, Serialize)]
+struct BillboardParams {
+	texture: TextureAsset,
+}
+
 #[derive(ComponentBase, Clone)]
 pub struct Billboard {
 	#[inner] inner: ComponentInner,
+	texture: TextureAsset,
 	ratio: f32,
 	layers: u32,
 	rotation: Cell<f32>,
@@ -39,7 +46,8 @@ pub struct Billboard {
 impl Billboard {
 	pub fn new(texture: TextureAsset, renderer: &mut Renderer) -> Result<Billboard> {
 		let pipeline = renderer.pipelines.get::<FoodPipeline>()?;
-		let texture = renderer.load(texture)?;
+		let source = texture;
+		let texture = renderer.load(source.clone())?;
 		let image_size = texture.image.image().dimensions().width_height();
 		let ratio = image_size[0] as f32 / image_size[1] as f32;
 		let layers = texture.image.image().dimensions().array_layers();
@@ -75,6 +83,7 @@ impl Billboard {
 		
 		Ok(Billboard {
 			inner: ComponentInner::from_render_type(RenderType::Transparent),
+			texture: source,
 			ratio,
 			layers,
 			rotation: Cell::new(0.0),
@@ -87,7 +96,23 @@ impl Billboard {
 	}
 }
 
+impl ComponentLoad for Billboard {
+	fn load(params: toml::Value, application: &Application) -> Result<Option<Self>> {
+		if application.headless() {
+			return Ok(None);
+		}
+		
+		let params: BillboardParams = params.try_into()?;
+		
+		Ok(Some(Billboard::new(params.texture, &mut *application.renderer()?)?))
+	}
+}
+
 impl Component for Billboard {
+	fn save(&self, _entity: &Entity, _application: &Application) -> Result<Option<toml::Value>> {
+		Ok(Some(toml::Value::try_from(BillboardParams { texture: self.texture.clone() })?))
+	}
+	
 	fn tick(&self, entity: &Entity, _application: &Application, _delta_time: Duration) -> Result<()> {
 		let state = entity.state_mut();
 		
@@ -8,21 +8,28 @@ use std::path::{PathBuf, Path};
 use std::sync::Arc;
 use anyhow::Result;
 use image::ImageFormat;
+use nalgebra::Quaternion;
 use mmd::WeightDeform;
 use mmd::pmx::bone::{BoneFlags, Connection};
 use mmd::pmx::joint::Joint;
+use mmd::pmx::rigid_body::RigidBody;
 use mmd::pmx::material::{Toon, EnvironmentBlendMode, DrawingFlags};
-use mmd::pmx::morph::Offsets;
+use mmd::pmx::morph::{MaterialMorphMethod, Offsets};
 use rapier3d::geometry::{ColliderBuilder, ColliderShape, Group, InteractionGroups};
+use serde_derive::{Deserialize, Serialize};
 
 use crate::{config, debug};
 use crate::math::{Color, Isometry3, Rot3, Vec2, Vec3, Vec4, PI};
 use crate::renderer::Renderer;
 use crate::renderer::assets_manager::{AssetKey, AssetsManager, TomlAsset};
 use crate::utils::PatternMatcher;
+use super::super::VertexIndex;
 use super::{Vertex, BodyPart};
 use super::overrides::{MMDConfig, MMDJointOverride, MMDRigidBodyOverride};
-use super::shared::{MMDModelShared, BoneDesc, BoneConnection, SubMeshDesc, JointDesc, ColliderDesc};
+use super::gltf::GltfAsset;
+use super::pmd::PmdAsset;
+use super::shared::{MMDModelShared, MMDModelSharedBuilder, BoneDesc, BoneConnection, BoneAppend, BoneIk, IkLink, SubMeshDesc, JointDesc, ColliderDesc, MaterialMorph, BoneMorph};
+use super::toon::{shared_toon, SHARED_TOON_COUNT};
 
 
 type MMDShapeType = mmd::pmx::rigid_body::ShapeType;
@@ -42,7 +49,7 @@ impl mmd::Config for MMDIndexConfig {
 	type AdditionalVec4s = Vec<Vec4>;
 }
 
-#[derive(Clone, Hash, Debug)]
+#[derive(Clone, Hash, Debug, Deserialize, Serialize)]
 pub struct PmxAsset {
 	path: PathBuf,
 	overrides: bool,
@@ -62,12 +69,15 @@ impl PmxAsset {
 			..self
 		}
 	}
-}
-
-impl AssetKey for PmxAsset {
-	type Asset = Arc<MMDModelShared>;
 	
-	fn load(&self, assets_manager: &mut AssetsManager, renderer: &mut Renderer) -> Result<Self::Asset> {
+	/// Builds the model without uploading it to the GPU, nor caching it, for headless mode.
+	pub fn load_headless(&self) -> Result<Arc<MMDModelShared>> {
+		let overrides = load_overrides(&self.path, self.overrides, None)?;
+		
+		Ok(Arc::new(self.read(overrides)?.build_headless()))
+	}
+	
+	fn read(&self, overrides: Option<MMDConfig>) -> Result<MMDModelSharedBuilder<u32>> {
 		let mut root = PathBuf::from(&self.path);
 		root.pop();
 		
@@ -75,15 +85,6 @@ impl AssetKey for PmxAsset {
 		
 		// dprintln!("{}", header);
 		
-		let mut overrides: Option<MMDConfig> = if self.overrides {
-			match assets_manager.load(TomlAsset::at(&root.join("model.toml")), renderer) {
-				Err(err) if err.downcast_ref::<IoError>().map(|err| err.kind()) == Some(ErrorKind::NotFound) => None,
-				overrides => Some(overrides?),
-			}
-		} else {
-			None
-		};
-		
 		let mut vertices_reader = mmd::VertexReader::new(header)?;
 		let vertices = vertices_reader.iter::<MMDIndexConfig>()
 		                              .map(|v| v.map(Into::into))
@@ -111,13 +112,24 @@ impl AssetKey for PmxAsset {
 		
 		let mut materials_reader = mmd::MaterialReader::new(textures_reader)?;
 		let mut last_index: u32 = 0;
+		// Texture ids of shared toons, added after the model's textures when first used
+		let mut shared_toons = [None; SHARED_TOON_COUNT];
 		
 		for material in materials_reader.iter::<MMDIndexConfig>() {
 			let material = material?;
 			
-			let toon_index = match material.toon {
-				Toon::Texture(id) => id,
-				Toon::Internal(_) => -1
+			let toon = match material.toon {
+				Toon::Texture(id) => id.try_into().ok(),
+				Toon::Internal(id) => match shared_toons.get_mut(id as usize) {
+					Some(Some(texture)) => Some(*texture),
+					Some(slot) => {
+						model.add_texture(shared_toon(id as usize)?);
+						textures_alpha.push(false);
+						*slot = Some(textures_alpha.len() - 1);
+						*slot
+					},
+					None => None,
+				},
 			};
 			
 			let sphere_mode = if material.environment_index < 0 {
@@ -142,7 +154,7 @@ impl AssetKey for PmxAsset {
 			model.add_sub_mesh(SubMeshDesc {
 				range: last_index .. last_index + material.surface_count as u32,
 				texture: material.texture_index.try_into().ok(),
-				toon: toon_index.try_into().ok(),
+				toon,
 				sphere_map: material.environment_index.try_into().ok(),
 				color: material.diffuse_color,
 				specular: material.specular_color,
@@ -162,18 +174,13 @@ impl AssetKey for PmxAsset {
 		                            .collect::<std::result::Result<Vec<mmd::Bone<MMDIndexConfig>>, _>>()?;
 		
 		for def in bone_defs.iter() {
-			let name = if def.universal_name.len() > 0 {
-				&def.universal_name
-			} else if let Some(translated) = debug::translate(&def.local_name) {
-				translated
-			} else {
-				&def.local_name
-			};
+			let name = display_name(&def.universal_name, &def.local_name);
 			
 			let parent = if def.parent < 0 { None } else { Some(def.parent as usize) };
 			
 			let model_pos = def.position.from_mmd();
 			let display = def.bone_flags.contains(BoneFlags::Display);
+			let operable = def.bone_flags.contains(BoneFlags::CanOperate);
 			
 			let mut color = if def.bone_flags.contains(BoneFlags::InverseKinematics) {
 				Color::GREEN
@@ -187,7 +194,7 @@ impl AssetKey for PmxAsset {
 				Color::D_WHITE
 			};
 			
-			if !def.bone_flags.contains(BoneFlags::CanOperate) {
+			if !operable {
 				color = color.lightness(0.5);
 			}
 			
@@ -204,13 +211,53 @@ impl AssetKey for PmxAsset {
 				model_pos - parent.position.from_mmd()
 			};
 			
-			model.add_bone(BoneDesc::new(name,
+			let mut bone = BoneDesc::new(name,
 			                             parent,
 			                             color,
 			                             model_pos,
 			                             local_pos,
 			                             display,
-			                             connection));
+			                             connection)
+			                       .local_name(&def.local_name)
+			                       .operable(operable && def.bone_flags.contains(BoneFlags::Rotatable),
+			                                 operable && def.bone_flags.contains(BoneFlags::Movable))
+			                       .layer(def.layer, def.bone_flags.contains(BoneFlags::PhysicsAfterDeform));
+			
+			let append_rotation = def.bone_flags.contains(BoneFlags::InheritRotation);
+			let append_translation = def.bone_flags.contains(BoneFlags::InheritTranslation);
+			
+			if let Some((parent, ratio)) = def.inherit {
+				if parent >= 0 && (append_rotation || append_translation) {
+					bone = bone.append(BoneAppend {
+						parent: parent as usize,
+						ratio,
+						rotation: append_rotation,
+						translation: append_translation,
+						local: def.bone_flags.contains(BoneFlags::LocalInherit),
+					});
+				}
+			}
+			
+			if let Some(ik) = &def.inverse_kinematics {
+				let links = ik.links.iter()
+				                    .filter(|link| link.ik_bone >= 0)
+				                    .map(|link| IkLink {
+					                    bone: link.ik_bone as usize,
+					                    limits: link.angle_limit.map(ik_limits_from_mmd),
+				                    })
+				                    .collect();
+				
+				if ik.ik_bone >= 0 {
+					bone = bone.ik(BoneIk {
+						target: ik.ik_bone as usize,
+						iterations: ik.iterations.max(0) as u32,
+						limit_angle: ik.limit_angle,
+						links,
+					});
+				}
+			}
+			
+			model.add_bone(bone);
 		}
 		
 		let mut morphs_reader = mmd::MorphReader::new(bones_reader)?;
@@ -218,201 +265,380 @@ impl AssetKey for PmxAsset {
 		for morph in morphs_reader.iter::<MMDIndexConfig>() {
 			let morph = morph?;
 			
-			if let Offsets::Vertex(offsets) = morph.offsets {
-				model.add_morph(offsets.iter()
-				                       .map(|offset| (offset.vertex, offset.offset.from_mmd()))
-				                       .collect());
+			// Every morph has to be added, to keep ids referenced by group morphs valid
+			match morph.offsets {
+				Offsets::Vertex(offsets) => {
+					model.add_morph(&morph.local_name,
+					                offsets.iter()
+					                       .map(|offset| (offset.vertex, offset.offset.from_mmd()))
+					                       .collect());
+				}
+				Offsets::UV(offsets) => {
+					model.add_uv_morph(&morph.local_name,
+					                   offsets.iter()
+					                          .map(|offset| (offset.vertex, offset.offset.xy()))
+					                          .collect());
+				}
+				Offsets::Material(offsets) => {
+					model.add_material_morph(&morph.local_name,
+					                         offsets.iter()
+					                                .map(|offset| MaterialMorph {
+						                                sub_mesh: offset.material.try_into().ok(),
+						                                additive: matches!(offset.method, MaterialMorphMethod::Additive),
+						                                color: offset.diffuse,
+						                                specular: offset.specular,
+						                                specularity: offset.specularity,
+						                                ambient: offset.ambient,
+						                                edge_color: offset.edge_color,
+						                                edge_scale: offset.edge_size,
+						                                texture_tint: offset.texture_tint,
+						                                sphere_tint: offset.environment_tint,
+						                                toon_tint: offset.toon_tint,
+					                                })
+					                                .collect());
+				}
+				Offsets::Bone(offsets) => {
+					model.add_bone_morph(&morph.local_name,
+					                     offsets.iter()
+					                            .filter(|offset| offset.bone >= 0)
+					                            .map(|offset| BoneMorph {
+						                            bone: offset.bone as usize,
+						                            translation: offset.translation.from_mmd(),
+						                            rotation: Rot3::new_normalize(Quaternion::new(offset.rotation.w, -offset.rotation.x, -offset.rotation.y, offset.rotation.z)),
+					                            })
+					                            .collect());
+				}
+				Offsets::Group(offsets) => {
+					model.add_group_morph(&morph.local_name,
+					                      offsets.iter()
+					                             .filter(|offset| offset.morph >= 0)
+					                             .map(|offset| (offset.morph as usize, offset.weight))
+					                             .collect());
+				}
+				// Flip, impulse and additional UV morphs are not supported
+				_ => {
+					model.add_group_morph(&morph.local_name, vec![]);
+				}
 			}
 		}
 		
 		let display_reader = mmd::DisplayReader::new(morphs_reader)?;
 		
-		let mut dump_config = config::get().gen_model_toml.then(|| MMDConfig::default());
-		
 		let mut rigid_body_reader = mmd::RigidBodyReader::new(display_reader)?;
-		let mut rigid_body_defs = rigid_body_reader.iter::<MMDIndexConfig>()
-		                                           .collect::<Result<Vec<_>, _>>()?;
-		
-		if let Some(overrides) = &mut overrides {
-			for rb in overrides.rigid_bodies.drain(..) {
-				if let Some(id) = rb.id {
-					if id >= rigid_body_defs.len() {
-						eprintln!("Model {} has no rigid body id {}", self.path.to_string_lossy(), id);
-						continue
-					}
-					
-					rb.apply_to(&mut rigid_body_defs[id]);
-				} else if let Some(pattern) = &rb.pattern {
-					let pattern = PatternMatcher::new(pattern);
-					
-					let mut matched = false;
-					for desc in rigid_body_defs.iter_mut() {
-						if pattern.matches(&desc.local_name) || pattern.matches(&desc.universal_name) {
-							matched = true;
-							rb.apply_to(desc);
-						}
-					}
-					
-					if !matched {
-						eprintln!("Model {} has no rigid body matching pattern {}", self.path.to_string_lossy(), pattern);
-					}
-				} else {
-					rigid_body_defs.push(rb.into());
-				}
-			}
-		}
+		let rigid_body_defs = rigid_body_reader.iter::<MMDIndexConfig>()
+		                                       .collect::<Result<Vec<_>, _>>()?;
 		
-		for (id, rigid_body) in rigid_body_defs.iter().enumerate() {
-			if let Some(dump_config) = &mut dump_config {
-				dump_config.rigid_bodies.push(MMDRigidBodyOverride::from_mmd(&rigid_body, id))
-			}
-			
-			let name = if rigid_body.universal_name.len() > 0 {
-				&rigid_body.universal_name
-			} else if let Some(translated) = debug::translate(&rigid_body.local_name) {
-				translated
-			} else {
-				&rigid_body.local_name
-			};
-			
-			let translation = rigid_body.shape_position.from_mmd();
-			let rotation = Rot3::from_axis_angle(&Vec3::y_axis(), -rigid_body.shape_rotation.y)
-			             * Rot3::from_axis_angle(&Vec3::x_axis(), -rigid_body.shape_rotation.x)
-			             * Rot3::from_axis_angle(&Vec3::z_axis(),  rigid_body.shape_rotation.z);
-			let position = Isometry3::from_parts(translation.into(), rotation);
-			
-			let volume;
-			let collider;
-			match rigid_body.shape {
-				MMDShapeType::Sphere => {
-					volume = 4.0 / 3.0 * PI * rigid_body.shape_size.x;
-					collider = ColliderBuilder::new(ColliderShape::ball(rigid_body.shape_size.x * MMD_UNIT_SIZE))
-				},
-				MMDShapeType::Box => {
-					volume = rigid_body.shape_size.x / rigid_body.shape_size.y / rigid_body.shape_size.z;
-					collider = ColliderBuilder::new(ColliderShape::cuboid(rigid_body.shape_size.x * MMD_UNIT_SIZE,
-					                                                      rigid_body.shape_size.y * MMD_UNIT_SIZE,
-					                                                      rigid_body.shape_size.z * MMD_UNIT_SIZE))
-				},
-				MMDShapeType::Capsule => {
-					volume = 4.0 / 3.0 * PI * rigid_body.shape_size.y
-					       + rigid_body.shape_size.x * rigid_body.shape_size.y * rigid_body.shape_size.y * PI;
-					collider = ColliderBuilder::new(ColliderShape::capsule(point![0.0, -rigid_body.shape_size.y * MMD_UNIT_SIZE / 2.0, 0.0],
-					                                                       point![0.0,  rigid_body.shape_size.y * MMD_UNIT_SIZE / 2.0, 0.0],
-					                                                       rigid_body.shape_size.x * MMD_UNIT_SIZE))
-				},
-			};
-			
-			let collider = collider.position(position)
-			                       .collision_groups(InteractionGroups::new(Group::from(1 << rigid_body.group_id), Group::from(0xFFFF0000 | rigid_body.non_collision_mask as u32)))
-			                       .density(rigid_body.mass / volume)
-			                       .build();
-			
-			model.add_collider(ColliderDesc::new(name,
-			                                     rigid_body.bone_index as usize,
-			                                     collider,
-			                                     rigid_body.move_attenuation,
-			                                     rigid_body.rotation_damping,
-			                                     rigid_body.repulsion,
-			                                     rigid_body.fiction,
-			                                     rigid_body.physics_mode));
+		let mut joints_reader = mmd::JointReader::new(rigid_body_reader)?;
+		let joints_defs = joints_reader.iter::<MMDIndexConfig>()
+		                               .map(|res| res.map(Into::into))
+		                               .collect::<Result<Vec<_>, _>>()?;
+		
+		add_overridden_physics(&mut model, &self.path, overrides, rigid_body_defs, joints_defs)?;
+		
+		Ok(model)
+	}
+}
+
+impl AssetKey for PmxAsset {
+	type Asset = Arc<MMDModelShared>;
+	
+	fn load(&self, assets_manager: &mut AssetsManager, renderer: &mut Renderer) -> Result<Self::Asset> {
+		let overrides = load_overrides(&self.path, self.overrides, Some((assets_manager, &mut *renderer)))?;
+		
+		Ok(Arc::new(self.read(overrides)?.build(renderer)?))
+	}
+}
+
+impl Display for PmxAsset {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "PMX model {}", self.path.to_string_lossy())
+	}
+}
+
+/// PMX, PMD or glTF model, accepted wherever a model can be loaded from a file.
+/// Variants are told apart by the name of the path field: `path`, `pmd` or `gltf`.
+#[derive(Clone, Hash, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum MMDAsset {
+	Pmx(PmxAsset),
+	Pmd(PmdAsset),
+	Gltf(GltfAsset),
+}
+
+impl MMDAsset {
+	/// Picks the format by extension of the file.
+	pub fn at(model_path: impl AsRef<Path>) -> Self {
+		let model_path = model_path.as_ref();
+		
+		match model_path.extension() {
+			Some(ext) if ext.eq_ignore_ascii_case("pmd") => MMDAsset::Pmd(PmdAsset::at(model_path)),
+			Some(ext) if ["gltf", "glb", "vrm"].iter().any(|gltf| ext.eq_ignore_ascii_case(gltf)) => MMDAsset::Gltf(GltfAsset::at(model_path)),
+			_ => MMDAsset::Pmx(PmxAsset::at(model_path)),
+		}
+	}
+	
+	pub fn no_overrides(self) -> Self {
+		match self {
+			MMDAsset::Pmx(asset) => MMDAsset::Pmx(asset.no_overrides()),
+			MMDAsset::Pmd(asset) => MMDAsset::Pmd(asset.no_overrides()),
+			MMDAsset::Gltf(asset) => MMDAsset::Gltf(asset.no_overrides()),
+		}
+	}
+	
+	pub fn load_headless(&self) -> Result<Arc<MMDModelShared>> {
+		match self {
+			MMDAsset::Pmx(asset) => asset.load_headless(),
+			MMDAsset::Pmd(asset) => asset.load_headless(),
+			MMDAsset::Gltf(asset) => asset.load_headless(),
+		}
+	}
+}
+
+impl From<PmxAsset> for MMDAsset {
+	fn from(asset: PmxAsset) -> Self {
+		MMDAsset::Pmx(asset)
+	}
+}
+
+impl From<PmdAsset> for MMDAsset {
+	fn from(asset: PmdAsset) -> Self {
+		MMDAsset::Pmd(asset)
+	}
+}
+
+impl From<GltfAsset> for MMDAsset {
+	fn from(asset: GltfAsset) -> Self {
+		MMDAsset::Gltf(asset)
+	}
+}
+
+impl AssetKey for MMDAsset {
+	type Asset = Arc<MMDModelShared>;
+	
+	fn load(&self, assets_manager: &mut AssetsManager, renderer: &mut Renderer) -> Result<Self::Asset> {
+		match self {
+			MMDAsset::Pmx(asset) => asset.load(assets_manager, renderer),
+			MMDAsset::Pmd(asset) => asset.load(assets_manager, renderer),
+			MMDAsset::Gltf(asset) => asset.load(assets_manager, renderer),
+		}
+	}
+}
+
+impl Display for MMDAsset {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			MMDAsset::Pmx(asset) => asset.fmt(f),
+			MMDAsset::Pmd(asset) => asset.fmt(f),
+			MMDAsset::Gltf(asset) => asset.fmt(f),
+		}
+	}
+}
+
+/// Reads `model.toml` next to the model. Without assets manager (headless mode) it is read directly, bypassing the cache.
+pub(super) fn load_overrides(model_path: &Path, enabled: bool, assets: Option<(&mut AssetsManager, &mut Renderer)>) -> Result<Option<MMDConfig>> {
+	if !enabled {
+		return Ok(None);
+	}
+	
+	let key = TomlAsset::at(&model_path.with_file_name("model.toml"));
+	let overrides = match assets {
+		Some((assets_manager, renderer)) => assets_manager.load(key, renderer),
+		None => key.read(),
+	};
+	
+	match overrides {
+		Err(err) if err.downcast_ref::<IoError>().map(|err| err.kind()) == Some(ErrorKind::NotFound) => Ok(None),
+		overrides => Ok(Some(overrides?)),
+	}
+}
+
+/// Prefers the English name, falling back to translated or original Japanese one.
+pub(super) fn display_name<'a>(universal_name: &'a str, local_name: &'a str) -> &'a str {
+	if universal_name.len() > 0 {
+		universal_name
+	} else if let Some(translated) = debug::translate(local_name) {
+		translated
+	} else {
+		local_name
+	}
+}
+
+/// Applies overrides to rigid bodies and joints, which are kept in MMD's format until then, and adds them with the roles to the model.
+/// Bones have to be added first, joint overrides can refer to them.
+pub(super) fn add_overridden_physics<VI: VertexIndex>(model: &mut MMDModelSharedBuilder<VI>,
+                                                      path: &Path,
+                                                      mut overrides: Option<MMDConfig>,
+                                                      mut rigid_body_defs: Vec<RigidBody<MMDIndexConfig>>,
+                                                      mut joints_defs: Vec<JointEx<MMDIndexConfig>>)
+                                                      -> Result<()> {
+	let mut dump_config = config::get().gen_model_toml.then(|| MMDConfig::default());
+	
+	if let Some(overrides) = &overrides {
+		for (role, pattern) in &overrides.bones {
+			model.add_bone_role(role, pattern);
 		}
 		
-		let mut joints_reader = mmd::JointReader::new(rigid_body_reader)?;
-		let mut joints_defs = joints_reader.iter::<MMDIndexConfig>()
-		                                   .map(|res| res.map(Into::into))
-		                                   .collect::<Result<Vec<_>, _>>()?;
-		
-		if let Some(overrides) = &mut overrides {
-			for mut joint in overrides.joints.drain(..) {
-				if let Err(err) = joint.normalize(&bone_defs, &rigid_body_defs) {
-					eprintln!("{}", err);
-					continue;
+		for (role, pattern) in &overrides.morphs {
+			model.add_morph_role(role, pattern);
+		}
+	}
+	
+	if let Some(overrides) = &mut overrides {
+		for rb in overrides.rigid_bodies.drain(..) {
+			if let Some(id) = rb.id {
+				if id >= rigid_body_defs.len() {
+					eprintln!("Model {} has no rigid body id {}", path.to_string_lossy(), id);
+					continue
 				}
 				
-				if let Some(id) = joint.id {
-					if id >= joints_defs.len() {
-						eprintln!("Model {} has no joint id {}", self.path.to_string_lossy(), id);
-						continue
-					}
-					
-					joint.apply_to(&mut joints_defs[id]);
-				} else if let Some(pattern) = &joint.pattern {
-					let pattern = PatternMatcher::new(pattern);
-					
-					let mut matched = false;
-					for desc in joints_defs.iter_mut() {
-						if pattern.matches(&desc.local_name) || pattern.matches(&desc.universal_name) {
-							matched = true;
-							joint.apply_to(desc);
-						}
-					}
-					
-					if !matched {
-						eprintln!("Model {} has no joints matching pattern {}", self.path.to_string_lossy(), pattern);
+				rb.apply_to(&mut rigid_body_defs[id]);
+			} else if let Some(pattern) = &rb.pattern {
+				let pattern = PatternMatcher::new(pattern);
+				
+				let mut matched = false;
+				for desc in rigid_body_defs.iter_mut() {
+					if pattern.matches(&desc.local_name) || pattern.matches(&desc.universal_name) {
+						matched = true;
+						rb.apply_to(desc);
 					}
-				} else {
-					joints_defs.push(joint.into());
 				}
+				
+				if !matched {
+					eprintln!("Model {} has no rigid body matching pattern {}", path.to_string_lossy(), pattern);
+				}
+			} else {
+				rigid_body_defs.push(rb.into());
 			}
 		}
+	}
+	
+	for (id, rigid_body) in rigid_body_defs.iter().enumerate() {
+		if let Some(dump_config) = &mut dump_config {
+			dump_config.rigid_bodies.push(MMDRigidBodyOverride::from_mmd(&rigid_body, id))
+		}
+		
+		let name = display_name(&rigid_body.universal_name, &rigid_body.local_name);
+		
+		let translation = rigid_body.shape_position.from_mmd();
+		let rotation = Rot3::from_axis_angle(&Vec3::y_axis(), -rigid_body.shape_rotation.y)
+		             * Rot3::from_axis_angle(&Vec3::x_axis(), -rigid_body.shape_rotation.x)
+		             * Rot3::from_axis_angle(&Vec3::z_axis(),  rigid_body.shape_rotation.z);
+		let position = Isometry3::from_parts(translation.into(), rotation);
+		
+		let volume;
+		let collider;
+		match rigid_body.shape {
+			MMDShapeType::Sphere => {
+				volume = 4.0 / 3.0 * PI * rigid_body.shape_size.x;
+				collider = ColliderBuilder::new(ColliderShape::ball(rigid_body.shape_size.x * MMD_UNIT_SIZE))
+			},
+			MMDShapeType::Box => {
+				volume = rigid_body.shape_size.x / rigid_body.shape_size.y / rigid_body.shape_size.z;
+				collider = ColliderBuilder::new(ColliderShape::cuboid(rigid_body.shape_size.x * MMD_UNIT_SIZE,
+				                                                      rigid_body.shape_size.y * MMD_UNIT_SIZE,
+				                                                      rigid_body.shape_size.z * MMD_UNIT_SIZE))
+			},
+			MMDShapeType::Capsule => {
+				volume = 4.0 / 3.0 * PI * rigid_body.shape_size.y
+				       + rigid_body.shape_size.x * rigid_body.shape_size.y * rigid_body.shape_size.y * PI;
+				collider = ColliderBuilder::new(ColliderShape::capsule(point![0.0, -rigid_body.shape_size.y * MMD_UNIT_SIZE / 2.0, 0.0],
+				                                                       point![0.0,  rigid_body.shape_size.y * MMD_UNIT_SIZE / 2.0, 0.0],
+				                                                       rigid_body.shape_size.x * MMD_UNIT_SIZE))
+			},
+		};
+		
+		let collider = collider.position(position)
+		                       .collision_groups(InteractionGroups::new(Group::from(1 << rigid_body.group_id), Group::from(0xFFFF0000 | rigid_body.non_collision_mask as u32)))
+		                       .density(rigid_body.mass / volume)
+		                       .build();
 		
-		for (id, joint) in joints_defs.iter().enumerate() {
-			if let Some(dump_config) = &mut dump_config {
-				dump_config.joints.push(MMDJointOverride::from_mmd(&joint, id));
+		model.add_collider(ColliderDesc::new(name,
+		                                     rigid_body.bone_index as usize,
+		                                     collider,
+		                                     rigid_body.move_attenuation,
+		                                     rigid_body.rotation_damping,
+		                                     rigid_body.repulsion,
+		                                     rigid_body.fiction,
+		                                     rigid_body.physics_mode));
+	}
+	
+	if let Some(overrides) = &mut overrides {
+		for mut joint in overrides.joints.drain(..) {
+			if let Err(err) = joint.normalize(model.bones(), &rigid_body_defs) {
+				eprintln!("{}", err);
+				continue;
 			}
 			
-			let name = if joint.universal_name.len() > 0 {
-				&joint.universal_name
-			} else if let Some(translated) = debug::translate(&joint.local_name) {
-				translated
+			if let Some(id) = joint.id {
+				if id >= joints_defs.len() {
+					eprintln!("Model {} has no joint id {}", path.to_string_lossy(), id);
+					continue
+				}
+				
+				joint.apply_to(&mut joints_defs[id]);
+			} else if let Some(pattern) = &joint.pattern {
+				let pattern = PatternMatcher::new(pattern);
+				
+				let mut matched = false;
+				for desc in joints_defs.iter_mut() {
+					if pattern.matches(&desc.local_name) || pattern.matches(&desc.universal_name) {
+						matched = true;
+						joint.apply_to(desc);
+					}
+				}
+				
+				if !matched {
+					eprintln!("Model {} has no joints matching pattern {}", path.to_string_lossy(), pattern);
+				}
 			} else {
-				&joint.local_name
-			};
-			
-			let translation = joint.position.from_mmd();
-			let rotation = Rot3::from_axis_angle(&Vec3::y_axis(), -joint.rotation.y)
-			             * Rot3::from_axis_angle(&Vec3::x_axis(), -joint.rotation.x)
-			             * Rot3::from_axis_angle(&Vec3::z_axis(),  joint.rotation.z);
-			let position = Isometry3::from_parts(translation.into(), rotation);
-			let position_min = joint.position_min.from_mmd_scale();
-			let position_max = joint.position_max.from_mmd_scale();
-			let rotation_min = vector!(joint.rotation_min.x, -joint.rotation_max.y, -joint.rotation_max.z);
-			let rotation_max = vector!(joint.rotation_max.x, -joint.rotation_min.y, -joint.rotation_min.z);
-			
-			model.add_joint(JointDesc::new(name,
-			                               joint.joint_type,
-			                               joint.rigid_body_a as usize,
-			                               joint.rigid_body_b as usize,
-			                               position,
-			                               position_min,
-			                               position_max,
-			                               rotation_min,
-			                               rotation_max,
-			                               joint.position_spring,
-			                               joint.rotation_spring,
-			                               joint.body_part));
+				joints_defs.push(joint.into());
+			}
 		}
-		
-		if let Some(dump_config) = &dump_config {
-			let model_name = self.path.file_prefix().map(OsStr::to_string_lossy).unwrap_or("unknown".into());
-			let dump_path = format!("{}_modeldump.toml", model_name);
-			
-			dprintln!("Dumping model2.toml of {} to {}", self.path.to_string_lossy(), dump_path);
-			fs::write(dump_path, toml::to_string_pretty(dump_config).unwrap()).unwrap();
+	}
+	
+	for (id, joint) in joints_defs.iter().enumerate() {
+		if let Some(dump_config) = &mut dump_config {
+			dump_config.joints.push(MMDJointOverride::from_mmd(&joint, id));
 		}
 		
-		Ok(Arc::new(model.build(renderer)?))
+		let name = display_name(&joint.universal_name, &joint.local_name);
+		
+		let translation = joint.position.from_mmd();
+		let rotation = Rot3::from_axis_angle(&Vec3::y_axis(), -joint.rotation.y)
+		             * Rot3::from_axis_angle(&Vec3::x_axis(), -joint.rotation.x)
+		             * Rot3::from_axis_angle(&Vec3::z_axis(),  joint.rotation.z);
+		let position = Isometry3::from_parts(translation.into(), rotation);
+		let position_min = joint.position_min.from_mmd_scale();
+		let position_max = joint.position_max.from_mmd_scale();
+		let rotation_min = vector!(joint.rotation_min.x, -joint.rotation_max.y, -joint.rotation_max.z);
+		let rotation_max = vector!(joint.rotation_max.x, -joint.rotation_min.y, -joint.rotation_min.z);
+		
+		model.add_joint(JointDesc::new(name,
+		                               joint.joint_type,
+		                               joint.rigid_body_a as usize,
+		                               joint.rigid_body_b as usize,
+		                               position,
+		                               position_min,
+		                               position_max,
+		                               rotation_min,
+		                               rotation_max,
+		                               joint.position_spring,
+		                               joint.rotation_spring,
+		                               joint.body_part));
 	}
-}
-
-impl Display for PmxAsset {
-	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-		write!(f, "PMX model {}", self.path.to_string_lossy())
+	
+	if let Some(dump_config) = &dump_config {
+		let model_name = path.file_prefix().map(OsStr::to_string_lossy).unwrap_or("unknown".into());
+		let dump_path = format!("{}_modeldump.toml", model_name);
+		
+		dprintln!("Dumping model2.toml of {} to {}", path.to_string_lossy(), dump_path);
+		fs::write(dump_path, toml::to_string_pretty(dump_config).unwrap()).unwrap();
 	}
+	
+	Ok(())
 }
 
-fn find_image_format<P: AsRef<Path>>(path: P) -> Result<ImageFormat> {
+pub(super) fn find_image_format<P: AsRef<Path>>(path: P) -> Result<ImageFormat> {
 	Ok(match imghdr::from_file(&path)? {
 		Some(imghdr::Type::Gif) => ImageFormat::Gif,
 		Some(imghdr::Type::Tiff) => ImageFormat::Tiff,
@@ -514,9 +740,9 @@ impl<C: mmd::Config> Display for JointEx<C>
 	}
 }
 
-const MMD_UNIT_SIZE: f32 = 7.9 / 100.0; // https://www.deviantart.com/hogarth-mmd/journal/1-MMD-unit-in-real-world-units-685870002
+pub(super) const MMD_UNIT_SIZE: f32 = 7.9 / 100.0; // https://www.deviantart.com/hogarth-mmd/journal/1-MMD-unit-in-real-world-units-685870002
 
-trait FromMMD {
+pub(super) trait FromMMD {
 	fn from_mmd(self) -> Self;
 	fn from_mmd_normal(self) -> Self;
 	fn from_mmd_scale(self) -> Self;
@@ -536,6 +762,11 @@ impl FromMMD for Vec3 {
 	}
 }
 
+/// Converts IK angle limits, as euler angles in radians. Mirroring Z negates X and Y rotations, swapping their bounds.
+pub(super) fn ik_limits_from_mmd((min, max): (Vec3, Vec3)) -> (Vec3, Vec3) {
+	(vector!(-max.x, -max.y, min.z), vector!(-min.x, -min.y, max.z))
+}
+
 trait FlattenArrayVec {
 	type Out;
 	fn flatten(self) -> Self::Out;
@@ -565,3 +796,43 @@ impl<T, const N: usize> FlattenArrayVec for Vec<[T; N]> {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	/// Same conversion as rotations in VMD and VPD files.
+	fn rotation_from_mmd(x: f32, y: f32, z: f32) -> Rot3 {
+		let rotation = Rot3::from_euler_angles(x, y, z);
+		Rot3::new_normalize(Quaternion::new(rotation.w, -rotation.i, -rotation.j, rotation.k))
+	}
+	
+	fn within((min, max): (Vec3, Vec3), rotation: Rot3) -> bool {
+		let (x, y, z) = rotation.euler_angles();
+		let angles = vector!(x, y, z);
+		
+		(0..3).all(|axis| angles[axis] >= min[axis] - 1.0e-4 && angles[axis] <= max[axis] + 1.0e-4)
+	}
+	
+	#[test]
+	fn knee_bends_backwards() {
+		let limits = ik_limits_from_mmd((vector!(-PI, 0.0, 0.0), vector!(-0.5_f32.to_radians(), 0.0, 0.0)));
+		
+		assert!(within(limits, rotation_from_mmd(-60.0_f32.to_radians(), 0.0, 0.0)));
+		assert!(!within(limits, rotation_from_mmd(60.0_f32.to_radians(), 0.0, 0.0)));
+	}
+	
+	#[test]
+	fn limits_match_rotations() {
+		let min = vector!(-0.3, -0.5, -0.2);
+		let max = vector!(0.4, 0.1, 0.6);
+		let limits = ik_limits_from_mmd((min, max));
+		
+		for corner in 0..8 {
+			let angle = |axis: usize| if corner & (1 << axis) == 0 { min[axis] * 0.9 } else { max[axis] * 0.9 };
+			
+			assert!(within(limits, rotation_from_mmd(angle(0), angle(1), angle(2))));
+			assert!(!within(limits, rotation_from_mmd(angle(0) * 1.5, angle(1) * 1.5, angle(2) * 1.5)));
+		}
+	}
+}
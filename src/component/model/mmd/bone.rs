@@ -1,7 +1,7 @@
 use egui::{Grid, RichText, Ui, WidgetText};
 
 use crate::application::{Application, EntityRef};
-use crate::math::{Color, Point3, Similarity3, Translation3, Isometry3};
+use crate::math::{Color, Point3, Rot3, Similarity3, Translation3, Isometry3, Vec2, Vec3};
 use crate::utils::{ExUi, id_fmt, Inspect, InspectMut, InspectObject};
 use super::super::super::ComponentRef;
 use super::MMDModel;
@@ -13,23 +13,69 @@ pub struct MMDBone {
 	pub id: usize,
 	pub model: ComponentRef<MMDModel>,
 	pub name: String,
+	pub local_name: String,
 	pub parent: Option<usize>,
 	pub color: Color,
 	pub rigid_body: EntityRef,
 	pub model_transform: Translation3,
 	pub local_transform: Translation3,
 	pub anim_transform: Similarity3,
+	/// Sum of bone morphs, recalculated every frame.
+	pub morph_transform: Isometry3,
+	/// Rotation applied by the IK solver, recalculated every frame.
+	pub ik_transform: Rot3,
+	/// Rotation and translation inherited from the append parent, recalculated every frame.
+	pub append_transform: Isometry3,
+	/// Aims the bone at a point, eg. set every frame by `LookAt`. Ignored when overridden.
+	pub look_at: Option<BoneLookAt>,
+	/// Rotation applied by the look-at solver, recalculated every frame.
+	pub look_transform: Rot3,
+	/// Manual pose set in pose editor or loaded from VPD, in parent space, applied before animation.
+	pub pose_transform: Isometry3,
 	pub override_transform: Option<Similarity3>,
 	pub rigid_body_transform: Isometry3,
 	pub display: bool,
+	pub rotatable: bool,
+	pub movable: bool,
 	pub connection: BoneConnection,
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct BoneLookAt {
+	/// Point in model space.
+	pub target: Point3,
+	/// Fraction of the remaining rotation towards the target taken by this bone.
+	pub weight: f32,
+	/// Maximum yaw and pitch, in radians.
+	pub limits: Vec2,
+	/// Yaw and pitch added after aiming, eg. eye saccades.
+	pub offset: Vec2,
+}
+
+impl BoneLookAt {
+	/// Rotation pointing bone's forward axis (Z) at the target. `target` is in bone space.
+	pub fn rotation(&self, target: Point3) -> Rot3 {
+		let dir = target.coords;
+		let yaw = dir.x.atan2(dir.z) * self.weight + self.offset.x;
+		let pitch = (-dir.y).atan2(dir.xz().norm()) * self.weight + self.offset.y;
+		
+		Rot3::from_axis_angle(&Vec3::y_axis(), yaw.clamp(-self.limits.x, self.limits.x))
+			* Rot3::from_axis_angle(&Vec3::x_axis(), pitch.clamp(-self.limits.y, self.limits.y))
+	}
+}
+
 impl MMDBone {
 	pub fn origin(&self) -> Point3 {
 		self.model_transform * Point3::origin()
 	}
 	
+	/// Animation combined with bone morphs and manual pose, before append and IK transforms.
+	pub fn pose(&self) -> Similarity3 {
+		Similarity3::from_parts(self.pose_transform.translation * self.morph_transform.translation * self.anim_transform.isometry.translation,
+		                        self.pose_transform.rotation * self.morph_transform.rotation * self.anim_transform.isometry.rotation,
+		                        self.anim_transform.scaling())
+	}
+	
 	pub fn attach_rigid_body(&mut self, rigid_body: EntityRef, model_pos: Isometry3) -> &mut Self {
 		self.rigid_body = rigid_body;
 		self.rigid_body_transform = self.model_transform.inverse() * model_pos;
@@ -43,15 +89,24 @@ impl From<&BoneDesc> for MMDBone {
 			id: desc.id,
 			model: ComponentRef::null(),
 			name: desc.name.clone(),
+			local_name: desc.local_name.clone(),
 			parent: desc.parent,
 			color: desc.color,
 			rigid_body: EntityRef::null(),
 			model_transform: desc.model_pos.into(),
 			local_transform: desc.local_pos.into(),
 			anim_transform: Similarity3::identity(),
+			morph_transform: Isometry3::identity(),
+			ik_transform: Rot3::identity(),
+			append_transform: Isometry3::identity(),
+			look_at: None,
+			look_transform: Rot3::identity(),
+			pose_transform: Isometry3::identity(),
 			override_transform: None,
 			rigid_body_transform: Isometry3::identity(),
 			display: desc.display,
+			rotatable: desc.rotatable,
+			movable: desc.movable,
 			connection: desc.connection,
 		}
 	}
@@ -68,6 +123,7 @@ impl InspectMut for MMDBone {
 				ui.inspect_row("ID", (self.model.clone(), self.id), application);
 				ui.inspect_row("Model", &self.model, application);
 				ui.inspect_row("Name", &self.name, ());
+				ui.inspect_row("Local Name", &self.local_name, ());
 				
 				if let Some(parent) = self.parent {
 					ui.inspect_row("Parent", (self.model.clone(), parent), application);
@@ -80,9 +136,16 @@ impl InspectMut for MMDBone {
 				ui.inspect_row("Model Tr", &mut self.model_transform, ());
 				ui.inspect_row("Local Tr", &mut self.local_transform, ());
 				ui.inspect_row("Animation Tr", &mut self.anim_transform, ());
+				ui.inspect_row("Morph Tr", &mut self.morph_transform, ());
+				ui.inspect_row("IK Tr", &mut self.ik_transform, ());
+				ui.inspect_row("Append Tr", &mut self.append_transform, ());
+				ui.inspect_row("Look Tr", &mut self.look_transform, ());
+				ui.inspect_row("Pose Tr", &mut self.pose_transform, ());
 				ui.inspect_row("Override Tr", &mut self.override_transform, ());
 				ui.inspect_row("Body Tr", &mut self.rigid_body_transform, ());
 				ui.inspect_row("Display", &mut self.display, ());
+				ui.inspect_row("Rotatable", &mut self.rotatable, ());
+				ui.inspect_row("Movable", &mut self.movable, ());
 				
 				match &mut self.connection {
 					BoneConnection::None =>           ui.inspect_row("Connection", RichText::new("NULL").monospace().italics(), ()),
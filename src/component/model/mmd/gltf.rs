@@ -0,0 +1,464 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::Result;
+use gltf::material::AlphaMode;
+use gltf::mesh::Mode;
+use mmd::pmx::rigid_body::{PhysicsMode, RigidBody, ShapeType};
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::math::{Color, Mat4, Point3, Vec2, Vec3, Vec4, PI};
+use crate::renderer::Renderer;
+use crate::renderer::assets_manager::{AssetKey, AssetsManager};
+use crate::utils::from_gltf_image;
+use super::{BodyPart, Vertex};
+use super::asset::{add_overridden_physics, load_overrides, FromMMD, JointEx, MMDIndexConfig, MMD_UNIT_SIZE};
+use super::overrides::{MMDConfig, MMDJointOverride};
+use super::shared::{MMDModelShared, MMDModelSharedBuilder, BoneDesc, BoneConnection, SubMeshDesc};
+use super::vrm::{Vrm, BONE_ROLES, MORPH_ROLES, RAGDOLL};
+
+
+/// Spring bones swing at most this far from rest, in degrees.
+const SPRING_LIMIT: f32 = 60.0;
+/// VRM stiffness is a force, roughly in 0..4 range, scaled to MMD's joint springs.
+const SPRING_STIFFNESS: f32 = 10.0;
+/// Spring bones and anchors with no hit radius still need some volume.
+const MIN_RADIUS: f32 = 0.005;
+
+#[derive(Clone, Hash, Debug, Deserialize, Serialize)]
+pub struct GltfAsset {
+	#[serde(rename = "gltf")]
+	path: PathBuf,
+	overrides: bool,
+}
+
+impl GltfAsset {
+	pub fn at(model_path: impl AsRef<Path>) -> Self {
+		GltfAsset {
+			path: model_path.as_ref().to_path_buf(),
+			overrides: true,
+		}
+	}
+	
+	pub fn no_overrides(self) -> Self {
+		Self {
+			overrides: false,
+			..self
+		}
+	}
+	
+	/// Builds the model without uploading it to the GPU, nor caching it, for headless mode.
+	pub fn load_headless(&self) -> Result<Arc<MMDModelShared>> {
+		let overrides = load_overrides(&self.path, self.overrides, None)?;
+		
+		Ok(Arc::new(self.read(overrides)?.build_headless()))
+	}
+	
+	fn read(&self, overrides: Option<MMDConfig>) -> Result<MMDModelSharedBuilder<u32>> {
+		let path = AssetsManager::find_asset_path(&self.path)?;
+		
+		// Extensions and extras are not exposed by the document, so raw JSON is kept and the document is built from it
+		let bytes = fs::read(&path)?;
+		let (json, blob) = if bytes.starts_with(b"glTF") {
+			let glb = gltf::Glb::from_slice(&bytes)?;
+			(serde_json::from_slice::<serde_json::Value>(&glb.json)?, glb.bin.map(Cow::into_owned))
+		} else {
+			(serde_json::from_slice::<serde_json::Value>(&bytes)?, None)
+		};
+		
+		let document = gltf::Document::from_json(serde::Deserialize::deserialize(&json)?)?;
+		let buffers = gltf::import_buffers(&document, path.parent(), blob)?;
+		let images = gltf::import_images(&document, path.parent(), &buffers)?;
+		
+		let mut vrm = Vrm::read(&json,
+		                        document.nodes().len(),
+		                        |node| document.nodes().nth(node).map_or(vec![], |node| node.children().map(|child| child.index()).collect()),
+		                        |node| document.nodes().nth(node).and_then(|node| node.mesh()).map(|mesh| mesh.index()))?;
+		
+		let scene = document.default_scene()
+		                    .or_else(|| document.scenes().next())
+		                    .ok_or(GltfNoSceneError)?;
+		
+		// VRM 0.x avatars face -Z, the rest of glTF models face +Z like converted MMD ones
+		let scene_transform = if vrm.as_ref().map_or(false, |vrm| vrm.legacy) {
+			Mat4::from_axis_angle(&Vec3::y_axis(), PI)
+		} else {
+			Mat4::identity()
+		};
+		
+		let humanoid_nodes = vrm.as_ref().map_or(vec![], |vrm| vrm.humanoid.values().copied().collect());
+		
+		// Every node becomes a bone, bone 0 is added as the common root of the scene
+		let mut bones = vec![BoneDesc::new("Root", None, Color::D_WHITE, Vec3::zeros(), Vec3::zeros(), false, BoneConnection::None)];
+		let mut node_bones = vec![0; document.nodes().len()];
+		let mut node_transforms = vec![Mat4::identity(); document.nodes().len()];
+		let mut mesh_nodes = vec![];
+		let mut stack: Vec<_> = scene.nodes().rev().map(|node| (node, 0, scene_transform)).collect();
+		
+		while let Some((node, parent, parent_transform)) = stack.pop() {
+			let transform = parent_transform * Mat4::from(node.transform().matrix());
+			let model_pos = transform.transform_point(&Point3::origin()).coords;
+			let id = bones.len();
+			
+			// Children are visited right after their parent, so the first one gets the next id
+			let connection = if node.children().next().is_some() { BoneConnection::Bone(id + 1) } else { BoneConnection::None };
+			let humanoid = humanoid_nodes.contains(&node.index());
+			let display = vrm.is_none() || humanoid;
+			let color = if humanoid { Color::BLUE.lightness(1.5) } else { Color::D_WHITE };
+			let name = node.name().map_or_else(|| format!("Node {}", node.index()), Into::into);
+			
+			bones.push(BoneDesc::new(name,
+			                         Some(parent),
+			                         color,
+			                         model_pos,
+			                         model_pos - bones[parent].model_pos,
+			                         display,
+			                         connection));
+			
+			node_bones[node.index()] = id;
+			node_transforms[node.index()] = transform;
+			
+			if node.mesh().is_some() {
+				mesh_nodes.push(node.clone());
+			}
+			
+			stack.extend(node.children().collect::<Vec<_>>().into_iter().rev().map(|child| (child, id, transform)));
+		}
+		
+		// Nodes outside of the scene are left on the root bone, VRM data can't use them
+		if let Some(vrm) = &mut vrm {
+			let dropped = vrm.retain_nodes(|node| node_bones[node] != 0);
+			
+			if dropped > 0 {
+				eprintln!("Skipping {} VRM bones, springs and colliders outside of the scene in {}", dropped, self.path.to_string_lossy());
+			}
+		}
+		
+		let mut vertices = vec![];
+		let mut indices = vec![];
+		let mut sub_meshes = vec![];
+		let mut morph_offsets = BTreeMap::<(usize, usize), Vec<(u32, Vec3)>>::new();
+		
+		for node in mesh_nodes {
+			let mesh = match node.mesh() {
+				Some(mesh) => mesh,
+				None => continue,
+			};
+			
+			// Skinned meshes ignore transform of their node, their joints place them instead
+			let joints = match node.skin() {
+				Some(skin) => {
+					let inverse_binds = skin.reader(|buffer| Some(&buffers[buffer.index()]))
+					                        .read_inverse_bind_matrices()
+					                        .map_or(vec![], |matrices| matrices.map(Mat4::from).collect());
+					
+					skin.joints()
+					    .enumerate()
+					    .map(|(id, joint)| (node_bones[joint.index()] as u32, node_transforms[joint.index()] * inverse_binds.get(id).copied().unwrap_or_else(Mat4::identity)))
+					    .collect()
+				},
+				None => vec![],
+			};
+			
+			for primitive in mesh.primitives().filter(|primitive| primitive.mode() == Mode::Triangles) {
+				let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+				let base = vertices.len() as u32;
+				
+				let positions = reader.read_positions()
+				                      .ok_or(GltfSyntaxError("primitive has no positions"))?
+				                      .map(Vec3::from)
+				                      .collect::<Vec<_>>();
+				let normals = reader.read_normals()
+				                    .map_or_else(|| vec![Vec3::y(); positions.len()], |normals| normals.map(Vec3::from).collect());
+				let uvs = reader.read_tex_coords(0)
+				                .map_or_else(|| vec![Vec2::zeros(); positions.len()], |uvs| uvs.into_f32().map(Vec2::from).collect());
+				let skin_joints = reader.read_joints(0).map(|joints| joints.into_u16().collect::<Vec<_>>());
+				let skin_weights = reader.read_weights(0).map(|weights| weights.into_f32().collect::<Vec<_>>());
+				
+				let counts = [Some(normals.len()), Some(uvs.len()), skin_joints.as_ref().map(Vec::len), skin_weights.as_ref().map(Vec::len)];
+				if counts.iter().flatten().any(|&count| count != positions.len()) {
+					return Err(GltfSyntaxError("vertex attributes differ in length").into());
+				}
+				
+				// Vertices are baked in the rest pose, bones have no rest rotation
+				let mut transforms = Vec::with_capacity(positions.len());
+				
+				for id in 0..positions.len() {
+					let (bones_indices, bones_weights, transform) = match (&skin_joints, &skin_weights) {
+						(Some(skin_joints), Some(skin_weights)) if !joints.is_empty() => {
+							let total = skin_weights[id].iter().sum::<f32>().max(f32::EPSILON);
+							let mut bones_indices = [0; 4];
+							let mut bones_weights = [0.0; 4];
+							let mut transform = Mat4::zeros();
+							
+							for slot in 0..4 {
+								let &(bone, joint_transform) = joints.get(skin_joints[id][slot] as usize)
+								                                     .ok_or(GltfSyntaxError("skin joint out of range"))?;
+								
+								bones_indices[slot] = bone;
+								bones_weights[slot] = skin_weights[id][slot] / total;
+								transform += joint_transform * bones_weights[slot];
+							}
+							
+							(bones_indices, bones_weights, transform)
+						},
+						_ => ([node_bones[node.index()] as u32, 0, 0, 0], [1.0, 0.0, 0.0, 0.0], node_transforms[node.index()]),
+					};
+					
+					vertices.push(Vertex::new(transform.transform_point(&positions[id].into()).coords,
+					                          transform.transform_vector(&normals[id]).normalize(),
+					                          uvs[id],
+					                          0.0,
+					                          bones_indices,
+					                          bones_weights,
+					                          None::<[Vec3; 3]>));
+					
+					transforms.push(transform);
+				}
+				
+				for (target, (offsets, _, _)) in reader.read_morph_targets().enumerate() {
+					let offsets = match offsets {
+						Some(offsets) => offsets.map(Vec3::from).collect::<Vec<_>>(),
+						None => continue,
+					};
+					
+					if offsets.len() != positions.len() {
+						return Err(GltfSyntaxError("morph target differs in length from positions").into());
+					}
+					
+					morph_offsets.entry((mesh.index(), target))
+					             .or_default()
+					             .extend(offsets.into_iter()
+					                            .enumerate()
+					                            .filter(|(_, offset)| offset.magnitude_squared() > 0.0)
+					                            .map(|(id, offset)| (base + id as u32, transforms[id].transform_vector(&offset))));
+				}
+				
+				let start = indices.len() as u32;
+				match reader.read_indices() {
+					Some(read) => indices.extend(read.into_u32().map(|index| base + index)),
+					None => indices.extend(base .. base + positions.len() as u32),
+				}
+				
+				let material = primitive.material();
+				let pbr = material.pbr_metallic_roughness();
+				
+				sub_meshes.push(SubMeshDesc {
+					range: start .. indices.len() as u32,
+					texture: pbr.base_color_texture().map(|info| info.texture().source().index()),
+					toon: None,
+					sphere_map: None,
+					color: Vec4::from(pbr.base_color_factor()),
+					specular: Vec3::zeros(),
+					specularity: 1.0,
+					ambient: Vec3::zeros(),
+					sphere_mode: 0,
+					no_cull: material.double_sided(),
+					opaque: material.alpha_mode() == AlphaMode::Opaque,
+					edge: None,
+				});
+			}
+		}
+		
+		let mut model = MMDModelShared::new(vertices, indices);
+		
+		// Texture ids are the indices of images
+		for image in images {
+			model.add_texture(from_gltf_image(image)?);
+		}
+		
+		for sub_mesh in sub_meshes {
+			model.add_sub_mesh(sub_mesh);
+		}
+		
+		for bone in bones {
+			model.add_bone(bone);
+		}
+		
+		let mut morph_ids = BTreeMap::new();
+		
+		for ((mesh, target), offsets) in morph_offsets {
+			let name = json["meshes"][mesh]["extras"]["targetNames"][target]
+				.as_str()
+				.map_or_else(|| format!("{} {}", document.meshes().nth(mesh).and_then(|mesh| mesh.name()).unwrap_or("Mesh"), target), Into::into);
+			
+			morph_ids.insert((mesh, target), morph_ids.len());
+			model.add_morph(name, offsets);
+		}
+		
+		if let Some(vrm) = &vrm {
+			for expression in &vrm.expressions {
+				model.add_group_morph(&expression.name,
+				                      expression.binds
+				                                .iter()
+				                                .filter_map(|&(mesh, target, weight)| morph_ids.get(&(mesh, target)).map(|&id| (id, weight)))
+				                                .collect());
+			}
+			
+			// Roles from model.toml are added later, replacing these
+			for &(role, bone) in BONE_ROLES {
+				if let Some(&node) = vrm.humanoid.get(bone) {
+					let name = model.bones()[node_bones[node]].name.clone();
+					model.add_bone_role(role, name);
+				}
+			}
+			
+			for &(role, preset) in MORPH_ROLES {
+				if let Some(expression) = vrm.expressions.iter().find(|expression| expression.preset.as_deref() == Some(preset)) {
+					model.add_morph_role(role, &expression.name);
+				}
+			}
+		}
+		
+		let (rigid_bodies, joints) = match &vrm {
+			Some(vrm) => vrm_physics(vrm, model.bones(), &node_bones, &node_transforms),
+			None => (vec![], vec![]),
+		};
+		
+		add_overridden_physics(&mut model, &self.path, overrides, rigid_bodies, joints)?;
+		
+		Ok(model)
+	}
+}
+
+impl AssetKey for GltfAsset {
+	type Asset = Arc<MMDModelShared>;
+	
+	fn load(&self, assets_manager: &mut AssetsManager, renderer: &mut Renderer) -> Result<Self::Asset> {
+		let overrides = load_overrides(&self.path, self.overrides, Some((assets_manager, &mut *renderer)))?;
+		
+		Ok(Arc::new(self.read(overrides)?.build(renderer)?))
+	}
+}
+
+impl Display for GltfAsset {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "glTF model {}", self.path.to_string_lossy())
+	}
+}
+
+/// Builds the ragdoll of humanoid bones, spring bones and their colliders in MMD's format, so model.toml can override them like PMX physics.
+fn vrm_physics(vrm: &Vrm, bones: &[BoneDesc], node_bones: &[usize], node_transforms: &[Mat4]) -> (Vec<RigidBody<MMDIndexConfig>>, Vec<JointEx<MMDIndexConfig>>) {
+	let mut rigid_bodies = vec![];
+	let mut joints = vec![];
+	let humanoid = |name: &str| vrm.humanoid.get(name).map(|&node| node_bones[node]);
+	
+	// Ragdoll parts collide with spring bones only, neighbouring parts overlap
+	for &(name, body_part, child, radius, limits) in RAGDOLL {
+		let bone_id = match humanoid(name) {
+			Some(bone_id) => bone_id,
+			None => continue,
+		};
+		
+		let from = bones[bone_id].model_pos;
+		let to = child.and_then(humanoid).map(|child| bones[child].model_pos);
+		
+		rigid_bodies.push(rigid_body(name, bone_id, from, to, radius, 0, 0b10, PhysicsMode::Dynamic, 0.5));
+		
+		if body_part != BodyPart::Hip {
+			joints.push(MMDJointOverride {
+				name: Some(name.to_string()),
+				using_bone: Some(bone_id),
+				rotation_min: Some(-Vec3::from(limits)),
+				rotation_max: Some(Vec3::from(limits)),
+				body_part: Some(body_part),
+				..MMDJointOverride::default()
+			});
+		}
+	}
+	
+	for collider in &vrm.colliders {
+		let center = node_transforms[collider.node].transform_point(&collider.offset.into()).coords;
+		
+		rigid_bodies.push(rigid_body("Collider", node_bones[collider.node], center, None, collider.radius, 0, 0b10, PhysicsMode::Static, 0.0));
+	}
+	
+	for spring in &vrm.springs {
+		let bone_id = node_bones[spring.node];
+		let tail = match bones[bone_id].connection {
+			BoneConnection::Bone(tail) => bones[tail].model_pos,
+			_ => continue,
+		};
+		
+		if rigid_bodies.iter().any(|rb| rb.bone_index == bone_id as i32) {
+			continue;
+		}
+		
+		// Chains hanging from bones without physics are pinned with a body that collides with nothing
+		let mut ancestors = std::iter::successors(bones[bone_id].parent, |&id| bones[id].parent);
+		if !ancestors.any(|id| rigid_bodies.iter().any(|rb| rb.bone_index == id as i32)) {
+			let parent = bones[bone_id].parent.unwrap_or(0);
+			
+			rigid_bodies.push(rigid_body("Anchor", parent, bones[parent].model_pos, None, MIN_RADIUS, 1, 0, PhysicsMode::Static, 0.0));
+		}
+		
+		rigid_bodies.push(rigid_body(&bones[bone_id].name, bone_id, tail, None, spring.hit_radius.max(MIN_RADIUS), 1, 0b1, PhysicsMode::Dynamic, spring.drag));
+		
+		joints.push(MMDJointOverride {
+			name: Some(bones[bone_id].name.clone()),
+			using_bone: Some(bone_id),
+			rotation_min: Some(Vec3::repeat(-SPRING_LIMIT)),
+			rotation_max: Some(Vec3::repeat(SPRING_LIMIT)),
+			rotation_spring: Some(Vec3::repeat(spring.stiffness * SPRING_STIFFNESS)),
+			..MMDJointOverride::default()
+		});
+	}
+	
+	// Joints can find their bodies only once all of them are added
+	let joints = joints.into_iter()
+	                   .filter_map(|mut joint| match joint.normalize(bones, &rigid_bodies) {
+		                   Ok(()) => Some(joint.into()),
+		                   Err(err) => {
+			                   eprintln!("Skipping joint {}: {}", joint.name.as_deref().unwrap_or_default(), err);
+			                   None
+		                   },
+	                   })
+	                   .collect();
+	
+	(rigid_bodies, joints)
+}
+
+/// Sphere at `from`, or capsule spanning from `from` to `to`. Positions are in model space, the result in MMD's coordinates.
+fn rigid_body(name: &str, bone: usize, from: Vec3, to: Option<Vec3>, radius: f32, group_id: u8, mask: u16, physics_mode: PhysicsMode, damping: f32) -> RigidBody<MMDIndexConfig> {
+	let (shape, shape_size, center, rotation) = match to {
+		Some(to) if (to - from).magnitude() > f32::EPSILON => {
+			let dir = (to - from).normalize();
+			
+			(ShapeType::Capsule,
+			 vector!(radius, (to - from).magnitude(), 0.0),
+			 (from + to) / 2.0,
+			 vector!(-dir.y.acos(), -dir.x.atan2(dir.z), 0.0))
+		},
+		_ => (ShapeType::Sphere, vector!(radius, 0.0, 0.0), from, Vec3::zeros()),
+	};
+	
+	RigidBody {
+		local_name: name.to_string(),
+		universal_name: name.to_string(),
+		bone_index: bone as i32,
+		group_id,
+		non_collision_mask: mask,
+		shape,
+		shape_size: shape_size / MMD_UNIT_SIZE,
+		shape_position: center.from_mmd_normal() / MMD_UNIT_SIZE,
+		shape_rotation: rotation,
+		mass: 1.0,
+		move_attenuation: damping,
+		rotation_damping: damping,
+		repulsion: 0.0,
+		fiction: 0.5,
+		physics_mode,
+	}
+}
+
+#[derive(Debug, Error)]
+#[error("glTF model has no scenes.")]
+pub struct GltfNoSceneError;
+
+#[derive(Debug, Error)]
+#[error("Malformed glTF model: {0}.")]
+pub struct GltfSyntaxError(pub(super) &'static str);
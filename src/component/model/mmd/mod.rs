@@ -4,11 +4,13 @@ use std::sync::Arc;
 use std::time::Duration;
 use anyhow::Result;
 use egui::Ui;
-use nalgebra::UnitQuaternion;
+use nalgebra::{Unit, UnitQuaternion};
 use rapier3d::dynamics::RigidBodyType;
 use rapier3d::geometry::Collider;
 use rapier3d::prelude::FixedJoint;
+use serde_derive::{Deserialize, Serialize};
 use simba::scalar::SubsetOf;
+use smallvec::SmallVec;
 use vulkano::buffer::{Buffer, Subbuffer, BufferUsage};
 use vulkano::buffer::allocator::{SubbufferAllocator, SubbufferAllocatorCreateInfo};
 use vulkano::command_buffer::CopyBufferInfo;
@@ -17,48 +19,77 @@ use vulkano::memory::allocator::MemoryUsage;
 use vulkano::pipeline::{Pipeline, PipelineBindPoint};
 
 pub mod asset;
+pub mod gltf;
 pub mod pipeline;
+pub mod pmd;
 pub mod shared;
 pub mod test;
+pub mod vmd;
+pub mod vpd;
+pub mod vrm;
 mod bone;
 mod overrides;
+mod player;
 mod rigid_body;
+mod toon;
 
 use crate::debug;
 use crate::application::{Application, Entity};
-use crate::math::{AMat4, Color, face_towards_lossy, Isometry3, IVec4, Mat4};
+use crate::math::{AMat4, Color, face_towards_lossy, Isometry3, IVec4, Mat4, Point3, Rot3, Similarity3, Translation3, Vec4};
 use crate::renderer::{RenderContext, Renderer, RenderType};
 use crate::renderer::pipelines::PipelineNoLayoutError;
-use crate::utils::{AutoCommandBufferBuilderEx, ExUi, IntoInfo, SubbufferAllocatorEx};
-use super::super::{Component, ComponentBase, ComponentRef, ComponentInner};
+use crate::utils::{AutoCommandBufferBuilderEx, ExUi, IntoInfo, PatternMatcher, SubbufferAllocatorEx};
+use super::super::{Component, ComponentBase, ComponentRef, ComponentInner, ComponentLoad};
 use super::super::physics::collider::ColliderComponent;
 use super::super::physics::joint::JointComponent;
-pub use bone::MMDBone;
+pub use bone::{BoneLookAt, MMDBone};
 pub use overrides::BodyPart;
 pub use pipeline::{MORPH_GROUP_SIZE, Vertex, Pc};
+pub use player::VmdPlayer;
 pub use rigid_body::MMDRigidBody;
-use shared::{MMDModelShared, BoneConnection};
+use asset::MMDAsset;
+use shared::{MMDModelShared, MMDModelSharedGpu, BoneConnection, BoneAppend, BoneDesc, BoneIk, MaterialInfo, MorphDesc, SubMesh};
 
 
 pub struct MMDModelState {
 	pub bones: Vec<MMDBone>,
 	pub rigid_bodies: Vec<ComponentRef<MMDRigidBody>>,
 	pub joints: Vec<ComponentRef<JointComponent>>,
+	/// Weights of all morphs, in PMX order.
 	pub morphs: Vec<f32>,
 	
+	/// Transforms from bones' frames to model space, kept until next frame.
 	bones_mats: Vec<AMat4>,
 	morphs_vec: Vec<IVec4>,
+	/// Morph weights including contributions of group morphs.
+	morphs_weights: Vec<f32>,
+	materials: Vec<MaterialInfo>,
+	/// Scale and color of sub meshes' edges.
+	edges: Vec<(f32, Vec4)>,
 	selected_bone: Option<usize>,
 }
 
+#[derive(Deserialize, Serialize)]
+struct MMDModelParams {
+	asset: MMDAsset,
+}
+
 #[derive(ComponentBase)]
 pub struct MMDModel {
 	#[inner] inner: ComponentInner,
 	pub state: RefCell<MMDModelState>,
+	source: Option<MMDAsset>,
 	shared: Arc<MMDModelShared>,
+	/// Not created in headless mode, the model is only simulated then.
+	gpu: Option<MMDModelGpu>,
+}
+
+/// Per instance buffers and descriptor sets.
+struct MMDModelGpu {
 	bones_ubo: Subbuffer<[Mat4]>,
 	morphs_ubo: Subbuffer<[IVec4]>,
 	offsets_ubo: Subbuffer<[IVec4]>,
+	materials_ubo: Subbuffer<[MaterialInfo]>,
 	morphs_set: Arc<PersistentDescriptorSet>,
 	model_set: Arc<PersistentDescriptorSet>,
 	model_edge_set: Option<Arc<PersistentDescriptorSet>>,
@@ -68,17 +99,136 @@ pub struct MMDModel {
 #[allow(dead_code)]
 impl MMDModel {
 	pub fn new(shared: Arc<MMDModelShared>, renderer: &mut Renderer) -> Result<MMDModel> {
+		let gpu = shared.gpu
+		                .as_ref()
+		                .map(|shared_gpu| MMDModelGpu::new(shared_gpu, shared.default_bones.len(), renderer))
+		                .transpose()?;
+		
+		Ok(MMDModel::with_gpu(shared, gpu))
+	}
+	
+	/// Bones, morphs and physics work as usual, but the model is never drawn.
+	pub fn headless(shared: Arc<MMDModelShared>) -> MMDModel {
+		MMDModel::with_gpu(shared, None)
+	}
+	
+	/// Loads the model through the renderer, or without uploading it in headless mode.
+	pub fn from_asset(asset: MMDAsset, application: &Application) -> Result<MMDModel> {
+		if application.headless() {
+			return Ok(MMDModel::headless(asset.load_headless()?));
+		}
+		
+		let renderer = &mut *application.renderer()?;
+		
+		MMDModel::new(renderer.load(asset)?, renderer)
+	}
+	
+	fn with_gpu(shared: Arc<MMDModelShared>, gpu: Option<MMDModelGpu>) -> MMDModel {
 		let bones = shared.default_bones.iter().map(Into::into).collect::<Vec<_>>();
-		let bones_count = bones.len();
-		let bones_mats = Vec::with_capacity(bones_count);
+		let bones_mats = Vec::with_capacity(bones.len());
+		let morphs = vec![0.0; shared.morphs.len()];
+		let morphs_weights = Vec::with_capacity(shared.morphs.len());
+		
+		MMDModel {
+			inner: ComponentInner::from_render_type(RenderType::Transparent),
+			state: RefCell::new(MMDModelState {
+				bones,
+				rigid_bodies: vec![],
+				joints: vec![],
+				morphs,
+				bones_mats,
+				morphs_vec: vec![],
+				morphs_weights,
+				materials: vec![],
+				edges: vec![],
+				selected_bone: None,
+			}),
+			source: None,
+			shared,
+			gpu,
+		}
+	}
+	
+	pub fn loaded(&self) -> bool {
+		self.shared.gpu.as_ref().map_or(false, |gpu| gpu.fence.check())
+	}
+	
+	pub fn state(&self) -> Ref<MMDModelState> {
+		self.state.borrow()
+	}
+	
+	pub fn state_mut(&self) -> RefMut<MMDModelState> {
+		self.state.borrow_mut()
+	}
+	
+	pub fn morphs_names(&self) -> &[String] {
+		&self.shared.morphs_names
+	}
+	
+	/// Finds bone mapped to `role` in `model.toml`, or the first bone matching any of `default_patterns`.
+	pub fn find_bone(&self, role: &str, default_patterns: &[&str]) -> Option<usize> {
+		let bones = &self.shared.default_bones;
+		let matches = |pattern: &PatternMatcher| bones.iter().position(|bone| pattern.matches(&bone.local_name) || pattern.matches(&bone.name));
+		
+		match self.shared.bones_roles.get(role) {
+			Some(pattern) => matches(pattern),
+			None => default_patterns.iter().find_map(|pattern| matches(&PatternMatcher::new(pattern))),
+		}
+	}
+	
+	/// Finds morph mapped to `role` in `model.toml`, or the first morph matching any of `default_patterns`.
+	pub fn find_morph(&self, role: &str, default_patterns: &[&str]) -> Option<usize> {
+		let names = &self.shared.morphs_names;
+		let matches = |pattern: &PatternMatcher| names.iter().position(|name| pattern.matches(name));
+		
+		match self.shared.morphs_roles.get(role) {
+			Some(pattern) => matches(pattern),
+			None => default_patterns.iter().find_map(|pattern| matches(&PatternMatcher::new(pattern))),
+		}
+	}
+	
+	fn draw_debug_bones(&self, model_matrix: Isometry3, bones: &[MMDBone], bones_mats: &[AMat4], selected: Option<usize>) {
+		for (id, bone) in bones.iter().enumerate() {
+			if bone.display {
+				let pos = model_matrix.transform_point(&bones_mats[id].transform_point(&Point3::origin()));
+				
+				let color = if selected.is_none() || Some(id) == selected {
+					bone.color
+				} else {
+					Color::BLACK.opactiy(0.5)
+				};
+				
+				debug::draw_point(pos, 10.0, color);
+				debug::draw_text(&bone.name, pos, debug::DebugOffset::bottom_right(8.0, 8.0), 32.0, color);
+				
+				match &bone.connection {
+					BoneConnection::None => {}
+					BoneConnection::Bone(con) => {
+						let cpos = model_matrix.transform_point(&bones_mats[*con].transform_point(&Point3::origin()));
+						debug::draw_line(pos, cpos, 3.0, color);
+					}
+					BoneConnection::Offset(cpos) => {
+						let cpos = model_matrix.transform_point(&bones_mats[id].transform_point(&Point3::from(*cpos)));
+						debug::draw_line(pos, cpos, 3.0, color);
+					}
+				}
+			}
+		}
+	}
+	
+	// pub fn try_clone(&self, renderer: &mut Renderer) -> Result<Box<dyn Model>, ModelError> {
+	// 	Ok(Box::new(MMDModel::new(self.shared.clone(), renderer)?))
+	// }
+}
+
+impl MMDModelGpu {
+	fn new(shared: &MMDModelSharedGpu, bones_count: usize, renderer: &mut Renderer) -> Result<MMDModelGpu> {
 		let bones_ubo = Buffer::new_slice(&renderer.memory_allocator,
 		                                  (BufferUsage::TRANSFER_DST | BufferUsage::STORAGE_BUFFER).into_info(),
 		                                  MemoryUsage::DeviceOnly.into_info(),
 		                                  bones_count as u64)?;
 		
-		let morphs = vec![0.0; shared.morphs_sizes.len()];
 		let morphs_vec_count = (shared.morphs_sizes.len() + 1) / 2;
-		let morphs_vec = Vec::with_capacity(morphs_vec_count);
 		let morphs_ubo = Buffer::new_slice(&renderer.memory_allocator,
 		                                   (BufferUsage::TRANSFER_DST | BufferUsage::STORAGE_BUFFER).into_info(),
 		                                   MemoryUsage::DeviceOnly.into_info(),
@@ -87,7 +237,12 @@ impl MMDModel {
 		let offsets_ubo = Buffer::new_slice(&renderer.memory_allocator,
 		                                    (BufferUsage::TRANSFER_DST | BufferUsage::STORAGE_BUFFER).into_info(),
 		                                    MemoryUsage::DeviceOnly.into_info(),
-		                                    shared.vertices.len() as u64)?;
+		                                    shared.vertices.len() as u64 * 2)?;
+		
+		let materials_ubo = Buffer::new_slice(&renderer.memory_allocator,
+		                                      (BufferUsage::TRANSFER_DST | BufferUsage::STORAGE_BUFFER).into_info(),
+		                                      MemoryUsage::DeviceOnly.into_info(),
+		                                      shared.sub_meshes.len() as u64)?;
 		
 		let upload_allocator = SubbufferAllocator::new(renderer.memory_allocator.clone(),
 		                                               SubbufferAllocatorCreateInfo {
@@ -117,6 +272,7 @@ impl MMDModel {
 			                                             WriteDescriptorSet::buffer(0, renderer.commons.clone()),
 			                                             WriteDescriptorSet::buffer(1, bones_ubo.clone()),
 			                                             WriteDescriptorSet::buffer(2, offsets_ubo.clone()),
+			                                             WriteDescriptorSet::buffer(3, materials_ubo.clone()),
 		                                             ])?;
 		
 		let model_edge_set = edge_layout.map(|edge_layout|
@@ -128,75 +284,39 @@ impl MMDModel {
 			                             ])
 		).transpose()?;
 		
-		Ok(MMDModel {
-			inner: ComponentInner::from_render_type(RenderType::Transparent),
-			state: RefCell::new(MMDModelState {
-				bones,
-				rigid_bodies: vec![],
-				joints: vec![],
-				morphs,
-				bones_mats,
-				morphs_vec,
-				selected_bone: None,
-			}),
-			shared,
+		Ok(MMDModelGpu {
 			bones_ubo,
 			morphs_ubo,
-			morphs_set,
 			offsets_ubo,
+			materials_ubo,
+			morphs_set,
 			model_set,
 			model_edge_set,
 			upload_allocator,
 		})
 	}
-	
-	pub fn loaded(&self) -> bool {
-		self.shared.fence.check()
-	}
-	
-	pub fn state(&self) -> Ref<MMDModelState> {
-		self.state.borrow()
-	}
-	
-	pub fn state_mut(&self) -> RefMut<MMDModelState> {
-		self.state.borrow_mut()
-	}
-	
-	fn draw_debug_bones(&self, model_matrix: Isometry3, bones: &[MMDBone], bones_mats: &[AMat4], selected: Option<usize>) {
-		for (id, bone) in bones.iter().enumerate() {
-			if bone.display {
-				let pos = model_matrix.transform_point(&bones_mats[id].transform_point(&bone.origin()));
-				
-				let color = if selected.is_none() || Some(id) == selected {
-					bone.color
-				} else {
-					Color::BLACK.opactiy(0.5)
-				};
-				
-				debug::draw_point(pos, 10.0, color);
-				debug::draw_text(&bone.name, pos, debug::DebugOffset::bottom_right(8.0, 8.0), 32.0, color);
-				
-				match &bone.connection {
-					BoneConnection::None => {}
-					BoneConnection::Bone(con) => {
-						let cpos = model_matrix.transform_point(&bones_mats[*con].transform_point(&bones[*con].origin()));
-						debug::draw_line(pos, cpos, 3.0, color);
-					}
-					BoneConnection::Offset(cpos) => {
-						let cpos = model_matrix.transform_point(&bones_mats[id].transform_point(&(&bone.origin() + cpos)));
-						debug::draw_line(pos, cpos, 3.0, color);
-					}
-				}
-			}
-		}
+}
+
+// Only models loaded through the registry are saved, the ones created by other components (eg. Miku) are recreated by them.
+impl ComponentLoad for MMDModel {
+	fn load(params: toml::Value, application: &Application) -> Result<Option<Self>> {
+		let params: MMDModelParams = params.try_into()?;
+		let mut model = MMDModel::from_asset(params.asset.clone(), application)?;
+		
+		model.source = Some(params.asset);
+		
+		Ok(Some(model))
 	}
-	
-	// pub fn try_clone(&self, renderer: &mut Renderer) -> Result<Box<dyn Model>, ModelError> {
-	// 	Ok(Box::new(MMDModel::new(self.shared.clone(), renderer)?))
-	// }
 }
 
 impl Component for MMDModel {
+	fn save(&self, _entity: &Entity, _application: &Application) -> Result<Option<toml::Value>> {
+		match &self.source {
+			Some(asset) => Ok(Some(toml::Value::try_from(MMDModelParams { asset: asset.clone() })?)),
+			None => Ok(None),
+		}
+	}
+	
 	fn start(&self, entity: &Entity, application: &Application) -> Result<()> {
 		let state = &mut *self.state.borrow_mut();
 		let ent_pos = *entity.state().position;
@@ -233,6 +353,7 @@ impl Component for MMDModel {
 				.position(ent_pos * bone.model_transform * face_towards_lossy(direction))
 				.gravity_scale(0.05)
 				.rigid_body_type(RigidBodyType::Dynamic)
+				.tag("NoSave", true)
 				.build();
 			
 			rigid_bodies.insert(bone_id, rb_ent);
@@ -251,7 +372,7 @@ impl Component for MMDModel {
 				collider.position()
 			);
 			
-			rb.add_component(ColliderComponent::new(collider));
+			rb.add_component(ColliderComponent::new(collider).transient());
 		}
 		
 		state.rigid_bodies.push(entity.add_component(MMDRigidBody::new(0, Some(BodyPart::Hip), Isometry3::identity(), ComponentRef::null(), self.as_cref())));
@@ -328,40 +449,44 @@ impl Component for MMDModel {
 	fn before_render(&self, entity: &Entity, context: &mut RenderContext, _renderer: &mut Renderer) -> Result<()> {
 		let state = &mut *self.state.borrow_mut();
 		
-		for bone in &state.bones {
-			let transform = if let Some(transform) = bone.override_transform {
-				transform.to_superset()
-			} else if let Some(id) = bone.parent {
-				&state.bones_mats[id] * &bone.local_transform * &bone.anim_transform
-			} else {
-				let transform: AMat4 = bone.anim_transform.to_superset();
-				&bone.local_transform * &transform
-			};
-		
-			state.bones_mats.push(transform);
-		}
-		
-		for (id, mat) in state.bones_mats.iter_mut().enumerate() {
-			*mat = *mat * state.bones[id].model_transform.inverse();
-		}
+		state.update_morphs_weights(&self.shared);
+		state.update_bone_morphs(&self.shared);
+		state.update_bones_mats(&self.shared);
 		
 		if debug::get_flag_or_default("DebugBonesDraw") {
 			self.draw_debug_bones(*entity.state().position, &state.bones, &state.bones_mats, state.selected_bone);
 		}
 		
-		let bone_buf = self.upload_allocator.from_iter(state.bones_mats.drain(..).map(|mat| {
-			let mut mat = mat.to_homogeneous();
+		let (gpu, shared_gpu) = match (&self.gpu, &self.shared.gpu) {
+			(Some(gpu), Some(shared_gpu)) => (gpu, shared_gpu),
+			_ => return Ok(()),
+		};
+		
+		// Vertices are in model space, so offset bones to their rest positions first
+		let bone_buf = gpu.upload_allocator.from_iter(state.bones_mats.iter().zip(&state.bones).map(|(mat, bone)| {
+			let mut mat = (*mat * bone.model_transform.inverse()).to_homogeneous();
 			let rot = UnitQuaternion::from_matrix(&mat.fixed_resize(0.0)).inverse();
 			mat.set_row(3, &rot.coords.transpose());
 			mat
 		}))?;
 		
-		context.builder.copy_buffer(CopyBufferInfo::buffers(bone_buf, self.bones_ubo.clone()))?;
+		context.builder.copy_buffer(CopyBufferInfo::buffers(bone_buf, gpu.bones_ubo.clone()))?;
+		
+		state.update_materials(&self.shared, &shared_gpu.sub_meshes);
+		
+		let material_buf = gpu.upload_allocator.from_iter(state.materials.iter().copied())?;
+		
+		context.builder.copy_buffer(CopyBufferInfo::buffers(material_buf, gpu.materials_ubo.clone()))?;
 		
 		state.morphs_vec.clear();
 		let mut max_size = 0;
 		let mut packing = false;
-		for (id, scale) in state.morphs.iter().enumerate() {
+		for (desc, scale) in self.shared.morphs.iter().zip(&state.morphs_weights) {
+			let id = match desc {
+				MorphDesc::Gpu(id) => *id,
+				_ => continue,
+			};
+			
 			if scale.abs() > f32::EPSILON {
 				if packing {
 					if let Some(last) = state.morphs_vec.last_mut() {
@@ -374,29 +499,29 @@ impl Component for MMDModel {
 				
 				packing = !packing;
 				
-				if self.shared.morphs_sizes[id] > max_size {
-					max_size = self.shared.morphs_sizes[id];
+				if shared_gpu.morphs_sizes[id] > max_size {
+					max_size = shared_gpu.morphs_sizes[id];
 				}
 			}
 		}
 		
 		if state.morphs_vec.is_empty() {
-			context.builder.fill_buffer(self.offsets_ubo.as_bytes().clone().cast_aligned(), 0)?;
+			context.builder.fill_buffer(gpu.offsets_ubo.as_bytes().clone().cast_aligned(), 0)?;
 		} else {
 			let groups = (max_size + MORPH_GROUP_SIZE - 1) / MORPH_GROUP_SIZE;
 			
-			let morph_buf = self.upload_allocator.from_iter(state.morphs_vec.iter().copied())?;
+			let morph_buf = gpu.upload_allocator.from_iter(state.morphs_vec.iter().copied())?;
 			
-			context.builder.copy_buffer(CopyBufferInfo::buffers(morph_buf, self.morphs_ubo.clone()))?
-			               .fill_buffer(self.offsets_ubo.as_bytes().clone().cast_aligned(), 0)?
-			               .bind_pipeline_compute(self.shared.morphs_pipeline.clone())
+			context.builder.copy_buffer(CopyBufferInfo::buffers(morph_buf, gpu.morphs_ubo.clone()))?
+			               .fill_buffer(gpu.offsets_ubo.as_bytes().clone().cast_aligned(), 0)?
+			               .bind_pipeline_compute(shared_gpu.morphs_pipeline.clone())
 			               .bind_descriptor_sets(PipelineBindPoint::Compute,
-			                                     self.shared.morphs_pipeline.layout().clone(),
+			                                     shared_gpu.morphs_pipeline.layout().clone(),
 			                                     0,
-			                                     self.morphs_set.clone())
-			               .push_constants(self.shared.morphs_pipeline.layout().clone(),
+			                                     gpu.morphs_set.clone())
+			               .push_constants(shared_gpu.morphs_pipeline.layout().clone(),
 			                               0,
-			                               self.shared.morphs_max_size as u32)
+			                               shared_gpu.morphs_max_size as u32)
 			               .dispatch([groups as u32, state.morphs_vec.len() as u32 * 2, 1])?;
 		}
 		
@@ -405,26 +530,32 @@ impl Component for MMDModel {
 	
 	fn render(&self, entity: &Entity, context: &mut RenderContext, _renderer: &mut Renderer) -> Result<()> {
 		if !self.loaded() { return Ok(()) }
+		let (gpu, shared_gpu) = match (&self.gpu, &self.shared.gpu) {
+			(Some(gpu), Some(shared_gpu)) => (gpu, shared_gpu),
+			_ => return Ok(()),
+		};
 		let model_matrix = entity.state().position.to_homogeneous();
+		let state = self.state.borrow();
 		
-		context.builder.bind_vertex_buffers(0, self.shared.vertices.clone())
-		               .bind_any_index_buffer(self.shared.indices.clone());
+		context.builder.bind_vertex_buffers(0, shared_gpu.vertices.clone())
+		               .bind_any_index_buffer(shared_gpu.indices.clone());
 		
 		// Opaque
-		for sub_mesh in self.shared.sub_meshes.iter() {
+		for (id, sub_mesh) in shared_gpu.sub_meshes.iter().enumerate() {
 			let (pipeline, mesh_set) = sub_mesh.main.clone();
 			
 			context.builder.bind_pipeline_graphics(pipeline.clone())
 			               .bind_descriptor_sets(PipelineBindPoint::Graphics,
 			                                     pipeline.layout().clone(),
 			                                     0,
-			                                     (self.model_set.clone(), mesh_set))
-			               .push_constants(self.shared.sub_meshes.first().unwrap().main.0.layout().clone(),
+			                                     (gpu.model_set.clone(), mesh_set))
+			               .push_constants(shared_gpu.sub_meshes.first().unwrap().main.0.layout().clone(),
 			                               0,
 			                               Pc {
 				                               model: model_matrix.into(),
 				                               color: Color::WHITE.into(),
 				                               scale: 1.0,
+				                               material: id as u32,
 			                               })
 			               .draw_indexed(sub_mesh.range.len() as u32,
 			                             1,
@@ -434,19 +565,20 @@ impl Component for MMDModel {
 		}
 		
 		// Transparent
-		for sub_mesh in self.shared.sub_meshes.iter() {
+		for (id, sub_mesh) in shared_gpu.sub_meshes.iter().enumerate() {
 			if let Some((pipeline, mesh_set)) = sub_mesh.transparent.clone() {
 				context.builder.bind_pipeline_graphics(pipeline.clone())
 				               .bind_descriptor_sets(PipelineBindPoint::Graphics,
 				                                     pipeline.layout().clone(),
 				                                     0,
-				                                     (self.model_set.clone(), mesh_set))
-				               .push_constants(self.shared.sub_meshes.first().unwrap().main.0.layout().clone(),
+				                                     (gpu.model_set.clone(), mesh_set))
+				               .push_constants(shared_gpu.sub_meshes.first().unwrap().main.0.layout().clone(),
 				                               0,
 				                               Pc {
 					                               model: model_matrix.into(),
 					                               color: Color::WHITE.into(),
 					                               scale: 1.0,
+					                               material: id as u32,
 				                               })
 				               .draw_indexed(sub_mesh.range.len() as u32,
 				                             1,
@@ -457,21 +589,23 @@ impl Component for MMDModel {
 		}
 		
 		// Outline
-		for sub_mesh in self.shared.sub_meshes.iter() {
+		for (id, sub_mesh) in shared_gpu.sub_meshes.iter().enumerate() {
 			if let Some((pipeline, mesh_set)) = sub_mesh.edge.clone() {
-				let edge_scale = (context.fov.0.x / 2.0).tan() * 2.0 * context.pixel_scale.x * sub_mesh.edge_scale;
-		
+				let (edge_scale, edge_color) = state.edges.get(id).copied().unwrap_or((sub_mesh.edge_scale, sub_mesh.edge_color));
+				let edge_scale = (context.fov.0.x / 2.0).tan() * 2.0 * context.pixel_scale.x * edge_scale;
+				
 				context.builder.bind_pipeline_graphics(pipeline.clone())
 				       .bind_descriptor_sets(PipelineBindPoint::Graphics,
 				                             pipeline.layout().clone(),
 				                             0,
-				                             (self.model_edge_set.clone().unwrap(), mesh_set))
+				                             (gpu.model_edge_set.clone().unwrap(), mesh_set))
 				       .push_constants(pipeline.layout().clone(),
 				                       0,
 				                       Pc {
 					                       model: model_matrix.into(),
-					                       color: sub_mesh.edge_color.into(),
+					                       color: edge_color.into(),
 					                       scale: edge_scale,
+					                       material: id as u32,
 				                       })
 				       .draw_indexed(sub_mesh.range.len() as u32,
 				                     1,
@@ -536,6 +670,219 @@ impl Component for MMDModel {
 }
 
 impl MMDModelState {
+	/// Adds weights of group morphs to their children.
+	fn update_morphs_weights(&mut self, shared: &MMDModelShared) {
+		self.morphs_weights.clear();
+		self.morphs_weights.extend_from_slice(&self.morphs);
+		
+		for (desc, &weight) in shared.morphs.iter().zip(&self.morphs) {
+			if let MorphDesc::Group(children) = desc {
+				if weight.abs() <= f32::EPSILON {
+					continue;
+				}
+				
+				for &(child, ratio) in children {
+					if let Some(child_weight) = self.morphs_weights.get_mut(child) {
+						*child_weight += weight * ratio;
+					}
+				}
+			}
+		}
+	}
+	
+	fn update_bone_morphs(&mut self, shared: &MMDModelShared) {
+		for bone in &mut self.bones {
+			bone.morph_transform = Isometry3::identity();
+		}
+		
+		for (desc, &weight) in shared.morphs.iter().zip(&self.morphs_weights) {
+			if let MorphDesc::Bone(offsets) = desc {
+				if weight.abs() <= f32::EPSILON {
+					continue;
+				}
+				
+				for offset in offsets {
+					let morph_transform = &mut self.bones[offset.bone].morph_transform;
+					morph_transform.translation.vector += offset.translation * weight;
+					morph_transform.rotation = Rot3::from_scaled_axis(offset.rotation.scaled_axis() * weight) * morph_transform.rotation;
+				}
+			}
+		}
+	}
+	
+	fn update_materials(&mut self, shared: &MMDModelShared, sub_meshes: &[SubMesh]) {
+		self.materials.clear();
+		self.materials.extend(sub_meshes.iter().map(|sub_mesh| sub_mesh.material));
+		self.edges.clear();
+		self.edges.extend(sub_meshes.iter().map(|sub_mesh| (sub_mesh.edge_scale, sub_mesh.edge_color)));
+		
+		for (desc, &weight) in shared.morphs.iter().zip(&self.morphs_weights) {
+			if let MorphDesc::Material(offsets) = desc {
+				if weight.abs() <= f32::EPSILON {
+					continue;
+				}
+				
+				for offset in offsets {
+					let targets = match offset.sub_mesh {
+						Some(id) => id..(id + 1).min(self.materials.len()),
+						None => 0..self.materials.len(),
+					};
+					
+					for id in targets {
+						offset.apply(&mut self.materials[id], &mut self.edges[id], weight);
+					}
+				}
+			}
+		}
+	}
+	
+	/// Calculates model space transforms of all bones in PMX transform order,
+	/// applying append transforms and solving IK chains as their bones are reached.
+	fn update_bones_mats(&mut self, shared: &MMDModelShared) {
+		for bone in &mut self.bones {
+			bone.ik_transform = Rot3::identity();
+			bone.append_transform = Isometry3::identity();
+			bone.look_transform = Rot3::identity();
+		}
+		
+		self.bones_mats.clear();
+		self.bones_mats.resize(self.bones.len(), AMat4::identity());
+		
+		let ik_enabled = !debug::get_flag_or_default("DebugIkDisable");
+		
+		for (pos, &id) in shared.transform_order.iter().enumerate() {
+			self.update_bone(id, &shared.default_bones);
+			
+			if let (true, Some(ik)) = (ik_enabled, &shared.default_bones[id].ik) {
+				self.solve_ik(id, ik);
+				
+				// Bones transformed so far can descend from, or append from, the links
+				for &id in &shared.transform_order[..=pos] {
+					self.update_bone(id, &shared.default_bones);
+				}
+			}
+		}
+	}
+	
+	fn update_bone(&mut self, id: usize, descs: &[BoneDesc]) {
+		if let Some(append) = &descs[id].append {
+			self.bones[id].append_transform = self.append_transform(append, descs);
+		}
+		
+		let look_at = self.bones[id].look_at;
+		if look_at.is_some() {
+			self.bones[id].look_transform = Rot3::identity();
+		}
+		
+		self.bones_mats[id] = self.bone_mat(id);
+		
+		// Parents come first in transform order, so they are already aimed when children are
+		if let Some(look_at) = look_at {
+			let target = self.bones_mats[id].inverse_transform_point(&look_at.target);
+			self.bones[id].look_transform = look_at.rotation(target);
+			self.bones_mats[id] = self.bone_mat(id);
+		}
+	}
+	
+	fn append_transform(&self, append: &BoneAppend, descs: &[BoneDesc]) -> Isometry3 {
+		let parent = &self.bones[append.parent];
+		
+		// Chained appends propagate only the inherited part, unless local
+		let source = if !append.local && descs[append.parent].append.is_some() {
+			parent.append_transform
+		} else {
+			parent.pose().isometry
+		};
+		
+		let translation = if append.translation {
+			Translation3::from(source.translation.vector * append.ratio)
+		} else {
+			Translation3::identity()
+		};
+		
+		let rotation = if append.rotation {
+			Rot3::from_scaled_axis((parent.ik_transform * source.rotation).scaled_axis() * append.ratio)
+		} else {
+			Rot3::identity()
+		};
+		
+		Isometry3::from_parts(translation, rotation)
+	}
+	
+	/// Requires transform of bone's parent to be up to date.
+	fn bone_mat(&self, id: usize) -> AMat4 {
+		let bone = &self.bones[id];
+		let pose = bone.pose();
+		let anim_transform = Similarity3::from_parts(bone.append_transform.translation * pose.isometry.translation,
+		                                             bone.append_transform.rotation * pose.isometry.rotation,
+		                                             pose.scaling())
+			* bone.ik_transform
+			* bone.look_transform;
+		
+		if let Some(transform) = bone.override_transform {
+			transform.to_superset()
+		} else if let Some(parent) = bone.parent {
+			&self.bones_mats[parent] * &bone.local_transform * &anim_transform
+		} else {
+			let transform: AMat4 = anim_transform.to_superset();
+			&bone.local_transform * &transform
+		}
+	}
+	
+	/// Cyclic Coordinate Descent: each link in turn is rotated to point the target at the IK bone.
+	fn solve_ik(&mut self, ik_bone: usize, ik: &BoneIk) {
+		let goal = self.bones_mats[ik_bone].transform_point(&Point3::origin());
+		
+		for _ in 0..ik.iterations {
+			for link in &ik.links {
+				if self.bones[link.bone].override_transform.is_some() {
+					continue;
+				}
+				
+				let effector = self.bones_mats[ik.target].transform_point(&Point3::origin());
+				let inv_link = self.bones_mats[link.bone].inverse();
+				let to_effector = (inv_link * effector).coords.normalize();
+				let to_goal = (inv_link * goal).coords.normalize();
+				
+				let angle = to_effector.dot(&to_goal).clamp(-1.0, 1.0).acos().min(ik.limit_angle);
+				let axis = match Unit::try_new(to_effector.cross(&to_goal), 1.0e-6) {
+					Some(axis) => axis,
+					None => continue,
+				};
+				
+				let bone = &mut self.bones[link.bone];
+				bone.ik_transform = bone.ik_transform * Rot3::from_axis_angle(&axis, angle);
+				
+				if let Some((min, max)) = link.limits {
+					let anim_rotation = bone.pose().isometry.rotation;
+					let (x, y, z) = (anim_rotation * bone.ik_transform).euler_angles();
+					let limited = Rot3::from_euler_angles(x.clamp(min.x, max.x), y.clamp(min.y, max.y), z.clamp(min.z, max.z));
+					bone.ik_transform = anim_rotation.inverse() * limited;
+				}
+				
+				// Only bones between the link and the target affect the next link
+				let mut chain: SmallVec<[usize; 8]> = self.bone_ancestors(ik.target)
+				                                           .take_while(|&id| id != link.bone)
+				                                           .collect();
+				chain.push(link.bone);
+				
+				for &id in chain.iter().rev() {
+					self.bones_mats[id] = self.bone_mat(id);
+				}
+			}
+			
+			let effector = self.bones_mats[ik.target].transform_point(&Point3::origin());
+			if (effector - goal).magnitude_squared() < 1.0e-8 {
+				break;
+			}
+		}
+	}
+	
+	/// Transform from bone's frame, with origin at the bone, to model space. Available after the model is rendered.
+	pub fn bone_transform(&self, bone_id: usize) -> Option<AMat4> {
+		self.bones_mats.get(bone_id).copied()
+	}
+	
 	fn bone_ancestors(&self, mut bone_id: usize) -> impl Iterator<Item = usize> + '_ {
 		Some(bone_id).into_iter()
 		             .chain(std::iter::from_fn(move || {
@@ -1,18 +1,23 @@
+use std::collections::BTreeMap;
 use anyhow::Result;
 use thiserror::Error;
-use mmd::pmx::bone::{Bone, Connection};
 use mmd::pmx::joint::{Joint, JointType};
 use mmd::pmx::rigid_body::{PhysicsMode, RigidBody, ShapeType};
 use serde_derive::{Deserialize, Serialize};
 
 use crate::math::{Vec3, PI};
-use super::asset::{MMDIndexConfig, JointEx};
+use super::asset::{FromMMD, MMDIndexConfig, JointEx, MMD_UNIT_SIZE};
+use super::shared::{BoneConnection, BoneDesc};
 
 
 #[derive(Serialize, Deserialize, Default, Clone, Debug)]
 pub struct MMDConfig {
 	#[serde(skip_serializing_if = "Vec::is_empty")] #[serde(default)] pub rigid_bodies: Vec<MMDRigidBodyOverride>,
 	#[serde(skip_serializing_if = "Vec::is_empty")] #[serde(default)] pub joints: Vec<MMDJointOverride>,
+	/// Bone name patterns by role, roles are defined by components animating the model.
+	#[serde(skip_serializing_if = "BTreeMap::is_empty")] #[serde(default)] pub bones: BTreeMap<String, String>,
+	/// Morph name patterns by role.
+	#[serde(skip_serializing_if = "BTreeMap::is_empty")] #[serde(default)] pub morphs: BTreeMap<String, String>,
 }
 
 #[derive(Serialize, Deserialize, Default, Clone, Debug)]
@@ -183,34 +188,34 @@ impl MMDJointOverride {
 		if let Some(value) = self.body_part       { joint.body_part = Some(value); }
 	}
 	
-	pub fn normalize(&mut self, bones: &[Bone<MMDIndexConfig>], rigid_bodies: &[RigidBody<MMDIndexConfig>]) -> Result<(), MMDOverrideError> {
+	/// Bones are already converted to model space, while overrides stay in MMD's coordinates.
+	pub fn normalize(&mut self, bones: &[BoneDesc], rigid_bodies: &[RigidBody<MMDIndexConfig>]) -> Result<(), MMDOverrideError> {
 		if let Some(bone_id) = self.using_bone {
 			let bone = bones.get(bone_id)
 			                .ok_or(MMDOverrideError::BoneNotFound(bone_id))?;
 			
 			if self.position.is_none() {
-				self.position = Some(bone.position);
+				self.position = Some(bone.model_pos.from_mmd_normal() / MMD_UNIT_SIZE);
 			}
 			
 			if self.rotation.is_none() {
-				let offset = match &bone.connection {
-					Connection::Index(con_id) => {
-						if *con_id < 0 { return Err(MMDOverrideError::InvalidOffset(bone_id)) }
+				let offset = match bone.connection {
+					BoneConnection::None => return Err(MMDOverrideError::InvalidOffset(bone_id)),
+					BoneConnection::Bone(con_id) => {
+						let position = bones.get(con_id)
+						                    .ok_or(MMDOverrideError::BoneNotFound(con_id))?
+							.model_pos;
 						
-						let position = bones.get(*con_id as usize)
-						                    .ok_or(MMDOverrideError::BoneNotFound(*con_id as usize))?
-							.position;
-						
-						position - bone.position
+						position - bone.model_pos
 					},
-					Connection::Position(offset) => {
-						if offset.magnitude_squared() < f32::EPSILON { return Err(MMDOverrideError::InvalidOffset(bone_id)); }
+					BoneConnection::Offset(offset) => {
+						if offset.magnitude_squared() < f32::EPSILON * MMD_UNIT_SIZE * MMD_UNIT_SIZE { return Err(MMDOverrideError::InvalidOffset(bone_id)); }
 						
-						*offset
+						offset
 					},
 				};
 				
-				let offset = -offset.normalize();
+				let offset = -offset.from_mmd_normal().normalize();
 				let pitch = offset.y.asin();
 				let yaw = if offset.x.abs() + offset.z.abs() < f32::EPSILON { PI } else { f32::atan2(-offset.x, -offset.z) };
 				
@@ -227,15 +232,15 @@ impl MMDJointOverride {
 			
 			let mut parent_id = bone.parent;
 			'outer:
-			while parent_id >= 0 {
+			while let Some(parent) = parent_id {
 				for (rb_id, rb) in rigid_bodies.iter().enumerate() {
-					if rb.bone_index == parent_id {
+					if rb.bone_index == parent as i32 {
 						self.rigid_body_b = Some(rb_id as i32);
 						break 'outer;
 					}
 				}
 				
-				parent_id = bones[parent_id as usize].parent;
+				parent_id = bones[parent].parent;
 			}
 			if self.rigid_body_b.is_none() { return Err(MMDOverrideError::AncestorsNoRigidBodies(bone_id)); }
 		}
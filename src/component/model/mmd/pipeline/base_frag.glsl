@@ -15,13 +15,23 @@ layout(set = 0, binding = 0) uniform Commons {
 	float ambient;
 } commons;
 
-layout(set = 1, binding = 0) uniform Material {
+struct Material {
 	vec4 color;
 	vec3 specular;
 	float specularity;
 	vec3 ambient;
 	uint sphere_mode;
-} material;
+	vec4 texture_mul;
+	vec4 texture_add;
+	vec4 sphere_mul;
+	vec4 sphere_add;
+	vec4 toon_mul;
+	vec4 toon_add;
+};
+
+layout(set = 0, binding = 3) readonly buffer Materials {
+	Material materials[];
+} materials;
 
 layout(set = 1, binding = 1) uniform sampler2D tex;
 layout(set = 1, binding = 2) uniform sampler2D toon;
@@ -31,11 +41,13 @@ layout(push_constant) uniform Pc {
 	mat4 model;
 	vec4 _pad_color;
 	float _pad_scale;
+	uint material;
 } pc;
 
 layout(constant_id = 0) const bool transparent_pass = false;
 
 void main() {
+	Material material = materials.materials[pc.material];
 	vec3 light_direction = commons.light_direction[gl_ViewIndex].xyz;
 	float lambert = dot(-f_normal, light_direction);
 	
@@ -46,19 +58,22 @@ void main() {
 	
 	vec2 sphere_uv = f_normal.xy * 0.5 + 0.5;
 	
-	o_color = texture(tex, f_uv);
+	vec4 sphere_color = texture(sphere, sphere_uv) * material.sphere_mul + material.sphere_add;
+	vec4 toon_color = texture(toon, vec2(0.5, 0.5 - lambert * 0.5)) * material.toon_mul + material.toon_add;
+	
+	o_color = texture(tex, f_uv) * material.texture_mul + material.texture_add;
 	
 	if(material.sphere_mode == 1) {
-		o_color.rgb *= texture(sphere, sphere_uv).rgb;
+		o_color.rgb *= sphere_color.rgb;
 	} else if(material.sphere_mode == 2) {
-		o_color.rgb += texture(sphere, sphere_uv).rgb;
+		o_color.rgb += sphere_color.rgb;
 	} else if(material.sphere_mode == 3) {
 		// TODO: Sub textures
 	}
 	
 	o_color *= clamp(vec4(material.ambient, 0.0) + material.color, 0.0, 1.0);
 	o_color.rgb += spec_light;
-	o_color.rgb *= texture(toon, vec2(0.5, 0.5 - lambert * 0.5)).rgb;
+	o_color.rgb *= toon_color.rgb;
 	
 	if(o_color.a < 1.0 && !transparent_pass) {
 		discard;
@@ -35,6 +35,7 @@ layout(push_constant) uniform Pc {
 	mat4 model;
 	vec4 _pad_color;
 	float _pad_scale;
+	uint material;
 } pc;
 
 void main() {
@@ -44,7 +45,9 @@ void main() {
 	
 	VertDesc vert = VertDesc(pos, normal);
 	
-	vec3 morph_pos = vec3(offsets.vecs[gl_VertexIndex].xyz) / 1000000.0;
+	// Each vertex has position offset followed by UV offset
+	vec3 morph_pos = vec3(offsets.vecs[gl_VertexIndex * 2].xyz) / 1000000.0;
+	vec2 morph_uv = vec2(offsets.vecs[gl_VertexIndex * 2 + 1].xy) / 1000000.0;
 	vert.pos += morph_pos;
 	
 	if(dot(sdef_c, sdef_c) > 0.01) {
@@ -66,6 +69,6 @@ void main() {
 	
 	gl_Position = mvp * vec4(vert.pos, 1.0);
 	f_pos = vert.pos;
-	f_uv = uv;
+	f_uv = uv + morph_uv;
 	f_normal = normalize(normal_matrix * vert.norm);
 }
@@ -27,7 +27,10 @@ void main() {
 	ivec3 offset = ivec3(morphsDesc.offsets[mmid * pc.morphsMaxSize + oid].xyz * scale);
 	int vertex = morphsDesc.offsets[mmid * pc.morphsMaxSize + oid].w;
 	
-	atomicAdd(outBuf.offsets[vertex].x, offset.x);
-	atomicAdd(outBuf.offsets[vertex].y, offset.y);
-	atomicAdd(outBuf.offsets[vertex].z, offset.z);
+	// Negative indices are UV offsets, stored right after position offset of the vertex
+	uint target = vertex < 0 ? uint(-vertex - 1) * 2 + 1 : uint(vertex) * 2;
+	
+	atomicAdd(outBuf.offsets[target].x, offset.x);
+	atomicAdd(outBuf.offsets[target].y, offset.y);
+	atomicAdd(outBuf.offsets[target].z, offset.z);
 }
@@ -11,6 +11,7 @@ layout(push_constant) uniform Pc {
 	mat4 model;
 	vec4 color;
 	float scale;
+	uint material;
 } pc;
 
 void main() {
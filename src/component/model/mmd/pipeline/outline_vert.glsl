@@ -33,6 +33,7 @@ layout(push_constant) uniform Pc {
 	mat4 model;
 	vec4 color;
 	float scale;
+	uint material;
 } pc;
 
 void main() {
@@ -42,7 +43,9 @@ void main() {
 	
 	VertDesc vert = VertDesc(pos, normal);
 	
-	vec3 morph_pos = vec3(offsets.vecs[gl_VertexIndex].xyz) / 1000000.0;
+	// Each vertex has position offset followed by UV offset
+	vec3 morph_pos = vec3(offsets.vecs[gl_VertexIndex * 2].xyz) / 1000000.0;
+	vec2 morph_uv = vec2(offsets.vecs[gl_VertexIndex * 2 + 1].xy) / 1000000.0;
 	vert.pos += morph_pos;
 	
 	if(dot(sdef_c, sdef_c) > 0.01) {
@@ -67,5 +70,5 @@ void main() {
 	view_pos += vec4(view_normal * pc.scale * edge_scale * length(vec3(view_pos)), 0.0);
 	
 	gl_Position = commons.projection[gl_ViewIndex] * view_pos;
-	f_uv = uv;
+	f_uv = uv + morph_uv;
 }
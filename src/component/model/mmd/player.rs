@@ -0,0 +1,215 @@
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Result;
+use egui::Ui;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::application::{Application, Entity};
+use crate::math::Similarity3;
+use crate::renderer::Renderer;
+use crate::utils::ExUi;
+use super::super::super::{Component, ComponentBase, ComponentInner, ComponentLoad, ComponentRef};
+use super::MMDModel;
+use super::vmd::{VmdAsset, VmdBindings, VmdMotion, VMD_FPS};
+
+
+#[derive(Deserialize, Serialize)]
+struct VmdPlayerParams {
+	asset: VmdAsset,
+	#[serde(default)] looping: bool,
+	#[serde(default = "default_speed")] speed: f32,
+	#[serde(default)] paused: bool,
+}
+
+fn default_speed() -> f32 { 1.0 }
+
+pub struct VmdPlayerState {
+	/// Playback position in seconds.
+	pub time: f32,
+	pub playing: bool,
+	pub looping: bool,
+	pub speed: f32,
+}
+
+/// Plays VMD motion on `MMDModel` of the same entity. Tracks are matched with bones and morphs by name.
+#[derive(ComponentBase)]
+pub struct VmdPlayer {
+	#[inner] inner: ComponentInner,
+	asset: VmdAsset,
+	motion: Arc<VmdMotion>,
+	model: ComponentRef<MMDModel>,
+	pub state: RefCell<VmdPlayerState>,
+	bindings: RefCell<VmdBindings>,
+}
+
+impl VmdPlayer {
+	pub fn new(asset: VmdAsset, renderer: &mut Renderer) -> Result<Self> {
+		let motion = renderer.load(asset.clone())?;
+		
+		Ok(VmdPlayer {
+			inner: ComponentInner::new_norender(),
+			asset,
+			motion,
+			model: ComponentRef::null(),
+			state: RefCell::new(VmdPlayerState {
+				time: 0.0,
+				playing: true,
+				looping: false,
+				speed: 1.0,
+			}),
+			bindings: RefCell::new(VmdBindings::default()),
+		})
+	}
+	
+	pub fn looping(self, looping: bool) -> Self {
+		self.state.borrow_mut().looping = looping;
+		self
+	}
+	
+	pub fn speed(self, speed: f32) -> Self {
+		self.state.borrow_mut().speed = speed;
+		self
+	}
+	
+	pub fn paused(self) -> Self {
+		self.state.borrow_mut().playing = false;
+		self
+	}
+	
+	pub fn play(&self) {
+		let mut state = self.state.borrow_mut();
+		
+		if !state.looping && state.time >= self.duration() {
+			state.time = 0.0;
+		}
+		
+		state.playing = true;
+	}
+	
+	pub fn pause(&self) {
+		self.state.borrow_mut().playing = false;
+	}
+	
+	pub fn seek(&self, time: f32) {
+		self.state.borrow_mut().time = time.clamp(0.0, self.duration());
+	}
+	
+	/// Length of the motion in seconds.
+	pub fn duration(&self) -> f32 {
+		self.motion.duration()
+	}
+	
+	fn bind(&self, model: &MMDModel) {
+		let bindings = self.motion.bind(model);
+		
+		if bindings.bones.len() < self.motion.bones.len() || bindings.morphs.len() < self.motion.morphs.len() {
+			dprintln!("{}: {} of {} bone tracks and {} of {} morph tracks matched",
+			          self.asset,
+			          bindings.bones.len(), self.motion.bones.len(),
+			          bindings.morphs.len(), self.motion.morphs.len());
+		}
+		
+		self.model.set(model.as_cref());
+		*self.bindings.borrow_mut() = bindings;
+	}
+}
+
+impl ComponentLoad for VmdPlayer {
+	fn load(params: toml::Value, application: &Application) -> Result<Option<Self>> {
+		if application.headless() {
+			return Ok(None);
+		}
+		
+		let params: VmdPlayerParams = params.try_into()?;
+		let mut player = VmdPlayer::new(params.asset, &mut *application.renderer()?)?
+			.looping(params.looping)
+			.speed(params.speed);
+		
+		if params.paused {
+			player = player.paused();
+		}
+		
+		Ok(Some(player))
+	}
+}
+
+impl Component for VmdPlayer {
+	fn save(&self, _entity: &Entity, _application: &Application) -> Result<Option<toml::Value>> {
+		let state = self.state.borrow();
+		
+		Ok(Some(toml::Value::try_from(VmdPlayerParams {
+			asset: self.asset.clone(),
+			looping: state.looping,
+			speed: state.speed,
+			paused: !state.playing,
+		})?))
+	}
+	
+	fn tick(&self, entity: &Entity, application: &Application, delta_time: Duration) -> Result<()> {
+		// Model can be added after the player, eg. by Miku component
+		if self.model.get(application).is_none() {
+			match entity.find_component_by_type::<MMDModel>() {
+				Some(model) => self.bind(model),
+				None => return Ok(()),
+			}
+		}
+		
+		let model = match self.model.get(application) {
+			Some(model) => model,
+			None => return Ok(()),
+		};
+		
+		let state = &mut *self.state.borrow_mut();
+		let duration = self.duration();
+		
+		if state.playing {
+			state.time += delta_time.as_secs_f32() * state.speed;
+			
+			if state.time < 0.0 || state.time > duration {
+				if state.looping && duration > 0.0 {
+					state.time = state.time.rem_euclid(duration);
+				} else {
+					state.time = state.time.clamp(0.0, duration);
+					state.playing = false;
+				}
+			}
+		}
+		
+		let frame = state.time * VMD_FPS;
+		let bindings = self.bindings.borrow();
+		let model_state = &mut *model.state_mut();
+		
+		for &(track_id, bone_id) in &bindings.bones {
+			let (translation, rotation) = self.motion.bones[track_id].sample(frame);
+			model_state.bones[bone_id].anim_transform = Similarity3::from_parts(translation.into(), rotation, 1.0);
+		}
+		
+		for &(track_id, morph_id) in &bindings.morphs {
+			model_state.morphs[morph_id] = self.motion.morphs[track_id].sample(frame);
+		}
+		
+		Ok(())
+	}
+	
+	fn on_inspect(&self, _entity: &Entity, ui: &mut Ui, application: &Application) {
+		let duration = self.duration();
+		let mut state = self.state.borrow_mut();
+		let bindings = self.bindings.borrow();
+		
+		ui.inspect_row("Motion", format!("{}", self.asset), ());
+		ui.inspect_row("Model", &self.model, application);
+		ui.inspect_row("Bone Tracks", format!("{} / {}", bindings.bones.len(), self.motion.bones.len()), ());
+		ui.inspect_row("Morph Tracks", format!("{} / {}", bindings.morphs.len(), self.motion.morphs.len()), ());
+		
+		ui.label("Playback");
+		if ui.button(if state.playing { "Pause" } else { "Play" }).clicked() {
+			state.playing = !state.playing;
+		}
+		ui.end_row();
+		
+		ui.inspect_row("Time", &mut state.time, (0.01, 0.0..=duration));
+		ui.inspect_row("Speed", &mut state.speed, (0.01, -4.0..=4.0));
+		ui.inspect_row("Loop", &mut state.looping, ());
+	}
+}
@@ -0,0 +1,616 @@
+use std::fmt::{Display, Formatter};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::Result;
+use mmd::pmx::joint::{Joint, JointType};
+use mmd::pmx::rigid_body::{PhysicsMode, RigidBody, ShapeType};
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::math::{Color, Vec2, Vec3, Vec4, PI};
+use crate::renderer::Renderer;
+use crate::renderer::assets_manager::{AssetKey, AssetsManager};
+use super::Vertex;
+use super::asset::{add_overridden_physics, display_name, find_image_format, ik_limits_from_mmd, load_overrides, FromMMD, MMDIndexConfig};
+use super::overrides::MMDConfig;
+use super::shared::{MMDModelShared, MMDModelSharedBuilder, BoneDesc, BoneConnection, BoneAppend, BoneIk, IkLink, SubMeshDesc};
+use super::toon::{shared_toon, shared_toon_id, shared_toon_name, SHARED_TOON_COUNT};
+use super::vmd::{decode_name, read_bytes, read_f32, read_u32, read_vec3};
+
+
+const PMD_MAGIC: &[u8] = b"Pmd";
+const NO_BONE: u16 = 0xFFFF;
+/// MikuMikuDance limits rotation of IK links only for knees, recognized by name.
+const KNEE_NAME: &str = "ひざ";
+
+#[derive(Clone, Hash, Debug, Deserialize, Serialize)]
+pub struct PmdAsset {
+	#[serde(rename = "pmd")]
+	path: PathBuf,
+	overrides: bool,
+}
+
+impl PmdAsset {
+	pub fn at(model_path: impl AsRef<Path>) -> Self {
+		PmdAsset {
+			path: model_path.as_ref().to_path_buf(),
+			overrides: true,
+		}
+	}
+	
+	pub fn no_overrides(self) -> Self {
+		Self {
+			overrides: false,
+			..self
+		}
+	}
+	
+	/// Builds the model without uploading it to the GPU, nor caching it, for headless mode.
+	pub fn load_headless(&self) -> Result<Arc<MMDModelShared>> {
+		let overrides = load_overrides(&self.path, self.overrides, None)?;
+		
+		Ok(Arc::new(self.read(overrides)?.build_headless()))
+	}
+	
+	fn read(&self, overrides: Option<MMDConfig>) -> Result<MMDModelSharedBuilder<u16>> {
+		let mut root = PathBuf::from(&self.path);
+		root.pop();
+		
+		let pmd = PmdModel::read(AssetsManager::find_asset(&self.path)?)?;
+		
+		let vertices = pmd.vertices.iter()
+		                           .map(Into::into)
+		                           .collect::<Vec<Vertex>>();
+		
+		let mut model = MMDModelShared::new(vertices, pmd.indices);
+		let mut textures = vec![];
+		let mut last_index: u32 = 0;
+		
+		for material in &pmd.materials {
+			let mut texture = None;
+			let mut sphere_map = None;
+			let mut sphere_mode = 0;
+			
+			// Texture and sphere map share the field, separated by an asterisk
+			for file in material.texture.split('*').filter(|file| !file.is_empty()) {
+				let id = add_texture(&mut model, &mut textures, &root, file)?;
+				
+				match file.rsplit('.').next().unwrap_or_default().to_ascii_lowercase().as_str() {
+					"sph" => { sphere_map = Some(id); sphere_mode = 1; },
+					"spa" => { sphere_map = Some(id); sphere_mode = 2; },
+					_ => texture = Some(id),
+				}
+			}
+			
+			// Shared toons are usually not shipped with the model
+			let toon = match pmd.toons.get(material.toon as usize) {
+				Some(file) if AssetsManager::find_asset_path(root.join(file)).is_ok() => Some(add_texture(&mut model, &mut textures, &root, file)?),
+				Some(file) => shared_toon_id(file).map(|id| add_shared_toon(&mut model, &mut textures, id)).transpose()?,
+				None => None,
+			};
+			
+			let has_alpha = texture.map_or(true, |id| textures[id].1);
+			
+			model.add_sub_mesh(SubMeshDesc {
+				range: last_index .. last_index + material.surface_count,
+				texture,
+				toon,
+				sphere_map,
+				color: material.diffuse,
+				specular: material.specular,
+				specularity: material.specularity,
+				ambient: material.ambient,
+				sphere_mode,
+				// MikuMikuDance draws both sides of translucent PMD materials
+				no_cull: material.diffuse.w < 1.0,
+				opaque: !has_alpha,
+				edge: material.edge.then_some((1.0, vector!(0.0, 0.0, 0.0, 1.0))),
+			});
+			
+			last_index += material.surface_count;
+		}
+		
+		for (id, def) in pmd.bones.iter().enumerate() {
+			let name = display_name(&def.universal_name, &def.local_name);
+			let parent = if def.parent == NO_BONE { None } else { Some(def.parent as usize) };
+			
+			let model_pos = def.position.from_mmd();
+			let local_pos = match parent {
+				Some(parent) => model_pos - pmd.bones[parent].position.from_mmd(),
+				None => model_pos,
+			};
+			
+			let (rotatable, movable) = match def.kind {
+				BoneKind::Rotate | BoneKind::Twist => (true, false),
+				BoneKind::RotateMove | BoneKind::Ik => (true, true),
+				_ => (false, false),
+			};
+			
+			let color = if def.kind == BoneKind::Ik {
+				Color::GREEN
+			} else if rotatable && movable {
+				Color::MAGENTA
+			} else if rotatable {
+				Color::BLUE.lightness(1.5)
+			} else {
+				Color::D_WHITE.lightness(0.5)
+			};
+			
+			let display = !matches!(def.kind, BoneKind::IkTarget | BoneKind::Invisible);
+			
+			// Rotation linked bones store the linked bone in place of the tail
+			let connection = match link(def.tail) {
+				Some(tail) if def.kind != BoneKind::RotationLink => BoneConnection::Bone(tail),
+				_ => BoneConnection::None,
+			};
+			
+			let mut bone = BoneDesc::new(name,
+			                             parent,
+			                             color,
+			                             model_pos,
+			                             local_pos,
+			                             display,
+			                             connection)
+			                       .local_name(&def.local_name)
+			                       .operable(rotatable, movable);
+			
+			match (def.kind, link(def.ik_parent), link(def.tail)) {
+				(BoneKind::RotationAppend, Some(parent), _) => {
+					bone = bone.append(BoneAppend {
+						parent,
+						ratio: 1.0,
+						rotation: true,
+						translation: false,
+						local: false,
+					});
+				},
+				// Ratio is stored in place of the IK bone, in percents
+				(BoneKind::RotationLink, _, Some(parent)) => {
+					bone = bone.append(BoneAppend {
+						parent,
+						ratio: def.ik_parent as f32 / 100.0,
+						rotation: true,
+						translation: false,
+						local: false,
+					});
+				},
+				_ => {},
+			}
+			
+			if let Some(ik) = pmd.iks.iter().find(|ik| ik.bone as usize == id) {
+				let links = ik.links.iter()
+				                    .map(|&link| IkLink {
+					                    bone: link as usize,
+					                    limits: pmd.bones.get(link as usize)
+					                                     .filter(|bone| bone.local_name.contains(KNEE_NAME))
+					                                     .map(|_| ik_limits_from_mmd((vector!(-PI, 0.0, 0.0), vector!(-0.5_f32.to_radians(), 0.0, 0.0)))),
+				                    })
+				                    .collect();
+				
+				bone = bone.ik(BoneIk {
+					target: ik.target as usize,
+					iterations: ik.iterations as u32,
+					// PMD stores the limit in units of 4 radians
+					limit_angle: ik.limit_angle * 4.0,
+					links,
+				});
+			}
+			
+			model.add_bone(bone);
+		}
+		
+		// Offsets of skin morphs point at vertices listed by the base morph, which holds their rest positions
+		let base = pmd.morphs.iter()
+		                     .find(|morph| morph.kind == 0)
+		                     .map_or(&[][..], |morph| &morph.offsets[..]);
+		
+		for morph in pmd.morphs.iter().filter(|morph| morph.kind != 0) {
+			model.add_morph(&morph.local_name,
+			                morph.offsets.iter()
+			                             .filter_map(|&(id, offset)| base.get(id as usize).map(|&(vertex, _)| (vertex as u16, offset.from_mmd())))
+			                             .collect());
+		}
+		
+		let joints = pmd.joints.into_iter()
+		                       .map(Into::into)
+		                       .collect();
+		
+		add_overridden_physics(&mut model, &self.path, overrides, pmd.rigid_bodies, joints)?;
+		
+		Ok(model)
+	}
+}
+
+impl AssetKey for PmdAsset {
+	type Asset = Arc<MMDModelShared>;
+	
+	fn load(&self, assets_manager: &mut AssetsManager, renderer: &mut Renderer) -> Result<Self::Asset> {
+		let overrides = load_overrides(&self.path, self.overrides, Some((assets_manager, &mut *renderer)))?;
+		
+		Ok(Arc::new(self.read(overrides)?.build(renderer)?))
+	}
+}
+
+impl Display for PmdAsset {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "PMD model {}", self.path.to_string_lossy())
+	}
+}
+
+/// Textures are referenced by file name, each one is loaded once. Returns texture id.
+fn add_texture(model: &mut MMDModelSharedBuilder<u16>, textures: &mut Vec<(String, bool)>, root: &Path, file: &str) -> Result<usize> {
+	if let Some(id) = textures.iter().position(|(loaded, _)| loaded == file) {
+		return Ok(id);
+	}
+	
+	let path = root.join(file);
+	let format = find_image_format(AssetsManager::find_asset_path(&path)?)?;
+	let texture = image::load(AssetsManager::find_asset(&path)?, format)?;
+	
+	textures.push((file.to_string(), texture.color().has_alpha()));
+	model.add_texture(texture);
+	
+	Ok(textures.len() - 1)
+}
+
+/// Shared toons are listed among textures under their own names, as the model doesn't have such files.
+fn add_shared_toon(model: &mut MMDModelSharedBuilder<u16>, textures: &mut Vec<(String, bool)>, id: usize) -> Result<usize> {
+	let name = shared_toon_name(id);
+	
+	if let Some(id) = textures.iter().position(|(loaded, _)| *loaded == name) {
+		return Ok(id);
+	}
+	
+	model.add_texture(shared_toon(id)?);
+	textures.push((name, false));
+	
+	Ok(textures.len() - 1)
+}
+
+/// Tail and IK bone indices use 0 for none, as the root can't be either.
+fn link(index: u16) -> Option<usize> {
+	if index == 0 || index == NO_BONE { None } else { Some(index as usize) }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BoneKind {
+	Rotate,
+	RotateMove,
+	Ik,
+	Unknown,
+	IkLink,
+	RotationAppend,
+	IkTarget,
+	Invisible,
+	Twist,
+	RotationLink,
+}
+
+impl From<u8> for BoneKind {
+	fn from(kind: u8) -> Self {
+		match kind {
+			0 => BoneKind::Rotate,
+			1 => BoneKind::RotateMove,
+			2 => BoneKind::Ik,
+			4 => BoneKind::IkLink,
+			5 => BoneKind::RotationAppend,
+			6 => BoneKind::IkTarget,
+			7 => BoneKind::Invisible,
+			8 => BoneKind::Twist,
+			9 => BoneKind::RotationLink,
+			_ => BoneKind::Unknown,
+		}
+	}
+}
+
+/// Contents of a PMD file. Rigid bodies and joints are converted to PMX ones right away, so `model.toml` overrides apply to them.
+struct PmdModel {
+	vertices: Vec<PmdVertex>,
+	indices: Vec<u16>,
+	materials: Vec<PmdMaterial>,
+	bones: Vec<PmdBone>,
+	iks: Vec<PmdIk>,
+	morphs: Vec<PmdMorph>,
+	toons: Vec<String>,
+	rigid_bodies: Vec<RigidBody<MMDIndexConfig>>,
+	joints: Vec<Joint<MMDIndexConfig>>,
+}
+
+struct PmdVertex {
+	position: Vec3,
+	normal: Vec3,
+	uv: Vec2,
+	bones: [u16; 2],
+	/// Weight of the first bone.
+	weight: f32,
+	edge: bool,
+}
+
+struct PmdMaterial {
+	diffuse: Vec4,
+	specularity: f32,
+	specular: Vec3,
+	ambient: Vec3,
+	toon: u8,
+	edge: bool,
+	surface_count: u32,
+	texture: String,
+}
+
+struct PmdBone {
+	local_name: String,
+	universal_name: String,
+	parent: u16,
+	tail: u16,
+	kind: BoneKind,
+	ik_parent: u16,
+	position: Vec3,
+}
+
+struct PmdIk {
+	bone: u16,
+	target: u16,
+	iterations: u16,
+	limit_angle: f32,
+	/// Ordered from the target towards the root.
+	links: Vec<u16>,
+}
+
+struct PmdMorph {
+	local_name: String,
+	/// 0 for the base morph.
+	kind: u8,
+	offsets: Vec<(u32, Vec3)>,
+}
+
+impl PmdModel {
+	fn read(mut reader: impl Read) -> Result<Self> {
+		// Sections after bone display groups were added in later versions of the format and may be missing
+		let mut bytes = vec![];
+		reader.read_to_end(&mut bytes)?;
+		let reader = &mut &bytes[..];
+		
+		if &read_bytes::<3>(reader)?[..] != PMD_MAGIC {
+			return Err(PmdInvalidHeaderError.into());
+		}
+		
+		// Version, model name and comment
+		read_f32(reader)?;
+		read_bytes::<20>(reader)?;
+		read_bytes::<256>(reader)?;
+		
+		let mut vertices = vec![];
+		
+		for _ in 0..read_u32(reader)? {
+			vertices.push(PmdVertex {
+				position: read_vec3(reader)?,
+				normal: read_vec3(reader)?,
+				uv: vector!(read_f32(reader)?, read_f32(reader)?),
+				bones: [read_u16(reader)?, read_u16(reader)?],
+				weight: read_u8(reader)? as f32 / 100.0,
+				edge: read_u8(reader)? == 0,
+			});
+		}
+		
+		let mut indices = vec![];
+		
+		for _ in 0..read_u32(reader)? {
+			indices.push(read_u16(reader)?);
+		}
+		
+		let mut materials = vec![];
+		
+		for _ in 0..read_u32(reader)? {
+			materials.push(PmdMaterial {
+				diffuse: vector!(read_f32(reader)?, read_f32(reader)?, read_f32(reader)?, read_f32(reader)?),
+				specularity: read_f32(reader)?,
+				specular: read_vec3(reader)?,
+				ambient: read_vec3(reader)?,
+				toon: read_u8(reader)?,
+				edge: read_u8(reader)? != 0,
+				surface_count: read_u32(reader)?,
+				texture: decode_name(&read_bytes::<20>(reader)?),
+			});
+		}
+		
+		let mut bones = vec![];
+		
+		for _ in 0..read_u16(reader)? {
+			bones.push(PmdBone {
+				local_name: decode_name(&read_bytes::<20>(reader)?),
+				universal_name: String::new(),
+				parent: read_u16(reader)?,
+				tail: read_u16(reader)?,
+				kind: read_u8(reader)?.into(),
+				ik_parent: read_u16(reader)?,
+				position: read_vec3(reader)?,
+			});
+		}
+		
+		let mut iks = vec![];
+		
+		for _ in 0..read_u16(reader)? {
+			let bone = read_u16(reader)?;
+			let target = read_u16(reader)?;
+			let links_count = read_u8(reader)?;
+			let iterations = read_u16(reader)?;
+			let limit_angle = read_f32(reader)?;
+			let links = (0..links_count).map(|_| read_u16(reader))
+			                            .collect::<std::io::Result<Vec<_>>>()?;
+			
+			iks.push(PmdIk { bone, target, iterations, limit_angle, links });
+		}
+		
+		let mut morphs = vec![];
+		
+		for _ in 0..read_u16(reader)? {
+			let local_name = decode_name(&read_bytes::<20>(reader)?);
+			let offsets_count = read_u32(reader)?;
+			let kind = read_u8(reader)?;
+			let offsets = (0..offsets_count).map(|_| Ok((read_u32(reader)?, read_vec3(reader)?)))
+			                                .collect::<std::io::Result<Vec<_>>>()?;
+			
+			morphs.push(PmdMorph { local_name, kind, offsets });
+		}
+		
+		// Morph display list, bone group names and bones in groups, used only by the editor
+		for _ in 0..read_u8(reader)? {
+			read_u16(reader)?;
+		}
+		
+		let bone_groups = read_u8(reader)?;
+		for _ in 0..bone_groups {
+			read_bytes::<50>(reader)?;
+		}
+		
+		for _ in 0..read_u32(reader)? {
+			read_bytes::<3>(reader)?;
+		}
+		
+		if !reader.is_empty() && read_u8(reader)? != 0 {
+			// English model name and comment
+			read_bytes::<20>(reader)?;
+			read_bytes::<256>(reader)?;
+			
+			for bone in &mut bones {
+				bone.universal_name = decode_name(&read_bytes::<20>(reader)?);
+			}
+			
+			// Base morph has no English name
+			for _ in 1..morphs.len() {
+				read_bytes::<20>(reader)?;
+			}
+			
+			for _ in 0..bone_groups {
+				read_bytes::<50>(reader)?;
+			}
+		}
+		
+		// Materials pick one of ten toons, the shared ones unless the model lists its own
+		let mut toons = (0..SHARED_TOON_COUNT).map(shared_toon_name)
+		                                      .collect::<Vec<_>>();
+		
+		if !reader.is_empty() {
+			for toon in &mut toons {
+				*toon = decode_name(&read_bytes::<100>(reader)?);
+			}
+		}
+		
+		let mut rigid_bodies = vec![];
+		let mut joints = vec![];
+		
+		if !reader.is_empty() {
+			for _ in 0..read_u32(reader)? {
+				let local_name = decode_name(&read_bytes::<20>(reader)?);
+				let bone = read_u16(reader)?;
+				let group_id = read_u8(reader)?;
+				let non_collision_mask = read_u16(reader)?;
+				
+				let shape = match read_u8(reader)? {
+					0 => ShapeType::Sphere,
+					1 => ShapeType::Box,
+					2 => ShapeType::Capsule,
+					_ => return Err(PmdSyntaxError("unknown rigid body shape").into()),
+				};
+				
+				let shape_size = read_vec3(reader)?;
+				let shape_position = read_vec3(reader)?;
+				let shape_rotation = read_vec3(reader)?;
+				let mass = read_f32(reader)?;
+				let move_attenuation = read_f32(reader)?;
+				let rotation_damping = read_f32(reader)?;
+				let repulsion = read_f32(reader)?;
+				let fiction = read_f32(reader)?;
+				
+				let physics_mode = match read_u8(reader)? {
+					0 => PhysicsMode::Static,
+					1 => PhysicsMode::Dynamic,
+					_ => PhysicsMode::DynamicWithBonePosition,
+				};
+				
+				// Unlike PMX, positions are relative to the bone. Bodies without one follow the root.
+				let bone_index = if bone == NO_BONE { 0 } else { bone as usize };
+				let origin = bones.get(bone_index).map_or_else(Vec3::zeros, |bone| bone.position);
+				
+				rigid_bodies.push(RigidBody {
+					local_name,
+					universal_name: String::new(),
+					bone_index: bone_index as i32,
+					group_id,
+					non_collision_mask,
+					shape,
+					shape_size,
+					shape_position: shape_position + origin,
+					shape_rotation,
+					mass,
+					move_attenuation,
+					rotation_damping,
+					repulsion,
+					fiction,
+					physics_mode,
+				});
+			}
+			
+			for _ in 0..read_u32(reader)? {
+				joints.push(Joint {
+					local_name: decode_name(&read_bytes::<20>(reader)?),
+					universal_name: String::new(),
+					joint_type: JointType::SpringFree,
+					rigid_body_a: read_u32(reader)? as i32,
+					rigid_body_b: read_u32(reader)? as i32,
+					position: read_vec3(reader)?,
+					rotation: read_vec3(reader)?,
+					position_min: read_vec3(reader)?,
+					position_max: read_vec3(reader)?,
+					rotation_min: read_vec3(reader)?,
+					rotation_max: read_vec3(reader)?,
+					position_spring: read_vec3(reader)?,
+					rotation_spring: read_vec3(reader)?,
+				});
+			}
+		}
+		
+		Ok(PmdModel {
+			vertices,
+			indices,
+			materials,
+			bones,
+			iks,
+			morphs,
+			toons,
+			rigid_bodies,
+			joints,
+		})
+	}
+}
+
+impl From<&PmdVertex> for Vertex {
+	fn from(vertex: &PmdVertex) -> Self {
+		Vertex::new(
+			vertex.position.from_mmd(),
+			vertex.normal.from_mmd_normal(),
+			vertex.uv,
+			if vertex.edge { 1.0 } else { 0.0 },
+			[vertex.bones[0] as u32, vertex.bones[1] as u32, 0, 0],
+			[vertex.weight, 1.0 - vertex.weight, 0.0, 0.0],
+			None::<[Vec3; 3]>,
+		)
+	}
+}
+
+fn read_u8(reader: &mut impl Read) -> std::io::Result<u8> {
+	Ok(read_bytes::<1>(reader)?[0])
+}
+
+fn read_u16(reader: &mut impl Read) -> std::io::Result<u16> {
+	Ok(u16::from_le_bytes(read_bytes(reader)?))
+}
+
+#[derive(Debug, Error)]
+#[error("Not a PMD model file.")]
+pub struct PmdInvalidHeaderError;
+
+#[derive(Debug, Error)]
+#[error("Malformed PMD model: {0}.")]
+pub struct PmdSyntaxError(&'static str);
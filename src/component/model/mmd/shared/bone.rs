@@ -4,12 +4,21 @@ use crate::math::{Color, Vec3};
 pub struct BoneDesc {
 	pub id: usize,
 	pub name: String,
+	pub local_name: String,
 	pub parent: Option<usize>,
 	pub color: Color,
 	pub display: bool,
+	/// Whether user can rotate and move the bone, eg. in pose editor.
+	pub rotatable: bool,
+	pub movable: bool,
 	pub connection: BoneConnection,
 	pub model_pos: Vec3,
 	pub local_pos: Vec3,
+	pub ik: Option<BoneIk>,
+	pub append: Option<BoneAppend>,
+	/// Deform layer, bones are transformed in ascending order of layers.
+	pub layer: i32,
+	pub after_physics: bool,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -19,17 +28,56 @@ pub enum BoneConnection {
 	Offset(Vec3),
 }
 
+/// Makes `target` bone reach position of the bone owning it, by rotating `links`.
+#[derive(Debug, Clone)]
+pub struct BoneIk {
+	pub target: usize,
+	pub iterations: u32,
+	/// Maximum rotation of a single link per iteration, in radians.
+	pub limit_angle: f32,
+	/// Ordered from the target towards the root.
+	pub links: Vec<IkLink>,
+}
+
+/// Makes the bone inherit a share of `parent`'s rotation and/or translation, on top of its own animation.
+#[derive(Debug, Clone)]
+pub struct BoneAppend {
+	pub parent: usize,
+	/// Can be negative, eg. to cancel out rotation of a shoulder.
+	pub ratio: f32,
+	pub rotation: bool,
+	pub translation: bool,
+	/// Inherit `parent`'s own animation, even if it is appended from another bone itself.
+	pub local: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct IkLink {
+	pub bone: usize,
+	/// Minimum and maximum euler angles relative to the parent.
+	pub limits: Option<(Vec3, Vec3)>,
+}
+
 impl BoneDesc {
 	pub fn new(name: impl Into<String>, parent: Option<usize>, color: Color, model_pos: Vec3, local_pos: Vec3, display: bool, connection: BoneConnection) -> Self {
+		let name = name.into();
+		
 		BoneDesc {
 			id: 0,
-			name: name.into(),
+			local_name: name.clone(),
+			name,
 			parent,
 			color,
 			model_pos,
 			local_pos,
 			display,
+			rotatable: true,
+			movable: false,
 			connection,
+			ik: None,
+			append: None,
+			layer: 0,
+			after_physics: false,
 		}
 	}
 	
@@ -39,4 +87,42 @@ impl BoneDesc {
 			..self
 		}
 	}
+	
+	/// Original (usually japanese) name, used to match motion tracks.
+	pub fn local_name(self, local_name: impl Into<String>) -> Self {
+		BoneDesc {
+			local_name: local_name.into(),
+			..self
+		}
+	}
+	
+	pub fn operable(self, rotatable: bool, movable: bool) -> Self {
+		BoneDesc {
+			rotatable,
+			movable,
+			..self
+		}
+	}
+	
+	pub fn ik(self, ik: BoneIk) -> Self {
+		BoneDesc {
+			ik: Some(ik),
+			..self
+		}
+	}
+	
+	pub fn append(self, append: BoneAppend) -> Self {
+		BoneDesc {
+			append: Some(append),
+			..self
+		}
+	}
+	
+	pub fn layer(self, layer: i32, after_physics: bool) -> Self {
+		BoneDesc {
+			layer,
+			after_physics,
+			..self
+		}
+	}
 }
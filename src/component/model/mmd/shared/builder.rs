@@ -1,4 +1,6 @@
+use std::collections::BTreeMap;
 use std::io::Cursor;
+use std::mem;
 use anyhow::Result;
 use image::{DynamicImage, ImageFormat};
 use vulkano::buffer::{Buffer, BufferUsage};
@@ -6,12 +8,12 @@ use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, Prim
 use vulkano::format::Format;
 use vulkano::image::{ImmutableImage, MipmapsCount, ImageDimensions};
 
-use crate::math::{IVec4, Vec3};
+use crate::math::{IVec4, Vec2, Vec3};
 use crate::renderer::Renderer;
-use crate::utils::{ImageEx, FenceCheck, BufferEx, IntoInfo};
+use crate::utils::{ImageEx, FenceCheck, BufferEx, IntoInfo, PatternMatcher};
 use super::super::super::VertexIndex;
 use super::super::pipeline::{MMDPipelineMorphs, MORPH_GROUP_SIZE};
-use super::{MMDModelShared, Vertex, BoneDesc, SubMesh, SubMeshDesc, ColliderDesc, JointDesc, MaterialInfo};
+use super::{MMDModelShared, MMDModelSharedGpu, Vertex, BoneDesc, SubMesh, SubMeshDesc, ColliderDesc, JointDesc, MaterialInfo, MorphDesc, MaterialMorph, BoneMorph};
 
 
 pub struct MMDModelSharedBuilder<VI: VertexIndex> {
@@ -20,9 +22,13 @@ pub struct MMDModelSharedBuilder<VI: VertexIndex> {
 	textures: Vec<DynamicImage>,
 	sub_meshes: Vec<SubMeshDesc>,
 	bones: Vec<BoneDesc>,
-	morphs: Vec<Vec<(VI, Vec3)>>,
+	morphs: Vec<MorphDesc>,
+	morphs_names: Vec<String>,
+	gpu_morphs: Vec<Vec<IVec4>>,
 	colliders: Vec<ColliderDesc>,
 	joints: Vec<JointDesc>,
+	bones_roles: BTreeMap<String, PatternMatcher>,
+	morphs_roles: BTreeMap<String, PatternMatcher>,
 }
 
 impl<VI: VertexIndex> MMDModelSharedBuilder<VI> {
@@ -34,8 +40,12 @@ impl<VI: VertexIndex> MMDModelSharedBuilder<VI> {
 			sub_meshes: vec![],
 			bones: vec![],
 			morphs: vec![],
+			morphs_names: vec![],
+			gpu_morphs: vec![],
 			colliders: vec![],
 			joints: vec![],
+			bones_roles: BTreeMap::new(),
+			morphs_roles: BTreeMap::new(),
 		}
 	}
 	
@@ -54,8 +64,59 @@ impl<VI: VertexIndex> MMDModelSharedBuilder<VI> {
 		self
 	}
 	
-	pub fn add_morph(&mut self, offsets: Vec<(VI, Vec3)>) -> &mut Self {
-		self.morphs.push(offsets);
+	pub fn bones(&self) -> &[BoneDesc] {
+		&self.bones
+	}
+	
+	pub fn add_morph(&mut self, name: impl Into<String>, offsets: Vec<(VI, Vec3)>) -> &mut Self {
+		let offsets = offsets.into_iter()
+		                     .map(|(index, offset)| gpu_morph_offset(Into::<u32>::into(index) as i32, offset))
+		                     .collect();
+		
+		self.add_gpu_morph(name, offsets)
+	}
+	
+	pub fn add_uv_morph(&mut self, name: impl Into<String>, offsets: Vec<(VI, Vec2)>) -> &mut Self {
+		// Negative indices tell the morph compute pass to offset UVs instead of positions
+		let offsets = offsets.into_iter()
+		                     .map(|(index, offset)| gpu_morph_offset(-(Into::<u32>::into(index) as i32) - 1, offset.push(0.0)))
+		                     .collect();
+		
+		self.add_gpu_morph(name, offsets)
+	}
+	
+	pub fn add_material_morph(&mut self, name: impl Into<String>, offsets: Vec<MaterialMorph>) -> &mut Self {
+		self.morphs.push(MorphDesc::Material(offsets));
+		self.morphs_names.push(name.into());
+		self
+	}
+	
+	pub fn add_bone_morph(&mut self, name: impl Into<String>, offsets: Vec<BoneMorph>) -> &mut Self {
+		self.morphs.push(MorphDesc::Bone(offsets));
+		self.morphs_names.push(name.into());
+		self
+	}
+	
+	pub fn add_group_morph(&mut self, name: impl Into<String>, children: Vec<(usize, f32)>) -> &mut Self {
+		self.morphs.push(MorphDesc::Group(children));
+		self.morphs_names.push(name.into());
+		self
+	}
+	
+	fn add_gpu_morph(&mut self, name: impl Into<String>, offsets: Vec<IVec4>) -> &mut Self {
+		self.morphs.push(MorphDesc::Gpu(self.gpu_morphs.len()));
+		self.morphs_names.push(name.into());
+		self.gpu_morphs.push(offsets);
+		self
+	}
+	
+	pub fn add_bone_role(&mut self, role: impl Into<String>, pattern: impl AsRef<str>) -> &mut Self {
+		self.bones_roles.insert(role.into(), PatternMatcher::new(pattern));
+		self
+	}
+	
+	pub fn add_morph_role(&mut self, role: impl Into<String>, pattern: impl AsRef<str>) -> &mut Self {
+		self.morphs_roles.insert(role.into(), PatternMatcher::new(pattern));
 		self
 	}
 	
@@ -70,18 +131,48 @@ impl<VI: VertexIndex> MMDModelSharedBuilder<VI> {
 	}
 	
 	pub fn build(mut self, renderer: &mut Renderer) -> Result<MMDModelShared> {
+		let gpu = self.upload(renderer)?;
+		
+		Ok(MMDModelShared {
+			gpu: Some(gpu),
+			..self.build_headless()
+		})
+	}
+	
+	/// Only bones, morphs and physics are kept, mesh and textures are dropped without uploading.
+	pub fn build_headless(self) -> MMDModelShared {
+		let default_bones = self.bones;
+		
+		let mut transform_order = (0..default_bones.len()).collect::<Vec<_>>();
+		transform_order.sort_by_key(|&id| (default_bones[id].after_physics, default_bones[id].layer, id));
+		
+		MMDModelShared {
+			default_bones,
+			transform_order,
+			morphs: self.morphs,
+			morphs_names: self.morphs_names,
+			colliders: self.colliders,
+			joints: self.joints,
+			bones_roles: self.bones_roles,
+			morphs_roles: self.morphs_roles,
+			gpu: None,
+		}
+	}
+	
+	/// Moves mesh, textures and GPU morphs out of the builder into GPU buffers.
+	fn upload(&mut self, renderer: &mut Renderer) -> Result<MMDModelSharedGpu> {
 		let mut upload_buffer = AutoCommandBufferBuilder::primary(&*renderer.command_buffer_allocator,
 		                                                          renderer.load_queue.queue_family_index(),
 		                                                          CommandBufferUsage::OneTimeSubmit)?;
 		
 		let vertices = Buffer::upload_iter(&renderer.memory_allocator,
 		                                   BufferUsage::VERTEX_BUFFER.into_info(),
-		                                   self.vertices.into_iter(),
+		                                   mem::take(&mut self.vertices).into_iter(),
 		                                   &mut upload_buffer)?;
 		
 		let indices = Buffer::upload_iter(&renderer.memory_allocator,
 		                                  BufferUsage::INDEX_BUFFER.into_info(),
-		                                  self.indices.into_iter(),
+		                                  mem::take(&mut self.indices).into_iter(),
 		                                  &mut upload_buffer)?;
 		
 		let default_tex = {
@@ -100,7 +191,7 @@ impl<VI: VertexIndex> MMDModelSharedBuilder<VI> {
 		
 		let mut images = vec![];
 		
-		for texture in self.textures {
+		for texture in mem::take(&mut self.textures) {
 			let width = texture.width();
 			let height = texture.height();
 			
@@ -116,7 +207,7 @@ impl<VI: VertexIndex> MMDModelSharedBuilder<VI> {
 		
 		let mut sub_meshes = vec![];
 		
-		for desc in self.sub_meshes {
+		for desc in mem::take(&mut self.sub_meshes) {
 			let texture = desc.texture.and_then(|id| images.get(id))
 			                  .cloned()
 			                  .unwrap_or_else(|| default_tex.clone());
@@ -135,40 +226,36 @@ impl<VI: VertexIndex> MMDModelSharedBuilder<VI> {
 				specularity: desc.specularity,
 				ambient: desc.ambient.into(),
 				sphere_mode: desc.sphere_mode,
+				texture_mul: vector!(1.0, 1.0, 1.0, 1.0),
+				sphere_mul: vector!(1.0, 1.0, 1.0, 1.0),
+				toon_mul: vector!(1.0, 1.0, 1.0, 1.0),
+				..MaterialInfo::default()
 			};
 			
-			let material_buffer = Buffer::upload_data(&renderer.memory_allocator,
-			                                          BufferUsage::UNIFORM_BUFFER.into_info(),
-			                                          material_info,
-			                                          &mut upload_buffer)?;
-			
-			let sub_mesh = SubMesh::new(desc.range, material_buffer, texture, toon, sphere_map, desc.opaque, desc.no_cull, desc.edge, renderer)?;
+			let sub_mesh = SubMesh::new(desc.range, material_info, texture, toon, sphere_map, desc.opaque, desc.no_cull, desc.edge, renderer)?;
 			
 			sub_meshes.push(sub_mesh);
 		}
 		
-		let default_bones = self.bones;
+		let mut gpu_morphs = mem::take(&mut self.gpu_morphs);
 		
-		// Create fake null morph if there is no morphs
-		if self.morphs.is_empty() {
-			self.morphs.push(vec![(VI::zeroed(), Vec3::zeros())])
+		// Create fake null morph if there is no GPU morphs
+		if gpu_morphs.is_empty() {
+			gpu_morphs.push(vec![IVec4::zeros()]);
 		}
 		
-		let morphs_sizes = self.morphs.iter()
-		                       .map(|v| v.len())
-		                       .collect::<Vec<_>>();
+		let morphs_sizes = gpu_morphs.iter()
+		                             .map(|v| v.len())
+		                             .collect::<Vec<_>>();
 		let morphs_max_size = morphs_sizes.iter().copied().max().unwrap_or(MORPH_GROUP_SIZE);
 		let morphs_max_size = (morphs_max_size + MORPH_GROUP_SIZE - 1) / MORPH_GROUP_SIZE * MORPH_GROUP_SIZE;
 		
 		let morphs_offsets = {
-			let mut offsets = vec![IVec4::zeros().into(); morphs_max_size * self.morphs.len()];
+			let mut offsets = vec![IVec4::zeros().into(); morphs_max_size * gpu_morphs.len()];
 			
-			for (mid, morph) in self.morphs.into_iter().enumerate() {
-				for (oid, (index, offset)) in morph.into_iter().enumerate() {
-					offsets[mid * morphs_max_size + oid] = vector!((offset.x * 1_000_000.0) as i32,
-					                                               (offset.y * 1_000_000.0) as i32,
-					                                               (offset.z * 1_000_000.0) as i32,
-					                                               Into::<u32>::into(index) as i32).into();
+			for (mid, morph) in gpu_morphs.into_iter().enumerate() {
+				for (oid, offset) in morph.into_iter().enumerate() {
+					offsets[mid * morphs_max_size + oid] = offset.into();
 				}
 			}
 			
@@ -185,18 +272,22 @@ impl<VI: VertexIndex> MMDModelSharedBuilder<VI> {
 		
 		let fence = FenceCheck::new(upload_future)?;
 		
-		Ok(MMDModelShared {
+		Ok(MMDModelSharedGpu {
 			vertices,
 			indices: indices.into(),
 			sub_meshes,
-			default_bones,
 			morphs_offsets,
 			morphs_sizes,
 			morphs_max_size,
 			morphs_pipeline,
 			fence,
-			colliders: self.colliders,
-			joints: self.joints,
 		})
 	}
 }
+
+fn gpu_morph_offset(target: i32, offset: Vec3) -> IVec4 {
+	vector!((offset.x * 1_000_000.0) as i32,
+	        (offset.y * 1_000_000.0) as i32,
+	        (offset.z * 1_000_000.0) as i32,
+	        target)
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use anyhow::Result;
 use vulkano::buffer::Subbuffer;
@@ -8,39 +9,57 @@ mod bone;
 mod builder;
 mod collider;
 mod joint;
+mod morph;
 mod sub_mesh;
 
 use crate::renderer::pipelines::PipelineNoLayoutError;
 use crate::math::IVec4;
-use crate::utils::{FenceCheck, IndexSubbuffer};
+use crate::utils::{FenceCheck, IndexSubbuffer, PatternMatcher};
 use super::super::VertexIndex;
 use super::Vertex;
-pub use bone::{BoneDesc, BoneConnection};
+pub use bone::{BoneDesc, BoneConnection, BoneAppend, BoneIk, IkLink};
 pub use builder::MMDModelSharedBuilder;
 pub use collider::ColliderDesc;
 pub use joint::JointDesc;
+pub use morph::{MorphDesc, MaterialMorph, BoneMorph};
 pub use sub_mesh::{MaterialInfo, SubMesh, SubMeshDesc};
 
 
 pub struct MMDModelShared {
+	pub default_bones: Vec<BoneDesc>,
+	/// Bone ids sorted by deform layer, bones deformed after physics go last.
+	pub transform_order: Vec<usize>,
+	pub morphs: Vec<MorphDesc>,
+	pub morphs_names: Vec<String>,
+	pub colliders: Vec<ColliderDesc>,
+	pub joints: Vec<JointDesc>,
+	/// Bone name patterns by role, from `model.toml`.
+	pub bones_roles: BTreeMap<String, PatternMatcher>,
+	pub morphs_roles: BTreeMap<String, PatternMatcher>,
+	/// Not uploaded in headless mode.
+	pub gpu: Option<MMDModelSharedGpu>,
+}
+
+/// Mesh, materials and morph offsets uploaded to the GPU.
+pub struct MMDModelSharedGpu {
 	pub vertices: Subbuffer<[Vertex]>,
 	pub indices: IndexSubbuffer,
 	pub sub_meshes: Vec<SubMesh>,
-	pub default_bones: Vec<BoneDesc>,
 	pub morphs_offsets: Subbuffer<[IVec4]>,
+	/// Sizes of GPU morphs.
 	pub morphs_sizes: Vec<usize>,
 	pub morphs_max_size: usize,
 	pub morphs_pipeline: Arc<ComputePipeline>,
 	pub fence: FenceCheck,
-	pub colliders: Vec<ColliderDesc>,
-	pub joints: Vec<JointDesc>,
 }
 
 impl MMDModelShared {
 	pub fn new<VI: VertexIndex>(vertices: Vec<Vertex>, indices: Vec<VI>) -> MMDModelSharedBuilder<VI> {
 		MMDModelSharedBuilder::new(vertices, indices)
 	}
-	
+}
+
+impl MMDModelSharedGpu {
 	pub fn layouts(&self) -> Result<(Arc<DescriptorSetLayout>, Option<Arc<DescriptorSetLayout>>)> {
 		let main = self.sub_meshes.first()
 		                          .map(|mesh| mesh.main.0.clone())
@@ -0,0 +1,71 @@
+use nalgebra::SVector;
+
+use crate::math::{Rot3, Vec3, Vec4};
+use super::MaterialInfo;
+
+
+/// Kind of a morph, indexed the same as `MMDModelState::morphs`.
+#[derive(Debug, Clone)]
+pub enum MorphDesc {
+	/// Vertex or UV offsets applied by the morph compute pass, holds id of the GPU morph.
+	Gpu(usize),
+	Material(Vec<MaterialMorph>),
+	Bone(Vec<BoneMorph>),
+	/// Pairs of morph id and ratio, weight of the group is added to its children.
+	Group(Vec<(usize, f32)>),
+}
+
+#[derive(Debug, Clone)]
+pub struct MaterialMorph {
+	/// `None` targets all sub meshes.
+	pub sub_mesh: Option<usize>,
+	/// Offsets are added to the material instead of multiplying it.
+	pub additive: bool,
+	pub color: Vec4,
+	pub specular: Vec3,
+	pub specularity: f32,
+	pub ambient: Vec3,
+	pub edge_color: Vec4,
+	pub edge_scale: f32,
+	pub texture_tint: Vec4,
+	pub sphere_tint: Vec4,
+	pub toon_tint: Vec4,
+}
+
+impl MaterialMorph {
+	pub fn apply(&self, material: &mut MaterialInfo, edge: &mut (f32, Vec4), weight: f32) {
+		if self.additive {
+			material.color += self.color * weight;
+			material.specular += self.specular * weight;
+			material.specularity += self.specularity * weight;
+			material.ambient += self.ambient * weight;
+			material.texture_add += self.texture_tint * weight;
+			material.sphere_add += self.sphere_tint * weight;
+			material.toon_add += self.toon_tint * weight;
+			edge.0 += self.edge_scale * weight;
+			edge.1 += self.edge_color * weight;
+		} else {
+			material.color.component_mul_assign(&factor(&self.color, weight));
+			material.specular.component_mul_assign(&factor(&self.specular, weight));
+			material.specularity *= 1.0 + (self.specularity - 1.0) * weight;
+			material.ambient.component_mul_assign(&factor(&self.ambient, weight));
+			material.texture_mul.component_mul_assign(&factor(&self.texture_tint, weight));
+			material.sphere_mul.component_mul_assign(&factor(&self.sphere_tint, weight));
+			material.toon_mul.component_mul_assign(&factor(&self.toon_tint, weight));
+			edge.0 *= 1.0 + (self.edge_scale - 1.0) * weight;
+			edge.1.component_mul_assign(&factor(&self.edge_color, weight));
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct BoneMorph {
+	pub bone: usize,
+	pub translation: Vec3,
+	pub rotation: Rot3,
+}
+
+/// Interpolates multiplier between one and the offset.
+fn factor<const N: usize>(offset: &SVector<f32, N>, weight: f32) -> SVector<f32, N> {
+	offset.map(|value| 1.0 + (value - 1.0) * weight)
+}
@@ -1,7 +1,7 @@
 use std::ops::Range;
 use std::sync::Arc;
 use anyhow::Result;
-use vulkano::buffer::{BufferContents, Subbuffer};
+use vulkano::buffer::BufferContents;
 use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
 use vulkano::image::ImmutableImage;
 use vulkano::image::view::ImageView;
@@ -24,10 +24,18 @@ pub struct MaterialInfo {
 	pub specularity: f32,
 	pub ambient: Vec3,
 	pub sphere_mode: u32,
+	pub texture_mul: Vec4,
+	pub texture_add: Vec4,
+	pub sphere_mul: Vec4,
+	pub sphere_add: Vec4,
+	pub toon_mul: Vec4,
+	pub toon_add: Vec4,
 }
 
 pub struct SubMesh {
 	pub range: Range<u32>,
+	/// Default material, copied to model's materials buffer before material morphs are applied.
+	pub material: MaterialInfo,
 	pub main: PipelineWithSet,
 	pub transparent: Option<PipelineWithSet>,
 	pub edge: Option<PipelineWithSet>,
@@ -37,7 +45,7 @@ pub struct SubMesh {
 
 impl SubMesh {
 	pub fn new(range: Range<u32>,
-	           material_buffer: Subbuffer<MaterialInfo>,
+	           material: MaterialInfo,
 	           texture: Arc<ImmutableImage>,
 	           toon: Arc<ImmutableImage>,
 	           sphere_map: Arc<ImmutableImage>,
@@ -59,7 +67,6 @@ impl SubMesh {
 		
 		let main_set = PersistentDescriptorSet::new(&renderer.descriptor_set_allocator,
 		                                            main_pipeline.layout().set_layouts().get(1).ok_or(PipelineNoLayoutError)?.clone(), [
-			                                            WriteDescriptorSet::buffer(0, material_buffer.clone()),
 			                                            WriteDescriptorSet::image_view_sampler(1, texture_view.clone(), sampler.clone()),
 			                                            WriteDescriptorSet::image_view_sampler(2, toon_view.clone(), sampler.clone()),
 			                                            WriteDescriptorSet::image_view_sampler(3, sphere_map_view.clone(), sampler.clone()),
@@ -67,6 +74,7 @@ impl SubMesh {
 		
 		let mut sub_mesh = SubMesh {
 			range,
+			material,
 			main: (main_pipeline, main_set),
 			transparent: None,
 			edge: None,
@@ -82,7 +90,6 @@ impl SubMesh {
 			
 			let set = PersistentDescriptorSet::new(&renderer.descriptor_set_allocator,
 			                                       pipeline.layout().set_layouts().get(1).ok_or(PipelineNoLayoutError)?.clone(), [
-				                                       WriteDescriptorSet::buffer(0, material_buffer.clone()),
 				                                       WriteDescriptorSet::image_view_sampler(1, texture_view.clone(), sampler.clone()),
 				                                       WriteDescriptorSet::image_view_sampler(2, toon_view.clone(), sampler.clone()),
 				                                       WriteDescriptorSet::image_view_sampler(3, sphere_map_view.clone(), sampler.clone()),
@@ -0,0 +1,54 @@
+use anyhow::Result;
+use image::{DynamicImage, Rgb, RgbImage};
+
+use crate::renderer::assets_manager::AssetsManager;
+use super::asset::find_image_format;
+
+
+/// MikuMikuDance ships ten toons, `toon01.bmp` to `toon10.bmp`, which models use without including them.
+pub const SHARED_TOON_COUNT: usize = 10;
+/// Shared toons placed here, usually in `assets_overrides`, replace the generated ones.
+const SHARED_TOON_DIR: &str = "mmd";
+const SHARED_TOON_SIZE: u32 = 32;
+/// Shadow color and width of the transition to it, approximating each of MikuMikuDance's toons.
+const SHARED_TOONS: [([u8; 3], f32); SHARED_TOON_COUNT] = [
+	([204, 204, 204], 0.05),
+	([255, 214, 200], 0.05),
+	([160, 160, 160], 0.05),
+	([236, 190, 180], 0.1),
+	([200, 210, 240], 0.1),
+	([240, 215, 200], 0.3),
+	([180, 180, 180], 0.3),
+	([215, 195, 170], 0.2),
+	([235, 220, 200], 0.5),
+	([250, 250, 250], 0.5),
+];
+
+pub fn shared_toon_name(id: usize) -> String {
+	format!("toon{:02}.bmp", id + 1)
+}
+
+/// Recognizes names of shared toons, like `toon03.bmp`, regardless of case.
+pub fn shared_toon_id(name: &str) -> Option<usize> {
+	(0..SHARED_TOON_COUNT).find(|&id| shared_toon_name(id).eq_ignore_ascii_case(name))
+}
+
+/// Loads the shared toon from assets if provided, otherwise generates a vertical gradient, lit at the top.
+pub fn shared_toon(id: usize) -> Result<DynamicImage> {
+	let path = format!("{}/{}", SHARED_TOON_DIR, shared_toon_name(id));
+	
+	if let Ok(found) = AssetsManager::find_asset_path(&path) {
+		return Ok(image::load(AssetsManager::find_asset(&path)?, find_image_format(found)?)?);
+	}
+	
+	let (shadow, width) = SHARED_TOONS.get(id).copied().unwrap_or(SHARED_TOONS[0]);
+	
+	let image = RgbImage::from_fn(SHARED_TOON_SIZE, SHARED_TOON_SIZE, |_, y| {
+		let v = (y as f32 + 0.5) / SHARED_TOON_SIZE as f32;
+		let shade = ((v - 0.5) / width + 0.5).clamp(0.0, 1.0);
+		
+		Rgb(shadow.map(|channel| (255.0 + (channel as f32 - 255.0) * shade).round() as u8))
+	});
+	
+	Ok(DynamicImage::ImageRgb8(image))
+}
@@ -0,0 +1,408 @@
+use std::fmt::{Display, Formatter};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::Result;
+use encoding_rs::SHIFT_JIS;
+use nalgebra::Quaternion;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::math::{Rot3, Vec2, Vec3};
+use crate::renderer::Renderer;
+use crate::renderer::assets_manager::{AssetKey, AssetsManager};
+use super::MMDModel;
+use super::asset::FromMMD;
+
+
+/// Frame rate of VMD keyframes.
+pub const VMD_FPS: f32 = 30.0;
+
+const VMD_MAGIC_V1: &[u8] = b"Vocaloid Motion Data file";
+const VMD_MAGIC_V2: &[u8] = b"Vocaloid Motion Data 0002";
+
+#[derive(Clone, Hash, Debug, Deserialize, Serialize)]
+pub struct VmdAsset {
+	path: PathBuf,
+}
+
+impl VmdAsset {
+	pub fn at(motion_path: impl AsRef<Path>) -> Self {
+		VmdAsset {
+			path: motion_path.as_ref().to_path_buf(),
+		}
+	}
+}
+
+impl AssetKey for VmdAsset {
+	type Asset = Arc<VmdMotion>;
+	
+	fn load(&self, _assets_manager: &mut AssetsManager, _renderer: &mut Renderer) -> Result<Self::Asset> {
+		Ok(Arc::new(VmdMotion::read(AssetsManager::find_asset(&self.path)?)?))
+	}
+}
+
+impl Display for VmdAsset {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "VMD motion {}", self.path.to_string_lossy())
+	}
+}
+
+/// Bone and morph keyframes of a MikuMikuDance motion. Camera, light and IK keyframes are ignored.
+#[derive(Debug, Clone)]
+pub struct VmdMotion {
+	pub model_name: String,
+	pub bones: Vec<BoneTrack>,
+	pub morphs: Vec<MorphTrack>,
+	/// Frame of the last keyframe.
+	pub length: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct BoneTrack {
+	pub name: String,
+	pub keyframes: Vec<BoneKeyframe>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BoneKeyframe {
+	pub frame: u32,
+	pub translation: Vec3,
+	pub rotation: Rot3,
+	/// Curves of X, Y, Z translation and rotation, used when interpolating from the previous keyframe.
+	pub curves: [BezierCurve; 4],
+}
+
+#[derive(Debug, Clone)]
+pub struct MorphTrack {
+	pub name: String,
+	pub keyframes: Vec<MorphKeyframe>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MorphKeyframe {
+	pub frame: u32,
+	pub weight: f32,
+}
+
+/// Pairs of (track id, bone or morph id).
+#[derive(Debug, Clone, Default)]
+pub struct VmdBindings {
+	pub bones: Vec<(usize, usize)>,
+	pub morphs: Vec<(usize, usize)>,
+}
+
+/// Cubic Bézier easing going from (0, 0) to (1, 1).
+#[derive(Debug, Clone, Copy)]
+pub struct BezierCurve {
+	pub p1: Vec2,
+	pub p2: Vec2,
+}
+
+impl VmdMotion {
+	pub fn read(mut reader: impl Read) -> Result<Self> {
+		let magic = read_bytes::<30>(&mut reader)?;
+		
+		let name_length = if magic.starts_with(VMD_MAGIC_V2) {
+			20
+		} else if magic.starts_with(VMD_MAGIC_V1) {
+			10
+		} else {
+			return Err(VmdInvalidHeaderError.into());
+		};
+		
+		let mut name = vec![0; name_length];
+		reader.read_exact(&mut name)?;
+		let model_name = decode_name(&name);
+		
+		let mut bones: Vec<BoneTrack> = vec![];
+		
+		for _ in 0..read_u32(&mut reader)? {
+			let name = decode_name(&read_bytes::<15>(&mut reader)?);
+			let frame = read_u32(&mut reader)?;
+			let translation = read_vec3(&mut reader)?.from_mmd();
+			let [x, y, z, w] = [read_f32(&mut reader)?, read_f32(&mut reader)?, read_f32(&mut reader)?, read_f32(&mut reader)?];
+			let rotation = Rot3::new_normalize(Quaternion::new(w, -x, -y, z));
+			let interpolation = read_bytes::<64>(&mut reader)?;
+			
+			let keyframe = BoneKeyframe {
+				frame,
+				translation,
+				rotation,
+				curves: [0, 1, 2, 3].map(|channel| BezierCurve::from_vmd(&interpolation, channel)),
+			};
+			
+			match bones.iter_mut().find(|track| track.name == name) {
+				Some(track) => track.keyframes.push(keyframe),
+				None => bones.push(BoneTrack { name, keyframes: vec![keyframe] }),
+			}
+		}
+		
+		let mut morphs: Vec<MorphTrack> = vec![];
+		
+		// Motions exported from older tools can end right after bone keyframes.
+		let morph_count = match read_u32(&mut reader) {
+			Ok(count) => count,
+			Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => 0,
+			Err(err) => return Err(err.into()),
+		};
+		
+		for _ in 0..morph_count {
+			let name = decode_name(&read_bytes::<15>(&mut reader)?);
+			let keyframe = MorphKeyframe {
+				frame: read_u32(&mut reader)?,
+				weight: read_f32(&mut reader)?,
+			};
+			
+			match morphs.iter_mut().find(|track| track.name == name) {
+				Some(track) => track.keyframes.push(keyframe),
+				None => morphs.push(MorphTrack { name, keyframes: vec![keyframe] }),
+			}
+		}
+		
+		for track in &mut bones {
+			track.keyframes.sort_by_key(|keyframe| keyframe.frame);
+		}
+		
+		for track in &mut morphs {
+			track.keyframes.sort_by_key(|keyframe| keyframe.frame);
+		}
+		
+		let length = bones.iter().flat_map(|track| track.keyframes.last().map(|keyframe| keyframe.frame))
+		                  .chain(morphs.iter().flat_map(|track| track.keyframes.last().map(|keyframe| keyframe.frame)))
+		                  .max()
+		                  .unwrap_or(0);
+		
+		Ok(VmdMotion {
+			model_name,
+			bones,
+			morphs,
+			length,
+		})
+	}
+	
+	pub fn duration(&self) -> f32 {
+		self.length as f32 / VMD_FPS
+	}
+	
+	/// Matches tracks with bones and morphs of the model by name.
+	pub fn bind(&self, model: &MMDModel) -> VmdBindings {
+		let state = model.state();
+		
+		let bones = self.bones
+		                .iter()
+		                .enumerate()
+		                .filter_map(|(track_id, track)| {
+			                state.bones
+			                     .iter()
+			                     .position(|bone| bone.local_name == track.name || bone.name == track.name)
+			                     .map(|bone_id| (track_id, bone_id))
+		                })
+		                .collect();
+		
+		let morphs = self.morphs
+		                 .iter()
+		                 .enumerate()
+		                 .filter_map(|(track_id, track)| {
+			                 model.morphs_names()
+			                      .iter()
+			                      .position(|name| *name == track.name)
+			                      .map(|morph_id| (track_id, morph_id))
+		                 })
+		                 .collect();
+		
+		VmdBindings { bones, morphs }
+	}
+}
+
+impl BoneTrack {
+	/// Returns translation and rotation at `frame`, which can be fractional.
+	pub fn sample(&self, frame: f32) -> (Vec3, Rot3) {
+		let next_id = self.keyframes.partition_point(|keyframe| keyframe.frame as f32 <= frame);
+		
+		if next_id == 0 || next_id >= self.keyframes.len() {
+			let keyframe = &self.keyframes[next_id.min(self.keyframes.len() - 1)];
+			return (keyframe.translation, keyframe.rotation);
+		}
+		
+		let prev = &self.keyframes[next_id - 1];
+		let next = &self.keyframes[next_id];
+		let time = (frame - prev.frame as f32) / (next.frame - prev.frame) as f32;
+		
+		let translation = vector!(
+			prev.translation.x + (next.translation.x - prev.translation.x) * next.curves[0].ease(time),
+			prev.translation.y + (next.translation.y - prev.translation.y) * next.curves[1].ease(time),
+			prev.translation.z + (next.translation.z - prev.translation.z) * next.curves[2].ease(time),
+		);
+		let rotation = prev.rotation.slerp(&next.rotation, next.curves[3].ease(time));
+		
+		(translation, rotation)
+	}
+}
+
+impl MorphTrack {
+	pub fn sample(&self, frame: f32) -> f32 {
+		let next_id = self.keyframes.partition_point(|keyframe| keyframe.frame as f32 <= frame);
+		
+		if next_id == 0 || next_id >= self.keyframes.len() {
+			return self.keyframes[next_id.min(self.keyframes.len() - 1)].weight;
+		}
+		
+		let prev = &self.keyframes[next_id - 1];
+		let next = &self.keyframes[next_id];
+		let time = (frame - prev.frame as f32) / (next.frame - prev.frame) as f32;
+		
+		prev.weight + (next.weight - prev.weight) * time
+	}
+}
+
+impl BezierCurve {
+	/// VMD stores control points of all four channels interleaved, as bytes in 0..=127 range.
+	fn from_vmd(interpolation: &[u8; 64], channel: usize) -> Self {
+		BezierCurve {
+			p1: vector!(interpolation[channel] as f32, interpolation[channel + 4] as f32) / 127.0,
+			p2: vector!(interpolation[channel + 8] as f32, interpolation[channel + 12] as f32) / 127.0,
+		}
+	}
+	
+	pub fn ease(&self, x: f32) -> f32 {
+		if self.p1.x == self.p1.y && self.p2.x == self.p2.y {
+			return x;
+		}
+		
+		// X is monotonic, find its parameter by bisection
+		let (mut low, mut high) = (0.0, 1.0);
+		let mut t = x;
+		
+		for _ in 0..16 {
+			let value = bezier(t, self.p1.x, self.p2.x);
+			
+			if value < x {
+				low = t;
+			} else {
+				high = t;
+			}
+			
+			t = (low + high) / 2.0;
+		}
+		
+		bezier(t, self.p1.y, self.p2.y)
+	}
+}
+
+fn bezier(t: f32, p1: f32, p2: f32) -> f32 {
+	let s = 1.0 - t;
+	
+	3.0 * s * s * t * p1 + 3.0 * s * t * t * p2 + t * t * t
+}
+
+pub(super) fn decode_name(bytes: &[u8]) -> String {
+	let end = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+	
+	SHIFT_JIS.decode_without_bom_handling(&bytes[..end]).0.into_owned()
+}
+
+pub(super) fn read_bytes<const N: usize>(reader: &mut impl Read) -> std::io::Result<[u8; N]> {
+	let mut buf = [0; N];
+	reader.read_exact(&mut buf)?;
+	Ok(buf)
+}
+
+pub(super) fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+	Ok(u32::from_le_bytes(read_bytes(reader)?))
+}
+
+pub(super) fn read_f32(reader: &mut impl Read) -> std::io::Result<f32> {
+	Ok(f32::from_le_bytes(read_bytes(reader)?))
+}
+
+pub(super) fn read_vec3(reader: &mut impl Read) -> std::io::Result<Vec3> {
+	Ok(vector!(read_f32(reader)?, read_f32(reader)?, read_f32(reader)?))
+}
+
+#[derive(Debug, Error)]
+#[error("Not a VMD motion file.")]
+pub struct VmdInvalidHeaderError;
+
+#[cfg(test)]
+mod tests {
+	use crate::math::PI;
+	use super::*;
+	
+	/// First row of the block, real files repeat it shifted for the other channels.
+	fn interpolation() -> [u8; 64] {
+		let mut interpolation = [0; 64];
+		interpolation[..16].copy_from_slice(&[10, 20, 30, 40, 50, 60, 70, 80, 90, 100, 110, 120, 127, 0, 1, 2]);
+		interpolation
+	}
+	
+	fn keyframe(frame: u32, translation: Vec3, rotation: Rot3) -> BoneKeyframe {
+		let linear = BezierCurve { p1: vector!(0.25, 0.25), p2: vector!(0.75, 0.75) };
+		
+		BoneKeyframe { frame, translation, rotation, curves: [linear; 4] }
+	}
+	
+	fn track() -> BoneTrack {
+		BoneTrack {
+			name: "Test".to_string(),
+			keyframes: vec![keyframe(10, Vec3::zeros(), Rot3::identity()),
+			                keyframe(20, vector!(10.0, 0.0, 0.0), Rot3::from_axis_angle(&Vec3::y_axis(), PI / 2.0))],
+		}
+	}
+	
+	fn assert_pose((translation, rotation): (Vec3, Rot3), expected_translation: Vec3, expected_angle: f32) {
+		assert!((translation - expected_translation).magnitude() < 1.0e-4, "{:?} != {:?}", translation, expected_translation);
+		assert!((rotation.angle() - expected_angle).abs() < 1.0e-4, "{} != {}", rotation.angle(), expected_angle);
+	}
+	
+	#[test]
+	fn curves_deinterleave() {
+		let interpolation = interpolation();
+		let curve = BezierCurve::from_vmd(&interpolation, 1);
+		
+		assert_eq!(curve.p1, vector!(20.0, 60.0) / 127.0);
+		assert_eq!(curve.p2, vector!(100.0, 0.0) / 127.0);
+		
+		let curve = BezierCurve::from_vmd(&interpolation, 3);
+		
+		assert_eq!(curve.p1, vector!(40.0, 80.0) / 127.0);
+		assert_eq!(curve.p2, vector!(120.0, 2.0) / 127.0);
+	}
+	
+	#[test]
+	fn linear_curve_is_identity() {
+		let curve = BezierCurve { p1: vector!(20.0, 20.0) / 127.0, p2: vector!(107.0, 107.0) / 127.0 };
+		
+		for x in [0.0, 0.1, 0.37, 0.5, 0.9, 1.0] {
+			assert_eq!(curve.ease(x), x);
+		}
+	}
+	
+	#[test]
+	fn ease_keeps_endpoints() {
+		for channel in 0..4 {
+			let curve = BezierCurve::from_vmd(&interpolation(), channel);
+			
+			assert!(curve.ease(0.0).abs() < 1.0e-3, "channel {}: {}", channel, curve.ease(0.0));
+			assert!((curve.ease(1.0) - 1.0).abs() < 1.0e-3, "channel {}: {}", channel, curve.ease(1.0));
+		}
+	}
+	
+	#[test]
+	fn sample_holds_before_first_keyframe() {
+		assert_pose(track().sample(0.0), Vec3::zeros(), 0.0);
+		assert_pose(track().sample(10.0), Vec3::zeros(), 0.0);
+	}
+	
+	#[test]
+	fn sample_interpolates_between_keyframes() {
+		assert_pose(track().sample(15.0), vector!(5.0, 0.0, 0.0), PI / 4.0);
+		assert_pose(track().sample(12.5), vector!(2.5, 0.0, 0.0), PI / 8.0);
+	}
+	
+	#[test]
+	fn sample_holds_after_last_keyframe() {
+		assert_pose(track().sample(20.0), vector!(10.0, 0.0, 0.0), PI / 2.0);
+		assert_pose(track().sample(100.0), vector!(10.0, 0.0, 0.0), PI / 2.0);
+	}
+}
@@ -0,0 +1,192 @@
+use std::fs;
+use std::path::Path;
+use anyhow::Result;
+use encoding_rs::SHIFT_JIS;
+use nalgebra::Quaternion;
+use thiserror::Error;
+
+use crate::math::{Isometry3, Rot3, Vec3};
+use super::MMDModel;
+use super::asset::{FromMMD, MMD_UNIT_SIZE};
+
+
+const VPD_MAGIC: &str = "Vocaloid Pose Data file";
+
+/// Static MikuMikuDance pose: local transforms of bones, and optionally morph weights.
+#[derive(Debug, Clone)]
+pub struct VpdPose {
+	pub model_name: String,
+	pub bones: Vec<VpdBone>,
+	pub morphs: Vec<VpdMorph>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VpdBone {
+	pub name: String,
+	pub translation: Vec3,
+	pub rotation: Rot3,
+}
+
+#[derive(Debug, Clone)]
+pub struct VpdMorph {
+	pub name: String,
+	pub weight: f32,
+}
+
+impl VpdPose {
+	pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+		VpdPose::read(&fs::read(path)?)
+	}
+	
+	pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+		if let Some(parent) = path.as_ref().parent() {
+			fs::create_dir_all(parent)?;
+		}
+		
+		fs::write(path, self.write())?;
+		
+		Ok(())
+	}
+	
+	pub fn read(bytes: &[u8]) -> Result<Self> {
+		let text = SHIFT_JIS.decode_without_bom_handling(bytes).0;
+		let mut lines = text.lines()
+		                    .map(|line| line.split("//").next().unwrap_or_default().trim())
+		                    .filter(|line| !line.is_empty());
+		
+		if lines.next() != Some(VPD_MAGIC) {
+			return Err(VpdInvalidHeaderError.into());
+		}
+		
+		let model_name = lines.next().ok_or(VpdSyntaxError("missing model name"))?;
+		let model_name = model_name.trim_end_matches(';').trim_end_matches(".osm").to_string();
+		
+		// Bone count, redundant with the blocks
+		lines.next().ok_or(VpdSyntaxError("missing bone count"))?;
+		
+		let mut bones = vec![];
+		let mut morphs = vec![];
+		
+		while let Some(line) = lines.next() {
+			let (kind, name) = line.split_once('{').ok_or(VpdSyntaxError("expected bone or morph block"))?;
+			let name = name.trim().to_string();
+			
+			if kind.starts_with("Bone") {
+				let [x, y, z] = parse_values(lines.next())?;
+				let translation = vector!(x, y, z).from_mmd();
+				let [x, y, z, w] = parse_values(lines.next())?;
+				let rotation = Rot3::new_normalize(Quaternion::new(w, -x, -y, z));
+				
+				bones.push(VpdBone { name, translation, rotation });
+			} else if kind.starts_with("Morph") {
+				let [weight] = parse_values(lines.next())?;
+				
+				morphs.push(VpdMorph { name, weight });
+			} else {
+				return Err(VpdSyntaxError("unknown block").into());
+			}
+			
+			if lines.next() != Some("}") {
+				return Err(VpdSyntaxError("unclosed block").into());
+			}
+		}
+		
+		Ok(VpdPose {
+			model_name,
+			bones,
+			morphs,
+		})
+	}
+	
+	/// Encodes the pose as Shift JIS text, the way MikuMikuDance does.
+	pub fn write(&self) -> Vec<u8> {
+		let mut text = format!("{}\r\n\r\n{}.osm;\r\n{};\r\n\r\n", VPD_MAGIC, self.model_name, self.bones.len());
+		
+		for (id, bone) in self.bones.iter().enumerate() {
+			let translation = bone.translation / MMD_UNIT_SIZE;
+			let rotation = bone.rotation.quaternion();
+			
+			text += &format!("Bone{}{{{}\r\n", id, bone.name);
+			text += &format!("  {:.6},{:.6},{:.6};\r\n", translation.x, translation.y, -translation.z);
+			text += &format!("  {:.6},{:.6},{:.6},{:.6};\r\n", -rotation.i, -rotation.j, rotation.k, rotation.w);
+			text += "}\r\n\r\n";
+		}
+		
+		for (id, morph) in self.morphs.iter().enumerate() {
+			text += &format!("Morph{}{{{}\r\n", id, morph.name);
+			text += &format!("  {:.6};\r\n", morph.weight);
+			text += "}\r\n\r\n";
+		}
+		
+		SHIFT_JIS.encode(&text).0.into_owned()
+	}
+	
+	/// Captures manual pose of the model, see `MMDBone::pose_transform`. Only bones and morphs that differ from rest are stored.
+	pub fn capture(model: &MMDModel, model_name: impl Into<String>) -> Self {
+		let state = model.state();
+		
+		let bones = state.bones
+		                 .iter()
+		                 .filter(|bone| bone.pose_transform != Isometry3::identity())
+		                 .map(|bone| VpdBone {
+			                 name: bone.local_name.clone(),
+			                 translation: bone.pose_transform.translation.vector,
+			                 rotation: bone.pose_transform.rotation,
+		                 })
+		                 .collect();
+		
+		let morphs = model.morphs_names()
+		                  .iter()
+		                  .zip(&state.morphs)
+		                  .filter(|(_, weight)| weight.abs() > f32::EPSILON)
+		                  .map(|(name, &weight)| VpdMorph { name: name.clone(), weight })
+		                  .collect();
+		
+		VpdPose {
+			model_name: model_name.into(),
+			bones,
+			morphs,
+		}
+	}
+	
+	/// Replaces manual pose of the model. Bones are matched by name, the ones missing from the pose are reset.
+	pub fn apply(&self, model: &MMDModel) {
+		let state = &mut *model.state_mut();
+		
+		for bone in &mut state.bones {
+			bone.pose_transform = self.bones
+			                          .iter()
+			                          .find(|pose| pose.name == bone.local_name || pose.name == bone.name)
+			                          .map_or_else(Isometry3::identity, |pose| Isometry3::from_parts(pose.translation.into(), pose.rotation));
+		}
+		
+		for morph in &self.morphs {
+			if let Some(id) = model.morphs_names().iter().position(|name| *name == morph.name) {
+				state.morphs[id] = morph.weight;
+			}
+		}
+	}
+}
+
+fn parse_values<const N: usize>(line: Option<&str>) -> Result<[f32; N]> {
+	let line = line.ok_or(VpdSyntaxError("unexpected end of file"))?;
+	let mut values = [0.0; N];
+	let mut parts = line.trim_end_matches(';').split(',');
+	
+	for value in values.iter_mut() {
+		*value = parts.next()
+		              .ok_or(VpdSyntaxError("not enough values"))?
+		              .trim()
+		              .parse()?;
+	}
+	
+	Ok(values)
+}
+
+#[derive(Debug, Error)]
+#[error("Not a VPD pose file.")]
+pub struct VpdInvalidHeaderError;
+
+#[derive(Debug, Error)]
+#[error("Malformed VPD pose: {0}.")]
+pub struct VpdSyntaxError(&'static str);
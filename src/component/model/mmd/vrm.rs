@@ -0,0 +1,493 @@
+use std::collections::BTreeMap;
+use anyhow::Result;
+use serde_derive::Deserialize;
+
+use crate::math::Vec3;
+use super::BodyPart;
+use super::gltf::GltfSyntaxError;
+
+
+/// Humanoid bones simulated as ragdoll parts: body part, humanoid bone the part reaches, radius in meters and rotation limits in degrees.
+/// Hips are the root of the ragdoll, they are not jointed.
+pub const RAGDOLL: &[(&str, BodyPart, Option<&str>, f32, [f32; 3])] = &[
+	("hips",          BodyPart::Hip,          Some("spine"),         0.12,  [ 0.0,  0.0,  0.0]),
+	("spine",         BodyPart::Abdomen,      Some("chest"),         0.11,  [20.0, 20.0, 15.0]),
+	("chest",         BodyPart::Torso,        Some("neck"),          0.12,  [20.0, 20.0, 15.0]),
+	("neck",          BodyPart::Neck,         Some("head"),          0.05,  [30.0, 40.0, 20.0]),
+	("head",          BodyPart::Head,         None,                  0.10,  [30.0, 50.0, 20.0]),
+	("leftUpperLeg",  BodyPart::LeftThigh,    Some("leftLowerLeg"),  0.07,  [70.0, 30.0, 30.0]),
+	("rightUpperLeg", BodyPart::RightThigh,   Some("rightLowerLeg"), 0.07,  [70.0, 30.0, 30.0]),
+	("leftLowerLeg",  BodyPart::LeftCalf,     Some("leftFoot"),      0.055, [80.0,  5.0,  5.0]),
+	("rightLowerLeg", BodyPart::RightCalf,    Some("rightFoot"),     0.055, [80.0,  5.0,  5.0]),
+	("leftFoot",      BodyPart::LeftFoot,     Some("leftToes"),      0.045, [30.0, 15.0, 15.0]),
+	("rightFoot",     BodyPart::RightFoot,    Some("rightToes"),     0.045, [30.0, 15.0, 15.0]),
+	("leftUpperArm",  BodyPart::LeftArm,      Some("leftLowerArm"),  0.045, [80.0, 80.0, 80.0]),
+	("rightUpperArm", BodyPart::RightArm,     Some("rightLowerArm"), 0.045, [80.0, 80.0, 80.0]),
+	("leftLowerArm",  BodyPart::LeftForearm,  Some("leftHand"),      0.04,  [10.0, 80.0, 10.0]),
+	("rightLowerArm", BodyPart::RightForearm, Some("rightHand"),     0.04,  [10.0, 80.0, 10.0]),
+	("leftHand",      BodyPart::LeftHand,     None,                  0.04,  [40.0, 30.0, 40.0]),
+	("rightHand",     BodyPart::RightHand,    None,                  0.04,  [40.0, 30.0, 40.0]),
+];
+
+/// Roles of bones driven by `Miku`, by humanoid bone.
+pub const BONE_ROLES: &[(&str, &str)] = &[
+	("hips", "lower_body"),
+	("spine", "upper_body"),
+	("chest", "upper_body2"),
+	("neck", "neck"),
+	("head", "head"),
+	("leftEye", "left_eye"),
+	("rightEye", "right_eye"),
+	("leftShoulder", "left_shoulder"),
+	("rightShoulder", "right_shoulder"),
+	("leftUpperArm", "left_arm"),
+	("rightUpperArm", "right_arm"),
+	("leftLowerArm", "left_elbow"),
+	("rightLowerArm", "right_elbow"),
+	("leftHand", "left_wrist"),
+	("rightHand", "right_wrist"),
+	("leftUpperLeg", "left_leg"),
+	("rightUpperLeg", "right_leg"),
+	("leftLowerLeg", "left_knee"),
+	("rightLowerLeg", "right_knee"),
+	("leftFoot", "left_ankle"),
+	("rightFoot", "right_ankle"),
+];
+
+/// Roles of morphs driven by `Miku`, by expression preset.
+pub const MORPH_ROLES: &[(&str, &str)] = &[
+	("aa", "a"),
+	("ih", "i"),
+	("ou", "u"),
+	("ee", "e"),
+	("oh", "o"),
+	("blink", "blink"),
+	("blinkLeft", "wink"),
+	("blinkRight", "wink_right"),
+	("happy", "smile"),
+	("angry", "anger"),
+	("sad", "troubled"),
+	("relaxed", "calm"),
+	("surprised", "surprised"),
+];
+
+/// VRM avatar data stored in glTF extensions. Both VRM 0.x and 1.0 are read into the same shape, using 1.0 names.
+#[derive(Debug, Clone, Default)]
+pub struct Vrm {
+	/// VRM 0.x avatars face -Z, unlike glTF models.
+	pub legacy: bool,
+	/// Humanoid bone names with their nodes.
+	pub humanoid: BTreeMap<String, usize>,
+	pub expressions: Vec<VrmExpression>,
+	pub springs: Vec<VrmSpringJoint>,
+	pub colliders: Vec<VrmCollider>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VrmExpression {
+	pub name: String,
+	pub preset: Option<String>,
+	/// Morph targets by mesh and target index, with weights.
+	pub binds: Vec<(usize, usize, f32)>,
+}
+
+/// Node swinging with the spring bone simulation.
+#[derive(Debug, Clone)]
+pub struct VrmSpringJoint {
+	pub node: usize,
+	pub hit_radius: f32,
+	pub stiffness: f32,
+	pub drag: f32,
+}
+
+/// Sphere pushing spring bones away, in node's space.
+#[derive(Debug, Clone)]
+pub struct VrmCollider {
+	pub node: usize,
+	pub offset: Vec3,
+	pub radius: f32,
+}
+
+impl Vrm {
+	/// Returns `None` if the model has no VRM extensions. `children` and `meshes` are looked up in the glTF document, by node.
+	/// Fails if any node is not below `nodes`, the count of document's nodes.
+	pub fn read(json: &serde_json::Value,
+	            nodes: usize,
+	            children: impl Fn(usize) -> Vec<usize>,
+	            meshes: impl Fn(usize) -> Option<usize>)
+	            -> Result<Option<Self>> {
+		let GltfJson { extensions } = serde::Deserialize::deserialize(json)?;
+		
+		let mut vrm = if let Some(vrm0) = extensions.vrm0 {
+			// Roots of spring groups are only walked, a bad one would have no children and vanish silently
+			if vrm0.secondary_animation.bone_groups.iter().flat_map(|group| &group.bones).any(|&node| node as i64 >= nodes as i64) {
+				return Err(GltfSyntaxError("VRM spring bone node out of range").into());
+			}
+			
+			Vrm::from_vrm0(vrm0, children)
+		} else if let Some(vrm1) = extensions.vrm1 {
+			Vrm::from_vrm1(vrm1, meshes)
+		} else {
+			return Ok(None);
+		};
+		
+		if let Some(spring_bone) = extensions.spring_bone {
+			vrm.add_spring_bone1(spring_bone);
+		}
+		
+		let out_of_range = vrm.humanoid.values().copied()
+		                      .chain(vrm.springs.iter().map(|spring| spring.node))
+		                      .chain(vrm.colliders.iter().map(|collider| collider.node))
+		                      .any(|node| node >= nodes);
+		
+		if out_of_range {
+			return Err(GltfSyntaxError("VRM node out of range").into());
+		}
+		
+		Ok(Some(vrm))
+	}
+	
+	/// Drops humanoid bones, spring joints and colliders of nodes rejected by `keep`. Returns how many were dropped.
+	pub fn retain_nodes(&mut self, keep: impl Fn(usize) -> bool) -> usize {
+		let count = |vrm: &Vrm| vrm.humanoid.len() + vrm.springs.len() + vrm.colliders.len();
+		let before = count(self);
+		
+		self.humanoid.retain(|_, node| keep(*node));
+		self.springs.retain(|spring| keep(spring.node));
+		self.colliders.retain(|collider| keep(collider.node));
+		
+		before - count(self)
+	}
+	
+	fn from_vrm0(vrm0: Vrm0, children: impl Fn(usize) -> Vec<usize>) -> Self {
+		let humanoid = vrm0.humanoid
+		                   .human_bones
+		                   .into_iter()
+		                   .filter(|bone| bone.node >= 0)
+		                   .map(|bone| (bone.bone, bone.node as usize))
+		                   .collect();
+		
+		let expressions = vrm0.blend_shape_master
+		                      .blend_shape_groups
+		                      .into_iter()
+		                      .map(|group| VrmExpression {
+			                      preset: preset_vrm0(&group.preset_name).map(Into::into),
+			                      name: group.name,
+			                      binds: group.binds
+			                                  .iter()
+			                                  .filter(|bind| bind.mesh >= 0 && bind.index >= 0)
+			                                  .map(|bind| (bind.mesh as usize, bind.index as usize, bind.weight / 100.0))
+			                                  .collect(),
+		                      })
+		                      .collect();
+		
+		// Groups list roots of swinging subtrees, leaves are only their tips
+		let mut springs = vec![];
+		for group in &vrm0.secondary_animation.bone_groups {
+			let mut stack: Vec<usize> = group.bones.iter().filter(|&&node| node >= 0).map(|&node| node as usize).collect();
+			
+			while let Some(node) = stack.pop() {
+				let node_children = children(node);
+				if node_children.is_empty() { continue; }
+				
+				springs.push(VrmSpringJoint {
+					node,
+					hit_radius: group.hit_radius,
+					stiffness: group.stiffness,
+					drag: group.drag_force,
+				});
+				
+				stack.extend(node_children);
+			}
+		}
+		
+		let colliders = vrm0.secondary_animation
+		                    .collider_groups
+		                    .iter()
+		                    .filter(|group| group.node >= 0)
+		                    .flat_map(|group| group.colliders.iter().map(move |collider| VrmCollider {
+			                    node: group.node as usize,
+			                    // VRM 0.x stores offsets in Unity's left-handed space
+			                    offset: vector!(collider.offset.x, collider.offset.y, -collider.offset.z),
+			                    radius: collider.radius,
+		                    }))
+		                    .collect();
+		
+		Vrm {
+			legacy: true,
+			humanoid,
+			expressions,
+			springs,
+			colliders,
+		}
+	}
+	
+	fn from_vrm1(vrm1: Vrm1, meshes: impl Fn(usize) -> Option<usize>) -> Self {
+		let humanoid = vrm1.humanoid
+		                   .human_bones
+		                   .into_iter()
+		                   .map(|(name, bone)| (name, bone.node))
+		                   .collect();
+		
+		let expression = |name: String, preset: bool, expression: Vrm1Expression| VrmExpression {
+			preset: preset.then(|| name.clone()),
+			name,
+			binds: expression.morph_target_binds
+			                 .iter()
+			                 .filter_map(|bind| meshes(bind.node).map(|mesh| (mesh, bind.index, bind.weight)))
+			                 .collect(),
+		};
+		
+		let expressions = vrm1.expressions.preset.into_iter().map(|(name, expr)| expression(name, true, expr))
+		                      .chain(vrm1.expressions.custom.into_iter().map(|(name, expr)| expression(name, false, expr)))
+		                      .collect();
+		
+		Vrm {
+			legacy: false,
+			humanoid,
+			expressions,
+			springs: vec![],
+			colliders: vec![],
+		}
+	}
+	
+	fn add_spring_bone1(&mut self, spring_bone: SpringBone1) {
+		for spring in &spring_bone.springs {
+			// The last joint is the tip of the chain
+			let swinging = spring.joints.len().saturating_sub(1);
+			
+			self.springs.extend(spring.joints[..swinging].iter().map(|joint| VrmSpringJoint {
+				node: joint.node,
+				hit_radius: joint.hit_radius,
+				stiffness: joint.stiffness,
+				drag: joint.drag_force,
+			}));
+		}
+		
+		// Capsules are approximated with spheres at their heads
+		self.colliders.extend(spring_bone.colliders.iter().filter_map(|collider| {
+			let sphere = collider.shape.sphere.as_ref().or(collider.shape.capsule.as_ref())?;
+			
+			Some(VrmCollider {
+				node: collider.node,
+				offset: sphere.offset.into(),
+				radius: sphere.radius,
+			})
+		}));
+	}
+}
+
+/// Maps VRM 0.x preset names to 1.0 ones.
+fn preset_vrm0(preset: &str) -> Option<&'static str> {
+	Some(match preset {
+		"a" => "aa",
+		"i" => "ih",
+		"u" => "ou",
+		"e" => "ee",
+		"o" => "oh",
+		"blink" => "blink",
+		"blink_l" => "blinkLeft",
+		"blink_r" => "blinkRight",
+		"joy" => "happy",
+		"angry" => "angry",
+		"sorrow" => "sad",
+		"fun" => "relaxed",
+		"neutral" => "neutral",
+		"lookup" => "lookUp",
+		"lookdown" => "lookDown",
+		"lookleft" => "lookLeft",
+		"lookright" => "lookRight",
+		_ => return None,
+	})
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct GltfJson {
+	extensions: Extensions,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Extensions {
+	#[serde(rename = "VRM")] vrm0: Option<Vrm0>,
+	#[serde(rename = "VRMC_vrm")] vrm1: Option<Vrm1>,
+	#[serde(rename = "VRMC_springBone")] spring_bone: Option<SpringBone1>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Vrm0 {
+	humanoid: Vrm0Humanoid,
+	blend_shape_master: Vrm0BlendShapeMaster,
+	secondary_animation: Vrm0SecondaryAnimation,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Vrm0Humanoid {
+	human_bones: Vec<Vrm0HumanBone>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Vrm0HumanBone {
+	bone: String,
+	node: i32,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Vrm0BlendShapeMaster {
+	blend_shape_groups: Vec<Vrm0BlendShapeGroup>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Vrm0BlendShapeGroup {
+	name: String,
+	preset_name: String,
+	binds: Vec<Vrm0Bind>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Vrm0Bind {
+	mesh: i32,
+	index: i32,
+	/// In percents.
+	weight: f32,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Vrm0SecondaryAnimation {
+	bone_groups: Vec<Vrm0BoneGroup>,
+	collider_groups: Vec<Vrm0ColliderGroup>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Vrm0BoneGroup {
+	// Sic
+	#[serde(rename = "stiffiness")] stiffness: f32,
+	drag_force: f32,
+	hit_radius: f32,
+	bones: Vec<i32>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Vrm0ColliderGroup {
+	node: i32,
+	colliders: Vec<Vrm0Collider>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Vrm0Collider {
+	offset: Vrm0Vec3,
+	radius: f32,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Vrm0Vec3 {
+	x: f32,
+	y: f32,
+	z: f32,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Vrm1 {
+	humanoid: Vrm1Humanoid,
+	expressions: Vrm1Expressions,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Vrm1Humanoid {
+	human_bones: BTreeMap<String, Vrm1HumanBone>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Vrm1HumanBone {
+	node: usize,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Vrm1Expressions {
+	preset: BTreeMap<String, Vrm1Expression>,
+	custom: BTreeMap<String, Vrm1Expression>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Vrm1Expression {
+	morph_target_binds: Vec<Vrm1Bind>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Vrm1Bind {
+	node: usize,
+	index: usize,
+	weight: f32,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct SpringBone1 {
+	colliders: Vec<Spring1Collider>,
+	springs: Vec<Spring1Spring>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Spring1Collider {
+	node: usize,
+	shape: Spring1Shape,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Spring1Shape {
+	sphere: Option<Spring1Sphere>,
+	capsule: Option<Spring1Sphere>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Spring1Sphere {
+	offset: [f32; 3],
+	radius: f32,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Spring1Spring {
+	joints: Vec<Spring1Joint>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Spring1Joint {
+	node: usize,
+	hit_radius: f32,
+	stiffness: f32,
+	drag_force: f32,
+}
+
+impl Default for Spring1Joint {
+	fn default() -> Self {
+		Spring1Joint {
+			node: 0,
+			hit_radius: 0.0,
+			stiffness: 1.0,
+			drag_force: 0.5,
+		}
+	}
+}
@@ -1,24 +1,42 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Display, Formatter};
 use std::path::{PathBuf, Path};
 use anyhow::Result;
-use obj::Obj;
+use gltf::material::AlphaMode;
+use gltf::mesh::Mode;
+use image::{DynamicImage, Rgba, RgbaImage};
+use obj::raw::{parse_mtl, parse_obj, RawObj};
+use obj::raw::material::MtlColor;
+use obj::raw::object::Polygon;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
 
+use crate::math::{Color, Mat4, Point3, Vec2, Vec3};
 use crate::renderer::Renderer;
-use crate::renderer::assets_manager::{AssetKey, AssetsManager, TextureAsset};
-use super::{SimpleModel, Vertex};
+use crate::renderer::assets_manager::{AssetKey, AssetsManager, TextureAsset, TextureBundle};
+use crate::utils::from_gltf_image;
+use super::{SimpleMaterial, SimpleModel, Vertex};
 
 
-#[derive(Clone, Hash, Debug)]
+/// Wavefront OBJ model. Faces are split by their MTL materials, unless a texture overrides all of them.
+#[derive(Clone, Hash, Debug, Deserialize, Serialize)]
 pub struct ObjAsset {
 	model: PathBuf,
-	texture: TextureAsset,
+	texture: Option<TextureAsset>,
 }
 
 impl ObjAsset {
-	pub fn at(model_path: impl AsRef<Path>, texture: impl Into<TextureAsset>) -> Self {
+	pub fn at(model_path: impl AsRef<Path>) -> Self {
 		ObjAsset {
 			model: model_path.as_ref().to_path_buf(),
-			texture: texture.into(),
+			texture: None,
+		}
+	}
+	
+	pub fn texture(self, texture: impl Into<TextureAsset>) -> Self {
+		ObjAsset {
+			texture: Some(texture.into()),
+			..self
 		}
 	}
 }
@@ -27,30 +45,297 @@ impl AssetKey for ObjAsset {
 	type Asset = SimpleModel;
 	
 	fn load(&self, assets_manager: &mut AssetsManager, renderer: &mut Renderer) -> Result<Self::Asset> {
-		let texture = assets_manager.load(self.texture.clone(), renderer)?;
-		let model: Obj<obj::TexturedVertex, u32> = obj::load_obj(AssetsManager::find_asset(&self.model)?)?;
+		let mut root = PathBuf::from(&self.model);
+		root.pop();
+		
+		let raw = parse_obj(AssetsManager::find_asset(&self.model)?)?;
+		
+		let mut mtl = HashMap::new();
+		if self.texture.is_none() {
+			for library in &raw.material_libraries {
+				match AssetsManager::find_asset(root.join(library)) {
+					Ok(file) => mtl.extend(parse_mtl(file)?.materials),
+					Err(err) => eprintln!("Missing material library of {}: {}", self.model.to_string_lossy(), err),
+				}
+			}
+		}
+		
+		let mut polygon_materials = vec![None; raw.polygons.len()];
+		for (name, group) in &raw.meshes {
+			for range in &group.polygons {
+				for material in &mut polygon_materials[range.start .. range.end] {
+					*material = Some(name.as_str());
+				}
+			}
+		}
 		
-		Ok(SimpleModel::new(
-			&model.vertices.iter().map(Into::into).collect::<Vec<_>>(),
-			&model.indices,
-			texture,
-			renderer,
-		)?)
+		// Corners sharing position, UV and normal are merged
+		let mut vertices = vec![];
+		let mut vertex_ids = HashMap::new();
+		let mut groups = BTreeMap::<_, Vec<u32>>::new();
+		
+		for (polygon, material) in raw.polygons.iter().zip(polygon_materials) {
+			let corners: Vec<(usize, Option<usize>, Option<usize>)> = match polygon {
+				Polygon::P(ids) => ids.iter().map(|&pos| (pos, None, None)).collect(),
+				Polygon::PT(ids) => ids.iter().map(|&(pos, uv)| (pos, Some(uv), None)).collect(),
+				Polygon::PN(ids) => ids.iter().map(|&(pos, normal)| (pos, None, Some(normal))).collect(),
+				Polygon::PTN(ids) => ids.iter().map(|&(pos, uv, normal)| (pos, Some(uv), Some(normal))).collect(),
+			};
+			
+			let mut ids = Vec::with_capacity(corners.len());
+			for corner in corners {
+				let id = match vertex_ids.get(&corner) {
+					Some(&id) => id,
+					None => {
+						vertices.push(obj_vertex(&raw, corner)?);
+						vertex_ids.insert(corner, vertices.len() as u32 - 1);
+						vertices.len() as u32 - 1
+					},
+				};
+				
+				ids.push(id);
+			}
+			
+			// Polygons are triangulated as fans
+			let indices = groups.entry(material).or_default();
+			for id in 1 .. ids.len().saturating_sub(1) {
+				indices.extend([ids[0], ids[id], ids[id + 1]]);
+			}
+		}
+		
+		let mut indices = vec![];
+		let mut materials = vec![];
+		
+		for (name, group) in groups {
+			let range = indices.len() as u32 .. (indices.len() + group.len()) as u32;
+			indices.extend(group);
+			
+			let material = name.and_then(|name| mtl.get(name));
+			let alpha = material.and_then(|material| material.dissolve).unwrap_or(1.0);
+			let diffuse = match material.and_then(|material| material.diffuse.as_ref()) {
+				Some(&MtlColor::Rgb(r, g, b)) => Color::new(r * alpha, g * alpha, b * alpha, alpha),
+				_ => Color::FULL_WHITE.opactiy(alpha),
+			};
+			
+			let (texture, color) = match (&self.texture, material.and_then(|material| material.diffuse_map.as_ref())) {
+				(Some(texture), _) => (assets_manager.load(texture.clone(), renderer)?, Color::FULL_WHITE),
+				(None, Some(map)) => (assets_manager.load(TextureAsset::at(root.join(&map.file)), renderer)?, Color::FULL_WHITE.opactiy(alpha)),
+				(None, None) => (white_texture(renderer)?, diffuse),
+			};
+			
+			materials.push(SimpleMaterial {
+				range,
+				texture,
+				color,
+				transparent: alpha < 1.0,
+				alpha_cutoff: 0.0,
+				double_sided: false,
+			});
+		}
+		
+		let mut model = SimpleModel::with_materials(&vertices, &indices, materials, renderer)?;
+		
+		model.source = Some(self.clone().into());
+		
+		Ok(model)
 	}
 }
 
 impl Display for ObjAsset {
 	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-		write!(f, "OBJ model {} ({})", self.model.to_string_lossy(), self.texture)
+		match &self.texture {
+			Some(texture) => write!(f, "OBJ model {} ({})", self.model.to_string_lossy(), texture),
+			None => write!(f, "OBJ model {}", self.model.to_string_lossy()),
+		}
+	}
+}
+
+/// Static glTF or GLB model, with its own textures and materials. Nodes are merged into a single mesh, skins and morph targets are ignored.
+#[derive(Clone, Hash, Debug, Deserialize, Serialize)]
+pub struct GltfPropAsset {
+	#[serde(rename = "gltf")]
+	model: PathBuf,
+}
+
+impl GltfPropAsset {
+	pub fn at(model_path: impl AsRef<Path>) -> Self {
+		GltfPropAsset {
+			model: model_path.as_ref().to_path_buf(),
+		}
+	}
+}
+
+impl AssetKey for GltfPropAsset {
+	type Asset = SimpleModel;
+	
+	fn load(&self, _assets_manager: &mut AssetsManager, renderer: &mut Renderer) -> Result<Self::Asset> {
+		let (document, buffers, images) = gltf::import(AssetsManager::find_asset_path(&self.model)?)?;
+		
+		let scene = document.default_scene()
+		                    .or_else(|| document.scenes().next())
+		                    .ok_or(GltfPropNoSceneError)?;
+		
+		let mut vertices = vec![];
+		let mut indices = vec![];
+		let mut materials = vec![];
+		let mut textures = BTreeMap::new();
+		let mut stack: Vec<_> = scene.nodes().map(|node| (node, Mat4::identity())).collect();
+		
+		while let Some((node, parent_transform)) = stack.pop() {
+			let transform = parent_transform * Mat4::from(node.transform().matrix());
+			
+			stack.extend(node.children().map(|child| (child, transform)));
+			
+			let mesh = match node.mesh() {
+				Some(mesh) => mesh,
+				None => continue,
+			};
+			
+			for primitive in mesh.primitives().filter(|primitive| primitive.mode() == Mode::Triangles) {
+				let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+				let base = vertices.len() as u32;
+				
+				let positions = reader.read_positions()
+				                      .ok_or(GltfPropSyntaxError("primitive has no positions"))?
+				                      .map(Vec3::from)
+				                      .collect::<Vec<_>>();
+				let normals = reader.read_normals()
+				                    .map_or_else(|| vec![Vec3::y(); positions.len()], |normals| normals.map(Vec3::from).collect());
+				let uvs = reader.read_tex_coords(0)
+				                .map_or_else(|| vec![Vec2::zeros(); positions.len()], |uvs| uvs.into_f32().map(Vec2::from).collect());
+				
+				for ((pos, normal), uv) in positions.iter().zip(&normals).zip(&uvs) {
+					vertices.push(Vertex::new(transform.transform_point(&Point3::from(*pos)).coords,
+					                          transform.transform_vector(normal).normalize(),
+					                          uv));
+				}
+				
+				let start = indices.len() as u32;
+				match reader.read_indices() {
+					Some(read) => indices.extend(read.into_u32().map(|index| base + index)),
+					None => indices.extend(base .. base + positions.len() as u32),
+				}
+				
+				let material = primitive.material();
+				let pbr = material.pbr_metallic_roughness();
+				let image = pbr.base_color_texture().map(|info| info.texture().source().index());
+				
+				// Images are uploaded once, untextured materials share a white one
+				if !textures.contains_key(&image) {
+					let texture = match image {
+						Some(id) => TextureBundle::from_raw_simple(from_gltf_image(images[id].clone())?, renderer)?,
+						None => white_texture(renderer)?,
+					};
+					
+					textures.insert(image, texture);
+				}
+				
+				let [r, g, b, a] = pbr.base_color_factor();
+				
+				materials.push(SimpleMaterial {
+					range: start .. indices.len() as u32,
+					texture: textures[&image].clone(),
+					color: Color::new(r * a, g * a, b * a, a),
+					transparent: material.alpha_mode() == AlphaMode::Blend,
+					alpha_cutoff: match material.alpha_mode() {
+						AlphaMode::Mask => material.alpha_cutoff().unwrap_or(0.5),
+						_ => 0.0,
+					},
+					double_sided: material.double_sided(),
+				});
+			}
+		}
+		
+		let mut model = SimpleModel::with_materials(&vertices, &indices, materials, renderer)?;
+		
+		model.source = Some(self.clone().into());
+		
+		Ok(model)
+	}
+}
+
+impl Display for GltfPropAsset {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "glTF prop {}", self.model.to_string_lossy())
+	}
+}
+
+/// Source of a `SimpleModel`, saved with it so it can be loaded back.
+/// OBJ models keep their path in `model` and glTF ones in `gltf`, which is how the variants are told apart.
+#[derive(Clone, Hash, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum SimpleAsset {
+	Obj(ObjAsset),
+	Gltf(GltfPropAsset),
+}
+
+impl SimpleAsset {
+	/// Picks the format by extension of the model. The texture overrides materials of OBJ models, glTF ones ignore it.
+	pub fn at(model_path: impl AsRef<Path>, texture: Option<&Path>) -> Self {
+		let model_path = model_path.as_ref();
+		
+		match (model_path.extension(), texture) {
+			(Some(ext), _) if ext.eq_ignore_ascii_case("gltf") || ext.eq_ignore_ascii_case("glb") => SimpleAsset::Gltf(GltfPropAsset::at(model_path)),
+			(_, Some(texture)) => SimpleAsset::Obj(ObjAsset::at(model_path).texture(texture)),
+			(_, None) => SimpleAsset::Obj(ObjAsset::at(model_path)),
+		}
+	}
+}
+
+impl From<ObjAsset> for SimpleAsset {
+	fn from(asset: ObjAsset) -> Self {
+		SimpleAsset::Obj(asset)
+	}
+}
+
+impl From<GltfPropAsset> for SimpleAsset {
+	fn from(asset: GltfPropAsset) -> Self {
+		SimpleAsset::Gltf(asset)
 	}
 }
 
-impl From<&obj::TexturedVertex> for Vertex {
-	fn from(vertex: &obj::TexturedVertex) -> Self {
-		Vertex::new(
-			vertex.position,
-			vertex.normal,
-			[vertex.texture[0], 1.0 - vertex.texture[1]]
-		)
+impl AssetKey for SimpleAsset {
+	type Asset = SimpleModel;
+	
+	fn load(&self, assets_manager: &mut AssetsManager, renderer: &mut Renderer) -> Result<Self::Asset> {
+		match self {
+			SimpleAsset::Obj(asset) => asset.load(assets_manager, renderer),
+			SimpleAsset::Gltf(asset) => asset.load(assets_manager, renderer),
+		}
+	}
+}
+
+impl Display for SimpleAsset {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			SimpleAsset::Obj(asset) => asset.fmt(f),
+			SimpleAsset::Gltf(asset) => asset.fmt(f),
+		}
 	}
 }
+
+/// OBJ corner, indices of its position, UV and normal.
+fn obj_vertex(raw: &RawObj, (pos, uv, normal): (usize, Option<usize>, Option<usize>)) -> Result<Vertex> {
+	let (x, y, z, _) = *raw.positions.get(pos).ok_or(ObjSyntaxError("position out of range"))?;
+	let (u, v, _) = uv.map_or(Ok((0.0, 0.0, 0.0)), |uv| raw.tex_coords.get(uv).copied().ok_or(ObjSyntaxError("UV out of range")))?;
+	let (nx, ny, nz) = normal.map_or(Ok((0.0, 1.0, 0.0)), |normal| raw.normals.get(normal).copied().ok_or(ObjSyntaxError("normal out of range")))?;
+	
+	Ok(Vertex::new([x, y, z], [nx, ny, nz], [u, 1.0 - v]))
+}
+
+/// Texture of untextured materials, so they can be tinted with their color.
+fn white_texture(renderer: &mut Renderer) -> Result<TextureBundle> {
+	TextureBundle::from_raw_simple(DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([255; 4]))), renderer)
+}
+
+#[derive(Debug, Error)]
+#[error("glTF prop has no scenes.")]
+pub struct GltfPropNoSceneError;
+
+#[derive(Debug, Error)]
+#[error("Malformed glTF prop: {0}.")]
+pub struct GltfPropSyntaxError(&'static str);
+
+#[derive(Debug, Error)]
+#[error("Malformed OBJ model: {0}.")]
+pub struct ObjSyntaxError(&'static str);
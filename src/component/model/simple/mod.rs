@@ -1,5 +1,7 @@
+use std::ops::Range;
 use std::sync::Arc;
 use anyhow::Result;
+use thiserror::Error;
 use vulkano::buffer::{Buffer, Subbuffer, BufferUsage};
 use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryCommandBufferAbstract};
 use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
@@ -9,27 +11,66 @@ use vulkano::sync::GpuFuture;
 pub mod asset;
 
 pub use crate::renderer::pipelines::default::Vertex;
-use crate::application::Entity;
+use crate::application::{Application, Entity};
 use crate::math::{AABB, aabb_from_points, Color, Point3, Similarity3};
 use crate::renderer::{RenderContext, Renderer, RenderType};
 use crate::renderer::assets_manager::TextureBundle;
-use crate::renderer::pipelines::default::{DefaultPipeline, Pc};
+use crate::renderer::pipelines::default::{DefaultNoCullPipeline, DefaultPipeline, Pc};
 use crate::renderer::pipelines::PipelineNoLayoutError;
 use crate::utils::{AutoCommandBufferBuilderEx, BufferEx, IntoInfo, FenceCheck, IndexSubbuffer};
-use super::super::{Component, ComponentBase, ComponentInner};
+use super::super::{Component, ComponentBase, ComponentInner, ComponentLoad};
 use super::VertexIndex;
-pub use asset::ObjAsset;
+pub use asset::{GltfPropAsset, ObjAsset, SimpleAsset};
 
 
 #[derive(ComponentBase, Clone)]
 pub struct SimpleModel {
 	#[inner] inner: ComponentInner,
 	aabb: AABB,
-	pipeline: Arc<GraphicsPipeline>,
+	sub_meshes: Vec<SimpleSubMesh>,
 	pub vertices: Subbuffer<[Vertex]>,
 	pub indices: IndexSubbuffer,
+	/// Descriptor set of the first material, for effects drawing the whole model at once.
 	pub set: Arc<PersistentDescriptorSet>,
 	pub fence: FenceCheck,
+	pub source: Option<SimpleAsset>,
+}
+
+/// Range of indices drawn with a single texture.
+#[derive(Clone)]
+pub struct SimpleMaterial {
+	pub range: Range<u32>,
+	pub texture: TextureBundle,
+	/// Multiplies the texture, with premultiplied alpha.
+	pub color: Color,
+	/// Drawn in the transparent pass.
+	pub transparent: bool,
+	/// Fragments with lower alpha are discarded, so cutouts don't need sorting. Zero keeps all of them.
+	pub alpha_cutoff: f32,
+	pub double_sided: bool,
+}
+
+#[derive(Clone)]
+struct SimpleSubMesh {
+	range: Range<u32>,
+	pipeline: Arc<GraphicsPipeline>,
+	set: Arc<PersistentDescriptorSet>,
+	color: Color,
+	transparent: bool,
+	alpha_cutoff: f32,
+}
+
+impl SimpleMaterial {
+	pub fn new(range: Range<u32>, texture: TextureBundle) -> Self {
+		SimpleMaterial {
+			range,
+			texture,
+			color: Color::FULL_WHITE,
+			transparent: false,
+			alpha_cutoff: 0.0,
+			double_sided: false,
+		}
+	}
 }
 
 #[allow(dead_code)]
@@ -40,8 +81,16 @@ impl SimpleModel {
 	               renderer: &mut Renderer)
 	               -> Result<SimpleModel>
 	               where VI: VertexIndex {
+		SimpleModel::with_materials(vertices, indices, vec![SimpleMaterial::new(0..indices.len() as u32, texture)], renderer)
+	}
+	
+	pub fn with_materials<VI>(vertices: &[Vertex],
+	                          indices: &[VI],
+	                          materials: Vec<SimpleMaterial>,
+	                          renderer: &mut Renderer)
+	                          -> Result<SimpleModel>
+	                          where VI: VertexIndex {
 		let aabb = aabb_from_points(vertices.iter().map(|v| Point3::from(v.pos)));
-		let pipeline = renderer.pipelines.get::<DefaultPipeline>()?;
 		
 		let mut upload_buffer = AutoCommandBufferBuilder::primary(&*renderer.command_buffer_allocator,
 		                                                          renderer.load_queue.queue_family_index(),
@@ -57,25 +106,54 @@ impl SimpleModel {
 		                                  indices.iter().copied(),
 		                                  &mut upload_buffer)?;
 		
-		let set = PersistentDescriptorSet::new(&renderer.descriptor_set_allocator,
-		                                       pipeline.layout().set_layouts().get(0).ok_or(PipelineNoLayoutError)?.clone(), [
-			                                       WriteDescriptorSet::buffer(0, renderer.commons.clone()),
-			                                       WriteDescriptorSet::image_view_sampler(1, texture.image.clone(), texture.sampler.clone()),
-		                                       ])?;
+		let mut sub_meshes = vec![];
+		let mut upload_future = upload_buffer.build()?
+		                                     .execute(renderer.load_queue.clone())?
+		                                     .boxed();
+		
+		for material in materials {
+			let pipeline = if material.double_sided {
+				renderer.pipelines.get::<DefaultNoCullPipeline>()?
+			} else {
+				renderer.pipelines.get::<DefaultPipeline>()?
+			};
+			
+			let set = PersistentDescriptorSet::new(&renderer.descriptor_set_allocator,
+			                                       pipeline.layout().set_layouts().get(0).ok_or(PipelineNoLayoutError)?.clone(), [
+				                                       WriteDescriptorSet::buffer(0, renderer.commons.clone()),
+				                                       WriteDescriptorSet::image_view_sampler(1, material.texture.image.clone(), material.texture.sampler.clone()),
+			                                       ])?;
+			
+			upload_future = upload_future.join(material.texture.fence.future()).boxed();
+			
+			sub_meshes.push(SimpleSubMesh {
+				range: material.range,
+				pipeline,
+				set,
+				color: material.color,
+				transparent: material.transparent,
+				alpha_cutoff: material.alpha_cutoff,
+			});
+		}
 		
-		let upload_future = upload_buffer.build()?
-		                                 .execute(renderer.load_queue.clone())?;
+		let set = sub_meshes.first().ok_or(SimpleModelNoMaterialsError)?.set.clone();
+		let fence = FenceCheck::new(upload_future)?;
 		
-		let fence = FenceCheck::new(upload_future.join(texture.fence.future()))?;
+		let render_type = if sub_meshes.iter().any(|sub_mesh| sub_mesh.transparent) {
+			RenderType::Opaque | RenderType::Transparent
+		} else {
+			RenderType::Opaque.into()
+		};
 		
 		Ok(SimpleModel {
-			inner: ComponentInner::from_render_type(RenderType::Opaque),
+			inner: ComponentInner::from_render_type(render_type),
 			aabb,
-			pipeline,
+			sub_meshes,
 			vertices,
 			indices: indices.into(),
 			set,
 			fence,
+			source: None,
 		})
 	}
 	
@@ -91,40 +169,80 @@ impl SimpleModel {
 		self.aabb
 	}
 	
+	/// Draws all materials at once, regardless of their transparency.
 	pub fn render_impl(&self, transform: Similarity3, color: Color, context: &mut RenderContext) -> Result<()> {
+		self.render_sub_meshes(transform, color, None, context)
+	}
+	
+	/// Draws only opaque or only transparent materials, unless `transparent` is `None`.
+	fn render_sub_meshes(&self, transform: Similarity3, color: Color, transparent: Option<bool>, context: &mut RenderContext) -> Result<()> {
 		if !self.loaded() { return Ok(()) }
 		
-		context.builder.bind_pipeline_graphics(self.pipeline.clone())
-		               .bind_vertex_buffers(0, self.vertices.clone())
-		               .bind_any_index_buffer(self.indices.clone())
-		               .bind_descriptor_sets(PipelineBindPoint::Graphics,
-		                                     self.pipeline.layout().clone(),
-		                                     0,
-		                                     self.set.clone())
-		               .push_constants(self.pipeline.layout().clone(),
-		                               0,
-		                               Pc {
-			                               model: transform.to_homogeneous().into(),
-			                               color: color.into(),
-		                               })
-		               .draw_indexed(self.indices.len() as u32,
-		                             1,
-		                             0,
-		                             0,
-		                             0)?;
+		context.builder.bind_vertex_buffers(0, self.vertices.clone())
+		               .bind_any_index_buffer(self.indices.clone());
+		
+		for sub_mesh in &self.sub_meshes {
+			if transparent.map_or(false, |transparent| transparent != sub_mesh.transparent) {
+				continue;
+			}
+			
+			let mut color = color;
+			color.component_mul_assign(&*sub_mesh.color);
+			
+			context.builder.bind_pipeline_graphics(sub_mesh.pipeline.clone())
+			               .bind_descriptor_sets(PipelineBindPoint::Graphics,
+			                                     sub_mesh.pipeline.layout().clone(),
+			                                     0,
+			                                     sub_mesh.set.clone())
+			               .push_constants(sub_mesh.pipeline.layout().clone(),
+			                               0,
+			                               Pc {
+				                               model: transform.to_homogeneous().into(),
+				                               color: color.into(),
+				                               alpha_cutoff: sub_mesh.alpha_cutoff,
+			                               })
+			               .draw_indexed(sub_mesh.range.end - sub_mesh.range.start,
+			                             1,
+			                             sub_mesh.range.start,
+			                             0,
+			                             0)?;
+		}
 		
 		Ok(())
 	}
 }
 
+/// Models can only be loaded back if they came from a `SimpleAsset`. Returns `None` in headless mode.
+impl ComponentLoad for SimpleModel {
+	fn load(params: toml::Value, application: &Application) -> Result<Option<Self>> {
+		if application.headless() {
+			return Ok(None);
+		}
+		
+		let asset: SimpleAsset = params.try_into()?;
+		
+		Ok(Some(application.renderer()?.load(asset)?))
+	}
+}
+
 impl Component for SimpleModel {
+	fn save(&self, _entity: &Entity, _application: &Application) -> Result<Option<toml::Value>> {
+		match &self.source {
+			Some(source) => Ok(Some(toml::Value::try_from(source)?)),
+			None => Ok(None),
+		}
+	}
+	
 	fn render(&self, entity: &Entity, context: &mut RenderContext, _renderer: &mut Renderer) -> Result<()> {
 		let base_pos = *entity.state().position;
+		let transparent = context.render_type == RenderType::Transparent;
 		
-		self.render_impl(Similarity3::from_isometry(base_pos, 1.0), Color::FULL_WHITE, context)?;
+		self.render_sub_meshes(Similarity3::from_isometry(base_pos, 1.0), Color::FULL_WHITE, Some(transparent), context)?;
 		
 		Ok(())
 	}
 }
 
-
+#[derive(Debug, Error)]
+#[error("Model has no materials.")]
+pub struct SimpleModelNoMaterialsError;
@@ -3,7 +3,7 @@ use anyhow::Result;
 
 use crate::application::{Entity, Application, Key};
 use crate::math::{Vec3, Isometry3, PI, to_euler, from_euler};
-use super::{Component, ComponentBase, ComponentInner};
+use super::{Component, ComponentBase, ComponentInner, ComponentLoad};
 
 
 #[derive(ComponentBase)]
@@ -19,7 +19,17 @@ impl PCControlled {
 	}
 }
 
+impl ComponentLoad for PCControlled {
+	fn load(_params: toml::Value, _application: &Application) -> Result<Option<Self>> {
+		Ok(Some(PCControlled::new()))
+	}
+}
+
 impl Component for PCControlled {
+	fn save(&self, _entity: &Entity, _application: &Application) -> Result<Option<toml::Value>> {
+		Ok(Some(toml::Table::new().into()))
+	}
+	
 	fn tick(&self, entity: &Entity, application: &Application, delta_time: Duration) -> Result<()> {
 		let mut entity = entity.state_mut();
 		let mut position = entity.position.translation.vector;
@@ -13,6 +13,7 @@ pub struct ColliderComponent {
 	#[inner] inner: ComponentInner,
 	template: Collider,
 	handle: Cell<ColliderHandle>,
+	transient: bool,
 }
 
 impl ColliderComponent {
@@ -21,9 +22,26 @@ impl ColliderComponent {
 			inner: ComponentInner::new_norender(),
 			template: collider,
 			handle: Cell::new(ColliderHandle::invalid()),
+			transient: false,
 		}
 	}
 	
+	/// Transient colliders are created by other components at runtime and are not saved with the scene.
+	pub fn transient(mut self) -> Self {
+		self.transient = true;
+		self
+	}
+	
+	pub fn is_transient(&self) -> bool {
+		self.transient
+	}
+	
+	/// Reports collisions of this collider to components of its entity. Off by default, most colliders have nobody listening.
+	pub fn collision_events(mut self) -> Self {
+		self.template.set_active_events(self.template.active_events() | ActiveEvents::COLLISION_EVENTS);
+		self
+	}
+	
 	pub fn handle(&self) -> ColliderHandle {
 		self.handle.get()
 	}
@@ -2,15 +2,21 @@ use std::cell::Cell;
 use std::time::Duration;
 use anyhow::Result;
 use egui::Ui;
+use serde_derive::{Deserialize, Serialize};
 
 use crate::application::{Entity, Application, Key, EntityRef};
 use crate::debug;
 use crate::utils::ExUi;
-use super::{Component, ComponentBase, ComponentInner};
+use super::{Component, ComponentBase, ComponentInner, ComponentLoad};
 use super::model::simple::ObjAsset;
 use super::pc_controlled::PCControlled;
 
 
+#[derive(Deserialize, Serialize)]
+struct PoVParams {
+	detachable: bool,
+}
+
 #[derive(ComponentBase)]
 pub struct PoV {
 	#[inner] inner: ComponentInner,
@@ -28,6 +34,14 @@ impl PoV {
 	}
 }
 
+impl ComponentLoad for PoV {
+	fn load(params: toml::Value, _application: &Application) -> Result<Option<Self>> {
+		let params: PoVParams = params.try_into()?;
+		
+		Ok(Some(PoV::new(params.detachable)))
+	}
+}
+
 impl Component for PoV {
 	fn tick(&self, entity: &Entity, application: &Application, _delta_time: Duration) -> Result<()> {
 		application.pov.set(entity.as_ref());
@@ -41,9 +55,10 @@ impl Component for PoV {
 					Entity::builder("Detached PoV")
 					       .position(*entity.state().position)
 					       .parent(entity.as_ref(), true)
-					       .component(application.renderer.borrow_mut().load(ObjAsset::at("camera/camera.obj", "camera/camera.png"))?)
+					       .component(application.renderer()?.load(ObjAsset::at("camera/camera.obj").texture("camera/camera.png"))?)
 					       .component(DetachedPoV::new())
 					       .collider_from_aabb(1000.0)
+					       .tag("NoSave", true)
 					       .build()
 				));
 			}
@@ -52,6 +67,10 @@ impl Component for PoV {
 		Ok(())
 	}
 	
+	fn save(&self, _entity: &Entity, _application: &Application) -> Result<Option<toml::Value>> {
+		Ok(Some(toml::Value::try_from(PoVParams { detachable: self.detachable })?))
+	}
+	
 	fn on_inspect(&self, _entity: &Entity, ui: &mut Ui, application: &Application) {
 		ui.inspect_row("Detached Ent", &self.detached, application);
 	}
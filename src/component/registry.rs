@@ -0,0 +1,61 @@
+use anyhow::Result;
+use thiserror::Error;
+
+use crate::application::Application;
+use super::{Component, ComponentLoad};
+use super::glow::Glow;
+use super::hand::HandComponent;
+use super::katamari::Katamari;
+use super::miku::{LookAt, Miku};
+use super::model::{MMDModel, SimpleModel};
+use super::model::billboard::Billboard;
+use super::model::mmd::VmdPlayer;
+use super::pc_controlled::PCControlled;
+use super::pov::PoV;
+use super::seat::Seat;
+use super::thruster::Thruster;
+use super::vr::VrRoot;
+
+
+pub type ComponentLoader = fn(toml::Value, &Application) -> Result<Option<Box<dyn Component>>>;
+
+pub struct ComponentRegistration {
+	pub name: &'static str,
+	pub loader: ComponentLoader,
+}
+
+macro_rules! register_components {
+	{ $( $typ:ident )* } => {
+		pub static COMPONENTS: &[ComponentRegistration] = &[
+			$(
+				ComponentRegistration {
+					name: stringify!($typ),
+					loader: |params, application| Ok($typ::load(params, application)?.map(|component| component.boxed())),
+				},
+			)*
+		];
+	}
+}
+
+register_components! { Billboard Glow HandComponent Katamari LookAt Miku MMDModel PCControlled PoV Seat SimpleModel Thruster VmdPlayer VrRoot }
+
+pub fn find(name: &str) -> Option<&'static ComponentRegistration> {
+	COMPONENTS.iter()
+	          .find(|registration| registration.name == name)
+}
+
+pub fn names() -> impl Iterator<Item = &'static str> {
+	COMPONENTS.iter()
+	          .map(|registration| registration.name)
+}
+
+/// Constructs a registered component by its type name. Returns `None` if the component can't be created in current mode.
+pub fn load(name: &str, params: toml::Value, application: &Application) -> Result<Option<Box<dyn Component>>> {
+	let registration = find(name).ok_or_else(|| UnknownComponentError(name.to_string()))?;
+	
+	(registration.loader)(params, application)
+}
+
+#[derive(Debug, Error)]
+#[error("Unknown component type: {0}")]
+pub struct UnknownComponentError(String);
@@ -1,11 +1,18 @@
 use std::time::Duration;
 use anyhow::Result;
+use serde_derive::{Deserialize, Serialize};
 
 use crate::application::{Entity, Application, EntityRef};
 use crate::math::{AABB, Point3, Rot3, Vec3};
-use super::{Component, ComponentBase, ComponentInner};
+use super::{Component, ComponentBase, ComponentInner, ComponentLoad};
 
 
+#[derive(Deserialize, Serialize)]
+struct SeatParams {
+	mins: Point3,
+	maxs: Point3,
+}
+
 #[derive(ComponentBase)]
 pub struct Seat {
 	#[inner] inner: ComponentInner,
@@ -23,7 +30,19 @@ impl Seat {
 	}
 }
 
+impl ComponentLoad for Seat {
+	fn load(params: toml::Value, _application: &Application) -> Result<Option<Self>> {
+		let params: SeatParams = params.try_into()?;
+		
+		Ok(Some(Seat::new(AABB::new(params.mins, params.maxs))))
+	}
+}
+
 impl Component for Seat {
+	fn save(&self, _entity: &Entity, _application: &Application) -> Result<Option<toml::Value>> {
+		Ok(Some(toml::Value::try_from(SeatParams { mins: self.trigger.mins, maxs: self.trigger.maxs })?))
+	}
+	
 	fn tick(&self, entity: &Entity, application: &Application, _delta_time: Duration) -> Result<()> {
 		if let Some(hmd) = application.find_entity(|e| e.tag("Head").unwrap_or_default()) {
 			let local_pos = entity.state().position.inverse() * *hmd.state().position * Point3::origin();
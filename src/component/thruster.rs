@@ -1,13 +1,14 @@
 use std::time::Duration;
 use anyhow::Result;
+use serde_derive::{Deserialize, Serialize};
 
 use crate::application::{Entity, Application, Hand};
-use super::{Component, ComponentBase, ComponentInner};
+use super::{Component, ComponentBase, ComponentInner, ComponentLoad};
 
 
 const FORCE: f32 = 1000.0;
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
 pub enum ThrusterDirection {
 	Forward,
 	Back,
@@ -15,6 +16,11 @@ pub enum ThrusterDirection {
 	Right,
 }
 
+#[derive(Deserialize, Serialize)]
+struct ThrusterParams {
+	direction: ThrusterDirection,
+}
+
 #[derive(ComponentBase)]
 pub struct Thruster {
 	#[inner] inner: ComponentInner,
@@ -30,7 +36,19 @@ impl Thruster {
 	}
 }
 
+impl ComponentLoad for Thruster {
+	fn load(params: toml::Value, _application: &Application) -> Result<Option<Self>> {
+		let params: ThrusterParams = params.try_into()?;
+		
+		Ok(Some(Thruster::new(params.direction)))
+	}
+}
+
 impl Component for Thruster {
+	fn save(&self, _entity: &Entity, _application: &Application) -> Result<Option<toml::Value>> {
+		Ok(Some(toml::Value::try_from(ThrusterParams { direction: self.direction })?))
+	}
+	
 	fn tick(&self, entity: &Entity, application: &Application, _delta_time: Duration) -> Result<()> {
 		if let Some(root) = application.find_entity(|e| e.name == "VR Root") {
 			if root.has_tag("Seat") {
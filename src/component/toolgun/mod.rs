@@ -11,6 +11,7 @@ use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
 
 mod axis;
 mod pipeline;
+mod pose;
 mod prop_manager;
 mod remover;
 mod rope;
@@ -51,6 +52,14 @@ pub struct ToolGunState {
 	render_tool: bool,
 }
 
+/// Screen showing the name of the current tool.
+struct ToolGunScreen {
+	pipeline: Arc<GraphicsPipeline>,
+	vertices: Subbuffer<[Vertex]>,
+	set: Arc<PersistentDescriptorSet>,
+	fence: FenceCheck,
+}
+
 #[derive(ComponentBase)]
 pub struct ToolGun {
 	#[inner] inner: ComponentInner,
@@ -58,49 +67,21 @@ pub struct ToolGun {
 	anim: Cell<Option<ToolGunAnim>>,
 	prop_collection: PropCollection,
 	grab_pos: Isometry3,
-	pipeline: Arc<GraphicsPipeline>,
-	vertices: Subbuffer<[Vertex]>,
-	set: Arc<PersistentDescriptorSet>,
-	fence: FenceCheck,
+	/// Not created in headless mode.
+	screen: Option<ToolGunScreen>,
 }
 
 impl ToolGun {
-	pub fn new(grab_pos: Isometry3, renderer: &mut Renderer) -> Result<Self> {
-		let pipeline = renderer.pipelines.get::<ToolGunTextPipeline>()?;
-		
-		let square = [
-			Vertex::new([-1.0, -1.0]),
-			Vertex::new([-1.0,  1.0]),
-			Vertex::new([ 1.0, -1.0]),
-			Vertex::new([ 1.0, -1.0]),
-			Vertex::new([-1.0,  1.0]),
-			Vertex::new([ 1.0,  1.0]),
-		];
-		
-		let mut upload_buffer = AutoCommandBufferBuilder::primary(&*renderer.command_buffer_allocator,
-		                                                          renderer.load_queue.queue_family_index(),
-		                                                          CommandBufferUsage::OneTimeSubmit)?;
-		
-		let vertices = Buffer::upload_iter(&renderer.memory_allocator,
-		                                   BufferUsage::VERTEX_BUFFER.into_info(),
-		                                   square.iter().cloned(),
-		                                   &mut upload_buffer)?;
-		
-		let set = PersistentDescriptorSet::new(&renderer.descriptor_set_allocator,
-		                                       pipeline.layout().set_layouts().get(0).ok_or(PipelineNoLayoutError)?.clone(), [
-			                                       WriteDescriptorSet::buffer(0, renderer.commons.clone()),
-		                                       ])?;
-		
-		let upload_future = upload_buffer.build()?
-		                                 .execute(renderer.load_queue.clone())?;
-		
-		let fence = FenceCheck::new(upload_future)?;
-		
-		let prop_manager = PropCollection::new(renderer)?;
+	pub fn new(grab_pos: Isometry3, application: &Application) -> Result<Self> {
+		let screen = if application.headless() {
+			None
+		} else {
+			Some(ToolGunScreen::new(&mut *application.renderer()?)?)
+		};
 		
 		let state = ToolGunState {
 			scroll: 0.0,
-			tools: get_all_tools(renderer),
+			tools: get_all_tools(application)?,
 			tool_id: 0,
 			menu_pos: None,
 			render_tool: false,
@@ -110,12 +91,9 @@ impl ToolGun {
 			inner: ComponentInner::from_render_type(RenderType::Transparent),
 			state: RefCell::new(state),
 			anim: Cell::new(None),
-			prop_collection: prop_manager,
+			prop_collection: PropCollection::new(application)?,
 			grab_pos,
-			pipeline,
-			vertices,
-			set,
-			fence,
+			screen,
 		})
 	}
 	
@@ -149,6 +127,47 @@ impl ToolGun {
 	}
 }
 
+impl ToolGunScreen {
+	fn new(renderer: &mut Renderer) -> Result<Self> {
+		let pipeline = renderer.pipelines.get::<ToolGunTextPipeline>()?;
+		
+		let square = [
+			Vertex::new([-1.0, -1.0]),
+			Vertex::new([-1.0,  1.0]),
+			Vertex::new([ 1.0, -1.0]),
+			Vertex::new([ 1.0, -1.0]),
+			Vertex::new([-1.0,  1.0]),
+			Vertex::new([ 1.0,  1.0]),
+		];
+		
+		let mut upload_buffer = AutoCommandBufferBuilder::primary(&*renderer.command_buffer_allocator,
+		                                                          renderer.load_queue.queue_family_index(),
+		                                                          CommandBufferUsage::OneTimeSubmit)?;
+		
+		let vertices = Buffer::upload_iter(&renderer.memory_allocator,
+		                                   BufferUsage::VERTEX_BUFFER.into_info(),
+		                                   square.iter().cloned(),
+		                                   &mut upload_buffer)?;
+		
+		let set = PersistentDescriptorSet::new(&renderer.descriptor_set_allocator,
+		                                       pipeline.layout().set_layouts().get(0).ok_or(PipelineNoLayoutError)?.clone(), [
+			                                       WriteDescriptorSet::buffer(0, renderer.commons.clone()),
+		                                       ])?;
+		
+		let upload_future = upload_buffer.build()?
+		                                 .execute(renderer.load_queue.clone())?;
+		
+		let fence = FenceCheck::new(upload_future)?;
+		
+		Ok(ToolGunScreen {
+			pipeline,
+			vertices,
+			set,
+			fence,
+		})
+	}
+}
+
 impl Component for ToolGun {
 	fn start(&self, entity: &Entity, _application: &Application) -> Result<()> {
 		entity.set_tag("GrabSticky", true);
@@ -236,7 +255,10 @@ impl Component for ToolGun {
 	}
 	
 	fn render(&self, entity: &Entity, context: &mut RenderContext, renderer: &mut Renderer) -> Result<()> {
-		if !self.fence.check() { return Ok(()); }
+		let screen = match &self.screen {
+			Some(screen) if screen.fence.check() => screen,
+			_ => return Ok(()),
+		};
 		let state = &mut *self.state.borrow_mut();
 		
 		let tool = state.tools.get_mut(state.tool_id);
@@ -255,19 +277,19 @@ impl Component for ToolGun {
 			-0.5
 		);
 		
-		context.builder.bind_pipeline_graphics(self.pipeline.clone())
-		               .bind_vertex_buffers(0, self.vertices.clone())
+		context.builder.bind_pipeline_graphics(screen.pipeline.clone())
+		               .bind_vertex_buffers(0, screen.vertices.clone())
 		               .bind_descriptor_sets(PipelineBindPoint::Graphics,
-		                                     self.pipeline.layout().clone(),
+		                                     screen.pipeline.layout().clone(),
 		                                     0,
-		                                     (self.set.clone(), text_entry.set))
-		               .push_constants(self.pipeline.layout().clone(),
+		                                     (screen.set.clone(), text_entry.set))
+		               .push_constants(screen.pipeline.layout().clone(),
 		                               0,
 		                               Pc {
 			                               model: model_matrix.to_homogeneous().into(),
 			                               uv_transform: uv_transform.into(),
 		                               })
-		               .draw(self.vertices.len() as u32,
+		               .draw(screen.vertices.len() as u32,
 		                     1,
 		                     0,
 		                     0)?;
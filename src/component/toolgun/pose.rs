@@ -0,0 +1,277 @@
+use anyhow::Result;
+use nalgebra::Unit;
+
+use crate::debug;
+use crate::application::{Application, Hand};
+use crate::math::{cast_ray_on_plane, Color, Isometry3, PI, Point3, Ray, Rot3, Translation3, Vec3};
+use super::super::ComponentBase;
+use super::super::model::MMDModel;
+use super::ToolGun;
+use super::tool::Tool;
+
+
+const RING_RADIUS: f32 = 0.12;
+const RING_STEPS: usize = 32;
+const ARROW_LENGTH: f32 = 0.2;
+/// Distance from handles, or bones, at which the ray hits them.
+const HANDLE_TOLERANCE: f32 = 0.015;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Handle {
+	Rotate(usize),
+	Translate(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Drag {
+	handle: Handle,
+	/// Gizmo frame when the drag started, handles stay in place until it ends.
+	frame: Isometry3,
+	/// Angle around, or distance along, the axis the drag started at.
+	start: f32,
+	/// Manual pose of the bone, or position of its rigid body, before the drag.
+	origin: Isometry3,
+}
+
+/// Rotates and moves bones of MMD models, aligned with their parents like in MikuMikuDance.
+/// Bones driven by physics are posed by moving their rigid bodies, so ragdolls should be frozen first.
+pub struct PoseTool {
+	hovered: Option<Handle>,
+	drag: Option<Drag>,
+}
+
+impl PoseTool {
+	pub fn new() -> Self {
+		PoseTool {
+			hovered: None,
+			drag: None,
+		}
+	}
+	
+	/// Returns true if the ray missed all handles and another bone should be picked.
+	fn pose(&mut self, model: &MMDModel, bone_id: usize, hand: Hand, ray: Ray, application: &Application) -> bool {
+		let fire = application.input.fire_btn(hand);
+		let ent_pos = *model.entity(application).state().position;
+		let state = &mut *model.state_mut();
+		
+		let (bone_transform, parent_transform) = match state.bone_transform(bone_id) {
+			Some(transform) => (transform, state.bones[bone_id].parent.and_then(|parent| state.bone_transform(parent))),
+			None => return false,
+		};
+		
+		let bone = &mut state.bones[bone_id];
+		let body = bone.override_transform.and(bone.rigid_body.get(application));
+		
+		// Parent's scale is ignored
+		let parent_rotation = parent_transform.map_or_else(Rot3::identity, |transform| Rot3::from_matrix(&transform.to_homogeneous().fixed_resize(0.0)));
+		let bone_pos = bone_transform.transform_point(&Point3::origin());
+		let frame = ent_pos * Isometry3::from_parts(bone_pos.coords.into(), parent_rotation);
+		let scale = debug::gizmo_scale(frame);
+		
+		let mut handles = vec![];
+		if bone.rotatable { handles.extend((0..3).map(Handle::Rotate)); }
+		if bone.movable { handles.extend((0..3).map(Handle::Translate)); }
+		
+		let mut pick = false;
+		
+		if let Some(drag) = self.drag {
+			if !fire.pressed {
+				self.drag = None;
+			} else if let Some((_, value)) = drag.handle.hit(drag.frame, scale, ray, false) {
+				let axis = drag.handle.axis();
+				let delta = value - drag.start;
+				
+				match (drag.handle, body) {
+					(Handle::Rotate(_), Some(body)) => {
+						let pivot = Translation3::from(drag.frame.translation.vector);
+						let rotation = Rot3::from_axis_angle(&(drag.frame.rotation * axis), delta);
+						*body.state_mut().position = pivot * rotation * pivot.inverse() * drag.origin;
+					},
+					(Handle::Translate(_), Some(body)) => {
+						let translation = Translation3::from(drag.frame.rotation * axis.into_inner() * delta);
+						*body.state_mut().position = translation * drag.origin;
+					},
+					(Handle::Rotate(_), None) => {
+						bone.pose_transform.rotation = Rot3::from_axis_angle(&axis, delta) * drag.origin.rotation;
+					},
+					(Handle::Translate(_), None) => {
+						bone.pose_transform.translation.vector = drag.origin.translation.vector + axis.into_inner() * delta;
+					},
+				}
+			}
+		} else {
+			let hovered = handles.iter()
+			                     .filter_map(|&handle| handle.hit(frame, scale, ray, true).map(|hit| (handle, hit)))
+			                     .min_by(|(_, (a, _)), (_, (b, _))| a.total_cmp(b));
+			
+			self.hovered = hovered.map(|(handle, _)| handle);
+			
+			if fire.down {
+				match hovered {
+					Some((handle, (_, start))) => self.drag = Some(Drag {
+						handle,
+						frame,
+						start,
+						origin: body.map_or(bone.pose_transform, |body| *body.state().position),
+					}),
+					None => pick = true,
+				}
+			}
+		}
+		
+		let active = self.drag.map(|drag| drag.handle).or(self.hovered);
+		let frame = self.drag.map_or(frame, |drag| drag.frame);
+		
+		for &handle in &handles {
+			let color = if active == Some(handle) { Color::YELLOW } else { handle.color() };
+			
+			match handle {
+				Handle::Rotate(_) => {
+					let plane = frame * handle.plane();
+					let ring = |step: usize| {
+						let angle = step as f32 / RING_STEPS as f32 * PI * 2.0;
+						plane * Point3::new(angle.cos() * RING_RADIUS * scale, angle.sin() * RING_RADIUS * scale, 0.0)
+					};
+					
+					for step in 0..RING_STEPS {
+						debug::draw_line(ring(step), ring(step + 1), 3.0, color);
+					}
+				},
+				Handle::Translate(_) => {
+					let tip = frame * Point3::from(handle.axis().into_inner() * ARROW_LENGTH * scale);
+					debug::draw_line(frame, tip, 4.0, color);
+					debug::draw_point(tip, 12.0, color);
+				},
+			}
+		}
+		
+		// Physics driven bones are marked with a different color
+		let color = if body.is_some() { Color::CYAN } else { Color::WHITE };
+		debug::draw_point(frame, 10.0, color);
+		debug::draw_text(&bone.name, frame, debug::DebugOffset::bottom_right(8.0, 8.0), 32.0, color);
+		
+		pick
+	}
+	
+	/// Selects the displayed bone closest to the ray.
+	fn pick_bone(&self, ray: Ray, application: &Application) {
+		let mut best = None;
+		
+		for entity in application.find_all_entities(|entity| entity.find_component_by_type::<MMDModel>().is_some()) {
+			let model = match entity.find_component_by_type::<MMDModel>() {
+				Some(model) => model,
+				None => continue,
+			};
+			
+			let ent_pos = *entity.state().position;
+			let state = model.state();
+			
+			for bone in state.bones.iter().filter(|bone| bone.display && (bone.rotatable || bone.movable)) {
+				let pos = match state.bone_transform(bone.id) {
+					Some(transform) => ent_pos * transform.transform_point(&Point3::origin()),
+					None => continue,
+				};
+				
+				let toi = (pos - ray.origin).dot(&ray.dir);
+				let distance = (ray.point_at(toi) - pos).magnitude();
+				
+				if toi > 0.0 && distance < HANDLE_TOLERANCE * debug::gizmo_scale(pos) && best.map_or(true, |(_, _, best_toi)| toi < best_toi) {
+					best = Some((model.as_cref(), bone.id, toi));
+				}
+			}
+		}
+		
+		if let Some((model, bone, _)) = best {
+			application.select((model, bone));
+		}
+	}
+}
+
+impl Tool for PoseTool {
+	fn name(&self) -> &str {
+		"Pose"
+	}
+	
+	fn tick(&mut self, _toolgun: &ToolGun, hand: Hand, ray: Ray, application: &Application) -> Result<()> {
+		let selection = application.get_selection();
+		
+		let pick = match (selection.mmd_model().get(application), selection.mmd_bone()) {
+			(Some(model), Some(bone_id)) => self.pose(model, bone_id, hand, ray, application),
+			_ => {
+				self.drag = None;
+				application.input.fire_btn(hand).down
+			},
+		};
+		
+		if pick {
+			self.pick_bone(ray, application);
+		}
+		
+		Ok(())
+	}
+}
+
+impl Handle {
+	fn axis(self) -> Unit<Vec3> {
+		match self {
+			Handle::Rotate(id) | Handle::Translate(id) => [Vec3::x_axis(), Vec3::y_axis(), Vec3::z_axis()][id],
+		}
+	}
+	
+	fn color(self) -> Color {
+		match self {
+			Handle::Rotate(id) | Handle::Translate(id) => [Color::RED, Color::GREEN, Color::BLUE][id],
+		}
+	}
+	
+	/// Turns XY plane to face the axis, so angles measured in it go counter-clockwise around the axis.
+	fn plane(self) -> Rot3 {
+		match self {
+			Handle::Rotate(0) | Handle::Translate(0) => Rot3::from_axis_angle(&Vec3::y_axis(), PI / 2.0),
+			Handle::Rotate(1) | Handle::Translate(1) => Rot3::from_axis_angle(&Vec3::x_axis(), -PI / 2.0),
+			_ => Rot3::identity(),
+		}
+	}
+	
+	/// Returns distance along the ray and value of the handle: angle around, or distance along its axis.
+	/// Unless `strict`, the ray doesn't need to touch the handle, to keep dragging when it slips off.
+	fn hit(self, frame: Isometry3, scale: f32, ray: Ray, strict: bool) -> Option<(f32, f32)> {
+		let tolerance = HANDLE_TOLERANCE * scale;
+		
+		match self {
+			Handle::Rotate(_) => {
+				let plane = frame * self.plane();
+				let hit = cast_ray_on_plane(plane, ray)?;
+				let toi = (plane * hit - ray.origin).magnitude();
+				
+				if strict && (hit.coords.xy().magnitude() - RING_RADIUS * scale).abs() > tolerance {
+					return None;
+				}
+				
+				Some((toi, hit.y.atan2(hit.x)))
+			},
+			Handle::Translate(_) => {
+				// Closest points of the ray and the axis line
+				let origin = frame * Point3::origin();
+				let dir = frame * self.axis().into_inner();
+				let offset = origin - ray.origin;
+				let dot = dir.dot(&ray.dir);
+				let denom = 1.0 - dot * dot;
+				
+				if denom < 1.0e-6 {
+					return None;
+				}
+				
+				let toi = (offset.dot(&ray.dir) - dot * offset.dot(&dir)) / denom;
+				let value = (dot * offset.dot(&ray.dir) - offset.dot(&dir)) / denom;
+				let distance = (ray.point_at(toi) - (origin + dir * value)).magnitude();
+				
+				if strict && (toi < 0.0 || distance > tolerance || value < 0.0 || value > ARROW_LENGTH * scale) {
+					return None;
+				}
+				
+				Some((toi, value))
+			},
+		}
+	}
+}
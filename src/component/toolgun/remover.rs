@@ -35,7 +35,7 @@ impl Tool for Remover {
 			       .and_then(|(c, _)| physics.collider_set.get(c))
 			       .map(|collider| collider.entity(application))
 		};
-			
+		
 		if let Some(target) = result {
 			if target.tag("World") != Some(true) {
 				target.remove();
@@ -1,13 +1,10 @@
 use anyhow::Result;
-use rapier3d::dynamics::RigidBodyType;
 use rapier3d::pipeline::QueryFilter;
 
 use crate::debug;
 use crate::application::{Hand, Application};
-use crate::application::entity::EntityBuilder;
 use crate::math::{Ray, Similarity3, Color, Rot3, Isometry3, Vec3, cast_ray_on_plane, face_upwards_lossy};
 use crate::renderer::RenderContext;
-use super::super::seat::Seat;
 use super::ToolGun;
 use super::tool::Tool;
 
@@ -81,7 +78,7 @@ impl Tool for Spawner {
 			if let Some((_, intersection)) = result {
 				if let Some(prop) = toolgun.prop_collection.props.get(self.prop_idx) {
 					let hit_point = ray.point_at(intersection.toi);
-					let offset = prop.model.aabb().mins.y;
+					let offset = prop.model.as_ref().map_or(0.0, |model| model.aabb().mins.y);
 					
 					let position = Isometry3::from_parts(
 						(hit_point - intersection.normal * offset).into(),
@@ -93,17 +90,7 @@ impl Tool for Spawner {
 					if application.input.fire_btn(hand).down {
 						toolgun.fire(application);
 						
-						let mut builder = EntityBuilder::new(&prop.name)
-							.rigid_body_type(RigidBodyType::Dynamic)
-							.position(position)
-							.component(prop.model.clone())
-							.collider(prop.collider.clone());
-						
-						if let Some(seat) = prop.seat {
-							builder = builder.component(Seat::new(seat));
-						}
-						
-						application.add_entity(builder.build());
+						application.add_prefab(&prop.name, position)?;
 					}
 				}
 			}
@@ -115,8 +102,8 @@ impl Tool for Spawner {
 	
 	fn render(&mut self, toolgun: &ToolGun, context: &mut RenderContext) -> Result<()> {
 		if let Some(ghost_pos) = self.ghost_pos {
-			if let Some(prop) = toolgun.prop_collection.props.get(self.prop_idx) {
-				prop.model.render_impl(Similarity3::from_isometry(ghost_pos, 1.0), Color::FULL_WHITE.opactiy(0.25), context)?;
+			if let Some(model) = toolgun.prop_collection.props.get(self.prop_idx).and_then(|prop| prop.model.as_ref()) {
+				model.render_impl(Similarity3::from_isometry(ghost_pos, 1.0), Color::FULL_WHITE.opactiy(0.25), context)?;
 			}
 		}
 		
@@ -125,13 +112,17 @@ impl Tool for Spawner {
 			let row_size = (size as f32).sqrt().ceil() as usize;
 			
 			for (id, prop) in toolgun.prop_collection.props.iter().enumerate() {
+				let model = match &prop.model {
+					Some(model) => model,
+					None => continue,
+				};
 				let x = (id % row_size) as f32;
 				let y = (id / row_size) as f32;
 				let hsize = row_size as f32 / 2.0 - 0.5;
 				let pos = vector!((x - hsize) * MENU_SPACING,
 				                  (hsize - y) * MENU_SPACING,
 				                  0.0);
-				let size = MENU_SCALE / prop.model.aabb().extents().max();
+				let size = MENU_SCALE / model.aabb().extents().max();
 				
 				let transform = menu_pos * Similarity3::from_parts(pos.into(), Rot3::from_euler_angles(0.0, 0.0, 0.0), size);
 				
@@ -141,7 +132,7 @@ impl Tool for Spawner {
 					Color::D_WHITE.opactiy(0.75)
 				};
 				
-				prop.model.render_impl(transform, color, context)?;
+				model.render_impl(transform, color, context)?;
 				
 				if let Some(tip) = &prop.tip {
 					debug::draw_text(tip, transform, debug::DebugOffset::top(0.0, 8.0), 32.0, color);
@@ -7,7 +7,7 @@ use crate::debug;
 use crate::application::{Application, Hand, Key};
 use crate::application::entity::EntityBuilder;
 use crate::math::{Color, face_upwards_lossy, Isometry3, Ray, Similarity3};
-use crate::renderer::{RenderContext, Renderer};
+use crate::renderer::RenderContext;
 use crate::utils::ColliderEx;
 use super::super::ComponentBase;
 use super::super::model::SimpleModel;
@@ -18,19 +18,29 @@ use super::ToolGun;
 use super::tool::Tool;
 
 
+/// Radius and half height of thrusters' colliders.
+const THRUSTER_SIZE: (f32, f32) = (0.2, 0.15);
+
 pub struct ThrusterTool {
 	direction: ThrusterDirection,
-	thruster_model: SimpleModel,
+	/// Not loaded in headless mode.
+	thruster_model: Option<SimpleModel>,
 	ghost_pos: Option<Isometry3>,
 }
 
 impl ThrusterTool {
-	pub fn new(renderer: &mut Renderer) -> Self {
-		ThrusterTool {
+	pub fn new(application: &Application) -> Result<Self> {
+		let thruster_model = if application.headless() {
+			None
+		} else {
+			Some(application.renderer()?.load(ObjAsset::at("shapes/thruster.obj").texture("shapes/thruster.png"))?)
+		};
+		
+		Ok(ThrusterTool {
 			direction: ThrusterDirection::Forward,
-			thruster_model: renderer.load(ObjAsset::at("shapes/thruster.obj", "shapes/thruster.png")).unwrap(),
+			thruster_model,
 			ghost_pos: None,
-		}
+		})
 	}
 }
 
@@ -80,7 +90,8 @@ impl Tool for ThrusterTool {
 		
 		if let Some((hit_ent, intersection)) = result {
 			let hit_point = ray.point_at(intersection.toi);
-			let offset = self.thruster_model.aabb().mins.y - 0.02;
+			let bottom = self.thruster_model.as_ref().map_or(-THRUSTER_SIZE.1, |model| model.aabb().mins.y);
+			let offset = bottom - 0.02;
 			
 			let ghost_pos = Isometry3::from_parts(
 				(hit_point - intersection.normal * offset).into(),
@@ -94,11 +105,16 @@ impl Tool for ThrusterTool {
 				
 				let local_pos = hit_ent.state().position.inverse() * ghost_pos;
 				
-				application.add_entity(EntityBuilder::new("Thruster")
+				let mut thruster = EntityBuilder::new("Thruster")
 					.rigid_body_type(RigidBodyType::Dynamic)
 					.position(ghost_pos)
-					.collider(ColliderBuilder::new(ColliderShape::cylinder(0.15, 0.2)).density(200.0).build())
-					.component(self.thruster_model.clone())
+					.collider(ColliderBuilder::new(ColliderShape::cylinder(THRUSTER_SIZE.1, THRUSTER_SIZE.0)).density(200.0).build());
+				
+				if let Some(model) = &self.thruster_model {
+					thruster = thruster.component(model.clone());
+				}
+				
+				application.add_entity(thruster
 					.component(Thruster::new(self.direction))
 					.component(JointComponent::new(
 						*FixedJoint::new()
@@ -116,8 +132,8 @@ impl Tool for ThrusterTool {
 	
 	
 	fn render(&mut self, _toolgun: &ToolGun, context: &mut RenderContext) -> Result<()> {
-		if let Some(ghost_pos) = self.ghost_pos {
-			self.thruster_model.render_impl(Similarity3::from_isometry(ghost_pos, 1.0), Color::FULL_WHITE.opactiy(0.25), context)?;
+		if let (Some(ghost_pos), Some(model)) = (self.ghost_pos, &self.thruster_model) {
+			model.render_impl(Similarity3::from_isometry(ghost_pos, 1.0), Color::FULL_WHITE.opactiy(0.25), context)?;
 		}
 		
 		Ok(())
@@ -2,9 +2,10 @@ use anyhow::Result;
 
 use crate::application::{Application, Hand};
 use crate::math::Ray;
-use crate::renderer::{RenderContext, Renderer};
+use crate::renderer::RenderContext;
 use super::ToolGun;
 use super::axis::Axis;
+use super::pose::PoseTool;
 use super::remover::Remover;
 use super::rope::RopeTool;
 use super::spawner::Spawner;
@@ -14,15 +15,16 @@ use super::weld::Weld;
 
 pub type ToolError = Box<dyn std::error::Error>;
 
-pub fn get_all_tools(renderer: &mut Renderer) -> Vec<Box<dyn Tool>> {
-	vec![
+pub fn get_all_tools(application: &Application) -> Result<Vec<Box<dyn Tool>>> {
+	Ok(vec![
 		Box::new(Spawner::new()),
 		Box::new(Remover::new()),
 		Box::new(Axis::new()),
-		Box::new(ThrusterTool::new(renderer)),
+		Box::new(ThrusterTool::new(application)?),
 		Box::new(Weld::new()),
 		Box::new(RopeTool::new()),
-	]
+		Box::new(PoseTool::new()),
+	])
 }
 
 #[allow(unused_variables)]
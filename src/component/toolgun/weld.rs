@@ -70,7 +70,7 @@ impl Tool for Weld {
 							            .set_local_frame2(local_pos),
 							hit_ent,
 						));
-				
+						
 						self.target = EntityRef::null();
 						self.ghost = None;
 					}
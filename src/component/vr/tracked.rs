@@ -30,7 +30,7 @@ impl VrTracked {
 
 impl Component for VrTracked {
 	fn tick(&self, entity: &Entity, application: &Application, _delta_time: Duration) -> Result<()> {
-		if !application.vr.as_ref().unwrap().lock().unwrap().system.is_tracked_device_connected(self.device_id) {
+		if !application.vr.as_ref().unwrap().is_tracked_device_connected(self.device_id) {
 			println!("Removing {}", entity.name);
 			entity.remove();
 		}
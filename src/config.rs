@@ -36,6 +36,20 @@ pub struct Config {
 	pub camera: CameraConfig,
 	/// Non VR mode
 	pub novr: NovrConfig,
+	/// Simulated VR mode
+	pub simvr: SimvrConfig,
+	/// Headless mode
+	pub headless: HeadlessConfig,
+	/// Offscreen rendering mode
+	#[arg_rename = "render-frame"] pub render_frame: RenderFrameConfig,
+	/// Physics simulation
+	pub physics: PhysicsConfig,
+	/// Scene file to load on startup. (empty - default scene)
+	pub scene: String,
+	/// Record inputs and VR poses to a file. (empty - disabled)
+	pub record: String,
+	/// Replay inputs and VR poses from a recording instead of live devices. (empty - disabled)
+	pub replay: String,
 	/// Window max framerate (0 - unlimited, not recommended)
 	pub window_max_fps: u32,
 }
@@ -94,6 +108,66 @@ pub struct NovrConfig {
 	pub fov: f32,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone, FromArgs)]
+pub struct SimvrConfig {
+	/// Simulate VR headset and controllers instead of using OpenVR. Eye resolution and fov are taken from novr.
+	#[arg_rename = ""] pub enabled: bool,
+	/// Script file driving simulated devices. (empty - use keyboard and mouse)
+	pub script: String,
+	/// Simulated interpupillary distance in meters.
+	pub ipd: f32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, FromArgs)]
+pub struct HeadlessConfig {
+	/// Run without renderer, window and VR. Only entities and physics are simulated.
+	#[arg_rename = ""] pub enabled: bool,
+	/// Number of frames to simulate before quitting. (0 - unlimited)
+	pub frames: u64,
+	/// Fixed frame time in milliseconds. (0 - use real time)
+	pub frame_time: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, FromArgs)]
+pub struct RenderFrameConfig {
+	/// Load the scene, simulate a number of frames, save rendered images and quit. No window or VR is used.
+	#[arg_rename = ""] pub enabled: bool,
+	/// Number of frames to simulate. The last one is always saved.
+	pub frames: u64,
+	/// Save every n-th frame as well. (0 - only the last frame)
+	pub interval: u64,
+	/// Output PNG file. Frame number is appended to the name if interval is set.
+	pub output: String,
+	/// Image width. (one eye)
+	pub width: u32,
+	/// Image height.
+	pub height: u32,
+	/// Horizontal fov
+	pub fov: f32,
+	/// Render both eyes side by side.
+	pub stereo: bool,
+	/// Interpupillary distance in meters, used in stereo.
+	pub ipd: f32,
+	/// Keep background transparent instead of black.
+	pub transparent: bool,
+	/// Camera position.
+	pub position: Vec3,
+	/// Point the camera looks at. (same as position - use scene's point of view)
+	pub target: Vec3,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, FromArgs)]
+pub struct PhysicsConfig {
+	/// Physics steps per second.
+	pub step_rate: u32,
+	/// Maximum number of physics steps per frame. Simulation slows down if exceeded.
+	pub max_substeps: u32,
+	/// Interpolate rendered positions between physics steps.
+	pub interpolate: bool,
+	/// Number of physics steps kept for rewinding, only used with a window. (0 - disabled)
+	pub history: u32,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum CameraAPI {
@@ -207,6 +207,15 @@ thread_local! {
     pub static DEBUG_CAPSULES: RefCell<Vec<DebugCapsule>> = RefCell::new(vec![]);
 }
 
+pub fn clear() {
+	DEBUG_POINTS.with(|points| points.borrow_mut().clear());
+	DEBUG_LINES.with(|lines| lines.borrow_mut().clear());
+	DEBUG_TEXTS.with(|texts| texts.borrow_mut().clear());
+	DEBUG_CAPSULES.with(|capsules| capsules.borrow_mut().clear());
+	DEBUG_BOXES.with(|boxes| boxes.borrow_mut().clear());
+	DEBUG_SPHERES.with(|spheres| spheres.borrow_mut().clear());
+}
+
 pub fn draw_point(position: impl Into<DebugPosition>, radius: f32, color: Color) {
 	DEBUG_POINTS.with(|points| {
 		points.borrow_mut().push(DebugPoint{ position: position.into(), radius, color });
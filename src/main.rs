@@ -91,7 +91,11 @@ fn run_application() -> Result<()> {
 	debug::setup_default_flags();
 	config::set(config);
 	
-	let application = Application::new()?;
+	let application = if config::get().headless.enabled {
+		Application::new_headless()?
+	} else {
+		Application::new()?
+	};
 	application.run()?;
 	
 	Ok(())
@@ -2,6 +2,7 @@ pub use std::f32::consts::PI;
 use std::any::Any;
 use std::ops::{Deref, DerefMut};
 use nalgebra::{Scalar, Transform, TCategory};
+use serde_derive::{Deserialize, Serialize};
 use simba::scalar::{SubsetOf, SupersetOf};
 
 
@@ -209,7 +210,7 @@ pub fn projective_clip() -> AMat4 {
 	))
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Color(Vec4);
 
 impl Color {
@@ -29,11 +29,7 @@ impl AssetsManager {
 	}
 	
 	pub fn load<Key: AssetKey + 'static>(&mut self, key: Key, renderer: &mut Renderer) -> Result<Key::Asset> {
-		let mut hasher = DefaultHasher::new();
-		TypeId::of::<Key>().hash(&mut hasher);
-		key.hash(&mut hasher);
-		let hash = hasher.finish();
-		
+		let hash = key_hash(&key);
 		let asset = self.cache.get(&hash);
 		
 		if let Some(asset) = asset {
@@ -46,6 +42,11 @@ impl AssetsManager {
 		}
 	}
 	
+	/// Drops cached asset, so the next `load` reads it again.
+	pub fn unload<Key: AssetKey + 'static>(&mut self, key: &Key) -> bool {
+		self.cache.remove(&key_hash(key)).is_some()
+	}
+	
 	pub fn find_asset(path: impl AsRef<Path>) -> Result<impl BufRead + Seek, AssetError> {
 		let asset_path = Self::find_asset_path(&path)?;
 		
@@ -73,6 +74,13 @@ pub trait AssetKey: Hash + Display {
 	fn load(&self, assets_manager: &mut AssetsManager, renderer: &mut Renderer) -> Result<Self::Asset>;
 }
 
+fn key_hash<Key: AssetKey + 'static>(key: &Key) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	TypeId::of::<Key>().hash(&mut hasher);
+	key.hash(&mut hasher);
+	hasher.finish()
+}
+
 // Windows why
 fn lookup_windows_path(root: &str, orig_path: &Path) -> Result<PathBuf, AssetError> {
 	if cfg!(target_os = "windows") {
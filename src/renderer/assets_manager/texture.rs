@@ -4,6 +4,7 @@ use std::sync::Arc;
 use anyhow::Result;
 use image::{ImageFormat, DynamicImage, ImageDecoder, AnimationDecoder, RgbaImage, imageops};
 use image::codecs::gif::GifDecoder;
+use serde_derive::{Deserialize, Serialize};
 use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryCommandBufferAbstract};
 use vulkano::format::Format;
 use vulkano::image::{ImmutableImage, ImageDimensions, MipmapsCount};
@@ -15,14 +16,22 @@ use super::super::Renderer;
 use super::{AssetKey, AssetsManager};
 
 
-#[derive(Clone, Hash, Debug)]
+#[derive(Clone, Hash, Debug, Deserialize, Serialize)]
 pub struct TextureAsset {
 	path: PathBuf,
-	filter: Filter,
+	#[serde(with = "FilterDef")] filter: Filter,
 	mipmaps: bool,
 	srgb: bool,
 }
 
+#[derive(Deserialize, Serialize)]
+#[serde(remote = "Filter", rename_all = "snake_case")]
+enum FilterDef {
+	Nearest,
+	Linear,
+	Cubic,
+}
+
 #[derive(Clone)]
 pub struct TextureBundle {
 	pub image: Arc<ImageView<ImmutableImage>>,
@@ -23,6 +23,21 @@ impl<T> TomlAsset<T> {
 			phantom: PhantomData,
 		}
 	}
+	
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+}
+
+impl<T> TomlAsset<T>
+	where T: DeserializeOwned {
+	/// Reads the file without caching, usable without a renderer.
+	pub fn read(&self) -> Result<T> {
+		let file = fs::read_to_string(AssetsManager::find_asset_path(&self.path)?)?;
+		let data = toml::from_str(&file)?;
+		
+		Ok(data)
+	}
 }
 
 impl<T> AssetKey for TomlAsset<T>
@@ -30,10 +45,7 @@ impl<T> AssetKey for TomlAsset<T>
 	type Asset = T;
 	
 	fn load(&self, _assets_manager: &mut AssetsManager, _renderer: &mut Renderer) -> Result<Self::Asset> {
-		let file = fs::read_to_string(AssetsManager::find_asset_path(&self.path)?)?;
-		let data = toml::from_str(&file)?;
-		
-		Ok(data)
+		self.read()
 	}
 }
 
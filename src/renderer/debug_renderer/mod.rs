@@ -16,7 +16,7 @@ mod text_cache;
 
 use crate::component::model::SimpleModel;
 use crate::component::model::simple::asset::ObjAsset;
-use crate::debug::{DEBUG_POINTS, DebugPoint, DEBUG_LINES, DebugLine, DEBUG_TEXTS, DebugText, DEBUG_BOXES, DEBUG_CAPSULES, DEBUG_SPHERES, DebugBox, DebugSphere, DebugCapsule};
+use crate::debug::{self, DEBUG_POINTS, DebugPoint, DEBUG_LINES, DebugLine, DEBUG_TEXTS, DebugText, DEBUG_BOXES, DEBUG_CAPSULES, DEBUG_SPHERES, DebugBox, DebugSphere, DebugCapsule};
 use crate::math::{Vec2, Rot2, PMat4, Isometry3, Similarity3, face_upwards_lossy, PI};
 use crate::utils::{AutoCommandBufferBuilderEx, SubbufferAllocatorEx};
 use super::{Renderer, RenderContext};
@@ -98,10 +98,10 @@ impl DebugRenderer {
 			
 			if load_models {
 				self.models = Some(DebugModels {
-					dbox: renderer.load(ObjAsset::at("debug/box.obj", TextureAsset::at("debug/tex.png").nearest().no_mipmaps()))?,
-					sphere: renderer.load(ObjAsset::at("debug/sphere.obj", TextureAsset::at("debug/tex.png").nearest().no_mipmaps()))?,
-					cbody: renderer.load(ObjAsset::at("debug/cbody.obj", TextureAsset::at("debug/tex.png").nearest().no_mipmaps()))?,
-					ccap: renderer.load(ObjAsset::at("debug/ccap.obj", TextureAsset::at("debug/tex.png").nearest().no_mipmaps()))?,
+					dbox: renderer.load(ObjAsset::at("debug/box.obj").texture(TextureAsset::at("debug/tex.png").nearest().no_mipmaps()))?,
+					sphere: renderer.load(ObjAsset::at("debug/sphere.obj").texture(TextureAsset::at("debug/tex.png").nearest().no_mipmaps()))?,
+					cbody: renderer.load(ObjAsset::at("debug/cbody.obj").texture(TextureAsset::at("debug/tex.png").nearest().no_mipmaps()))?,
+					ccap: renderer.load(ObjAsset::at("debug/ccap.obj").texture(TextureAsset::at("debug/tex.png").nearest().no_mipmaps()))?,
 				});
 			}
 		}
@@ -192,12 +192,7 @@ impl DebugRenderer {
 	}
 	
 	pub fn reset(&mut self) {
-		DEBUG_POINTS.with(|points| points.borrow_mut().clear());
-		DEBUG_LINES.with(|lines| lines.borrow_mut().clear());
-		DEBUG_TEXTS.with(|texts| texts.borrow_mut().clear());
-		DEBUG_CAPSULES.with(|capsules| capsules.borrow_mut().clear());
-		DEBUG_BOXES.with(|boxes| boxes.borrow_mut().clear());
-		DEBUG_SPHERES.with(|spheres| spheres.borrow_mut().clear());
+		debug::clear();
 		
 		self.text_cache.borrow_mut().cleanup();
 	}
@@ -318,7 +313,7 @@ impl DebugRenderer {
 				&line.color
 			));
 		}
-	
+		
 		for id in 0..edges {
 			let angle = dir * Rot2::from_angle(PI / edges as f32 * id as f32 - PI / 2.0);
 			let offset = angle.transform_vector(&-Vec2::x()).component_mul(pixel_scale) * line.width;
@@ -623,6 +623,20 @@ impl Renderer {
 		Ok(())
 	}
 	
+	/// Blocks until all submitted work has finished, so that results can be read back on the CPU.
+	pub fn wait_idle(&mut self) -> Result<()> {
+		if let Some(future) = self.future.take() {
+			if future.queue().is_none() {
+				return Ok(())
+			}
+			
+			future.then_signal_fence_and_flush()?
+			      .wait(None)?;
+		}
+		
+		Ok(())
+	}
+	
 	pub fn debug_text_cache(&self) -> RefMut<TextCache> {
 		self.debug_renderer.as_ref().unwrap().text_cache()
 	}
@@ -633,6 +647,12 @@ impl Renderer {
 		self.assets_manager = Some(assets_manager);
 		result
 	}
+	
+	/// Loads the asset again, even if it's cached.
+	pub fn reload<Key: AssetKey + 'static>(&mut self, key: Key) -> Result<Key::Asset> {
+		self.assets_manager.as_mut().unwrap().unload(&key);
+		self.load(key)
+	}
 }
 
 
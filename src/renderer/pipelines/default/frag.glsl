@@ -19,6 +19,7 @@ layout(set = 0, binding = 1) uniform sampler2D tex;
 layout(push_constant) uniform Pc {
 	mat4 model;
 	vec4 color;
+	float alpha_cutoff;
 } pc;
 
 void main() {
@@ -26,5 +27,9 @@ void main() {
 	float lambert = max(dot(-f_normal, light_direction), 0.0);
 	float light = lambert * (1.0 - commons.ambient) + commons.ambient;
 	
-	o_color = texture(tex, f_uv) * pc.color * vec4(light, light, light, 1.0);
+	vec4 color = texture(tex, f_uv) * pc.color;
+	
+	if(color.a < pc.alpha_cutoff) discard;
+	
+	o_color = color * vec4(light, light, light, 1.0);
 }
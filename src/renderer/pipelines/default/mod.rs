@@ -57,29 +57,44 @@ impl PipelineConstructor for DefaultPipeline {
 	type PipeType = GraphicsPipeline;
 	
 	fn new(render_pass: &Arc<RenderPass>) -> Result<Arc<Self::PipeType>> {
-		let device = render_pass.device();
-		let vs = vert::load(device.clone()).unwrap();
-		let fs = frag::load(device.clone()).unwrap();
-		
-		Ok(
-			GraphicsPipeline::start()
-				.vertex_input_state(Vertex::per_vertex())
-				.vertex_shader(vs.entry_point("main").unwrap(), ())
-				.viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
-				.fragment_shader(fs.entry_point("main").unwrap(), ())
-				.depth_stencil_state(DepthStencilState::simple_depth_test())
-				.rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
-				.color_blend_state(ColorBlendState::new(1).blend(pre_mul_alpha_blending()))
-				.render_pass(render_pass.clone().first_subpass())
-				.multisample_state(MultisampleState {
-					rasterization_samples: render_pass.clone().first_subpass().num_samples().unwrap_or(SampleCount::Sample1),
-					..MultisampleState::new()
-				})
-				.build(device.clone())?
-		)
+		default_pipeline(render_pass, CullMode::Back)
+	}
+}
+
+/// Same as `DefaultPipeline`, for double-sided materials.
+pub struct DefaultNoCullPipeline;
+
+impl PipelineConstructor for DefaultNoCullPipeline {
+	type PipeType = GraphicsPipeline;
+	
+	fn new(render_pass: &Arc<RenderPass>) -> Result<Arc<Self::PipeType>> {
+		default_pipeline(render_pass, CullMode::None)
 	}
 }
 
+fn default_pipeline(render_pass: &Arc<RenderPass>, cull_mode: CullMode) -> Result<Arc<GraphicsPipeline>> {
+	let device = render_pass.device();
+	let vs = vert::load(device.clone()).unwrap();
+	let fs = frag::load(device.clone()).unwrap();
+	
+	Ok(
+		GraphicsPipeline::start()
+			.vertex_input_state(Vertex::per_vertex())
+			.vertex_shader(vs.entry_point("main").unwrap(), ())
+			.viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+			.fragment_shader(fs.entry_point("main").unwrap(), ())
+			.depth_stencil_state(DepthStencilState::simple_depth_test())
+			.rasterization_state(RasterizationState::new().cull_mode(cull_mode))
+			.color_blend_state(ColorBlendState::new(1).blend(pre_mul_alpha_blending()))
+			.render_pass(render_pass.clone().first_subpass())
+			.multisample_state(MultisampleState {
+				rasterization_samples: render_pass.clone().first_subpass().num_samples().unwrap_or(SampleCount::Sample1),
+				..MultisampleState::new()
+			})
+			.build(device.clone())?
+	)
+}
+
 pub struct DefaultGlowPipeline;
 
 impl PipelineConstructor for DefaultGlowPipeline {
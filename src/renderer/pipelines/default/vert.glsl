@@ -18,6 +18,7 @@ layout(set = 0, binding = 0) uniform Commons {
 layout(push_constant) uniform Pc {
 	mat4 model;
 	vec4 color;
+	float alpha_cutoff;
 } pc;
 
 void main() {
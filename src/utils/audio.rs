@@ -0,0 +1,226 @@
+use std::io::{BufRead, Read, Seek};
+use std::path::Path;
+use anyhow::Result;
+use lewton::inside_ogg::OggStreamReader;
+use thiserror::Error;
+
+
+/// Chunk size written by streaming encoders, which don't know the length upfront.
+const UNKNOWN_SIZE: u32 = 0xFFFFFFFF;
+
+/// Decoded audio, downmixed to mono.
+#[derive(Debug, Clone)]
+pub struct AudioClip {
+	pub sample_rate: u32,
+	pub samples: Vec<f32>,
+}
+
+impl AudioClip {
+	/// Decodes WAV or OGG Vorbis file, depending on the extension of `path`.
+	pub fn read(path: impl AsRef<Path>, reader: impl BufRead + Seek) -> Result<Self> {
+		let extension = path.as_ref()
+		                    .extension()
+		                    .map(|ext| ext.to_string_lossy().to_lowercase())
+		                    .unwrap_or_default();
+		
+		match extension.as_str() {
+			"wav" | "wave" => AudioClip::read_wav(reader),
+			"ogg" | "oga" => AudioClip::read_ogg(reader),
+			_ => Err(AudioUnsupportedTypeError(path.as_ref().to_string_lossy().to_string()).into()),
+		}
+	}
+	
+	/// Chunk sizes are only upper bounds, so streamed files with unknown sizes and truncated files are read until their end.
+	pub fn read_wav(mut reader: impl Read) -> Result<Self> {
+		let header = read_bytes::<12>(&mut reader)?;
+		
+		if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+			return Err(WavInvalidHeaderError.into());
+		}
+		
+		// Size of RIFF chunk includes the WAVE tag
+		let riff_size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+		let mut reader = reader.take(match riff_size {
+			UNKNOWN_SIZE => u64::MAX,
+			size => (size as u64).saturating_sub(4),
+		});
+		let mut format = None;
+		
+		loop {
+			let id = read_bytes::<4>(&mut reader)?;
+			let size = u32::from_le_bytes(read_bytes(&mut reader)?) as u64;
+			let mut data = vec![];
+			(&mut reader).take(size).read_to_end(&mut data)?;
+			
+			// Chunks are padded to even sizes
+			if size % 2 == 1 && data.len() as u64 == size {
+				let _ = reader.read(&mut [0]);
+			}
+			
+			match &id {
+				b"fmt " if data.len() >= 16 => {
+					let mut tag = u16::from_le_bytes([data[0], data[1]]);
+					let channels = u16::from_le_bytes([data[2], data[3]]);
+					let sample_rate = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+					let bits = u16::from_le_bytes([data[14], data[15]]);
+					
+					// WAVE_FORMAT_EXTENSIBLE keeps the actual format at the start of sub format GUID
+					if tag == 0xFFFE && data.len() >= 26 {
+						tag = u16::from_le_bytes([data[24], data[25]]);
+					}
+					
+					format = Some((tag, channels.max(1) as usize, sample_rate, bits));
+				},
+				b"data" => {
+					let (tag, channels, sample_rate, bits) = format.ok_or(WavInvalidHeaderError)?;
+					
+					let decoded: Vec<f32> = match (tag, bits) {
+						(1, 8) => data.iter().map(|&s| (s as f32 - 128.0) / 128.0).collect(),
+						(1, 16) => data.chunks_exact(2).map(|s| i16::from_le_bytes([s[0], s[1]]) as f32 / 32768.0).collect(),
+						(1, 24) => data.chunks_exact(3).map(|s| i32::from_le_bytes([0, s[0], s[1], s[2]]) as f32 / 2147483648.0).collect(),
+						(1, 32) => data.chunks_exact(4).map(|s| i32::from_le_bytes([s[0], s[1], s[2], s[3]]) as f32 / 2147483648.0).collect(),
+						(3, 32) => data.chunks_exact(4).map(|s| f32::from_le_bytes([s[0], s[1], s[2], s[3]])).collect(),
+						_ => return Err(WavUnsupportedFormatError(tag, bits).into()),
+					};
+					
+					return Ok(AudioClip {
+						sample_rate,
+						samples: downmix(&decoded, channels),
+					});
+				},
+				_ => {},
+			}
+		}
+	}
+	
+	pub fn read_ogg(reader: impl Read + Seek) -> Result<Self> {
+		let mut stream = OggStreamReader::new(reader)?;
+		let channels = stream.ident_hdr.audio_channels.max(1) as usize;
+		let mut samples = vec![];
+		
+		while let Some(packet) = stream.read_dec_packet_itl()? {
+			let packet: Vec<f32> = packet.iter().map(|&s| s as f32 / 32768.0).collect();
+			samples.extend(downmix(&packet, channels));
+		}
+		
+		Ok(AudioClip {
+			sample_rate: stream.ident_hdr.audio_sample_rate,
+			samples,
+		})
+	}
+	
+	/// Length in seconds.
+	pub fn duration(&self) -> f32 {
+		self.samples.len() as f32 / self.sample_rate as f32
+	}
+}
+
+/// Encodes mono 16-bit PCM WAV file.
+#[cfg(test)]
+pub fn encode_wav(sample_rate: u32, samples: &[f32]) -> Vec<u8> {
+	let data: Vec<u8> = samples.iter()
+	                           .flat_map(|s| ((s.clamp(-1.0, 1.0) * 32767.0) as i16).to_le_bytes())
+	                           .collect();
+	
+	let mut wav = vec![];
+	wav.extend(b"RIFF");
+	wav.extend((36 + data.len() as u32).to_le_bytes());
+	wav.extend(b"WAVEfmt ");
+	wav.extend(16_u32.to_le_bytes());
+	wav.extend(1_u16.to_le_bytes());
+	wav.extend(1_u16.to_le_bytes());
+	wav.extend(sample_rate.to_le_bytes());
+	wav.extend((sample_rate * 2).to_le_bytes());
+	wav.extend(2_u16.to_le_bytes());
+	wav.extend(16_u16.to_le_bytes());
+	wav.extend(b"data");
+	wav.extend((data.len() as u32).to_le_bytes());
+	wav.extend(data);
+	wav
+}
+
+fn downmix(interleaved: &[f32], channels: usize) -> Vec<f32> {
+	interleaved.chunks_exact(channels)
+	           .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+	           .collect()
+}
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> std::io::Result<[u8; N]> {
+	let mut buf = [0; N];
+	reader.read_exact(&mut buf)?;
+	Ok(buf)
+}
+
+#[derive(Debug, Error)]
+#[error("Not a RIFF WAVE file.")]
+pub struct WavInvalidHeaderError;
+
+#[derive(Debug, Error)]
+#[error("Unsupported WAV format {0} with {1} bits per sample.")]
+pub struct WavUnsupportedFormatError(u16, u16);
+
+#[derive(Debug, Error)]
+#[error("Unsupported audio file: {0}. Only WAV and OGG Vorbis are supported.")]
+pub struct AudioUnsupportedTypeError(String);
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn tone(sample_rate: u32, freq: f32, length: usize) -> Vec<f32> {
+		(0..length).map(|id| (id as f32 / sample_rate as f32 * freq * std::f32::consts::TAU).sin() * 0.5)
+		           .collect()
+	}
+	
+	#[test]
+	fn reads_pcm16() {
+		let samples = tone(8000, 440.0, 800);
+		let clip = AudioClip::read_wav(&encode_wav(8000, &samples)[..]).unwrap();
+		
+		assert_eq!(clip.sample_rate, 8000);
+		assert_eq!(clip.samples.len(), samples.len());
+		assert!(clip.samples.iter().zip(&samples).all(|(a, b)| (a - b).abs() < 0.001));
+	}
+	
+	#[test]
+	fn reads_streamed_sizes() {
+		let mut wav = encode_wav(8000, &tone(8000, 440.0, 800));
+		wav[4..8].copy_from_slice(&UNKNOWN_SIZE.to_le_bytes());
+		wav[40..44].copy_from_slice(&UNKNOWN_SIZE.to_le_bytes());
+		
+		let clip = AudioClip::read_wav(&wav[..]).unwrap();
+		
+		assert_eq!(clip.samples.len(), 800);
+	}
+	
+	#[test]
+	fn reads_truncated() {
+		let wav = encode_wav(8000, &tone(8000, 440.0, 800));
+		
+		let clip = AudioClip::read_wav(&wav[..wav.len() - 101]).unwrap();
+		
+		assert_eq!(clip.samples.len(), 749);
+	}
+	
+	#[test]
+	fn skips_pad_byte() {
+		let wav = encode_wav(8000, &tone(8000, 440.0, 800));
+		
+		// Odd sized chunk before the format one
+		let mut padded = wav[..12].to_vec();
+		padded.extend(b"junk");
+		padded.extend(3_u32.to_le_bytes());
+		padded.extend([1, 2, 3, 0]);
+		padded.extend(&wav[12..]);
+		padded[4..8].copy_from_slice(&(padded.len() as u32 - 8).to_le_bytes());
+		
+		let clip = AudioClip::read_wav(&padded[..]).unwrap();
+		
+		assert_eq!(clip.samples.len(), 800);
+	}
+	
+	#[test]
+	fn rejects_other_files() {
+		assert!(AudioClip::read_wav(&b"RIFF\0\0\0\0AVI LIST"[..]).is_err());
+	}
+}
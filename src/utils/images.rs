@@ -1,4 +1,7 @@
-use image::DynamicImage;
+use anyhow::Result;
+use gltf::image::Format;
+use image::{DynamicImage, ImageBuffer};
+use thiserror::Error;
 
 
 pub trait ImageEx {
@@ -53,6 +56,21 @@ impl ImageEx for DynamicImage {
 	}
 }
 
+/// Wraps pixels decoded by glTF importer, only 8 bit formats are supported.
+pub fn from_gltf_image(image: gltf::image::Data) -> Result<DynamicImage> {
+	let (width, height) = (image.width, image.height);
+	
+	let converted = match image.format {
+		Format::R8 => ImageBuffer::from_raw(width, height, image.pixels).map(DynamicImage::ImageLuma8),
+		Format::R8G8 => ImageBuffer::from_raw(width, height, image.pixels).map(DynamicImage::ImageLumaA8),
+		Format::R8G8B8 => ImageBuffer::from_raw(width, height, image.pixels).map(DynamicImage::ImageRgb8),
+		Format::R8G8B8A8 => ImageBuffer::from_raw(width, height, image.pixels).map(DynamicImage::ImageRgba8),
+		format => return Err(GltfImageFormatError(format).into()),
+	};
+	
+	Ok(converted.ok_or(GltfImageSizeError)?)
+}
+
 fn to_linear(srgb: u8) -> f32 {
 	if srgb <= (0.0031308 / 12.92 * 255.0) as u8 {
 		srgb as f32 / 255.0 / 12.92
@@ -68,3 +86,11 @@ fn to_srgb(linear: f32) -> u8 {
 		((1.055 * linear.powf(1.0 / 2.4) - 0.055) * 255.0) as u8
 	}
 }
+
+#[derive(Debug, Error)]
+#[error("Unsupported glTF image format {0:?}.")]
+pub struct GltfImageFormatError(Format);
+
+#[derive(Debug, Error)]
+#[error("glTF image data does not match its size.")]
+pub struct GltfImageSizeError;
@@ -3,6 +3,7 @@ use openvr::compositor::WaitPoses;
 use openvr_sys::{TrackedDevicePose_t, HmdMatrix34_t, HmdVector3_t, ETrackedDeviceClass_TrackedDeviceClass_Invalid};
 
 pub mod from_args;
+mod audio;
 mod fence_check;
 mod fps_counter;
 mod gui;
@@ -16,6 +17,7 @@ mod rapier;
 mod vulkan;
 mod ref_cell_iter;
 
+pub use audio::*;
 pub use fence_check::*;
 pub use fps_counter::*;
 pub use gui::*;